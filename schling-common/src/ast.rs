@@ -0,0 +1,726 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+
+use crate::html::{self, Token};
+use crate::markup::{Markup, MarkupLang};
+use crate::math::Math;
+use crate::sanitize::{Filter, Policy};
+use crate::toc::{anchor_headings, Anchors};
+use crate::wiki::{self, Titles};
+use crate::{fountain, id};
+
+/// The alignment of a table column.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+impl From<Alignment> for Align {
+    fn from(align: Alignment) -> Self {
+        match align {
+            Alignment::None => Align::None,
+            Alignment::Left => Align::Left,
+            Alignment::Center => Align::Center,
+            Alignment::Right => Align::Right,
+        }
+    }
+}
+
+/// The kind of an element of the document tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Paragraph,
+    /// A heading, with the id of its anchor.
+    Heading {
+        level: u8,
+        id: Option<String>,
+    },
+    BlockQuote,
+    /// A code block with the language of its fenced info string.
+    CodeBlock(Option<String>),
+    /// A list, ordered when it has a start number.
+    List(Option<u64>),
+    Item,
+    Table,
+    /// The header row of a table.
+    TableHead,
+    TableRow,
+    TableCell {
+        header: bool,
+        align: Align,
+    },
+    Emphasis,
+    Strong,
+    Strikethrough,
+    /// A link, whose target is missing when it is not allowed by the policy.
+    Link {
+        href: Option<String>,
+        title: String,
+    },
+    /// An image, whose children are the alternative text.
+    Image {
+        src: Option<String>,
+        title: String,
+    },
+    /// An element of embedded HTML, allowed by the policy.
+    Html {
+        name: String,
+        attrs: Vec<(String, String)>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Element {
+    pub kind: Kind,
+    pub children: Vec<Node>,
+}
+
+impl Element {
+    pub fn new(kind: Kind) -> Self {
+        Self {
+            kind,
+            children: vec![],
+        }
+    }
+
+    /// Whether the element is an embedded HTML element with the name.
+    pub fn is_html(&self, tag: &str) -> bool {
+        matches!(&self.kind, Kind::Html { name, .. } if name == tag)
+    }
+}
+
+/// A reference to a footnote.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NoteRef {
+    pub number: usize,
+    /// The id of the definition.
+    pub target: String,
+    /// The id of the reference, if it is the first reference to the footnote.
+    pub id: Option<String>,
+}
+
+/// A node of the document tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    Text(String),
+    /// Inline code.
+    Code(String),
+    SoftBreak,
+    HardBreak,
+    Rule,
+    TaskMarker(bool),
+    FootnoteRef(NoteRef),
+    Math(Math),
+    Element(Element),
+}
+
+impl Node {
+    /// The text of the node without formatting, such as the alternative text of images.
+    pub fn text(&self) -> String {
+        match self {
+            Node::Text(text) | Node::Code(text) => text.clone(),
+            Node::Math(math) => math.tex.clone(),
+            Node::SoftBreak | Node::HardBreak => " ".to_string(),
+            Node::Element(el) => el.children.iter().map(Node::text).collect(),
+            Node::Rule | Node::TaskMarker(_) | Node::FootnoteRef(_) => String::new(),
+        }
+    }
+}
+
+impl From<Element> for Node {
+    fn from(el: Element) -> Self {
+        Node::Element(el)
+    }
+}
+
+/// A footnote definition, rendered with the other notes of the section or document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Note {
+    pub number: usize,
+    pub id: String,
+    /// The id of the first reference to the note.
+    pub back: String,
+    pub content: Vec<Node>,
+}
+
+/// Numbers the footnotes of one or more sections, and collects their definitions.
+///
+/// Parsing every section of a document with the same notes numbers the footnotes
+/// continuously, as endnotes of the document.
+#[derive(Clone, Debug, Default)]
+pub struct Notes {
+    /// Prefix of the ids of the notes, distinguishing equal labels in different sections.
+    scope: String,
+    /// The number of each footnote by scope and label, in order of the first reference.
+    numbers: HashMap<(String, String), usize>,
+    defs: Vec<Note>,
+    /// The titles of the project, and the document of the sections, resolving wiki links.
+    titles: Option<(Titles, id::Doc)>,
+}
+
+impl Notes {
+    pub fn new(scope: &str) -> Self {
+        Self {
+            scope: scope.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Links the wiki links of the sections of the document to the titles of its project.
+    pub fn with_titles(mut self, titles: Titles, doc: id::Doc) -> Self {
+        self.titles = Some((titles, doc));
+        self
+    }
+
+    /// Sets the scope of the labels of the following sections.
+    pub fn set_scope(&mut self, scope: &str) {
+        self.scope = scope.to_string();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.defs.is_empty()
+    }
+
+    /// The definitions in the order of their numbers.
+    pub fn into_notes(mut self) -> Vec<Note> {
+        self.defs.sort_by_key(|note| note.number);
+        self.defs
+    }
+
+    /// The number of the footnote, and whether it is referenced for the first time.
+    fn number(&mut self, label: &str) -> (usize, bool) {
+        let next = self.numbers.len() + 1;
+        let key = (self.scope.clone(), label.to_string());
+        let number = *self.numbers.entry(key).or_insert(next);
+        (number, number == next)
+    }
+
+    fn def_id(&self, label: &str) -> String {
+        format!("fn-{}{}", self.scope, label)
+    }
+
+    fn ref_id(&self, label: &str) -> String {
+        format!("fnref-{}{}", self.scope, label)
+    }
+}
+
+/// The deepest nesting of elements, deeper ones are flattened into their parent so that the
+/// recursive walks of the tree do not overflow the stack.
+const MAX_DEPTH: usize = 100;
+
+/// An open element.
+struct Frame {
+    el: Element,
+    /// Whether the element was opened by raw HTML, instead of a Markdown construct.
+    raw: bool,
+}
+
+/// Builds the document tree from the events of the Markdown parser.
+struct Builder<'n> {
+    nodes: Vec<Node>,
+    spine: Vec<Frame>,
+    /// The alignment of the columns of the current table.
+    aligns: Vec<Align>,
+    /// The index of the current cell in the row.
+    cell: usize,
+    in_head: bool,
+    notes: &'n mut Notes,
+    policy: &'n Policy,
+    /// The state of the policy across the raw HTML fragments.
+    filter: Filter,
+    /// The inline nodes replaced by placeholders in the Markdown source.
+    inlines: Vec<Node>,
+    /// The label of the current footnote definition.
+    note: Option<String>,
+    /// The Markdown elements left open but flattened, past the [`MAX_DEPTH`].
+    flattened: usize,
+}
+
+impl<'n> Builder<'n> {
+    fn new(notes: &'n mut Notes, policy: &'n Policy) -> Self {
+        Self {
+            nodes: vec![],
+            spine: vec![],
+            aligns: vec![],
+            cell: 0,
+            in_head: false,
+            notes,
+            policy,
+            filter: Filter::default(),
+            inlines: vec![],
+            note: None,
+            flattened: 0,
+        }
+    }
+
+    /// Whether the elements opened now are flattened into their parent.
+    fn is_full(&self) -> bool {
+        self.flattened > 0 || self.spine.len() >= MAX_DEPTH
+    }
+
+    fn open(&mut self, kind: Kind) {
+        if self.is_full() {
+            self.flattened += 1;
+            return;
+        }
+        self.spine.push(Frame {
+            el: Element::new(kind),
+            raw: false,
+        });
+    }
+
+    fn add_child(&mut self, child: Node) {
+        let nodes = match self.spine.last_mut() {
+            Some(frame) => &mut frame.el.children,
+            None => &mut self.nodes,
+        };
+        match (nodes.last_mut(), child) {
+            (Some(Node::Text(last)), Node::Text(text)) => last.push_str(&text),
+            (_, child) => nodes.push(child),
+        }
+    }
+
+    /// Closes the innermost Markdown element, and the raw elements left open inside of it.
+    fn close(&mut self) {
+        if self.flattened > 0 {
+            self.flattened -= 1;
+            return;
+        }
+        while let Some(frame) = self.spine.pop() {
+            let raw = frame.raw;
+            self.add_child(frame.el.into());
+            if !raw {
+                break;
+            }
+        }
+    }
+
+    /// Closes all elements opened at or after the position in the spine.
+    fn close_from(&mut self, pos: usize) {
+        while self.spine.len() > pos {
+            if let Some(frame) = self.spine.pop() {
+                self.add_child(frame.el.into());
+            }
+        }
+    }
+
+    /// Closes the raw elements left open in the innermost Markdown element, and removes it
+    /// from the spine.
+    fn pop(&mut self) -> Option<Element> {
+        if self.flattened > 0 {
+            self.flattened -= 1;
+            return None;
+        }
+        while let Some(frame) = self.spine.pop() {
+            if !frame.raw {
+                return Some(frame.el);
+            }
+            self.add_child(frame.el.into());
+        }
+        None
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.filter.is_stripping() {
+            return;
+        }
+        let mut rest = text;
+        while let Some(start) = rest.find(PLACEHOLDER_START) {
+            let after = &rest[start + PLACEHOLDER_START.len_utf8()..];
+            let Some((index, tail)) = after.split_once(PLACEHOLDER_END) else {
+                break;
+            };
+            let Some(node) = index.parse().ok().and_then(|i: usize| self.inlines.get(i)) else {
+                break;
+            };
+            let node = node.clone();
+            if start > 0 {
+                self.add_child(Node::Text(rest[..start].to_string()));
+            }
+            self.add_child(node);
+            rest = tail;
+        }
+        if !rest.is_empty() {
+            self.add_child(Node::Text(rest.to_string()));
+        }
+    }
+
+    fn url(&self, url: &str) -> Option<String> {
+        Some(url.to_string()).filter(|url| self.policy.allows_url(url))
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => self.open(Kind::Paragraph),
+            Tag::Heading { level, .. } => self.open(Kind::Heading {
+                level: level as u8,
+                id: None,
+            }),
+            Tag::BlockQuote(_) => self.open(Kind::BlockQuote),
+            Tag::CodeBlock(kind) => {
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split(' ')
+                        .next()
+                        .filter(|lang| !lang.is_empty())
+                        .map(str::to_string),
+                    CodeBlockKind::Indented => None,
+                };
+                self.open(Kind::CodeBlock(lang));
+            }
+            Tag::List(start) => self.open(Kind::List(start)),
+            Tag::Item => self.open(Kind::Item),
+            Tag::FootnoteDefinition(label) => {
+                self.notes.number(&label);
+                self.note = Some(label.to_string());
+                self.open(Kind::Item);
+            }
+            Tag::Table(aligns) => {
+                self.aligns = aligns.into_iter().map(Align::from).collect();
+                self.open(Kind::Table);
+            }
+            Tag::TableHead => {
+                self.in_head = true;
+                self.cell = 0;
+                self.open(Kind::TableHead);
+            }
+            Tag::TableRow => {
+                self.cell = 0;
+                self.open(Kind::TableRow);
+            }
+            Tag::TableCell => {
+                let align = self.aligns.get(self.cell).copied().unwrap_or_default();
+                self.cell += 1;
+                self.open(Kind::TableCell {
+                    header: self.in_head,
+                    align,
+                });
+            }
+            Tag::Emphasis => self.open(Kind::Emphasis),
+            Tag::Strong => self.open(Kind::Strong),
+            Tag::Strikethrough => self.open(Kind::Strikethrough),
+            Tag::Link {
+                dest_url, title, ..
+            } => self.open(Kind::Link {
+                href: self.url(&dest_url),
+                title: title.to_string(),
+            }),
+            Tag::Image {
+                dest_url, title, ..
+            } => self.open(Kind::Image {
+                src: self.url(&dest_url),
+                title: title.to_string(),
+            }),
+            Tag::DefinitionList => self.open_html("dl"),
+            Tag::DefinitionListTitle => self.open_html("dt"),
+            Tag::DefinitionListDefinition => self.open_html("dd"),
+            Tag::Superscript => self.open_html("sup"),
+            Tag::Subscript => self.open_html("sub"),
+            // the fragments of HTML blocks are added as raw HTML.
+            Tag::HtmlBlock | Tag::MetadataBlock(_) => {}
+        }
+    }
+
+    /// Opens an element of the syntax extensions with no kind of its own.
+    fn open_html(&mut self, name: &str) {
+        self.open(Kind::Html {
+            name: name.to_string(),
+            attrs: vec![],
+        });
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::TableHead => {
+                self.in_head = false;
+                self.close();
+            }
+            TagEnd::FootnoteDefinition => {
+                let label = self.note.take().unwrap_or_default();
+                if let Some(el) = self.pop() {
+                    let (number, _) = self.notes.number(&label);
+                    let note = Note {
+                        number,
+                        id: self.notes.def_id(&label),
+                        back: self.notes.ref_id(&label),
+                        content: el.children,
+                    };
+                    self.notes.defs.push(note);
+                }
+            }
+            TagEnd::HtmlBlock | TagEnd::MetadataBlock(_) => {}
+            _ => self.close(),
+        }
+    }
+
+    /// Adds the elements of a raw HTML fragment allowed by the policy, tags may be closed by
+    /// later fragments.
+    fn raw_html(&mut self, src: &str) {
+        let tokens: Vec<Token> = html::tokenize(src)
+            .into_iter()
+            .filter_map(|token| self.filter.apply(self.policy, token))
+            .collect();
+        for token in tokens {
+            match token {
+                Token::Start {
+                    name,
+                    attrs,
+                    self_closing,
+                } => {
+                    let void = html::is_void(&name) || self_closing;
+                    let el = Element::new(Kind::Html { name, attrs });
+                    if void {
+                        self.add_child(el.into());
+                    } else if !self.is_full() {
+                        self.spine.push(Frame { el, raw: true });
+                    }
+                }
+                Token::End(_) if self.flattened > 0 => {}
+                Token::End(name) => {
+                    // only raw elements opened inside of the current Markdown element are closed.
+                    let pos = self
+                        .spine
+                        .iter()
+                        .rposition(|frame| !frame.raw || frame.el.is_html(&name));
+                    if let Some(pos) = pos.filter(|pos| self.spine[*pos].raw) {
+                        self.close_from(pos);
+                    }
+                }
+                Token::Text(text) => self.text(&text),
+                Token::Comment(_) => {}
+            }
+        }
+    }
+
+    fn event(&mut self, ev: Event) {
+        match ev {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => self.text(&text),
+            Event::Code(code) => self.add_child(Node::Code(code.to_string())),
+            Event::InlineMath(tex) => self.add_child(Node::Math(Math::new(tex.trim(), false))),
+            Event::DisplayMath(tex) => self.add_child(Node::Math(Math::new(tex.trim(), true))),
+            Event::Html(src) | Event::InlineHtml(src) => self.raw_html(&src),
+            Event::FootnoteReference(label) => {
+                let (number, first) = self.notes.number(&label);
+                let note = NoteRef {
+                    number,
+                    target: self.notes.def_id(&label),
+                    id: first.then(|| self.notes.ref_id(&label)),
+                };
+                self.add_child(Node::FootnoteRef(note));
+            }
+            Event::SoftBreak => self.add_child(Node::SoftBreak),
+            Event::HardBreak => self.add_child(Node::HardBreak),
+            Event::Rule => self.add_child(Node::Rule),
+            Event::TaskListMarker(checked) => self.add_child(Node::TaskMarker(checked)),
+        }
+    }
+
+    fn finish(mut self) -> Vec<Node> {
+        self.close_from(0);
+        self.nodes
+    }
+}
+
+pub(crate) fn options() -> Options {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_SMART_PUNCTUATION);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+    options.insert(Options::ENABLE_MATH);
+    options
+}
+
+/// Marks the position of an inline node in the Markdown source handed to the parser, as
+/// characters of the private use area are left alone by Markdown.
+const PLACEHOLDER_START: char = '\u{E000}';
+const PLACEHOLDER_END: char = '\u{E001}';
+
+/// Adds the node to the inline nodes, and returns the placeholder replacing it in the source.
+pub(crate) fn placeholder(inlines: &mut Vec<Node>, node: Node) -> String {
+    inlines.push(node);
+    format!(
+        "{}{}{}",
+        PLACEHOLDER_START,
+        inlines.len() - 1,
+        PLACEHOLDER_END
+    )
+}
+
+/// The ranges of code and HTML in the Markdown source, which are kept verbatim.
+pub(crate) fn verbatim_ranges(src: &str) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    for (event, range) in Parser::new_ext(src, options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) | Event::Code(_) | Event::Html(_) => ranges.push(range),
+            _ => {}
+        }
+    }
+    ranges
+}
+
+/// Parses Markdown into the document tree, collecting its footnotes into `notes` and removing
+/// the embedded HTML and URLs not allowed by the policy.
+///
+/// Formulas between `$` and `$$` become math nodes, and wiki links between `[[` and `]]` link
+/// to the sections of the project when the notes know its titles.
+pub fn parse_markdown(src: &str, notes: &mut Notes, policy: &Policy) -> Vec<Node> {
+    let mut inlines = vec![];
+    let titles = notes.titles.as_ref().map(|(titles, doc)| (titles, *doc));
+    let src = wiki::extract(src, &mut inlines, titles);
+    let mut builder = Builder::new(notes, policy);
+    builder.inlines = inlines;
+    for ev in Parser::new_ext(&src, options()) {
+        builder.event(ev);
+    }
+    builder.finish()
+}
+
+/// Parses a HTML fragment into the document tree, removing the elements not allowed by the
+/// policy.
+pub fn parse_html(src: &str, policy: &Policy) -> Vec<Node> {
+    let mut notes = Notes::default();
+    let mut builder = Builder::new(&mut notes, policy);
+    builder.raw_html(src);
+    builder.finish()
+}
+
+impl Markup {
+    /// Parses the markup into the document tree, anchoring the headings in the scope of the
+    /// notes.
+    pub fn to_ast(&self, notes: &mut Notes, policy: &Policy) -> Vec<Node> {
+        let mut nodes = match self.lang {
+            MarkupLang::Html => parse_html(&self.text, policy),
+            MarkupLang::Md => parse_markdown(&self.text, notes, policy),
+            MarkupLang::Fountain => fountain::parse(&self.text).to_ast(),
+        };
+        anchor_headings(&mut nodes, &mut Anchors::new(&notes.scope));
+        nodes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn text(text: &str) -> Node {
+        Node::Text(text.to_string())
+    }
+
+    fn el(kind: Kind, children: Vec<Node>) -> Node {
+        Node::Element(Element { kind, children })
+    }
+
+    fn parse(src: &str) -> Vec<Node> {
+        parse_markdown(src, &mut Notes::default(), &Policy::default())
+    }
+
+    #[test]
+    fn markdown() {
+        assert_eq!(
+            parse("# Title\n\nSome *text* and `code`."),
+            vec![
+                el(Kind::Heading { level: 1, id: None }, vec![text("Title")]),
+                el(
+                    Kind::Paragraph,
+                    vec![
+                        text("Some "),
+                        el(Kind::Emphasis, vec![text("text")]),
+                        text(" and "),
+                        Node::Code("code".to_string()),
+                        text("."),
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn embedded_html() {
+        let span = Kind::Html {
+            name: "span".to_string(),
+            attrs: vec![("class".to_string(), "x".to_string())],
+        };
+        assert_eq!(
+            parse("a <span class=\"x\" onclick=\"y\">b</span> c"),
+            vec![el(
+                Kind::Paragraph,
+                vec![text("a "), el(span, vec![text("b")]), text(" c")]
+            )]
+        );
+        assert_eq!(
+            Markup::html_str("<p>a<script>b</script></p>")
+                .to_ast(&mut Notes::default(), &Policy::default()),
+            vec![el(
+                Kind::Html {
+                    name: "p".to_string(),
+                    attrs: vec![]
+                },
+                vec![text("a")]
+            )]
+        );
+    }
+
+    #[test]
+    fn footnotes() {
+        let mut notes = Notes::new("s-");
+        let nodes = parse_markdown("a[^n]\n\n[^n]: note", &mut notes, &Policy::default());
+        assert_eq!(
+            nodes,
+            vec![el(
+                Kind::Paragraph,
+                vec![
+                    text("a"),
+                    Node::FootnoteRef(NoteRef {
+                        number: 1,
+                        target: "fn-s-n".to_string(),
+                        id: Some("fnref-s-n".to_string()),
+                    })
+                ]
+            )]
+        );
+        assert_eq!(
+            notes.into_notes(),
+            vec![Note {
+                number: 1,
+                id: "fn-s-n".to_string(),
+                back: "fnref-s-n".to_string(),
+                content: vec![el(Kind::Paragraph, vec![text("note")])],
+            }]
+        );
+    }
+
+    #[test]
+    fn flattens_deep_nesting() {
+        use crate::render::ToHtml;
+
+        fn depth(nodes: &[Node]) -> usize {
+            nodes
+                .iter()
+                .map(|node| match node {
+                    Node::Element(el) => 1 + depth(&el.children),
+                    _ => 0,
+                })
+                .max()
+                .unwrap_or(0)
+        }
+
+        let html = Markup::html_str(&format!("{}deep", "<div>".repeat(50_000)));
+        let quotes = Markup::md_str(&format!("{} deep", ">".repeat(50_000)));
+        let lists = Markup::md_str(&format!("{}deep", "- ".repeat(10_000)));
+        for markup in [html, quotes, lists] {
+            let nodes = markup.to_ast(&mut Notes::default(), &Policy::default());
+            assert_eq!(depth(&nodes), MAX_DEPTH);
+            assert!(nodes.to_html().contains("deep"));
+        }
+    }
+}
@@ -0,0 +1,456 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::io::{Cursor, Read, Seek, Write};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::data::{Doc, Proj, Sec};
+use crate::id;
+
+/// The version of the archive format written, bumped with each change of the format.
+pub const VERSION: u32 = 2;
+
+pub const MANIFEST: &str = "manifest.json";
+const PROJECT: &str = "project.json";
+const SETTINGS: &str = "settings.json";
+
+/// The largest size a file of an archive may have uncompressed.
+pub const FILE_LIMIT: u64 = 16 << 20;
+
+/// The settings of the editor by their key, such as the themes of highlighted code.
+pub type Settings = BTreeMap<String, Value>;
+
+/// The files of an archive as JSON, by their path.
+type Files = BTreeMap<String, Value>;
+
+/// Upgrades the files of an archive to the next version of the format.
+type Migration = fn(&mut Files) -> Result<(), Error>;
+
+/// The migrations from each older version to the next one, starting with version 1.
+const MIGRATIONS: [Migration; VERSION as usize - 1] = [add_revisions];
+
+/// Version 2 lists the sections with revisions in the manifest.
+fn add_revisions(files: &mut Files) -> Result<(), Error> {
+    if let Some(Value::Object(manifest)) = files.get_mut(MANIFEST) {
+        manifest.insert("revisions".to_string(), Value::Array(vec![]));
+        manifest.insert("version".to_string(), Value::from(2));
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Zip(zip::result::ZipError),
+    /// A file of the archive at the path that is not valid.
+    Json(String, serde_json::Error),
+    /// An archive of a format version this one does not know, or without a version.
+    Version(Option<u64>),
+    /// A file listed in the manifest that is not in the archive.
+    Missing(String),
+    /// A file of the archive at the path that is larger than the limit uncompressed.
+    TooLarge(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "cannot read the backup: {}", err),
+            Error::Zip(err) => write!(f, "malformed backup archive: {}", err),
+            Error::Json(path, err) => write!(f, "malformed {} in the backup: {}", path, err),
+            Error::Version(Some(version)) => {
+                write!(f, "unsupported backup version {}", version)
+            }
+            Error::Version(None) => write!(f, "the backup has no version"),
+            Error::Missing(path) => write!(f, "the backup is missing {}", path),
+            Error::TooLarge(path) => write!(f, "{} in the backup is too large", path),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(err: zip::result::ZipError) -> Self {
+        Error::Zip(err)
+    }
+}
+
+/// The first file of the archive, listing the others.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub proj: id::Proj,
+    pub title: String,
+    pub docs: Vec<id::Doc>,
+    pub sections: Vec<id::Sec>,
+    /// The sections with revisions.
+    pub revisions: Vec<id::Sec>,
+}
+
+/// The files the manifest lists, in the format of all versions.
+#[derive(Deserialize)]
+struct Listing {
+    docs: Vec<id::Doc>,
+    sections: Vec<id::Sec>,
+    #[serde(default)]
+    revisions: Vec<id::Sec>,
+}
+
+/// A past version of a section, as the file of the section at a commit of the history of the
+/// project.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Revision {
+    pub sec: id::Sec,
+    pub commit: String,
+    pub author: String,
+    pub email: String,
+    /// The seconds since the Unix epoch.
+    pub time: i64,
+    pub message: String,
+    pub content: String,
+}
+
+/// A project with all its documents and sections, the revisions of the sections and the
+/// settings of the editor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Backup {
+    pub proj: Proj,
+    pub docs: Vec<Doc>,
+    pub sections: Vec<Sec>,
+    /// The revisions, the latest first for each section.
+    pub revisions: Vec<Revision>,
+    pub settings: Settings,
+}
+
+fn doc_path(id: id::Doc) -> String {
+    format!("documents/{}.json", String::from(id))
+}
+
+fn sec_path(id: id::Sec) -> String {
+    format!("sections/{}.json", String::from(id))
+}
+
+fn revisions_path(id: id::Sec) -> String {
+    format!("revisions/{}.json", String::from(id))
+}
+
+impl Backup {
+    pub fn new(proj: Proj, chapters: Vec<(Doc, Vec<Sec>)>) -> Self {
+        let (docs, sections): (Vec<_>, Vec<_>) = chapters.into_iter().unzip();
+        Self {
+            proj,
+            docs,
+            sections: sections.into_iter().flatten().collect(),
+            revisions: vec![],
+            settings: Settings::new(),
+        }
+    }
+
+    pub fn with_revisions(mut self, revisions: Vec<Revision>) -> Self {
+        self.revisions = revisions;
+        self
+    }
+
+    pub fn with_settings(mut self, settings: Settings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    pub fn manifest(&self) -> Manifest {
+        Manifest {
+            version: VERSION,
+            proj: self.proj.head.id,
+            title: self.proj.head.title.clone(),
+            docs: self.docs.iter().map(|doc| doc.head.id).collect(),
+            sections: self.sections.iter().map(|sec| sec.head.id).collect(),
+            revisions: self.revised_sections(),
+        }
+    }
+
+    /// The sections with revisions, in the order of their first revision.
+    fn revised_sections(&self) -> Vec<id::Sec> {
+        let mut ids = vec![];
+        for revision in &self.revisions {
+            if !ids.contains(&revision.sec) {
+                ids.push(revision.sec);
+            }
+        }
+        ids
+    }
+
+    /// Writes the backup as a zip archive of JSON files, with a file per document and section.
+    pub fn write<W: Write + Seek>(&self, writer: W) -> Result<W, Error> {
+        let mut zip = ZipWriter::new(writer);
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let mut put = |path: &str, value: serde_json::Result<Vec<u8>>| -> Result<(), Error> {
+            let value = value.map_err(|err| Error::Json(path.to_string(), err))?;
+            zip.start_file(path, options)?;
+            zip.write_all(&value)?;
+            Ok(())
+        };
+        put(MANIFEST, serde_json::to_vec_pretty(&self.manifest()))?;
+        put(PROJECT, serde_json::to_vec_pretty(&self.proj))?;
+        for doc in &self.docs {
+            put(&doc_path(doc.head.id), serde_json::to_vec_pretty(doc))?;
+        }
+        for sec in &self.sections {
+            put(&sec_path(sec.head.id), serde_json::to_vec_pretty(sec))?;
+        }
+        for id in self.revised_sections() {
+            let revisions: Vec<&Revision> = self
+                .revisions
+                .iter()
+                .filter(|revision| revision.sec == id)
+                .collect();
+            put(&revisions_path(id), serde_json::to_vec_pretty(&revisions))?;
+        }
+        put(SETTINGS, serde_json::to_vec_pretty(&self.settings))?;
+        Ok(zip.finish()?)
+    }
+
+    /// The backup as the bytes of a zip archive.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        self.write(Cursor::new(vec![]))
+            .map(|cursor| cursor.into_inner())
+    }
+
+    /// Reads a backup archive, migrating the archives of older versions. Only the files the
+    /// manifest lists are read, each up to the [`FILE_LIMIT`].
+    pub fn read(data: &[u8]) -> Result<Self, Error> {
+        Self::read_with_limit(data, FILE_LIMIT)
+    }
+
+    fn read_with_limit(data: &[u8], limit: u64) -> Result<Self, Error> {
+        let mut archive = ZipArchive::new(Cursor::new(data))?;
+        let manifest = read_file(&mut archive, MANIFEST, limit)?
+            .ok_or_else(|| Error::Missing(MANIFEST.to_string()))?;
+        let version = manifest.get("version").and_then(Value::as_u64);
+        let old = match version {
+            Some(old) if old >= 1 && old <= VERSION as u64 => old,
+            version => return Err(Error::Version(version)),
+        };
+        let listing = Listing::deserialize(&manifest)
+            .map_err(|err| Error::Json(MANIFEST.to_string(), err))?;
+
+        let mut files = Files::from([(MANIFEST.to_string(), manifest)]);
+        let paths = [PROJECT.to_string(), SETTINGS.to_string()]
+            .into_iter()
+            .chain(listing.docs.into_iter().map(doc_path))
+            .chain(listing.sections.into_iter().map(sec_path))
+            .chain(listing.revisions.into_iter().map(revisions_path));
+        for path in paths {
+            if let Some(value) = read_file(&mut archive, &path, limit)? {
+                files.insert(path, value);
+            }
+        }
+        for migrate in &MIGRATIONS[old as usize - 1..] {
+            migrate(&mut files)?;
+        }
+
+        let manifest: Manifest = take(&mut files, MANIFEST)?;
+        let proj: Proj = take(&mut files, PROJECT)?;
+        let docs = manifest
+            .docs
+            .iter()
+            .map(|id| take(&mut files, &doc_path(*id)))
+            .collect::<Result<_, _>>()?;
+        let sections = manifest
+            .sections
+            .iter()
+            .map(|id| take(&mut files, &sec_path(*id)))
+            .collect::<Result<_, _>>()?;
+        let mut revisions = vec![];
+        for id in &manifest.revisions {
+            revisions.extend(take::<Vec<Revision>>(&mut files, &revisions_path(*id))?);
+        }
+        let settings = match files.contains_key(SETTINGS) {
+            true => take(&mut files, SETTINGS)?,
+            false => Settings::new(),
+        };
+        Ok(Self {
+            proj,
+            docs,
+            sections,
+            revisions,
+            settings,
+        })
+    }
+
+    /// The documents with their sections, in the order of the project.
+    pub fn chapters(&self) -> Vec<(Doc, Vec<Sec>)> {
+        self.proj
+            .content
+            .iter()
+            .filter_map(|head| self.docs.iter().find(|doc| doc.head.id == head.id))
+            .map(|doc| {
+                let sections = doc
+                    .content
+                    .iter()
+                    .filter_map(|head| self.sections.iter().find(|sec| sec.head.id == head.id))
+                    .cloned()
+                    .collect();
+                (doc.clone(), sections)
+            })
+            .collect()
+    }
+}
+
+/// The JSON file of the archive at the path, none when the archive does not have it.
+fn read_file<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    path: &str,
+    limit: u64,
+) -> Result<Option<Value>, Error> {
+    let file = match archive.by_name(path) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let mut content = vec![];
+    file.take(limit + 1).read_to_end(&mut content)?;
+    if content.len() as u64 > limit {
+        return Err(Error::TooLarge(path.to_string()));
+    }
+    let value =
+        serde_json::from_slice(&content).map_err(|err| Error::Json(path.to_string(), err))?;
+    Ok(Some(value))
+}
+
+/// Removes the file from the archive, as the type it holds.
+fn take<T: for<'de> Deserialize<'de>>(files: &mut Files, path: &str) -> Result<T, Error> {
+    let value = files
+        .remove(path)
+        .ok_or_else(|| Error::Missing(path.to_string()))?;
+    serde_json::from_value(value).map_err(|err| Error::Json(path.to_string(), err))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::{DocHead, ProjHead, SecHead};
+    use crate::markup::Markup;
+
+    fn backup() -> Backup {
+        let proj = id::Proj::new(3);
+        let doc = id::Doc::new(1, proj);
+        let sections = vec![
+            SecHead::new(id::Sec::new(0, doc), 0, "Start".to_string())
+                .body(Markup::md_str("Once.")),
+            SecHead::new(id::Sec::new(5, doc), 1, "End".to_string()).body(Markup::md_str("Done.")),
+        ];
+        let heads = sections.iter().map(|sec| sec.head.clone()).collect();
+        let doc = DocHead::new(doc, 0, "Tale".to_string()).body(heads);
+        let proj = ProjHead::new(proj, 0, "Works".to_string()).body(vec![doc.head.clone()]);
+        let settings = Settings::from([("theme".to_string(), Value::from("Solarized"))]);
+        let revisions = ["Done.", "Almost."].map(|text| Revision {
+            sec: sections[1].head.id,
+            commit: format!("{:040}", text.len()),
+            author: "Ada".to_string(),
+            email: "ada@example.org".to_string(),
+            time: 1_700_000_000,
+            message: "Save the section “End”".to_string(),
+            content: text.to_string(),
+        });
+        Backup::new(proj, vec![(doc, sections)])
+            .with_revisions(revisions.to_vec())
+            .with_settings(settings)
+    }
+
+    #[test]
+    fn round_trips() {
+        let backup = backup();
+        let restored = Backup::read(&backup.to_bytes().unwrap()).unwrap();
+        assert_eq!(restored, backup);
+        assert_eq!(restored.chapters()[0].1[1].content.text, "Done.");
+        assert_eq!(restored.manifest().version, VERSION);
+    }
+
+    #[test]
+    fn migrates_old_versions() {
+        let data = backup().to_bytes().unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(&data[..])).unwrap();
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        for i in 0..archive.len() {
+            let file = archive.by_index(i).unwrap();
+            if file.name() != MANIFEST && !file.name().starts_with("revisions/") {
+                zip.raw_copy_file(file).unwrap();
+            }
+        }
+        let mut manifest = serde_json::to_value(backup().manifest()).unwrap();
+        manifest["version"] = Value::from(1);
+        manifest.as_object_mut().unwrap().remove("revisions");
+        zip.start_file(MANIFEST, SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(manifest.to_string().as_bytes()).unwrap();
+        let data = zip.finish().unwrap().into_inner();
+        assert_eq!(
+            Backup::read(&data).unwrap(),
+            backup().with_revisions(vec![])
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        zip.start_file(MANIFEST, SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(br#"{"version": 99}"#).unwrap();
+        let data = zip.finish().unwrap().into_inner();
+        assert!(matches!(Backup::read(&data), Err(Error::Version(Some(99)))));
+    }
+
+    #[test]
+    fn reports_missing_files() {
+        let data = backup().to_bytes().unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(&data[..])).unwrap();
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        for i in 0..archive.len() {
+            let file = archive.by_index(i).unwrap();
+            if !file.name().starts_with("documents/") {
+                zip.raw_copy_file(file).unwrap();
+            }
+        }
+        let data = zip.finish().unwrap().into_inner();
+        let Err(Error::Missing(path)) = Backup::read(&data) else {
+            panic!("the document should be missing");
+        };
+        assert_eq!(path, "documents/0000000300000001.json");
+    }
+
+    #[test]
+    fn reads_only_listed_files() {
+        let data = backup().to_bytes().unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(&data[..])).unwrap();
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        for i in 0..archive.len() {
+            zip.raw_copy_file(archive.by_index(i).unwrap()).unwrap();
+        }
+        zip.start_file(
+            "documents/0000000300000002.json",
+            SimpleFileOptions::default(),
+        )
+        .unwrap();
+        zip.write_all(b"not json").unwrap();
+        let data = zip.finish().unwrap().into_inner();
+        assert_eq!(Backup::read(&data).unwrap(), backup());
+    }
+
+    #[test]
+    fn limits_file_sizes() {
+        let data = backup().to_bytes().unwrap();
+        let Err(Error::TooLarge(path)) = Backup::read_with_limit(&data, 50) else {
+            panic!("a file should be too large");
+        };
+        assert_eq!(path, MANIFEST);
+    }
+}
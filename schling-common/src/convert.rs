@@ -0,0 +1,813 @@
+use std::fmt::{Display, Formatter};
+
+use crate::ast::{Align, Element, Kind, Node, Notes};
+use crate::html::{self, Token};
+use crate::markup::{Markup, MarkupLang};
+use crate::render::ToHtml;
+use crate::sanitize::{text_align, Policy};
+use crate::toc;
+
+/// A construct of the source that the converted markup does not represent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Loss {
+    /// An element removed when the HTML is read with the default policy.
+    Element(String),
+    /// An attribute removed from an element.
+    Attribute { element: String, name: String },
+    /// An element without Markdown equivalent, kept as embedded HTML.
+    Embedded(String),
+    /// Block content of an element holding a single line, such as a table cell.
+    Flattened(String),
+}
+
+impl Display for Loss {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Loss::Element(name) => write!(f, "<{}> elements are removed", name),
+            Loss::Attribute { element, name } => {
+                write!(f, "the {} attribute of <{}> is removed", name, element)
+            }
+            Loss::Embedded(name) => write!(f, "<{}> elements are kept as HTML", name),
+            Loss::Flattened(name) => write!(f, "the content of <{}> is joined into a line", name),
+        }
+    }
+}
+
+/// The markup converted into another language, with the constructs lost on the way.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Conversion {
+    pub markup: Markup,
+    pub losses: Vec<Loss>,
+}
+
+#[derive(Default)]
+struct Losses(Vec<Loss>);
+
+impl Losses {
+    fn push(&mut self, loss: Loss) {
+        if !self.0.contains(&loss) {
+            self.0.push(loss);
+        }
+    }
+}
+
+impl MarkupLang {
+    /// Whether markup in this language can be converted into the other language.
+    pub fn converts_to(self, lang: MarkupLang) -> bool {
+        self == lang || lang != MarkupLang::Fountain
+    }
+}
+
+impl Markup {
+    /// Converts the markup into the language, keeping what the target can represent.
+    ///
+    /// Returns `None` if the language cannot be written, as Fountain is only read.
+    pub fn convert(&self, lang: MarkupLang) -> Option<Conversion> {
+        if !self.lang.converts_to(lang) {
+            return None;
+        }
+        let mut losses = Losses::default();
+        let policy = Policy::default();
+        if self.lang == MarkupLang::Html && lang != MarkupLang::Html {
+            report_policy(&self.text, &policy, &mut losses);
+        }
+        let text = match lang {
+            _ if lang == self.lang => self.text.clone(),
+            MarkupLang::Html => {
+                let html = self.render_html(&policy);
+                report_policy(&html, &policy, &mut losses);
+                html
+            }
+            MarkupLang::Md => {
+                let nodes = self.to_ast(&mut Notes::default(), &policy);
+                let nodes = normalize(nodes, &mut losses);
+                let mut text = join(&blocks(&nodes), false);
+                text.push('\n');
+                text
+            }
+            MarkupLang::Fountain => unreachable!("Fountain is not written"),
+        };
+        Some(Conversion {
+            markup: Markup::new(text, lang),
+            losses: losses.0,
+        })
+    }
+}
+
+/// Reports the elements and attributes of the HTML removed by the policy.
+fn report_policy(src: &str, policy: &Policy, losses: &mut Losses) {
+    for token in html::tokenize(src) {
+        if let Token::Start { name, attrs, .. } = token {
+            if !policy.allows_tag(&name) {
+                losses.push(Loss::Element(name));
+                continue;
+            }
+            let allowed = policy.filter_attributes(&name, attrs.clone());
+            for (attr, _) in attrs.into_iter().filter(|attr| !allowed.contains(attr)) {
+                losses.push(Loss::Attribute {
+                    element: name.clone(),
+                    name: attr,
+                });
+            }
+        }
+    }
+}
+
+fn take(attrs: &mut Vec<(String, String)>, name: &str) -> Option<String> {
+    let pos = attrs.iter().position(|(attr, _)| attr == name)?;
+    Some(attrs.remove(pos).1)
+}
+
+fn report_attributes(name: &str, attrs: Vec<(String, String)>, losses: &mut Losses) {
+    for (attr, _) in attrs {
+        losses.push(Loss::Attribute {
+            element: name.to_string(),
+            name: attr,
+        });
+    }
+}
+
+/// Collapses the white space of HTML text, as browsers do outside of preformatted text.
+fn collapse(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            space = true;
+        } else {
+            if space {
+                out.push(' ');
+                space = false;
+            }
+            out.push(c);
+        }
+    }
+    if space {
+        out.push(' ');
+    }
+    out
+}
+
+/// Replaces the embedded HTML elements having a Markdown equivalent by their native kind.
+fn normalize(nodes: Vec<Node>, losses: &mut Losses) -> Vec<Node> {
+    nodes
+        .into_iter()
+        .map(|node| normalize_node(node, losses))
+        .collect()
+}
+
+fn normalize_node(node: Node, losses: &mut Losses) -> Node {
+    let Element { kind, children } = match node {
+        Node::Text(text) => return Node::Text(collapse(&text)),
+        Node::Element(el) => el,
+        node => return node,
+    };
+    let (name, mut attrs) = match kind {
+        Kind::Html { name, attrs } => (name, attrs),
+        Kind::CodeBlock(_) => return Element { kind, children }.into(),
+        kind => {
+            let children = normalize(children, losses);
+            return Element { kind, children }.into();
+        }
+    };
+    let kind = match name.as_str() {
+        "p" => Kind::Paragraph,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+            // the anchor is kept if it is the one given to the Markdown heading.
+            let text: String = children.iter().map(Node::text).collect();
+            if attrs.contains(&("id".to_string(), toc::slug(&text))) {
+                take(&mut attrs, "id");
+            }
+            Kind::Heading {
+                level: name.as_bytes()[1] - b'0',
+                id: None,
+            }
+        }
+        "blockquote" => Kind::BlockQuote,
+        "ul" => Kind::List(None),
+        "ol" => {
+            let start = take(&mut attrs, "start").and_then(|start| start.parse().ok());
+            Kind::List(Some(start.unwrap_or(1)))
+        }
+        "li" => Kind::Item,
+        "em" | "i" => Kind::Emphasis,
+        "strong" | "b" => Kind::Strong,
+        "del" | "s" => Kind::Strikethrough,
+        "a" => Kind::Link {
+            href: take(&mut attrs, "href"),
+            title: take(&mut attrs, "title").unwrap_or_default(),
+        },
+        "img" => {
+            let alt = take(&mut attrs, "alt").unwrap_or_default();
+            let kind = Kind::Image {
+                src: take(&mut attrs, "src"),
+                title: take(&mut attrs, "title").unwrap_or_default(),
+            };
+            report_attributes(&name, attrs, losses);
+            return Element {
+                kind,
+                children: vec![Node::Text(alt)],
+            }
+            .into();
+        }
+        "br" | "hr" | "code" => {
+            report_attributes(&name, attrs, losses);
+            return match name.as_str() {
+                "br" => Node::HardBreak,
+                "hr" => Node::Rule,
+                _ => {
+                    if children
+                        .iter()
+                        .any(|child| matches!(child, Node::Element(_)))
+                    {
+                        losses.push(Loss::Flattened(name));
+                    }
+                    Node::Code(children.iter().map(Node::text).collect())
+                }
+            };
+        }
+        "pre" => {
+            report_attributes(&name, attrs, losses);
+            let (lang, text) = match children.as_slice() {
+                [Node::Element(code)] if code.is_html("code") => {
+                    let Kind::Html { attrs, .. } = &code.kind else {
+                        unreachable!()
+                    };
+                    let lang = attrs
+                        .iter()
+                        .find(|(attr, _)| attr == "class")
+                        .and_then(|(_, class)| class.strip_prefix("language-"))
+                        .map(str::to_string);
+                    (lang, code.children.iter().map(Node::text).collect())
+                }
+                _ => (None, children.iter().map(Node::text).collect()),
+            };
+            return Element {
+                kind: Kind::CodeBlock(lang),
+                children: vec![Node::Text(text)],
+            }
+            .into();
+        }
+        "table" => {
+            let children = normalize(children, losses);
+            return match table(&children, losses) {
+                Some(rows) if attrs.is_empty() => Element {
+                    kind: Kind::Table,
+                    children: rows,
+                }
+                .into(),
+                _ => {
+                    losses.push(Loss::Embedded(name.clone()));
+                    Element {
+                        kind: Kind::Html { name, attrs },
+                        children,
+                    }
+                    .into()
+                }
+            };
+        }
+        _ => {
+            // the parts of a table are reported with the table.
+            if !TABLE_ELEMENTS.contains(&name.as_str()) {
+                losses.push(Loss::Embedded(name.clone()));
+            }
+            return Element {
+                kind: Kind::Html { name, attrs },
+                children: normalize(children, losses),
+            }
+            .into();
+        }
+    };
+    report_attributes(&name, attrs, losses);
+    Element {
+        kind,
+        children: normalize(children, losses),
+    }
+    .into()
+}
+
+/// The rows of a HTML table as a Markdown table, which has a header row and cells without
+/// spans.
+fn table(children: &[Node], losses: &mut Losses) -> Option<Vec<Node>> {
+    let mut rows = vec![];
+    collect_rows(children, &mut rows)?;
+    let mut rows = rows.into_iter();
+    let head = rows.next()?;
+    let mut cells = |row: &Element, header: bool| -> Option<Vec<Node>> {
+        let mut cells = vec![];
+        for cell in row.children.iter() {
+            let Node::Element(el) = cell else {
+                continue;
+            };
+            let Kind::Html { name, attrs } = &el.kind else {
+                return None;
+            };
+            if name != if header { "th" } else { "td" } {
+                return None;
+            }
+            let mut align = Align::None;
+            for (attr, value) in attrs {
+                let side = match attr.as_str() {
+                    "align" => value.as_str(),
+                    "style" => text_align(value)?,
+                    _ => return None,
+                };
+                align = match side {
+                    "left" => Align::Left,
+                    "center" => Align::Center,
+                    "right" => Align::Right,
+                    _ => return None,
+                };
+            }
+            let children = if el.children.iter().any(is_block) {
+                losses.push(Loss::Flattened(name.clone()));
+                flatten(&el.children)
+            } else {
+                el.children.clone()
+            };
+            cells.push(
+                Element {
+                    kind: Kind::TableCell { header, align },
+                    children,
+                }
+                .into(),
+            );
+        }
+        Some(cells)
+    };
+    let head = cells(head, true)?;
+    let width = head.len();
+    let mut table = vec![Element {
+        kind: Kind::TableHead,
+        children: head,
+    }
+    .into()];
+    for row in rows {
+        let row = cells(row, false)?;
+        if row.len() > width {
+            return None;
+        }
+        table.push(
+            Element {
+                kind: Kind::TableRow,
+                children: row,
+            }
+            .into(),
+        );
+    }
+    Some(table)
+}
+
+/// Collects the rows of the table sections, failing on other content.
+fn collect_rows<'a>(children: &'a [Node], rows: &mut Vec<&'a Element>) -> Option<()> {
+    for child in children {
+        match child {
+            Node::Text(text) if text.trim().is_empty() => {}
+            Node::Element(el) if el.is_html("tr") => rows.push(el),
+            Node::Element(el)
+                if el.is_html("thead") || el.is_html("tbody") || el.is_html("tfoot") =>
+            {
+                collect_rows(&el.children, rows)?
+            }
+            _ => return None,
+        }
+    }
+    Some(())
+}
+
+/// The inline content of the blocks, separated by spaces.
+fn flatten(nodes: &[Node]) -> Vec<Node> {
+    let mut inline = vec![];
+    for node in nodes {
+        match node {
+            Node::Element(el) if is_block(node) => {
+                if !inline.is_empty() {
+                    inline.push(Node::Text(" ".to_string()));
+                }
+                inline.extend(flatten(&el.children));
+            }
+            Node::Rule => {}
+            node => inline.push(node.clone()),
+        }
+    }
+    inline
+}
+
+fn is_block(node: &Node) -> bool {
+    match node {
+        Node::Rule => true,
+        Node::Element(el) => match &el.kind {
+            Kind::Html { name, .. } => html::is_block(name),
+            kind => !matches!(
+                kind,
+                Kind::Emphasis
+                    | Kind::Strong
+                    | Kind::Strikethrough
+                    | Kind::Link { .. }
+                    | Kind::Image { .. }
+            ),
+        },
+        _ => false,
+    }
+}
+
+/// Writes the document tree as Markdown, separating its blocks by blank lines.
+pub(crate) fn markdown(nodes: &[Node]) -> String {
+    join(&blocks(nodes), false)
+}
+
+/// Writes the nodes as Markdown blocks, with the inline content between blocks as paragraphs.
+fn blocks(nodes: &[Node]) -> Vec<String> {
+    let mut blocks = vec![];
+    let mut start = 0;
+    for (pos, node) in nodes.iter().enumerate() {
+        if is_block(node) {
+            push_paragraph(&mut blocks, &nodes[start..pos]);
+            start = pos + 1;
+            blocks.push(block(node));
+        }
+    }
+    push_paragraph(&mut blocks, &nodes[start..]);
+    blocks
+}
+
+fn push_paragraph(blocks: &mut Vec<String>, nodes: &[Node]) {
+    let text = inline(nodes);
+    let text = text.trim();
+    if !text.is_empty() {
+        blocks.push(escape_line_starts(text));
+    }
+}
+
+fn join(blocks: &[String], tight: bool) -> String {
+    blocks.join(if tight { "\n" } else { "\n\n" })
+}
+
+/// Prefixes the lines of the text, keeping empty lines without trailing white space.
+fn prefix_lines(text: &str, first: &str, rest: &str) -> String {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let prefix = if i == 0 { first } else { rest };
+            if line.is_empty() {
+                prefix.trim_end().to_string()
+            } else {
+                format!("{}{}", prefix, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn has_paragraph(el: &Element) -> bool {
+    el.children
+        .iter()
+        .any(|child| matches!(child, Node::Element(el) if el.kind == Kind::Paragraph))
+}
+
+fn block(node: &Node) -> String {
+    let el = match node {
+        Node::Element(el) => el,
+        _ => return "---".to_string(),
+    };
+    match &el.kind {
+        Kind::Paragraph => escape_line_starts(inline(&el.children).trim()),
+        Kind::Heading { level, .. } => {
+            let text = inline(&el.children).replace('\n', " ");
+            format!("{} {}", "#".repeat(*level as usize), text.trim())
+        }
+        Kind::BlockQuote => prefix_lines(&join(&blocks(&el.children), false), "> ", "> "),
+        Kind::CodeBlock(lang) => {
+            let text: String = el.children.iter().map(Node::text).collect();
+            let fence = "`".repeat(3.max(longest_run(&text, '`') + 1));
+            let newline = if text.ends_with('\n') { "" } else { "\n" };
+            format!(
+                "{}{}\n{}{}{}",
+                fence,
+                lang.as_deref().unwrap_or_default(),
+                text,
+                newline,
+                fence
+            )
+        }
+        Kind::List(start) => {
+            let items: Vec<&Element> = el
+                .children
+                .iter()
+                .filter_map(|child| match child {
+                    Node::Element(item) => Some(item),
+                    _ => None,
+                })
+                .collect();
+            let tight = !items.iter().any(|item| has_paragraph(item));
+            let items: Vec<String> = items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    let marker = match start {
+                        Some(start) => format!("{}. ", start + i as u64),
+                        None => "- ".to_string(),
+                    };
+                    let content = join(&blocks(&item.children), !has_paragraph(item));
+                    prefix_lines(&content, &marker, &" ".repeat(marker.len()))
+                })
+                .collect();
+            join(&items, tight)
+        }
+        Kind::Table => {
+            let mut lines = vec![];
+            for row in el.children.iter() {
+                let Node::Element(row) = row else {
+                    continue;
+                };
+                let cells: Vec<&Element> = row
+                    .children
+                    .iter()
+                    .filter_map(|cell| match cell {
+                        Node::Element(cell) => Some(cell),
+                        _ => None,
+                    })
+                    .collect();
+                let text: Vec<String> = cells
+                    .iter()
+                    .map(|cell| {
+                        let text = inline(&cell.children).replace('\n', " ");
+                        text.trim().replace('|', "\\|")
+                    })
+                    .collect();
+                lines.push(format!("| {} |", text.join(" | ")));
+                if row.kind == Kind::TableHead {
+                    let delimiters: Vec<&str> = cells
+                        .iter()
+                        .map(|cell| match cell.kind {
+                            Kind::TableCell {
+                                align: Align::Left, ..
+                            } => ":--",
+                            Kind::TableCell {
+                                align: Align::Center,
+                                ..
+                            } => ":-:",
+                            Kind::TableCell {
+                                align: Align::Right,
+                                ..
+                            } => "--:",
+                            _ => "---",
+                        })
+                        .collect();
+                    lines.push(format!("| {} |", delimiters.join(" | ")));
+                }
+            }
+            lines.join("\n")
+        }
+        Kind::Html { name, .. } if TABLE_ELEMENTS.contains(&name.as_str()) => {
+            // tables are kept on consecutive lines, so that they form a single HTML block.
+            node.to_html()
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        Kind::Html { name, attrs } => {
+            let open = start_tag(name, attrs, false);
+            if el.children.is_empty() {
+                format!("{}</{}>", open, name)
+            } else {
+                format!(
+                    "{}\n\n{}\n\n</{}>",
+                    open,
+                    join(&blocks(&el.children), false),
+                    name
+                )
+            }
+        }
+        _ => join(&blocks(&el.children), false),
+    }
+}
+
+/// The elements of tables, embedded as a whole.
+const TABLE_ELEMENTS: &[&str] = &[
+    "caption", "colgroup", "table", "tbody", "td", "tfoot", "th", "thead", "tr",
+];
+
+fn start_tag(name: &str, attrs: &[(String, String)], void: bool) -> String {
+    let mut tag = format!("<{}", name);
+    for (attr, value) in attrs {
+        tag.push_str(&format!(" {}=\"{}\"", attr, html::escape(value)));
+    }
+    tag.push_str(if void { " />" } else { ">" });
+    tag
+}
+
+fn longest_run(text: &str, c: char) -> usize {
+    text.split(|other| other != c)
+        .map(str::len)
+        .max()
+        .unwrap_or_default()
+}
+
+/// Escapes the characters starting a block at the beginning of lines of a paragraph.
+fn escape_line_starts(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            if line.starts_with(['#', '>', '-', '+', '=']) {
+                format!("\\{}", line)
+            } else if digits > 0 && line[digits..].starts_with(['.', ')']) {
+                format!("{}\\{}", &line[..digits], &line[digits..])
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Escapes the characters of the text having a meaning in Markdown.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '&' | '~' | '$'
+        ) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn inline(nodes: &[Node]) -> String {
+    let mut out = String::new();
+    for node in nodes {
+        match node {
+            Node::Text(text) if out.ends_with('\n') => out.push_str(&escape(text.trim_start())),
+            Node::Text(text) => out.push_str(&escape(text)),
+            Node::Code(code) => {
+                let fence = "`".repeat(longest_run(code, '`') + 1);
+                let pad = if code.starts_with(['`', ' ']) || code.ends_with(['`', ' ']) {
+                    " "
+                } else {
+                    ""
+                };
+                out.push_str(&format!("{0}{1}{2}{1}{0}", fence, pad, code));
+            }
+            Node::SoftBreak => out.push('\n'),
+            Node::HardBreak => out.push_str("\\\n"),
+            Node::Rule => {}
+            Node::TaskMarker(checked) => out.push_str(if *checked { "[x] " } else { "[ ] " }),
+            Node::Math(math) => out.push_str(&math.delimited()),
+            Node::FootnoteRef(note) => out.push_str(&format!("[^{}]", note.number)),
+            Node::Element(el) => inline_element(el, &mut out),
+        }
+    }
+    out
+}
+
+fn inline_element(el: &Element, out: &mut String) {
+    let children = inline(&el.children);
+    match &el.kind {
+        Kind::Emphasis | Kind::Strong | Kind::Strikethrough if children.trim().is_empty() => {
+            out.push_str(&children)
+        }
+        Kind::Emphasis => out.push_str(&format!("*{}*", children)),
+        Kind::Strong => out.push_str(&format!("**{}**", children)),
+        Kind::Strikethrough => out.push_str(&format!("~~{}~~", children)),
+        Kind::Link {
+            href: Some(href),
+            title,
+        } => out.push_str(&format!("[{}]({})", children, destination(href, title))),
+        Kind::Image {
+            src: Some(src),
+            title,
+        } => {
+            let alt: String = el.children.iter().map(Node::text).collect();
+            out.push_str(&format!("![{}]({})", escape(&alt), destination(src, title)));
+        }
+        Kind::Image { src: None, .. } => {
+            let alt: String = el.children.iter().map(Node::text).collect();
+            out.push_str(&escape(&alt));
+        }
+        Kind::Html { name, attrs } if html::is_void(name) => {
+            out.push_str(&start_tag(name, attrs, true))
+        }
+        Kind::Html { name, attrs } => {
+            out.push_str(&start_tag(name, attrs, false));
+            out.push_str(&children);
+            out.push_str(&format!("</{}>", name));
+        }
+        _ => out.push_str(&children),
+    }
+}
+
+fn destination(url: &str, title: &str) -> String {
+    let mut dest = if url.is_empty() || url.contains([' ', '(', ')', '<', '>']) {
+        format!("<{}>", url.replace('<', "%3C").replace('>', "%3E"))
+    } else {
+        url.to_string()
+    };
+    if !title.is_empty() {
+        dest.push_str(&format!(" \"{}\"", title.replace('"', "\\\"")));
+    }
+    dest
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn to_md(html: &str) -> Conversion {
+        Markup::html_str(html).convert(MarkupLang::Md).unwrap()
+    }
+
+    #[test]
+    fn html_to_markdown() {
+        let conversion = to_md(
+            "<h2 id=\"top\">Title</h2>\n<p>Some <em>em</em>, <b>bold</b>,\n  \
+            <code>x`y</code> and <a href=\"https://example.org\" title=\"T\">a link</a>.</p>\n\
+            <ul><li>one</li><li>two<ol start=\"3\"><li>three</li></ol></li></ul>\
+            <pre><code class=\"language-rust\">fn main() {\n}\n</code></pre>\
+            <blockquote><p>1. not a list</p><p>*stars*</p></blockquote><hr>\
+            <table><thead><tr><th>a</th><th align=\"right\">b|c</th></tr></thead>\
+            <tbody><tr><td>1</td><td><p>2</p></td></tr></tbody></table>",
+        );
+        assert_eq!(
+            conversion.markup.text,
+            "## Title\n\n\
+            Some *em*, **bold**, ``x`y`` and [a link](https://example.org \"T\").\n\n\
+            - one\n\
+            - two\n  \
+              3. three\n\n\
+            ```rust\nfn main() {\n}\n```\n\n\
+            > 1\\. not a list\n>\n> \\*stars\\*\n\n\
+            ---\n\n\
+            | a | b\\|c |\n| --- | --: |\n| 1 | 2 |\n"
+        );
+        assert_eq!(
+            conversion.losses,
+            vec![
+                Loss::Attribute {
+                    element: "h2".to_string(),
+                    name: "id".to_string()
+                },
+                Loss::Flattened("td".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_losses() {
+        let conversion =
+            to_md("<div class=\"x\"><p>a <u>b</u><script>c</script></p></div><table><tr><td>d</td></tr></table>");
+        assert_eq!(
+            conversion.markup.text,
+            "<div class=\"x\">\n\na <u>b</u>\n\n</div>\n\n<table><tr><td>d</td></tr></table>\n"
+        );
+        assert_eq!(
+            conversion.losses,
+            vec![
+                Loss::Element("script".to_string()),
+                Loss::Embedded("div".to_string()),
+                Loss::Embedded("u".to_string()),
+                Loss::Embedded("table".to_string()),
+            ]
+        );
+
+        let conversion = Markup::md_str("- [x] done[^1]\n\n[^1]: Note.")
+            .convert(MarkupLang::Html)
+            .unwrap();
+        assert_eq!(
+            conversion.losses,
+            vec![
+                Loss::Element("input".to_string()),
+                Loss::Element("section".to_string()),
+                Loss::Attribute {
+                    element: "li".to_string(),
+                    name: "value".to_string()
+                },
+            ]
+        );
+        assert_eq!(Markup::md_str("a").convert(MarkupLang::Fountain), None);
+    }
+
+    #[test]
+    fn markdown_round_trip() {
+        let docs = [
+            "# Title\n\nSome *em*, **strong**, ~~gone~~ and `code`.\n",
+            "A [link](https://example.org \"Title\") and ![an image](cat.png).\n",
+            "Hard\\\nbreak and a <span class=\"x\">span</span>.\n",
+            "> quote\n>\n> - a\n> - b\n",
+            "1. one\n\n   more\n\n2. two\n",
+            "```rust\nfn main() {}\n```\n\n---\n\nText with \\* and \\_ and # signs.\n",
+            "| a | b |\n| --- | --- |\n| 1 | 2 |\n",
+            "| a | b | c |\n| :-- | :-: | --: |\n| 1 | 2 | 3 |\n",
+            "Costs \\$5 and \\$10.\n",
+        ];
+        for md in docs {
+            let html = Markup::md_str(md).convert(MarkupLang::Html).unwrap();
+            assert!(html.losses.is_empty(), "{}: {:?}", md, html.losses);
+            let back = html.markup.convert(MarkupLang::Md).unwrap();
+            assert_eq!(back.markup.text, md);
+        }
+    }
+}
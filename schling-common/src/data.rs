@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{id, markup::Markup, ord_by};
+
+macro_rules! data_for_head {
+    ($head:ident, $body:ident, $content:ty) => {
+        #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+        pub struct $head {
+            pub id: id::$body,
+            pub order: u32,
+            pub title: String,
+            /// The previous titles, which links may still refer to.
+            #[serde(default, skip_serializing_if = "Vec::is_empty")]
+            pub aliases: Vec<String>,
+            /// A short summary of the content, as on an index card.
+            #[serde(default, skip_serializing_if = "String::is_empty")]
+            pub synopsis: String,
+            /// The label of the content, such as the point of view of a scene.
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            pub label: Option<String>,
+            /// The progress of the writing, such as a first draft.
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            pub status: Option<String>,
+        }
+        ord_by!($head, order);
+
+        impl $head {
+            pub fn new(id: id::$body, order: u32, title: String) -> Self {
+                Self {
+                    id,
+                    order,
+                    title,
+                    aliases: vec![],
+                    synopsis: String::new(),
+                    label: None,
+                    status: None,
+                }
+            }
+
+            pub fn body(self, content: $content) -> $body {
+                $body {
+                    head: self,
+                    content,
+                }
+            }
+
+            /// Renames the head, keeping the previous title as an alias.
+            pub fn own_with_title(&self, title: String) -> Self {
+                let mut aliases = self.aliases.clone();
+                let previous = self.title.trim();
+                if !previous.is_empty() && previous != title.trim() {
+                    aliases.retain(|alias| alias != previous && alias != title.trim());
+                    aliases.push(previous.to_string());
+                }
+                Self {
+                    title,
+                    aliases,
+                    ..self.clone()
+                }
+            }
+        }
+
+        #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+        pub struct $body {
+            pub head: $head,
+            pub content: $content,
+        }
+        ord_by!($body, head);
+
+        impl $body {
+            pub fn new(head: $head, content: $content) -> Self {
+                Self { head, content }
+            }
+
+            pub fn own_with_title(&self, title: String) -> Self {
+                Self {
+                    head: self.head.own_with_title(title),
+                    content: self.content.clone(),
+                }
+            }
+
+            pub fn own_with_content(&self, content: $content) -> Self {
+                Self {
+                    head: self.head.clone(),
+                    content,
+                }
+            }
+        }
+    };
+}
+
+data_for_head!(SecHead, Sec, Markup);
+data_for_head!(DocHead, Doc, Vec<SecHead>);
+data_for_head!(ProjHead, Proj, Vec<DocHead>);
+
+/// The document `value` of the first project, with a Markdown section per title and text, as the
+/// exports take it.
+#[cfg(test)]
+pub(crate) fn chapter(value: u32, title: &str, texts: &[(&str, &str)]) -> (Doc, Vec<Sec>) {
+    let doc = id::Doc::new(value, id::Proj::new(1));
+    let sections: Vec<Sec> = texts
+        .iter()
+        .enumerate()
+        .map(|(i, (title, text))| {
+            let id = id::Sec::new(i as u32, doc);
+            SecHead::new(id, i as u32, title.to_string()).body(Markup::md_str(text))
+        })
+        .collect();
+    let heads = sections.iter().map(|sec| sec.head.clone()).collect();
+    (
+        DocHead::new(doc, value, title.to_string()).body(heads),
+        sections,
+    )
+}
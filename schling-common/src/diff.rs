@@ -0,0 +1,301 @@
+#[cfg(feature = "yew-wasm")]
+use yew::{html, Html};
+
+#[cfg(feature = "yew-wasm")]
+use crate::source::ToDom;
+use crate::source::{Symbol, Text, Trivia};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// Consecutive symbols with the same kind of change.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Run<'a> {
+    pub change: Change,
+    pub symbols: Vec<Symbol<'a>>,
+}
+
+impl<'a> Run<'a> {
+    pub fn text(&self) -> String {
+        self.symbols.iter().map(|s| s.as_str()).collect()
+    }
+
+    fn is_space(&self) -> bool {
+        self.symbols
+            .iter()
+            .all(|s| matches!(s, Symbol::Trivia(Trivia::Space(_))))
+    }
+}
+
+/// The changes transforming one text into another.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Diff<'a> {
+    pub runs: Vec<Run<'a>>,
+}
+
+impl<'a> Diff<'a> {
+    pub fn is_identical(&self) -> bool {
+        self.runs.iter().all(|r| r.change == Change::Equal)
+    }
+
+    fn push(&mut self, change: Change, symbol: Symbol<'a>) {
+        match self.runs.last_mut() {
+            Some(run) if run.change == change => run.symbols.push(symbol),
+            _ => self.runs.push(Run {
+                change,
+                symbols: vec![symbol],
+            }),
+        }
+    }
+
+    fn push_run(&mut self, run: Run<'a>) {
+        for symbol in run.symbols {
+            self.push(run.change, symbol);
+        }
+    }
+
+    /// Absorbs spaces between two changes into the changes, so that replacing multiple words
+    /// yields a single deletion followed by a single insertion.
+    fn cleanup(self) -> Self {
+        let mut runs = self.runs.into_iter().peekable();
+        let mut deleted = Diff::default();
+        let mut inserted = Diff::default();
+        let mut result = Diff::default();
+        let mut changed = false;
+
+        while let Some(run) = runs.next() {
+            let bridge = run.change == Change::Equal
+                && changed
+                && run.is_space()
+                && matches!(runs.peek(), Some(next) if next.change != Change::Equal);
+            match run.change {
+                Change::Equal if !bridge => {
+                    result.flush(&mut deleted, &mut inserted);
+                    changed = false;
+                    result.push_run(run);
+                }
+                Change::Equal => {
+                    for symbol in run.symbols {
+                        deleted.push(Change::Delete, symbol);
+                        inserted.push(Change::Insert, symbol);
+                    }
+                }
+                Change::Delete => {
+                    changed = true;
+                    deleted.push_run(run);
+                }
+                Change::Insert => {
+                    changed = true;
+                    inserted.push_run(run);
+                }
+            }
+        }
+        result.flush(&mut deleted, &mut inserted);
+        result
+    }
+
+    fn flush(&mut self, deleted: &mut Diff<'a>, inserted: &mut Diff<'a>) {
+        for run in deleted.runs.drain(..).chain(inserted.runs.drain(..)) {
+            self.push_run(run);
+        }
+    }
+}
+
+/// Computes the shortest edit script between the symbols of `old` and `new`, using the
+/// algorithm of Myers, so that changes align to whole words and punctuation.
+pub fn diff<'a>(old: &Text<'a>, new: &Text<'a>) -> Diff<'a> {
+    let old = &old.symbols[..];
+    let new = &new.symbols[..];
+
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut diff = Diff::default();
+    for symbol in &old[..prefix] {
+        diff.push(Change::Equal, *symbol);
+    }
+    for (change, symbol) in myers(
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    ) {
+        diff.push(change, symbol);
+    }
+    for symbol in &old[old.len() - suffix..] {
+        diff.push(Change::Equal, *symbol);
+    }
+    diff.cleanup()
+}
+
+fn myers<'a>(old: &[Symbol<'a>], new: &[Symbol<'a>]) -> Vec<(Change, Symbol<'a>)> {
+    let n = old.len() as isize;
+    let m = new.len() as isize;
+    let max = (n + m) as usize;
+    let offset = max as isize;
+    let mut v = vec![0isize; 2 * max + 2];
+    let mut trace = vec![];
+
+    'search: for d in 0..=max as isize {
+        // only the diagonals -d..=d are read when walking back from step d.
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // walk the trace backwards to recover the edit script.
+    let mut edits = vec![];
+    let (mut x, mut y) = (n, m);
+    for (d, window) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        // the furthest x on the diagonal before step d, 0 outside of the window at step 0.
+        let v = |k: isize| match k.abs() <= d {
+            true => window[(k + d) as usize],
+            false => 0,
+        };
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v(k - 1) < v(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            edits.push((Change::Equal, old[x as usize]));
+        }
+        if d > 0 {
+            if x == prev_x {
+                y -= 1;
+                edits.push((Change::Insert, new[y as usize]));
+            } else {
+                x -= 1;
+                edits.push((Change::Delete, old[x as usize]));
+            }
+        }
+    }
+    edits.reverse();
+    edits
+}
+
+#[cfg(feature = "yew-wasm")]
+impl<'a> ToDom for Run<'a> {
+    fn to_dom(self) -> Html {
+        let symbols = html! {
+            {for self.symbols.into_iter().map(|symbol| symbol.to_dom())}
+        };
+        match self.change {
+            Change::Equal => symbols,
+            Change::Insert => html!(<ins class="diff-ins">{symbols}</ins>),
+            Change::Delete => html!(<del class="diff-del">{symbols}</del>),
+        }
+    }
+}
+
+#[cfg(feature = "yew-wasm")]
+impl<'a> ToDom for Diff<'a> {
+    fn to_dom(self) -> Html {
+        html! {
+        <span class="diff">
+            {for self.runs.into_iter().map(|run| run.to_dom())}
+        </span>
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn runs(old: &str, new: &str) -> Vec<(Change, String)> {
+        diff(&Text::parse(old), &Text::parse(new))
+            .runs
+            .iter()
+            .map(|run| (run.change, run.text()))
+            .collect()
+    }
+
+    #[test]
+    fn identical() {
+        let text = Text::parse("Nothing changed.");
+        assert!(diff(&text, &text).is_identical());
+    }
+
+    #[test]
+    fn aligns_to_words() {
+        assert_eq!(
+            runs("The quick fox.", "The quick brown fox!"),
+            vec![
+                (Change::Equal, "The quick ".to_string()),
+                (Change::Insert, "brown ".to_string()),
+                (Change::Equal, "fox".to_string()),
+                (Change::Delete, ".".to_string()),
+                (Change::Insert, "!".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn merges_replaced_words() {
+        assert_eq!(
+            runs("a red old car", "a blue new car"),
+            vec![
+                (Change::Equal, "a ".to_string()),
+                (Change::Delete, "red old".to_string()),
+                (Change::Insert, "blue new".to_string()),
+                (Change::Equal, " car".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn from_and_to_empty() {
+        assert_eq!(runs("", "new"), vec![(Change::Insert, "new".to_string())]);
+        assert_eq!(runs("old", ""), vec![(Change::Delete, "old".to_string())]);
+    }
+
+    #[test]
+    fn recovers_long_rewrites() {
+        let old: Vec<String> = (0..400).map(|i| format!("w{}", i)).collect();
+        let new: Vec<String> = (0..400)
+            .map(|i| match i % 3 {
+                0 => format!("x{}", i),
+                _ => format!("w{}", i),
+            })
+            .collect();
+        let (old, new) = (old.join(" "), new.join(" "));
+        let runs = runs(&old, &new);
+        let side = |skipped: Change| -> String {
+            runs.iter()
+                .filter(|(change, _)| *change != skipped)
+                .map(|(_, text)| text.as_str())
+                .collect()
+        };
+        assert_eq!(side(Change::Insert), old);
+        assert_eq!(side(Change::Delete), new);
+    }
+}
@@ -0,0 +1,1639 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io::{Cursor, Read, Write};
+
+use roxmltree::{Document, Node as Xml};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::ast::{Align, Element, Kind, Node, NoteRef, Notes};
+use crate::convert;
+use crate::data::{Doc, DocHead, Sec, SecHead};
+use crate::html;
+use crate::id;
+use crate::manuscript::{Manuscript, Typeface};
+use crate::markup::{Markup, MarkupLang};
+use crate::sanitize::Policy;
+
+const W: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+const R: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+const PACKAGE_RELS: &str = "http://schemas.openxmlformats.org/package/2006/relationships";
+const DC: &str = "http://purl.org/dc/elements/1.1/";
+
+/// The fonts recognized as code when they format a run.
+pub(crate) const MONOSPACE_FONTS: &[&str] = &[
+    "consolas",
+    "courier",
+    "courier new",
+    "dejavu sans mono",
+    "menlo",
+    "monaco",
+    "source code pro",
+];
+
+/// The reason a Word document cannot be written or read.
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Zip(zip::result::ZipError),
+    Xml(roxmltree::Error),
+    /// The package lacks a required part, such as the main document.
+    Missing(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "cannot access the document: {}", err),
+            Error::Zip(err) => write!(f, "malformed document package: {}", err),
+            Error::Xml(err) => write!(f, "malformed document part: {}", err),
+            Error::Missing(part) => write!(f, "the document has no {}", part),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(err: zip::result::ZipError) -> Self {
+        Error::Zip(err)
+    }
+}
+
+impl From<roxmltree::Error> for Error {
+    fn from(err: roxmltree::Error) -> Self {
+        Error::Xml(err)
+    }
+}
+
+/// Writes the document as a Word document.
+///
+/// The title of the document is the title paragraph, and the titled sections start with a
+/// first level heading, so the headings of their content are one level lower. Every section
+/// but the first starts on a new page.
+pub fn export(doc: &Doc, sections: &[Sec]) -> Result<Vec<u8>, Error> {
+    let mut writer = Writer::default();
+    let mut notes = Notes::default();
+    let mut body = paragraph("Title", &runs(&doc.head.title, ""), false);
+    for (i, sec) in sections.iter().enumerate() {
+        writer.page_break = i > 0;
+        if !sec.head.title.trim().is_empty() {
+            let title = runs(&sec.head.title, "");
+            body.push_str(&paragraph("Heading1", &title, writer.page_break));
+            writer.page_break = false;
+        }
+        notes.set_scope(&format!("section-{}-", sec.head.id));
+        let nodes = sec.content.to_ast(&mut notes, &Policy::default());
+        body.push_str(&writer.blocks(&nodes, &Context::default()));
+        if writer.page_break {
+            body.push_str(&paragraph("", "", true));
+        }
+    }
+    let footnotes = writer.footnotes(notes);
+    writer.package(&doc.head.title, &body, &footnotes, &styles(None), None)
+}
+
+/// Writes the manuscript in the Standard Manuscript Format.
+pub(crate) fn manuscript(manuscript: &Manuscript) -> Result<Vec<u8>, Error> {
+    let mut writer = Writer {
+        scene_break: "#".to_string(),
+        ..Writer::default()
+    };
+    let mut body = String::new();
+    let contact = std::iter::once(&manuscript.author).chain(&manuscript.contact);
+    for (i, line) in contact.enumerate() {
+        let mut content = runs(line, "");
+        if i == 0 {
+            content.push_str("<w:r><w:tab/></w:r>");
+            content.push_str(&runs(&manuscript.word_count(), ""));
+        }
+        body.push_str(&paragraph("Contact", &content, false));
+    }
+    body.push_str(&paragraph("Title", &runs(&manuscript.title, ""), false));
+    let byline = format!("by {}", manuscript.byline());
+    body.push_str(&paragraph("Byline", &runs(&byline, ""), false));
+    let mut notes = Notes::default();
+    for (i, sec) in manuscript.sections.iter().enumerate() {
+        if i > 0 {
+            body.push_str(&paragraph("SceneBreak", &runs("#", ""), false));
+        }
+        notes.set_scope(&format!("section-{}-", sec.head.id));
+        let nodes = sec.content.to_ast(&mut notes, &Policy::default());
+        body.push_str(&writer.blocks(&nodes, &Context::default()));
+    }
+    body.push_str(&paragraph("SceneBreak", &runs("END", ""), false));
+    let footnotes = writer.footnotes(notes);
+    let header = format!(
+        "<w:p><w:pPr><w:pStyle w:val=\"Header\"/></w:pPr>{}\
+        <w:fldSimple w:instr=\" PAGE \"><w:r><w:t>2</w:t></w:r></w:fldSimple></w:p>",
+        runs(&format!("{} / ", manuscript.header()), "")
+    );
+    let styles = styles(Some(manuscript.typeface));
+    writer.package(&manuscript.title, &body, &footnotes, &styles, Some(&header))
+}
+
+const XML_DECLARATION: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n";
+
+const NAMESPACES: &str =
+    "xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\" \
+xmlns:r=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships\"";
+
+/// The content types of the parts, with a header part when the pages have one.
+fn content_types(header: bool) -> String {
+    let mut types = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+        <Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\
+        <Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+        <Default Extension=\"xml\" ContentType=\"application/xml\"/>\
+        <Override PartName=\"/word/document.xml\" \
+        ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml\"/>\
+        <Override PartName=\"/word/styles.xml\" \
+        ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml\"/>\
+        <Override PartName=\"/word/numbering.xml\" \
+        ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.numbering+xml\"/>\
+        <Override PartName=\"/word/footnotes.xml\" \
+        ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.footnotes+xml\"/>\
+        <Override PartName=\"/docProps/core.xml\" \
+        ContentType=\"application/vnd.openxmlformats-package.core-properties+xml\"/>",
+    );
+    if header {
+        types.push_str(
+            "<Override PartName=\"/word/header1.xml\" \
+            ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.header+xml\"/>",
+        );
+    }
+    types.push_str("</Types>");
+    types
+}
+
+const ROOT_RELS: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\
+<Relationship Id=\"rId1\" \
+Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument\" \
+Target=\"word/document.xml\"/>\
+<Relationship Id=\"rId2\" \
+Type=\"http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties\" \
+Target=\"docProps/core.xml\"/>\
+</Relationships>";
+
+/// US Letter pages with margins of an inch, with the header on all pages but the first.
+fn section_properties(header: bool) -> String {
+    let header = if header {
+        "<w:headerReference w:type=\"default\" r:id=\"rIdHeader\"/>"
+    } else {
+        ""
+    };
+    let title_page = if header.is_empty() {
+        ""
+    } else {
+        "<w:titlePg/>"
+    };
+    format!(
+        "<w:sectPr>{}<w:pgSz w:w=\"12240\" w:h=\"15840\"/>\
+        <w:pgMar w:top=\"1440\" w:right=\"1440\" w:bottom=\"1440\" w:left=\"1440\" \
+        w:header=\"720\" w:footer=\"720\" w:gutter=\"0\"/>{}</w:sectPr>",
+        header, title_page
+    )
+}
+
+fn core_properties(title: &str) -> String {
+    format!(
+        "{}<cp:coreProperties \
+        xmlns:cp=\"http://schemas.openxmlformats.org/package/2006/metadata/core-properties\" \
+        xmlns:dc=\"http://purl.org/dc/elements/1.1/\"><dc:title>{}</dc:title></cp:coreProperties>",
+        XML_DECLARATION,
+        xml_text(title)
+    )
+}
+
+/// The styles of the exported documents, named as Word names its built-in styles.
+///
+/// The styles of manuscripts set all text in the typeface at 12 points, double spaced with
+/// indented paragraphs.
+fn styles(manuscript: Option<Typeface>) -> String {
+    let font = match manuscript {
+        None => "Cambria",
+        Some(Typeface::Courier) => "Courier New",
+        Some(Typeface::Times) => "Times New Roman",
+    };
+    let (spacing, normal, emphasis, title_size, small) = match manuscript {
+        None => ("<w:spacing w:after=\"120\"/>", "", "<w:b/>", 40, 20),
+        Some(_) => (
+            "<w:spacing w:after=\"0\" w:line=\"480\" w:lineRule=\"auto\"/>",
+            "<w:pPr><w:ind w:firstLine=\"720\"/></w:pPr>",
+            "",
+            24,
+            24,
+        ),
+    };
+    let mut styles = format!(
+        "<w:docDefaults><w:rPrDefault><w:rPr>\
+        <w:rFonts w:ascii=\"{0}\" w:hAnsi=\"{0}\" w:eastAsia=\"{0}\" w:cs=\"{0}\"/>\
+        <w:sz w:val=\"24\"/></w:rPr></w:rPrDefault>\
+        <w:pPrDefault><w:pPr>{1}</w:pPr></w:pPrDefault></w:docDefaults>\
+        <w:style w:type=\"paragraph\" w:default=\"1\" w:styleId=\"Normal\"><w:name w:val=\"Normal\"/>{2}</w:style>\
+        <w:style w:type=\"paragraph\" w:styleId=\"Title\"><w:name w:val=\"Title\"/>\
+        <w:basedOn w:val=\"Normal\"/><w:next w:val=\"Normal\"/>\
+        <w:pPr><w:jc w:val=\"center\"/><w:spacing w:before=\"{3}\" w:after=\"{4}\"/>\
+        <w:ind w:firstLine=\"0\"/></w:pPr>\
+        <w:rPr>{5}<w:sz w:val=\"{6}\"/></w:rPr></w:style>",
+        font,
+        spacing,
+        normal,
+        if manuscript.is_some() { 2880 } else { 480 },
+        if manuscript.is_some() { 0 } else { 480 },
+        emphasis,
+        title_size
+    );
+    for level in 1..=9 {
+        let size = match manuscript {
+            None => 36_u32.saturating_sub(4 * level).max(24),
+            Some(_) => 24,
+        };
+        styles.push_str(&format!(
+            "<w:style w:type=\"paragraph\" w:styleId=\"Heading{0}\"><w:name w:val=\"heading {0}\"/>\
+            <w:basedOn w:val=\"Normal\"/><w:next w:val=\"Normal\"/>\
+            <w:pPr><w:keepNext/><w:spacing w:before=\"240\"/><w:ind w:firstLine=\"0\"/>{1}\
+            <w:outlineLvl w:val=\"{2}\"/></w:pPr>\
+            <w:rPr>{3}<w:sz w:val=\"{4}\"/></w:rPr></w:style>",
+            level,
+            if manuscript.is_some() {
+                "<w:jc w:val=\"center\"/>"
+            } else {
+                ""
+            },
+            level - 1,
+            emphasis,
+            size
+        ));
+    }
+    if manuscript.is_some() {
+        styles.push_str(
+            "<w:style w:type=\"paragraph\" w:styleId=\"Contact\"><w:name w:val=\"Contact\"/>\
+            <w:basedOn w:val=\"Normal\"/><w:pPr><w:tabs><w:tab w:val=\"right\" w:pos=\"9360\"/></w:tabs>\
+            <w:spacing w:line=\"240\" w:lineRule=\"auto\"/><w:ind w:firstLine=\"0\"/></w:pPr></w:style>\
+            <w:style w:type=\"paragraph\" w:styleId=\"Byline\"><w:name w:val=\"Byline\"/>\
+            <w:basedOn w:val=\"Normal\"/><w:next w:val=\"Normal\"/>\
+            <w:pPr><w:jc w:val=\"center\"/><w:spacing w:after=\"480\"/><w:ind w:firstLine=\"0\"/></w:pPr></w:style>\
+            <w:style w:type=\"paragraph\" w:styleId=\"Header\"><w:name w:val=\"header\"/>\
+            <w:basedOn w:val=\"Normal\"/><w:pPr><w:jc w:val=\"right\"/>\
+            <w:spacing w:line=\"240\" w:lineRule=\"auto\"/><w:ind w:firstLine=\"0\"/></w:pPr></w:style>",
+        );
+    }
+    styles.push_str(&format!(
+        "<w:style w:type=\"paragraph\" w:styleId=\"SceneBreak\"><w:name w:val=\"Scene Break\"/>\
+        <w:basedOn w:val=\"Normal\"/><w:next w:val=\"Normal\"/>\
+        <w:pPr><w:jc w:val=\"center\"/><w:ind w:firstLine=\"0\"/></w:pPr></w:style>\
+        <w:style w:type=\"paragraph\" w:styleId=\"Quote\"><w:name w:val=\"Quote\"/>\
+        <w:basedOn w:val=\"Normal\"/><w:pPr><w:ind w:left=\"720\" w:right=\"720\" w:firstLine=\"0\"/></w:pPr>\
+        <w:rPr>{1}</w:rPr></w:style>\
+        <w:style w:type=\"paragraph\" w:styleId=\"SourceCode\"><w:name w:val=\"Source Code\"/>\
+        <w:basedOn w:val=\"Normal\"/><w:pPr><w:spacing w:after=\"0\"/><w:ind w:firstLine=\"0\"/></w:pPr>\
+        <w:rPr><w:rFonts w:ascii=\"Courier New\" w:hAnsi=\"Courier New\"/><w:sz w:val=\"{0}\"/></w:rPr></w:style>\
+        <w:style w:type=\"paragraph\" w:styleId=\"ListParagraph\"><w:name w:val=\"List Paragraph\"/>\
+        <w:basedOn w:val=\"Normal\"/><w:pPr><w:spacing w:after=\"0\"/><w:ind w:left=\"720\" w:firstLine=\"0\"/></w:pPr></w:style>\
+        <w:style w:type=\"paragraph\" w:styleId=\"FootnoteText\"><w:name w:val=\"footnote text\"/>\
+        <w:basedOn w:val=\"Normal\"/><w:pPr><w:spacing w:line=\"240\" w:lineRule=\"auto\"/>\
+        <w:ind w:firstLine=\"0\"/></w:pPr><w:rPr><w:sz w:val=\"{0}\"/></w:rPr></w:style>\
+        <w:style w:type=\"character\" w:styleId=\"FootnoteReference\"><w:name w:val=\"footnote reference\"/>\
+        <w:rPr><w:vertAlign w:val=\"superscript\"/></w:rPr></w:style>\
+        <w:style w:type=\"character\" w:styleId=\"VerbatimChar\"><w:name w:val=\"Verbatim Char\"/>\
+        <w:rPr><w:rFonts w:ascii=\"Courier New\" w:hAnsi=\"Courier New\"/><w:sz w:val=\"{0}\"/></w:rPr></w:style>\
+        <w:style w:type=\"character\" w:styleId=\"Hyperlink\"><w:name w:val=\"Hyperlink\"/>\
+        <w:rPr><w:color w:val=\"0563C1\"/><w:u w:val=\"single\"/></w:rPr></w:style>\
+        <w:style w:type=\"table\" w:styleId=\"Table\"><w:name w:val=\"Table\"/>\
+        <w:tblPr><w:tblBorders>\
+        <w:top w:val=\"single\" w:sz=\"4\" w:space=\"0\" w:color=\"888888\"/>\
+        <w:left w:val=\"single\" w:sz=\"4\" w:space=\"0\" w:color=\"888888\"/>\
+        <w:bottom w:val=\"single\" w:sz=\"4\" w:space=\"0\" w:color=\"888888\"/>\
+        <w:right w:val=\"single\" w:sz=\"4\" w:space=\"0\" w:color=\"888888\"/>\
+        <w:insideH w:val=\"single\" w:sz=\"4\" w:space=\"0\" w:color=\"888888\"/>\
+        <w:insideV w:val=\"single\" w:sz=\"4\" w:space=\"0\" w:color=\"888888\"/>\
+        </w:tblBorders></w:tblPr></w:style>",
+        small,
+        if manuscript.is_some() { "" } else { "<w:i/>" }
+    ));
+    format!(
+        "{}<w:styles {}>{}</w:styles>",
+        XML_DECLARATION, NAMESPACES, styles
+    )
+}
+
+/// The text without the characters XML does not allow, escaped.
+fn xml_text(text: &str) -> String {
+    let text: String = text
+        .chars()
+        .filter(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+        .collect();
+    html::escape(&text).into_owned()
+}
+
+/// A run of plain text with the properties.
+fn runs(text: &str, properties: &str) -> String {
+    if text.is_empty() {
+        return String::new();
+    }
+    let properties = if properties.is_empty() {
+        String::new()
+    } else {
+        format!("<w:rPr>{}</w:rPr>", properties)
+    };
+    format!(
+        "<w:r>{}<w:t xml:space=\"preserve\">{}</w:t></w:r>",
+        properties,
+        xml_text(text)
+    )
+}
+
+/// A paragraph of the style with the runs, optionally starting on a new page.
+fn paragraph(style: &str, runs: &str, page_break: bool) -> String {
+    let mut properties = String::new();
+    if !style.is_empty() {
+        properties.push_str(&format!("<w:pStyle w:val=\"{}\"/>", style));
+    }
+    if page_break {
+        properties.push_str("<w:pageBreakBefore/>");
+    }
+    if properties.is_empty() {
+        format!("<w:p>{}</w:p>", runs)
+    } else {
+        format!("<w:p><w:pPr>{}</w:pPr>{}</w:p>", properties, runs)
+    }
+}
+
+/// The formatting of the runs of an inline element.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Format {
+    pub(crate) bold: bool,
+    pub(crate) italic: bool,
+    pub(crate) strike: bool,
+    pub(crate) code: bool,
+    link: bool,
+}
+
+impl Format {
+    fn properties(self) -> String {
+        let mut properties = String::new();
+        if self.code {
+            properties.push_str("<w:rStyle w:val=\"VerbatimChar\"/>");
+        } else if self.link {
+            properties.push_str("<w:rStyle w:val=\"Hyperlink\"/>");
+        }
+        if self.bold {
+            properties.push_str("<w:b/>");
+        }
+        if self.italic {
+            properties.push_str("<w:i/>");
+        }
+        if self.strike {
+            properties.push_str("<w:strike/>");
+        }
+        properties
+    }
+}
+
+/// The surroundings of the blocks being written.
+#[derive(Clone, Debug, Default)]
+struct Context {
+    /// The style of the paragraphs, such as in a quote.
+    style: String,
+    /// The numbering instance and level of the items of the enclosing list.
+    list: Option<(usize, usize)>,
+}
+
+impl Context {
+    fn footnote() -> Self {
+        Self {
+            style: "FootnoteText".to_string(),
+            list: None,
+        }
+    }
+}
+
+/// Writes the document tree as WordprocessingML, collecting the parts it refers to.
+struct Writer {
+    /// The targets of the hyperlinks, by the index of their relationship.
+    links: Vec<String>,
+    /// The start of the numbered lists and `None` for the bulleted ones, by their instance.
+    lists: Vec<Option<u64>>,
+    /// Whether the next paragraph starts a new page.
+    page_break: bool,
+    /// Whether footnotes are written, which cannot reference footnotes.
+    footnote: bool,
+    /// The text of the centered paragraphs marking scene breaks.
+    scene_break: String,
+}
+
+impl Default for Writer {
+    fn default() -> Self {
+        Self {
+            links: vec![],
+            lists: vec![],
+            page_break: false,
+            footnote: false,
+            scene_break: "* * *".to_string(),
+        }
+    }
+}
+
+impl Writer {
+    /// The footnotes, each opening with its reference mark.
+    fn footnotes(&mut self, notes: Notes) -> String {
+        let mut footnotes = String::new();
+        self.footnote = true;
+        for note in notes.into_notes() {
+            let content = self.blocks(&note.content, &Context::footnote());
+            let content = match content.find("</w:pPr>") {
+                Some(pos) => {
+                    let (head, tail) = content.split_at(pos + "</w:pPr>".len());
+                    format!(
+                        "{}<w:r><w:rPr><w:rStyle w:val=\"FootnoteReference\"/></w:rPr>\
+                        <w:footnoteRef/></w:r><w:r><w:t xml:space=\"preserve\"> </w:t></w:r>{}",
+                        head, tail
+                    )
+                }
+                None => content,
+            };
+            footnotes.push_str(&format!(
+                "<w:footnote w:id=\"{}\">{}</w:footnote>",
+                note.number, content
+            ));
+        }
+        footnotes
+    }
+
+    /// The package of the document with the body, and the header of the pages after the
+    /// first.
+    fn package(
+        &self,
+        title: &str,
+        body: &str,
+        footnotes: &str,
+        styles: &str,
+        header: Option<&str>,
+    ) -> Result<Vec<u8>, Error> {
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let mut add = |name: &str, content: &str| -> Result<(), Error> {
+            zip.start_file(name, options)?;
+            zip.write_all(content.as_bytes())?;
+            Ok(())
+        };
+        add("[Content_Types].xml", &content_types(header.is_some()))?;
+        add("_rels/.rels", ROOT_RELS)?;
+        add("docProps/core.xml", &core_properties(title))?;
+        add(
+            "word/_rels/document.xml.rels",
+            &self.relationships(header.is_some()),
+        )?;
+        add(
+            "word/document.xml",
+            &format!(
+                "{}<w:document {}><w:body>{}{}</w:body></w:document>",
+                XML_DECLARATION,
+                NAMESPACES,
+                body,
+                section_properties(header.is_some())
+            ),
+        )?;
+        add("word/styles.xml", styles)?;
+        add("word/numbering.xml", &self.numbering())?;
+        add(
+            "word/footnotes.xml",
+            &format!(
+                "{}<w:footnotes {}>\
+                <w:footnote w:type=\"separator\" w:id=\"-1\"><w:p><w:r><w:separator/></w:r></w:p></w:footnote>\
+                <w:footnote w:type=\"continuationSeparator\" w:id=\"0\"><w:p><w:r><w:continuationSeparator/></w:r></w:p></w:footnote>\
+                {}</w:footnotes>",
+                XML_DECLARATION, NAMESPACES, footnotes
+            ),
+        )?;
+        if let Some(header) = header {
+            add(
+                "word/header1.xml",
+                &format!(
+                    "{}<w:hdr {}>{}</w:hdr>",
+                    XML_DECLARATION, NAMESPACES, header
+                ),
+            )?;
+        }
+        Ok(zip.finish()?.into_inner())
+    }
+
+    fn blocks(&mut self, nodes: &[Node], context: &Context) -> String {
+        let mut out = String::new();
+        let mut inline: Vec<Node> = vec![];
+        for node in nodes {
+            if is_block(node) {
+                self.flush(&mut inline, context, &mut out);
+                self.block(node, context, &mut out);
+            } else {
+                inline.push(node.clone());
+            }
+        }
+        self.flush(&mut inline, context, &mut out);
+        out
+    }
+
+    /// Writes the inline nodes between blocks as a paragraph.
+    fn flush(&mut self, inline: &mut Vec<Node>, context: &Context, out: &mut String) {
+        if inline.iter().all(|node| node.text().trim().is_empty()) {
+            inline.clear();
+            return;
+        }
+        let runs = self.runs(inline, Format::default());
+        out.push_str(&self.paragraph(context, &runs));
+        inline.clear();
+    }
+
+    fn paragraph(&mut self, context: &Context, runs: &str) -> String {
+        let mut properties = String::new();
+        let style = match context.list {
+            Some(_) => "ListParagraph",
+            None => &context.style,
+        };
+        if !style.is_empty() {
+            properties.push_str(&format!("<w:pStyle w:val=\"{}\"/>", style));
+        }
+        if std::mem::take(&mut self.page_break) {
+            properties.push_str("<w:pageBreakBefore/>");
+        }
+        if let Some((list, level)) = context.list {
+            properties.push_str(&format!(
+                "<w:numPr><w:ilvl w:val=\"{}\"/><w:numId w:val=\"{}\"/></w:numPr>",
+                level,
+                list + 1
+            ));
+        }
+        if properties.is_empty() {
+            format!("<w:p>{}</w:p>", runs)
+        } else {
+            format!("<w:p><w:pPr>{}</w:pPr>{}</w:p>", properties, runs)
+        }
+    }
+
+    fn block(&mut self, node: &Node, context: &Context, out: &mut String) {
+        let el = match node {
+            Node::Element(el) => el,
+            _ => {
+                let context = Context {
+                    style: "SceneBreak".to_string(),
+                    list: None,
+                };
+                let runs = runs(&self.scene_break, "");
+                out.push_str(&self.paragraph(&context, &runs));
+                return;
+            }
+        };
+        match &el.kind {
+            Kind::Paragraph => {
+                let runs = self.runs(&el.children, Format::default());
+                out.push_str(&self.paragraph(context, &runs));
+            }
+            Kind::Heading { level, .. } => {
+                let runs = self.runs(&el.children, Format::default());
+                let context = Context {
+                    style: format!("Heading{}", (level + 1).min(9)),
+                    list: None,
+                };
+                out.push_str(&self.paragraph(&context, &runs));
+            }
+            Kind::BlockQuote => {
+                let context = Context {
+                    style: "Quote".to_string(),
+                    ..context.clone()
+                };
+                out.push_str(&self.blocks(&el.children, &context));
+            }
+            Kind::CodeBlock(_) => {
+                let code: String = el.children.iter().map(Node::text).collect();
+                let context = Context {
+                    style: "SourceCode".to_string(),
+                    list: None,
+                };
+                for line in code.trim_end_matches('\n').split('\n') {
+                    let runs = runs(line, "");
+                    out.push_str(&self.paragraph(&context, &runs));
+                }
+            }
+            Kind::List(start) => {
+                self.lists.push(*start);
+                let list = self.lists.len() - 1;
+                let level = context.list.map_or(0, |(_, level)| level + 1);
+                for item in &el.children {
+                    let children = match item {
+                        Node::Element(item) => &item.children,
+                        _ => continue,
+                    };
+                    let context = Context {
+                        style: context.style.clone(),
+                        list: Some((list, level.min(8))),
+                    };
+                    out.push_str(&self.blocks(children, &context));
+                }
+            }
+            Kind::Table => self.table(el, out),
+            _ => out.push_str(&self.blocks(&el.children, context)),
+        }
+    }
+
+    fn table(&mut self, table: &Element, out: &mut String) {
+        let rows: Vec<&Element> = table
+            .children
+            .iter()
+            .filter_map(|row| match row {
+                Node::Element(row) => Some(row),
+                _ => None,
+            })
+            .collect();
+        let columns = rows.iter().map(|row| row.children.len()).max().unwrap_or(0);
+        if columns == 0 {
+            return;
+        }
+        out.push_str(
+            "<w:tbl><w:tblPr><w:tblStyle w:val=\"Table\"/><w:tblW w:w=\"0\" w:type=\"auto\"/></w:tblPr>\
+            <w:tblGrid>",
+        );
+        for _ in 0..columns {
+            out.push_str("<w:gridCol w:w=\"2000\"/>");
+        }
+        out.push_str("</w:tblGrid>");
+        for row in rows {
+            out.push_str("<w:tr>");
+            if row.kind == Kind::TableHead {
+                out.push_str("<w:trPr><w:tblHeader/></w:trPr>");
+            }
+            for cell in &row.children {
+                let (format, children) = match cell {
+                    Node::Element(Element {
+                        kind: Kind::TableCell { header, .. },
+                        children,
+                    }) => (
+                        Format {
+                            bold: *header,
+                            ..Format::default()
+                        },
+                        &children[..],
+                    ),
+                    node => (Format::default(), std::slice::from_ref(node)),
+                };
+                let runs = self.runs(children, format);
+                out.push_str(&format!(
+                    "<w:tc><w:tcPr><w:tcW w:w=\"0\" w:type=\"auto\"/></w:tcPr><w:p>{}</w:p></w:tc>",
+                    runs
+                ));
+            }
+            out.push_str("</w:tr>");
+        }
+        out.push_str("</w:tbl>");
+    }
+
+    fn runs(&mut self, nodes: &[Node], format: Format) -> String {
+        let mut out = String::new();
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(&runs(text, &format.properties())),
+                Node::Code(code) => {
+                    let format = Format {
+                        code: true,
+                        ..format
+                    };
+                    out.push_str(&runs(code, &format.properties()));
+                }
+                Node::SoftBreak => out.push_str(&runs(" ", &format.properties())),
+                Node::HardBreak => out.push_str("<w:r><w:br/></w:r>"),
+                Node::Rule => {}
+                Node::TaskMarker(checked) => {
+                    let marker = if *checked { "☒ " } else { "☐ " };
+                    out.push_str(&runs(marker, &format.properties()));
+                }
+                Node::Math(math) => out.push_str(&runs(&math.tex, &format.properties())),
+                Node::FootnoteRef(note) if !self.footnote => out.push_str(&format!(
+                    "<w:r><w:rPr><w:rStyle w:val=\"FootnoteReference\"/></w:rPr>\
+                    <w:footnoteReference w:id=\"{}\"/></w:r>",
+                    note.number
+                )),
+                Node::FootnoteRef(_) => {}
+                Node::Element(el) => {
+                    let format = match &el.kind {
+                        Kind::Emphasis => Format {
+                            italic: true,
+                            ..format
+                        },
+                        Kind::Strong => Format {
+                            bold: true,
+                            ..format
+                        },
+                        Kind::Strikethrough => Format {
+                            strike: true,
+                            ..format
+                        },
+                        Kind::Image { .. } => Format {
+                            italic: true,
+                            ..format
+                        },
+                        Kind::Html { name, .. } => match name.as_str() {
+                            "em" | "i" | "cite" => Format {
+                                italic: true,
+                                ..format
+                            },
+                            "strong" | "b" => Format {
+                                bold: true,
+                                ..format
+                            },
+                            "del" | "s" => Format {
+                                strike: true,
+                                ..format
+                            },
+                            "code" | "kbd" | "samp" => Format {
+                                code: true,
+                                ..format
+                            },
+                            "br" => {
+                                out.push_str("<w:r><w:br/></w:r>");
+                                continue;
+                            }
+                            _ => format,
+                        },
+                        _ => format,
+                    };
+                    match &el.kind {
+                        Kind::Link {
+                            href: Some(href), ..
+                        } => {
+                            let format = Format {
+                                link: true,
+                                ..format
+                            };
+                            let runs = self.runs(&el.children, format);
+                            let target = match href.strip_prefix('#') {
+                                Some(anchor) => format!("w:anchor=\"{}\"", xml_text(anchor)),
+                                None => {
+                                    self.links.push(href.clone());
+                                    format!("r:id=\"rIdLink{}\"", self.links.len())
+                                }
+                            };
+                            out.push_str(&format!(
+                                "<w:hyperlink {}>{}</w:hyperlink>",
+                                target, runs
+                            ));
+                        }
+                        _ => out.push_str(&self.runs(&el.children, format)),
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    fn relationships(&self, header: bool) -> String {
+        let mut rels = format!(
+            "{}<Relationships xmlns=\"{}\">\
+            <Relationship Id=\"rIdStyles\" Type=\"{}/styles\" Target=\"styles.xml\"/>\
+            <Relationship Id=\"rIdNumbering\" Type=\"{}/numbering\" Target=\"numbering.xml\"/>\
+            <Relationship Id=\"rIdFootnotes\" Type=\"{}/footnotes\" Target=\"footnotes.xml\"/>",
+            XML_DECLARATION, PACKAGE_RELS, R, R, R
+        );
+        if header {
+            rels.push_str(&format!(
+                "<Relationship Id=\"rIdHeader\" Type=\"{}/header\" Target=\"header1.xml\"/>",
+                R
+            ));
+        }
+        for (i, link) in self.links.iter().enumerate() {
+            rels.push_str(&format!(
+                "<Relationship Id=\"rIdLink{}\" Type=\"{}/hyperlink\" Target=\"{}\" \
+                TargetMode=\"External\"/>",
+                i + 1,
+                R,
+                xml_text(link)
+            ));
+        }
+        rels.push_str("</Relationships>");
+        rels
+    }
+
+    /// The numbering definitions, with an instance for each list to restart its numbers.
+    fn numbering(&self) -> String {
+        let mut numbering = String::new();
+        for (id, bullet) in [(0, true), (1, false)] {
+            numbering.push_str(&format!("<w:abstractNum w:abstractNumId=\"{}\">", id));
+            for level in 0..9 {
+                let (format, text) = if bullet {
+                    ("bullet".to_string(), "•".to_string())
+                } else {
+                    ("decimal".to_string(), format!("%{}.", level + 1))
+                };
+                numbering.push_str(&format!(
+                    "<w:lvl w:ilvl=\"{}\"><w:start w:val=\"1\"/><w:numFmt w:val=\"{}\"/>\
+                    <w:lvlText w:val=\"{}\"/><w:lvlJc w:val=\"left\"/>\
+                    <w:pPr><w:ind w:left=\"{}\" w:hanging=\"360\"/></w:pPr></w:lvl>",
+                    level,
+                    format,
+                    text,
+                    720 * (level + 1)
+                ));
+            }
+            numbering.push_str("</w:abstractNum>");
+        }
+        for (i, start) in self.lists.iter().enumerate() {
+            let abstract_id = if start.is_some() { 1 } else { 0 };
+            numbering.push_str(&format!(
+                "<w:num w:numId=\"{}\"><w:abstractNumId w:val=\"{}\"/>",
+                i + 1,
+                abstract_id
+            ));
+            if let Some(start) = start {
+                numbering.push_str(&format!(
+                    "<w:lvlOverride w:ilvl=\"0\"><w:startOverride w:val=\"{}\"/></w:lvlOverride>",
+                    start
+                ));
+            }
+            numbering.push_str("</w:num>");
+        }
+        format!(
+            "{}<w:numbering {}>{}</w:numbering>",
+            XML_DECLARATION, NAMESPACES, numbering
+        )
+    }
+}
+
+fn is_block(node: &Node) -> bool {
+    match node {
+        Node::Rule => true,
+        Node::Element(el) => match &el.kind {
+            Kind::Html { name, .. } => {
+                matches!(
+                    name.as_str(),
+                    "p" | "div" | "section" | "blockquote" | "figure"
+                )
+            }
+            Kind::Emphasis
+            | Kind::Strong
+            | Kind::Strikethrough
+            | Kind::Link { .. }
+            | Kind::Image { .. } => false,
+            _ => true,
+        },
+        _ => false,
+    }
+}
+
+/// Reads a Word document as a document with Markdown sections.
+///
+/// A first level heading or a page break starts a new section, titled by the heading. The
+/// title paragraph, or else the title of the document properties, is the title of the
+/// document.
+pub fn import(data: &[u8], id: id::Doc) -> Result<(Doc, Vec<Sec>), Error> {
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+    let rels = read_part(&mut archive, "_rels/.rels")?.unwrap_or_default();
+    let main = Document::parse(&rels)
+        .ok()
+        .and_then(|rels| {
+            rels.descendants()
+                .find(|rel| {
+                    rel.attribute("Type")
+                        .is_some_and(|kind| kind.ends_with("/officeDocument"))
+                })
+                .and_then(|rel| rel.attribute("Target"))
+                .map(|target| target.trim_start_matches('/').to_string())
+        })
+        .unwrap_or_else(|| "word/document.xml".to_string());
+    let (dir, file) = main.rsplit_once('/').unwrap_or(("", &main));
+    let part = |name: &str| {
+        if dir.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", dir, name)
+        }
+    };
+
+    let document = read_part(&mut archive, &main)?
+        .ok_or_else(|| Error::Missing("main document".to_string()))?;
+    let rels = read_part(&mut archive, &part(&format!("_rels/{}.rels", file)))?;
+    let styles = read_part(&mut archive, &part("styles.xml"))?;
+    let numbering = read_part(&mut archive, &part("numbering.xml"))?;
+    let footnotes = read_part(&mut archive, &part("footnotes.xml"))?;
+    let core = read_part(&mut archive, "docProps/core.xml")?;
+
+    let mut reader = Reader::default();
+    if let Some(rels) = &rels {
+        for rel in Document::parse(rels)?.descendants() {
+            if let (Some(id), Some(target)) = (rel.attribute("Id"), rel.attribute("Target")) {
+                reader.links.insert(id.to_string(), target.to_string());
+            }
+        }
+    }
+    if let Some(styles) = &styles {
+        for style in Document::parse(styles)?
+            .descendants()
+            .filter(|el| is(el, "style"))
+        {
+            let name = style
+                .children()
+                .find(|el| is(el, "name"))
+                .and_then(|el| attr(&el, "val"));
+            if let (Some(id), Some(name)) = (attr(&style, "styleId"), name) {
+                reader.styles.insert(id.to_string(), name.to_lowercase());
+            }
+        }
+    }
+    if let Some(numbering) = &numbering {
+        reader.read_numbering(&Document::parse(numbering)?);
+    }
+    if let Some(footnotes) = &footnotes {
+        let footnotes = Document::parse(footnotes)?;
+        for note in footnotes.descendants().filter(|el| is(el, "footnote")) {
+            if let Some(id) = attr(&note, "id") {
+                reader
+                    .footnotes
+                    .insert(id.to_string(), reader.note_content(note));
+            }
+        }
+    }
+
+    let document = Document::parse(&document)?;
+    let body = document
+        .descendants()
+        .find(|el| is(el, "body"))
+        .ok_or_else(|| Error::Missing("body".to_string()))?;
+    reader.sections.push(Part::default());
+    reader.read_blocks(body);
+
+    let title = reader
+        .title
+        .clone()
+        .or_else(|| {
+            let core = Document::parse(core.as_deref()?).ok()?;
+            let title = core
+                .descendants()
+                .find(|el| el.has_tag_name((DC, "title")))?
+                .text()?
+                .trim()
+                .to_string();
+            (!title.is_empty()).then_some(title)
+        })
+        .unwrap_or_default();
+    let mut parts: Vec<Part> = reader
+        .sections
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect();
+    for part in &mut parts {
+        number_notes(&mut part.blocks, &mut part.notes);
+    }
+    let sections: Vec<Sec> = parts
+        .into_iter()
+        .enumerate()
+        .map(|(i, part)| {
+            let head = SecHead::new(id::Sec::new(i as u32, id), i as u32, part.title.clone());
+            head.body(Markup::new(part.markdown(), MarkupLang::Md))
+        })
+        .collect();
+    let heads = sections.iter().map(|sec| sec.head.clone()).collect();
+    Ok((DocHead::new(id, 0, title).body(heads), sections))
+}
+
+fn read_part(archive: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<Option<String>, Error> {
+    let mut file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    Ok(Some(content))
+}
+
+/// Whether the node is the WordprocessingML element with the name.
+fn is(node: &Xml, name: &str) -> bool {
+    node.has_tag_name((W, name))
+}
+
+fn attr<'a>(node: &Xml<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attribute((W, name))
+}
+
+/// The value of the child element of the properties, such as the style of a paragraph.
+fn property<'a>(properties: Option<Xml<'a, '_>>, name: &str) -> Option<Xml<'a, 'a>> {
+    properties?.children().find(|el| is(el, name))
+}
+
+/// Whether a toggle property such as `w:b` is set.
+fn toggle(properties: Option<Xml>, name: &str) -> bool {
+    property(properties, name)
+        .is_some_and(|el| !matches!(attr(&el, "val"), Some("0" | "false" | "off" | "none")))
+}
+
+/// A section being read.
+#[derive(Default)]
+pub(crate) struct Part {
+    pub(crate) title: String,
+    pub(crate) blocks: Vec<Node>,
+    /// The content of the footnotes, by their number in the section.
+    pub(crate) notes: Vec<Vec<Node>>,
+}
+
+impl Part {
+    fn is_empty(&self) -> bool {
+        self.title.trim().is_empty() && self.blocks.is_empty()
+    }
+
+    pub(crate) fn markdown(&self) -> String {
+        let mut text = convert::markdown(&self.blocks);
+        for (i, note) in self.notes.iter().enumerate() {
+            let content = convert::markdown(note);
+            let label = format!("[^{}]: ", i + 1);
+            let content: Vec<String> = content
+                .lines()
+                .enumerate()
+                .map(|(j, line)| match j {
+                    0 => format!("{}{}", label, line),
+                    _ if line.is_empty() => String::new(),
+                    _ => format!("    {}", line),
+                })
+                .collect();
+            text.push_str("\n\n");
+            text.push_str(&content.join("\n"));
+        }
+        text.push('\n');
+        text
+    }
+}
+
+/// A piece of a paragraph: formatted text, or a node such as a link or a break.
+pub(crate) enum Piece {
+    Text(Format, String),
+    Node(Node),
+}
+
+/// Reads the parts of a Word document into sections.
+#[derive(Default)]
+struct Reader {
+    /// The names of the styles by their id, in lowercase.
+    styles: HashMap<String, String>,
+    /// The targets of the relationships by their id.
+    links: HashMap<String, String>,
+    /// The start of each level of the numbered lists, `None` for bullets, by numbering id.
+    numbering: HashMap<String, Vec<Option<u64>>>,
+    footnotes: HashMap<String, Vec<Node>>,
+    title: Option<String>,
+    sections: Vec<Part>,
+}
+
+impl Reader {
+    fn read_numbering(&mut self, numbering: &Document) {
+        let mut abstracts = HashMap::new();
+        for definition in numbering.descendants().filter(|el| is(el, "abstractNum")) {
+            let levels: Vec<Option<u64>> = definition
+                .children()
+                .filter(|el| is(el, "lvl"))
+                .map(|level| {
+                    let properties = Some(level);
+                    let format = property(properties, "numFmt").and_then(|el| attr(&el, "val"));
+                    let start = property(properties, "start")
+                        .and_then(|el| attr(&el, "val")?.parse().ok())
+                        .unwrap_or(1);
+                    match format {
+                        Some("bullet") | None => None,
+                        Some(_) => Some(start),
+                    }
+                })
+                .collect();
+            if let Some(id) = attr(&definition, "abstractNumId") {
+                abstracts.insert(id.to_string(), levels);
+            }
+        }
+        for num in numbering.descendants().filter(|el| is(el, "num")) {
+            let definition = property(Some(num), "abstractNumId").and_then(|el| attr(&el, "val"));
+            let (Some(id), Some(definition)) = (attr(&num, "numId"), definition) else {
+                continue;
+            };
+            let mut levels = abstracts.get(definition).cloned().unwrap_or_default();
+            for level in num.children().filter(|el| is(el, "lvlOverride")) {
+                let index: Option<usize> = attr(&level, "ilvl").and_then(|i| i.parse().ok());
+                let start: Option<u64> = property(Some(level), "startOverride")
+                    .and_then(|el| attr(&el, "val")?.parse().ok());
+                if let (Some(index), Some(start)) = (index, start) {
+                    if let Some(Some(level)) = levels.get_mut(index) {
+                        *level = start;
+                    }
+                }
+            }
+            self.numbering.insert(id.to_string(), levels);
+        }
+    }
+
+    fn style(&self, properties: Option<Xml>, name: &str) -> String {
+        property(properties, name)
+            .and_then(|el| attr(&el, "val"))
+            .map(|id| {
+                self.styles
+                    .get(id)
+                    .cloned()
+                    .unwrap_or_else(|| id.to_lowercase())
+            })
+            .unwrap_or_default()
+    }
+
+    /// The paragraphs of a footnote.
+    fn note_content(&self, note: Xml) -> Vec<Node> {
+        note.children()
+            .filter(|el| is(el, "p"))
+            .filter_map(|p| {
+                let mut pieces = vec![];
+                self.read_runs(p, Format::default(), &mut pieces, &mut vec![]);
+                let mut nodes = group(pieces);
+                trim_start(&mut nodes);
+                (!nodes.is_empty()).then(|| paragraph_node(nodes))
+            })
+            .collect()
+    }
+
+    fn part(&mut self) -> &mut Part {
+        self.sections.last_mut().expect("a section is being read")
+    }
+
+    fn new_section(&mut self) {
+        if !self.part().is_empty() {
+            self.sections.push(Part::default());
+        }
+    }
+
+    fn read_blocks(&mut self, parent: Xml) {
+        for el in parent.children().filter(Xml::is_element) {
+            if is(&el, "p") {
+                self.read_paragraph(el);
+            } else if is(&el, "tbl") {
+                self.read_table(el);
+            } else if is(&el, "sdt") || is(&el, "sdtContent") || is(&el, "customXml") {
+                self.read_blocks(el);
+            }
+        }
+    }
+
+    fn read_paragraph(&mut self, p: Xml) {
+        let properties = p.children().find(|el| is(el, "pPr"));
+        let style = self.style(properties, "pStyle");
+        let mut pieces = vec![];
+        let mut breaks = vec![];
+        self.read_runs(p, Format::default(), &mut pieces, &mut breaks);
+        let break_before = toggle(properties, "pageBreakBefore") || breaks.first() == Some(&0);
+        let break_after = breaks.iter().any(|pos| *pos > 0);
+        if break_before {
+            self.new_section();
+        }
+
+        if style.starts_with("source code") || style == "code" || style == "html preformatted" {
+            let text: String = pieces
+                .iter()
+                .map(|piece| match piece {
+                    Piece::Text(_, text) => text.clone(),
+                    Piece::Node(node) => node.text(),
+                })
+                .collect();
+            match self.part().blocks.last_mut() {
+                Some(Node::Element(el)) if matches!(el.kind, Kind::CodeBlock(_)) => {
+                    el.children.push(Node::Text(format!("{}\n", text)))
+                }
+                _ => {
+                    let mut el = Element::new(Kind::CodeBlock(None));
+                    el.children.push(Node::Text(format!("{}\n", text)));
+                    self.part().blocks.push(el.into());
+                }
+            }
+        } else {
+            let mut nodes = group(pieces);
+            trim_start(&mut nodes);
+            let text: String = nodes.iter().map(Node::text).collect();
+            let level = style
+                .strip_prefix("heading ")
+                .and_then(|level| level.trim().parse::<u8>().ok());
+            let list = property(properties, "numPr").and_then(|num| {
+                let id = property(Some(num), "numId").and_then(|el| attr(&el, "val"))?;
+                let level: usize = property(Some(num), "ilvl")
+                    .and_then(|el| attr(&el, "val")?.parse().ok())
+                    .unwrap_or(0);
+                let levels = self.numbering.get(id)?;
+                Some((level, levels.get(level).copied().flatten()))
+            });
+            if style == "title" {
+                if self.title.is_none() {
+                    self.title = Some(text.trim().to_string());
+                }
+            } else if level == Some(1) {
+                if !self.part().is_empty() {
+                    self.sections.push(Part::default());
+                }
+                self.part().title = text.trim().to_string();
+            } else if text.trim().is_empty() && !nodes.iter().any(is_note) {
+                // empty paragraphs only add space.
+            } else if let Some(level) = level {
+                let mut el = Element::new(Kind::Heading {
+                    level: (level - 1).min(6),
+                    id: None,
+                });
+                el.children = nodes;
+                self.part().blocks.push(el.into());
+            } else if let Some((level, start)) = list {
+                push_item(&mut self.part().blocks, level, start, nodes);
+            } else if matches!(text.trim(), "* * *" | "***" | "#" | "⁂") {
+                self.part().blocks.push(Node::Rule);
+            } else if style.contains("quote") || style == "block text" {
+                let paragraph = paragraph_node(nodes);
+                match self.part().blocks.last_mut() {
+                    Some(Node::Element(el)) if el.kind == Kind::BlockQuote => {
+                        el.children.push(paragraph)
+                    }
+                    _ => {
+                        let mut el = Element::new(Kind::BlockQuote);
+                        el.children.push(paragraph);
+                        self.part().blocks.push(el.into());
+                    }
+                }
+            } else {
+                self.part().blocks.push(paragraph_node(nodes));
+            }
+        }
+        if break_after {
+            self.new_section();
+        }
+    }
+
+    fn read_table(&mut self, table: Xml) {
+        let mut el = Element::new(Kind::Table);
+        for (i, row) in table.children().filter(|el| is(el, "tr")).enumerate() {
+            let mut cells = vec![];
+            for cell in row.children().filter(|el| is(el, "tc")) {
+                let mut nodes = vec![];
+                for p in cell.children().filter(|el| is(el, "p")) {
+                    let mut pieces = vec![];
+                    self.read_runs(p, Format::default(), &mut pieces, &mut vec![]);
+                    if !nodes.is_empty() {
+                        nodes.push(Node::Text(" ".to_string()));
+                    }
+                    nodes.extend(group(pieces));
+                }
+                trim_start(&mut nodes);
+                if i == 0 {
+                    // the header cells are strong without being marked so.
+                    nodes = unwrap_strong(nodes);
+                }
+                cells.push(Node::Element(Element {
+                    kind: Kind::TableCell {
+                        header: i == 0,
+                        align: Align::None,
+                    },
+                    children: nodes,
+                }));
+            }
+            el.children.push(Node::Element(Element {
+                kind: if i == 0 {
+                    Kind::TableHead
+                } else {
+                    Kind::TableRow
+                },
+                children: cells,
+            }));
+        }
+        if !el.children.is_empty() {
+            self.part().blocks.push(el.into());
+        }
+    }
+
+    /// Reads the runs of the element, and the positions of its page breaks in the pieces.
+    fn read_runs(
+        &self,
+        parent: Xml,
+        format: Format,
+        pieces: &mut Vec<Piece>,
+        breaks: &mut Vec<usize>,
+    ) {
+        for el in parent.children().filter(Xml::is_element) {
+            if is(&el, "r") {
+                self.read_run(el, format, pieces, breaks);
+            } else if is(&el, "hyperlink") {
+                let href = match (attr_r(&el, "id"), attr(&el, "anchor")) {
+                    (Some(id), _) => self.links.get(id).cloned(),
+                    (None, Some(anchor)) => Some(format!("#{}", anchor)),
+                    _ => None,
+                };
+                let mut inner = vec![];
+                self.read_runs(el, format, &mut inner, breaks);
+                let children = group(inner);
+                match href {
+                    Some(href) => pieces.push(Piece::Node(Node::Element(Element {
+                        kind: Kind::Link {
+                            href: Some(href),
+                            title: String::new(),
+                        },
+                        children,
+                    }))),
+                    None => pieces.extend(children.into_iter().map(Piece::Node)),
+                }
+            } else if is(&el, "ins")
+                || is(&el, "smartTag")
+                || is(&el, "customXml")
+                || is(&el, "fldSimple")
+                || is(&el, "sdt")
+                || is(&el, "sdtContent")
+            {
+                self.read_runs(el, format, pieces, breaks);
+            }
+        }
+    }
+
+    fn read_run(&self, run: Xml, format: Format, pieces: &mut Vec<Piece>, breaks: &mut Vec<usize>) {
+        let properties = run.children().find(|el| is(el, "rPr"));
+        let style = self.style(properties, "rStyle");
+        let font = property(properties, "rFonts")
+            .and_then(|el| attr(&el, "ascii"))
+            .unwrap_or_default()
+            .to_lowercase();
+        let format = Format {
+            bold: format.bold || toggle(properties, "b"),
+            italic: format.italic || toggle(properties, "i"),
+            strike: format.strike || toggle(properties, "strike") || toggle(properties, "dstrike"),
+            code: format.code
+                || style.contains("verbatim")
+                || style.contains("code")
+                || MONOSPACE_FONTS.contains(&font.as_str()),
+            link: false,
+        };
+        let push = |pieces: &mut Vec<Piece>, text: &str| match pieces.last_mut() {
+            Some(Piece::Text(last, content)) if *last == format => content.push_str(text),
+            _ => pieces.push(Piece::Text(format, text.to_string())),
+        };
+        for el in run.children().filter(Xml::is_element) {
+            if is(&el, "t") {
+                push(pieces, el.text().unwrap_or_default());
+            } else if is(&el, "tab") {
+                push(pieces, " ");
+            } else if is(&el, "noBreakHyphen") {
+                push(pieces, "-");
+            } else if is(&el, "br") && attr(&el, "type") == Some("page") {
+                breaks.push(pieces.len());
+            } else if is(&el, "br") || is(&el, "cr") {
+                pieces.push(Piece::Node(Node::HardBreak));
+            } else if is(&el, "footnoteReference") {
+                let content = attr(&el, "id").and_then(|id| self.footnotes.get(id));
+                pieces.push(Piece::Node(Node::FootnoteRef(NoteRef {
+                    number: 0,
+                    target: String::new(),
+                    id: None,
+                })));
+                pieces.push(Piece::Node(Node::Element(Element {
+                    kind: Kind::Html {
+                        name: FOOTNOTE.to_string(),
+                        attrs: vec![],
+                    },
+                    children: content.cloned().unwrap_or_default(),
+                })));
+            }
+        }
+    }
+}
+
+/// The placeholder element carrying the content of a footnote until its section is known.
+const FOOTNOTE: &str = "footnote";
+
+fn attr_r<'a>(node: &Xml<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attribute((R, name))
+}
+
+fn is_note(node: &Node) -> bool {
+    matches!(node, Node::FootnoteRef(_))
+}
+
+/// Numbers the footnotes of the paragraph in the section, taking their content.
+fn number_notes(nodes: &mut Vec<Node>, notes: &mut Vec<Vec<Node>>) {
+    let mut i = 0;
+    while i < nodes.len() {
+        if is_note(&nodes[i]) {
+            let content = match nodes.get(i + 1) {
+                Some(Node::Element(el)) if el.is_html(FOOTNOTE) => match nodes.remove(i + 1) {
+                    Node::Element(el) => el.children,
+                    _ => vec![],
+                },
+                _ => vec![],
+            };
+            notes.push(content);
+            if let Node::FootnoteRef(note) = &mut nodes[i] {
+                note.number = notes.len();
+            }
+        } else if let Node::Element(el) = &mut nodes[i] {
+            number_notes(&mut el.children, notes);
+        }
+        i += 1;
+    }
+}
+
+pub(crate) fn paragraph_node(children: Vec<Node>) -> Node {
+    Node::Element(Element {
+        kind: Kind::Paragraph,
+        children,
+    })
+}
+
+/// Appends the item to the list of the level at the end of the blocks.
+pub(crate) fn push_item(
+    blocks: &mut Vec<Node>,
+    level: usize,
+    start: Option<u64>,
+    content: Vec<Node>,
+) {
+    if level > 0 {
+        if let Some(Node::Element(list)) = blocks.last_mut() {
+            if let (Kind::List(_), Some(Node::Element(item))) =
+                (&list.kind, list.children.last_mut())
+            {
+                return push_item(&mut item.children, level - 1, start, content);
+            }
+        }
+    }
+    let item = Node::Element(Element {
+        kind: Kind::Item,
+        children: content,
+    });
+    match blocks.last_mut() {
+        Some(Node::Element(list)) if matches!(list.kind, Kind::List(current) if current.is_some() == start.is_some()) => {
+            list.children.push(item)
+        }
+        _ => blocks.push(Node::Element(Element {
+            kind: Kind::List(start),
+            children: vec![item],
+        })),
+    }
+}
+
+/// Removes the white space at the start of the nodes, such as after a footnote mark.
+pub(crate) fn trim_start(nodes: &mut Vec<Node>) {
+    while let Some(Node::Text(text)) = nodes.first_mut() {
+        let trimmed = text.trim_start();
+        if trimmed.is_empty() {
+            nodes.remove(0);
+        } else {
+            *text = trimmed.to_string();
+            break;
+        }
+    }
+}
+
+fn unwrap_strong(nodes: Vec<Node>) -> Vec<Node> {
+    nodes
+        .into_iter()
+        .flat_map(|node| match node {
+            Node::Element(el) if el.kind == Kind::Strong => el.children,
+            node => vec![node],
+        })
+        .collect()
+}
+
+/// The formatting flags nesting the pieces, outermost first.
+const NESTING: [fn(Format) -> bool; 3] = [|f| f.bold, |f| f.italic, |f| f.strike];
+
+/// Nests the formatted pieces into elements, grouping neighbors with the same formatting.
+pub(crate) fn group(pieces: Vec<Piece>) -> Vec<Node> {
+    group_level(pieces, 0)
+}
+
+fn group_level(pieces: Vec<Piece>, level: usize) -> Vec<Node> {
+    let Some(flag) = NESTING.get(level) else {
+        return pieces
+            .into_iter()
+            .map(|piece| match piece {
+                Piece::Text(format, text) if format.code => Node::Code(text),
+                Piece::Text(_, text) => Node::Text(text),
+                Piece::Node(node) => node,
+            })
+            .collect();
+    };
+    let kind = match level {
+        0 => Kind::Strong,
+        1 => Kind::Emphasis,
+        _ => Kind::Strikethrough,
+    };
+    let mut nodes = vec![];
+    let mut run: Vec<Piece> = vec![];
+    let mut inside = false;
+    let end = |run: &mut Vec<Piece>, inside: bool, nodes: &mut Vec<Node>| {
+        let children = group_level(std::mem::take(run), level + 1);
+        if inside {
+            nodes.extend(hoist_spaces(kind.clone(), children));
+        } else {
+            nodes.extend(children);
+        }
+    };
+    for piece in pieces {
+        let flagged = match &piece {
+            Piece::Text(format, text) => flag(*format) && !text.trim().is_empty(),
+            // nodes such as breaks and links continue the formatting around them.
+            Piece::Node(_) => inside,
+        };
+        if flagged != inside && !run.is_empty() {
+            end(&mut run, inside, &mut nodes);
+        }
+        inside = flagged;
+        run.push(piece);
+    }
+    if !run.is_empty() {
+        end(&mut run, inside, &mut nodes);
+    }
+    nodes
+}
+
+/// Wraps the children in an element of the kind, with the white space at their ends outside,
+/// as Markdown emphasis cannot start or end with white space.
+fn hoist_spaces(kind: Kind, mut children: Vec<Node>) -> Vec<Node> {
+    let mut before = None;
+    let mut after = None;
+    if let Some(Node::Text(text)) = children.first_mut() {
+        let trimmed = text.trim_start();
+        if trimmed.len() < text.len() {
+            before = Some(text[..text.len() - trimmed.len()].to_string());
+            *text = trimmed.to_string();
+        }
+    }
+    if let Some(Node::Text(text)) = children.last_mut() {
+        let trimmed = text.trim_end();
+        if trimmed.len() < text.len() {
+            after = Some(text[trimmed.len()..].to_string());
+            *text = trimmed.to_string();
+        }
+    }
+    let mut nodes: Vec<Node> = before.map(Node::Text).into_iter().collect();
+    nodes.push(Node::Element(Element { kind, children }));
+    nodes.extend(after.map(Node::Text));
+    nodes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::chapter;
+
+    fn doc(texts: &[(&str, &str)]) -> (Doc, Vec<Sec>) {
+        chapter(1, "A <Story>", texts)
+    }
+
+    fn round_trip(texts: &[(&str, &str)]) -> (Doc, Vec<Sec>) {
+        let (doc, sections) = doc(texts);
+        let bytes = export(&doc, &sections).unwrap();
+        import(&bytes, doc.head.id).unwrap()
+    }
+
+    #[test]
+    fn round_trips_formatting() {
+        let text = "It *begins* with **bold** and ~~struck~~ `code`.[^a]\n\n\
+            ## A Scene\n\n\
+            A [link](https://example.com) and *nested **strong***.\n\n\
+            [^a]: A note & *more*.\n";
+        let (doc, sections) = round_trip(&[("Arrival", text), ("", "Then it ends.")]);
+        assert_eq!(doc.head.title, "A <Story>");
+        assert_eq!(doc.content.len(), 2);
+        assert_eq!(sections[0].head.title, "Arrival");
+        assert_eq!(sections[0].content.lang, MarkupLang::Md);
+        assert_eq!(
+            sections[0].content.text,
+            "It *begins* with **bold** and ~~struck~~ `code`.[^1]\n\n\
+            ## A Scene\n\n\
+            A [link](https://example.com) and *nested* ***strong***.\n\n\
+            [^1]: A note \\& *more*.\n"
+        );
+        assert_eq!(sections[1].head.title, "");
+        assert_eq!(sections[1].content.text, "Then it ends.\n");
+    }
+
+    #[test]
+    fn round_trips_blocks() {
+        let text = "> Quoted\n> twice.\n\n\
+            ```\nfn main() {}\nlet x;\n```\n\n\
+            - one\n- two\n  1. nested\n\n\
+            ***\n\n\
+            | A | B |\n|---|---|\n| 1 | 2 |\n";
+        let (_, sections) = round_trip(&[("Blocks", text)]);
+        assert_eq!(
+            sections[0].content.text,
+            "> Quoted twice.\n\n\
+            ```\nfn main() {}\nlet x;\n```\n\n\
+            - one\n- two\n  1. nested\n\n\
+            ---\n\n\
+            | A | B |\n| --- | --- |\n| 1 | 2 |\n"
+        );
+    }
+
+    #[test]
+    fn splits_on_headings_and_page_breaks() {
+        let (doc, sections) = round_trip(&[("One", "First."), ("Two", ""), ("", "Third.")]);
+        let titles: Vec<&str> = sections.iter().map(|sec| sec.head.title.as_str()).collect();
+        assert_eq!(titles, ["One", "Two", ""]);
+        assert_eq!(doc.content[2].id, sections[2].head.id);
+        assert_eq!(sections[2].content.text, "Third.\n");
+    }
+}
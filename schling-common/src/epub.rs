@@ -0,0 +1,580 @@
+use std::fmt::{Display, Formatter};
+use std::io::{Cursor, Seek, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::ast::{Element, Kind, Node, Notes};
+use crate::data::{Doc, Sec};
+use crate::highlight::Theme;
+use crate::html::escape;
+use crate::id;
+use crate::render::ToHtml;
+use crate::sanitize::Policy;
+use crate::toc;
+use crate::wiki::{Target, Titles};
+
+/// The style sheet of the book, unless it is replaced.
+pub const DEFAULT_STYLE: &str = "\
+body { font-family: serif; line-height: 1.5; margin: 0 5%; }
+h1.chapter { text-align: center; margin: 3em 0 2em; page-break-before: always; }
+p { margin: 0; text-indent: 1.5em; }
+h1 + p, h2 + p, h3 + p, hr + p, section > p:first-child { text-indent: 0; }
+hr.scene-break { border: none; margin: 1.5em 0; text-align: center; }
+hr.scene-break::after { content: \"* * *\"; }
+blockquote { margin: 1em 2em; }
+pre { white-space: pre-wrap; font-size: 0.85em; }
+table { border-collapse: collapse; margin: 1em auto; }
+th, td { border: 1px solid #888; padding: 0.2em 0.5em; }
+.footnotes { font-size: 0.9em; margin-top: 2em; }
+.math-error, .wiki-link.missing { color: #b00; }
+.cover { margin: 0; padding: 0; text-align: center; }
+.cover img { max-width: 100%; max-height: 100%; }
+";
+
+/// The publication metadata of a book.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Metadata {
+    pub title: String,
+    pub authors: Vec<String>,
+    /// The BCP 47 language tag of the content, such as `en` or `de-CH`.
+    pub language: String,
+    /// The unique identifier of the publication, such as an ISBN or URN.
+    pub identifier: String,
+    pub publisher: Option<String>,
+    pub description: Option<String>,
+    /// The time of the last modification, as `YYYY-MM-DDThh:mm:ssZ`.
+    pub modified: String,
+}
+
+impl Metadata {
+    pub fn new(title: &str, identifier: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            authors: vec![],
+            language: "en".to_string(),
+            identifier: identifier.to_string(),
+            publisher: None,
+            description: None,
+            modified: timestamp(SystemTime::now()),
+        }
+    }
+}
+
+/// The UTC time in the format of the modification date of EPUB.
+pub fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // the civil date of the days since 1970-01-01, after Howard Hinnant.
+    let days = days as i64 + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// An image embedded into the book.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    /// The media type, one of `image/jpeg`, `image/png`, `image/gif` or `image/svg+xml`.
+    pub media_type: String,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    fn extension(&self) -> &str {
+        match self.media_type.as_str() {
+            "image/jpeg" => "jpg",
+            "image/png" => "png",
+            "image/gif" => "gif",
+            "image/webp" => "webp",
+            _ => "svg",
+        }
+    }
+}
+
+/// A document of the book with its sections, which are separated by scene breaks.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chapter {
+    pub doc: Doc,
+    pub sections: Vec<Sec>,
+}
+
+/// The reason a book cannot be written.
+#[derive(Debug)]
+pub enum Error {
+    /// The book has no chapters, but a publication needs content.
+    Empty,
+    Io(std::io::Error),
+    Zip(zip::result::ZipError),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Empty => write!(f, "the book has no chapters"),
+            Error::Io(err) => write!(f, "cannot write the book: {}", err),
+            Error::Zip(err) => write!(f, "cannot write the book: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(err: zip::result::ZipError) -> Self {
+        Error::Zip(err)
+    }
+}
+
+/// An EPUB 3 publication of documents.
+///
+/// Wiki links between the sections of the book link to their chapters. Images of the content
+/// are replaced by their alternative text, as the book embeds no resources but the cover.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Book {
+    pub metadata: Metadata,
+    /// The cover image, or a generated cover showing the title when it is missing.
+    pub cover: Option<Image>,
+    pub style: String,
+    pub chapters: Vec<Chapter>,
+}
+
+impl Book {
+    pub fn new(metadata: Metadata) -> Self {
+        Self {
+            metadata,
+            cover: None,
+            style: DEFAULT_STYLE.to_string(),
+            chapters: vec![],
+        }
+    }
+
+    pub fn with_cover(mut self, cover: Image) -> Self {
+        self.cover = Some(cover);
+        self
+    }
+
+    pub fn with_chapter(mut self, doc: Doc, sections: Vec<Sec>) -> Self {
+        self.chapters.push(Chapter { doc, sections });
+        self
+    }
+
+    /// Writes the book as an EPUB container.
+    pub fn write<W: Write + Seek>(&self, writer: W) -> Result<W, Error> {
+        if self.chapters.is_empty() {
+            return Err(Error::Empty);
+        }
+        let mut zip = ZipWriter::new(writer);
+        // the uncompressed media type comes first, so that the format is recognized.
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        zip.start_file("mimetype", stored)?;
+        zip.write_all(b"application/epub+zip")?;
+
+        let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let mut add = |name: &str, content: &[u8]| -> Result<(), Error> {
+            zip.start_file(name, deflated)?;
+            zip.write_all(content)?;
+            Ok(())
+        };
+        add("META-INF/container.xml", CONTAINER.as_bytes())?;
+        let cover = self.cover.clone().unwrap_or_else(|| self.generated_cover());
+        let cover_path = format!("images/cover.{}", cover.extension());
+        add(&format!("OEBPS/{}", cover_path), &cover.data)?;
+        add("OEBPS/cover.xhtml", self.cover_page(&cover_path).as_bytes())?;
+        let mut style = self.style.clone();
+        style.push_str(&Theme::LIGHT.css());
+        add("OEBPS/style.css", style.as_bytes())?;
+        add("OEBPS/nav.xhtml", self.navigation().as_bytes())?;
+        let chapters = self.chapter_pages();
+        for (i, page) in chapters.iter().enumerate() {
+            add(&format!("OEBPS/{}", chapter_file(i)), page.as_bytes())?;
+        }
+        add(
+            "OEBPS/content.opf",
+            self.package(&cover, &cover_path, &chapters).as_bytes(),
+        )?;
+        Ok(zip.finish()?)
+    }
+
+    /// The book as the bytes of an EPUB file.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.write(Cursor::new(vec![]))?.into_inner())
+    }
+
+    fn generated_cover(&self) -> Image {
+        let mut svg = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 600 900\">\
+            <rect width=\"600\" height=\"900\" fill=\"#2f3e4e\"/>",
+        );
+        svg.push_str(&format!(
+            "<text x=\"300\" y=\"360\" font-family=\"serif\" font-size=\"48\" fill=\"#fff\" \
+            text-anchor=\"middle\">{}</text>",
+            escape(&self.metadata.title)
+        ));
+        svg.push_str(&format!(
+            "<text x=\"300\" y=\"600\" font-family=\"serif\" font-size=\"28\" fill=\"#ddd\" \
+            text-anchor=\"middle\">{}</text></svg>",
+            escape(&self.metadata.authors.join(", "))
+        ));
+        Image {
+            media_type: "image/svg+xml".to_string(),
+            data: svg.into_bytes(),
+        }
+    }
+
+    fn cover_page(&self, cover_path: &str) -> String {
+        xhtml(
+            &self.metadata,
+            "Cover",
+            &format!(
+                "<section class=\"cover\" epub:type=\"cover\"><img src=\"{}\" alt=\"{}\" /></section>\n",
+                cover_path,
+                escape(&self.metadata.title)
+            ),
+        )
+    }
+
+    /// The navigation document with the chapters and their titled sections.
+    fn navigation(&self) -> String {
+        let mut body = String::from("<nav epub:type=\"toc\" id=\"toc\"><h1>Contents</h1><ol>\n");
+        for (i, chapter) in self.chapters.iter().enumerate() {
+            let file = chapter_file(i);
+            body.push_str(&format!(
+                "<li><a href=\"{}\">{}</a>",
+                file,
+                escape(&chapter.doc.head.title)
+            ));
+            let titled: Vec<&Sec> = chapter
+                .sections
+                .iter()
+                .filter(|sec| !sec.head.title.trim().is_empty())
+                .collect();
+            if !titled.is_empty() {
+                body.push_str("<ol>");
+                for sec in titled {
+                    body.push_str(&format!(
+                        "<li><a href=\"{}#{}\">{}</a></li>",
+                        file,
+                        toc::section_anchor(sec.head.id),
+                        escape(&sec.head.title)
+                    ));
+                }
+                body.push_str("</ol>");
+            }
+            body.push_str("</li>\n");
+        }
+        body.push_str("</ol></nav>\n");
+        body.push_str(&format!(
+            "<nav epub:type=\"landmarks\" hidden=\"hidden\"><ol>\
+            <li><a epub:type=\"cover\" href=\"cover.xhtml\">Cover</a></li>\
+            <li><a epub:type=\"bodymatter\" href=\"{}\">Start</a></li></ol></nav>\n",
+            chapter_file(0)
+        ));
+        xhtml(&self.metadata, "Contents", &body)
+    }
+
+    fn chapter_pages(&self) -> Vec<String> {
+        let mut titles = Titles::default();
+        for chapter in &self.chapters {
+            titles.insert_doc(&chapter.doc.head);
+            for sec in &chapter.sections {
+                titles.insert_sec(&sec.head);
+            }
+        }
+        self.chapters
+            .iter()
+            .map(|chapter| {
+                let doc = chapter.doc.head.id;
+                let mut notes = Notes::default().with_titles(titles.clone(), doc);
+                let mut body = format!(
+                    "<section epub:type=\"chapter\" id=\"{}\">\n<h1 class=\"chapter\">{}</h1>\n",
+                    toc::document_anchor(doc),
+                    escape(&chapter.doc.head.title)
+                );
+                for (i, sec) in chapter.sections.iter().enumerate() {
+                    if i > 0 {
+                        body.push_str("<hr class=\"scene-break\" />\n");
+                    }
+                    let anchor = toc::section_anchor(sec.head.id);
+                    notes.set_scope(&format!("{}-", anchor));
+                    let mut nodes = sec.content.to_ast(&mut notes, &Policy::default());
+                    without_images(&mut nodes);
+                    body.push_str(&format!("<section id=\"{}\">\n", anchor));
+                    body.push_str(&nodes.to_html());
+                    body.push_str("</section>\n");
+                }
+                notes.write_html(&mut body);
+                body.push_str("</section>\n");
+                let body = self.link_chapters(&body);
+                xhtml(&self.metadata, &chapter.doc.head.title, &body)
+            })
+            .collect()
+    }
+
+    /// Points the wiki links to sections and documents to the files of their chapters.
+    fn link_chapters(&self, body: &str) -> String {
+        let mut body = body.to_string();
+        for (i, chapter) in self.chapters.iter().enumerate() {
+            let file = chapter_file(i);
+            let target = Target::Doc(chapter.doc.head.id);
+            body = body.replace(
+                &format!("href=\"{}\" data-target=\"{}\"", target.href(), target),
+                &format!("href=\"{}\"", file),
+            );
+            for sec in &chapter.sections {
+                let target = Target::Sec(sec.head.id);
+                body = body.replace(
+                    &format!("href=\"{}\" data-target=\"{}\"", target.href(), target),
+                    &format!("href=\"{}{}\"", file, target.href()),
+                );
+            }
+        }
+        body
+    }
+
+    fn package(&self, cover: &Image, cover_path: &str, chapters: &[String]) -> String {
+        let meta = &self.metadata;
+        let mut opf = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" \
+            unique-identifier=\"book-id\" xml:lang=\"{0}\">\n\
+            <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+            <dc:identifier id=\"book-id\">{1}</dc:identifier>\n\
+            <dc:title>{2}</dc:title>\n\
+            <dc:language>{0}</dc:language>\n",
+            escape(&meta.language),
+            escape(&meta.identifier),
+            escape(&meta.title)
+        );
+        for author in &meta.authors {
+            opf.push_str(&format!("<dc:creator>{}</dc:creator>\n", escape(author)));
+        }
+        if let Some(publisher) = &meta.publisher {
+            opf.push_str(&format!(
+                "<dc:publisher>{}</dc:publisher>\n",
+                escape(publisher)
+            ));
+        }
+        if let Some(description) = &meta.description {
+            opf.push_str(&format!(
+                "<dc:description>{}</dc:description>\n",
+                escape(description)
+            ));
+        }
+        opf.push_str(&format!(
+            "<meta property=\"dcterms:modified\">{}</meta>\n\
+            <meta name=\"cover\" content=\"cover-image\" />\n\
+            </metadata>\n<manifest>\n\
+            <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\" />\n\
+            <item id=\"style\" href=\"style.css\" media-type=\"text/css\" />\n\
+            <item id=\"cover-image\" href=\"{}\" media-type=\"{}\" properties=\"cover-image\" />\n\
+            <item id=\"cover\" href=\"cover.xhtml\" media-type=\"application/xhtml+xml\" />\n",
+            escape(&meta.modified),
+            cover_path,
+            escape(&cover.media_type)
+        ));
+        for (i, page) in chapters.iter().enumerate() {
+            let properties = if page.contains("<math") {
+                " properties=\"mathml\""
+            } else {
+                ""
+            };
+            opf.push_str(&format!(
+                "<item id=\"chapter-{}\" href=\"{}\" media-type=\"application/xhtml+xml\"{} />\n",
+                i + 1,
+                chapter_file(i),
+                properties
+            ));
+        }
+        opf.push_str("</manifest>\n<spine>\n<itemref idref=\"cover\" />\n");
+        for i in 0..chapters.len() {
+            opf.push_str(&format!("<itemref idref=\"chapter-{}\" />\n", i + 1));
+        }
+        opf.push_str("</spine>\n</package>\n");
+        opf
+    }
+}
+
+const CONTAINER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n\
+<rootfiles>\n\
+<rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\" />\n\
+</rootfiles>\n\
+</container>\n";
+
+fn chapter_file(index: usize) -> String {
+    format!("chapter-{:03}.xhtml", index + 1)
+}
+
+/// A content document of the book.
+fn xhtml(meta: &Metadata, title: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n\
+        <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" \
+        lang=\"{0}\" xml:lang=\"{0}\">\n\
+        <head>\n<meta charset=\"UTF-8\" />\n<title>{1}</title>\n\
+        <link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\" />\n</head>\n\
+        <body>\n{2}</body>\n</html>\n",
+        escape(&meta.language),
+        escape(title),
+        body
+    )
+}
+
+/// Replaces the images by their alternative text.
+fn without_images(nodes: &mut [Node]) {
+    for node in nodes {
+        if let Node::Element(el) = node {
+            if let Kind::Image { .. } = el.kind {
+                let alt: String = el.children.iter().map(Node::text).collect();
+                let mut span = Element::new(Kind::Emphasis);
+                span.children.push(Node::Text(alt));
+                *el = span;
+            } else {
+                without_images(&mut el.children);
+            }
+        }
+    }
+}
+
+/// The identifier of a project exported as a book.
+pub fn identifier(proj: id::Proj) -> String {
+    format!("urn:schling:proj:{}", proj)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use zip::ZipArchive;
+
+    use super::*;
+    use crate::data::chapter;
+
+    fn book() -> Book {
+        let proj = id::Proj::new(1);
+        let (one, one_sections) = chapter(
+            1,
+            "The <Beginning>",
+            &[
+                ("Arrival", "It *begins*.[^a]\n\n[^a]: A note & more."),
+                ("", "Then $x^2$ and ![a cat](cat.png), see [[Departure]]."),
+            ],
+        );
+        let (two, two_sections) = chapter(2, "The End", &[("Departure", "<br>Fin.")]);
+        let mut meta = Metadata::new("A Book", &identifier(proj));
+        meta.authors = vec!["Ann Author".to_string()];
+        meta.modified = timestamp(UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000));
+        Book::new(meta)
+            .with_chapter(one, one_sections)
+            .with_chapter(two, two_sections)
+    }
+
+    fn read(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+        let mut content = String::new();
+        archive
+            .by_name(name)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        content
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        let time = UNIX_EPOCH + std::time::Duration::from_secs(951_868_800 + 3661);
+        assert_eq!(timestamp(time), "2000-03-01T01:01:01Z");
+    }
+
+    #[test]
+    fn writes_container() {
+        let bytes = book().to_bytes().unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        {
+            let mimetype = archive.by_index(0).unwrap();
+            assert_eq!(mimetype.name(), "mimetype");
+            assert_eq!(mimetype.compression(), CompressionMethod::Stored);
+        }
+        assert_eq!(read(&mut archive, "mimetype"), "application/epub+zip");
+        let names: Vec<String> = archive.file_names().map(str::to_string).collect();
+        for name in &names {
+            if name.ends_with(".xml") || name.ends_with(".xhtml") || name.ends_with(".opf") {
+                let content = read(&mut archive, name);
+                let options = roxmltree::ParsingOptions {
+                    allow_dtd: true,
+                    ..Default::default()
+                };
+                if let Err(err) = roxmltree::Document::parse_with_options(&content, options) {
+                    panic!("{} is not well-formed: {}\n{}", name, err, content);
+                }
+            }
+        }
+
+        let opf = read(&mut archive, "OEBPS/content.opf");
+        assert!(
+            opf.contains("<dc:identifier id=\"book-id\">urn:schling:proj:00000001</dc:identifier>")
+        );
+        assert!(opf.contains("<meta property=\"dcterms:modified\">2023-11-14T22:13:20Z</meta>"));
+        assert!(opf.contains("<dc:creator>Ann Author</dc:creator>"));
+        assert!(opf.contains(
+            "href=\"chapter-001.xhtml\" media-type=\"application/xhtml+xml\" properties=\"mathml\""
+        ));
+        assert!(opf.contains(
+            "href=\"images/cover.svg\" media-type=\"image/svg+xml\" properties=\"cover-image\""
+        ));
+        // every file of the manifest is in the container.
+        let manifest = roxmltree::Document::parse(&opf).unwrap();
+        for item in manifest
+            .descendants()
+            .filter(|node| node.has_tag_name("item"))
+        {
+            let href = format!("OEBPS/{}", item.attribute("href").unwrap());
+            assert!(names.contains(&href), "{}", href);
+        }
+
+        let nav = read(&mut archive, "OEBPS/nav.xhtml");
+        assert!(nav.contains(
+            "<li><a href=\"chapter-001.xhtml\">The &lt;Beginning&gt;</a>\
+            <ol><li><a href=\"chapter-001.xhtml#section-0\">Arrival</a></li></ol></li>"
+        ));
+
+        let chapter = read(&mut archive, "OEBPS/chapter-001.xhtml");
+        assert!(chapter.contains("<hr class=\"scene-break\" />"));
+        assert!(chapter.contains("<em>a cat</em>"));
+        assert!(chapter.contains("href=\"chapter-002.xhtml#section-0\">Departure</a>"));
+        assert!(chapter.contains("<section class=\"footnotes\">"));
+        assert!(read(&mut archive, "OEBPS/chapter-002.xhtml").contains("<br />"));
+    }
+
+    #[test]
+    fn needs_chapters() {
+        let book = Book::new(Metadata::new("Empty", "urn:empty"));
+        assert!(matches!(book.to_bytes(), Err(Error::Empty)));
+    }
+}
@@ -0,0 +1,749 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use pulldown_cmark::{Event, HeadingLevel, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+
+use crate::ast::options;
+use crate::data::{Doc, DocHead, Proj, ProjHead, Sec, SecHead};
+use crate::id;
+use crate::markup::{Markup, MarkupLang};
+use crate::toc::slug;
+
+/// The files of a folder by their path in it, with `/` separating the folders.
+pub type Files = BTreeMap<String, String>;
+
+/// A document with its sections.
+pub type Chapter = (Doc, Vec<Sec>);
+
+/// The checksums of the files at the last sync, by path.
+pub type Snapshot = BTreeMap<String, u32>;
+
+/// The file with the metadata of the project or of the document of a folder.
+pub const INDEX: &str = "_index.md";
+
+/// The file of the snapshot in the synced folder.
+pub const SNAPSHOT: &str = ".schling-sync.yaml";
+
+/// The reason a folder cannot be read or written.
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    /// The front matter of the file at the path is not valid.
+    Yaml(String, serde_yaml::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "cannot sync the folder: {}", err),
+            Error::Yaml(path, err) => write!(f, "malformed front matter in {}: {}", path, err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// The side a file changed on since the last sync.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    /// Changed in the project, and written to the folder.
+    Project,
+    /// Changed in the folder, and read into the project.
+    Folder,
+    /// Changed differently on both sides. The project keeps its version, and the version of
+    /// the folder is kept next to the file with the `.conflict` extension.
+    Conflict,
+}
+
+/// The project after a sync, with the changes of its files.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sync {
+    pub proj: Proj,
+    pub chapters: Vec<Chapter>,
+    pub changes: BTreeMap<String, Change>,
+}
+
+/// The metadata of the front matter, all optional in files added to the folder.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Front {
+    id: Option<String>,
+    order: Option<u32>,
+    title: Option<String>,
+    aliases: Vec<String>,
+    synopsis: String,
+    label: Option<String>,
+    status: Option<String>,
+}
+
+impl Front {
+    fn title(&self) -> String {
+        self.title.as_deref().unwrap_or_default().trim().to_string()
+    }
+
+    /// Sets the metadata of a head besides its id, order and title.
+    fn apply(
+        self,
+        aliases: &mut Vec<String>,
+        synopsis: &mut String,
+        label: &mut Option<String>,
+        status: &mut Option<String>,
+    ) {
+        *aliases = self.aliases;
+        *synopsis = self.synopsis;
+        *label = self.label;
+        *status = self.status;
+    }
+}
+
+/// The extension of the files of the markup language.
+fn extension(lang: MarkupLang) -> &'static str {
+    match lang {
+        MarkupLang::Md => "md",
+        MarkupLang::Html => "html",
+        MarkupLang::Fountain => "fountain",
+    }
+}
+
+fn language(path: &str) -> Option<MarkupLang> {
+    match path.rsplit_once('.')?.1 {
+        "md" => Some(MarkupLang::Md),
+        "html" => Some(MarkupLang::Html),
+        "fountain" => Some(MarkupLang::Fountain),
+        _ => None,
+    }
+}
+
+/// The name of a file or folder, by its position and title.
+fn name(position: usize, title: &str) -> String {
+    match title.trim() {
+        "" => format!("{:02}-section", position + 1),
+        title => format!("{:02}-{}", position + 1, slug(title)),
+    }
+}
+
+/// The file with the head as YAML front matter before the text.
+fn with_front<H: Serialize>(head: &H, text: &str) -> String {
+    let yaml = serde_yaml::to_string(head).unwrap_or_default();
+    format!("---\n{}---\n\n{}", yaml, text)
+}
+
+/// The front matter of the file and the text after it.
+fn read_front<'a>(path: &str, content: &'a str) -> Result<(Front, &'a str), Error> {
+    let Some(rest) = content.strip_prefix("---\n") else {
+        return Ok((Front::default(), content));
+    };
+    let (yaml, text) = match rest.find("\n---\n") {
+        Some(end) => (&rest[..end + 1], &rest[end + 5..]),
+        None => match rest.strip_suffix("\n---") {
+            Some(yaml) => (yaml, ""),
+            None => return Ok((Front::default(), content)),
+        },
+    };
+    let front = match yaml.trim() {
+        "" => Front::default(),
+        _ => serde_yaml::from_str(yaml).map_err(|err| Error::Yaml(path.to_string(), err))?,
+    };
+    Ok((front, text.strip_prefix('\n').unwrap_or(text)))
+}
+
+/// Maps the project to a tree of files.
+///
+/// Each document is a folder named by its position and title, with an [`INDEX`] file holding
+/// its metadata as front matter. Each section is a file in the folder of its document, named
+/// by its position and title, with its id, order, title and other metadata as YAML front
+/// matter before its text. The extension of the file is the one of its markup language. The
+/// [`INDEX`] file at the root holds the metadata of the project.
+pub fn export(proj: &ProjHead, chapters: &[Chapter]) -> Files {
+    let mut files = Files::new();
+    files.insert(INDEX.to_string(), with_front(proj, ""));
+    for (i, chapter) in chapters.iter().enumerate() {
+        export_chapter(&mut files, i, chapter);
+    }
+    files
+}
+
+/// Replaces the folder of the document at the position in the files of an exported project,
+/// as [`export`] maps it.
+pub fn export_chapter(files: &mut Files, position: usize, (doc, sections): &Chapter) {
+    let prefix = format!("{:02}-", position + 1);
+    files.retain(|path, _| {
+        !path
+            .split_once('/')
+            .is_some_and(|(folder, _)| folder.starts_with(&prefix))
+    });
+    let folder = name(position, &doc.head.title);
+    files.insert(format!("{}/{}", folder, INDEX), with_front(&doc.head, ""));
+    for (j, sec) in sections.iter().enumerate() {
+        files.insert(
+            file(&folder, j, sec),
+            with_front(&sec.head, &sec.content.text),
+        );
+    }
+}
+
+/// The path of the file of a section, by its position in its document.
+fn file(folder: &str, position: usize, sec: &Sec) -> String {
+    let name = name(position, &sec.head.title);
+    format!("{}/{}.{}", folder, name, extension(sec.content.lang))
+}
+
+/// The path of the file the section is exported to.
+pub fn section_path(chapters: &[Chapter], id: id::Sec) -> Option<String> {
+    chapters
+        .iter()
+        .enumerate()
+        .find_map(|(i, (doc, sections))| {
+            let j = sections.iter().position(|sec| sec.head.id == id)?;
+            Some(file(&name(i, &doc.head.title), j, &sections[j]))
+        })
+}
+
+/// A document read from the folder.
+struct Folder<'a> {
+    key: &'a str,
+    front: Front,
+    sections: Vec<File<'a>>,
+}
+
+/// A section read from a file.
+struct File<'a> {
+    path: &'a str,
+    front: Front,
+    text: &'a str,
+    lang: MarkupLang,
+}
+
+/// Reads a tree of files into the documents of the project.
+///
+/// The folders are the documents and their files the sections, ordered by the `order` of
+/// their front matter, and then by name. The files and folders without an id or with the id
+/// of another project or document get a new one, and their order follows their position.
+/// A file at the root, besides the [`INDEX`], is a document of its own.
+///
+/// When splitting, the first level headings of Markdown files start new sections titled by
+/// them. The file keeps its metadata for the text before the first heading, or for the first
+/// heading when the file has no title and no text before it.
+pub fn import(files: &Files, proj: id::Proj, split: bool) -> Result<(Proj, Vec<Chapter>), Error> {
+    let mut head = ProjHead::new(proj, 0, String::new());
+    let mut folders: Vec<Folder> = vec![];
+    for (path, content) in files {
+        let Some(lang) = language(path) else {
+            continue;
+        };
+        let (front, text) = read_front(path, content)?;
+        let (key, file) = match path.split_once('/') {
+            Some((key, file)) => (key, file),
+            None if path == INDEX => {
+                head.title = front.title();
+                head.order = front.order.unwrap_or_default();
+                let head = &mut head;
+                front.apply(
+                    &mut head.aliases,
+                    &mut head.synopsis,
+                    &mut head.label,
+                    &mut head.status,
+                );
+                continue;
+            }
+            // a file at the root, without the metadata of a document.
+            None => {
+                let stem = path
+                    .rsplit_once('.')
+                    .map_or(path.as_str(), |(stem, _)| stem);
+                let title = Some(front.title.clone().unwrap_or_else(|| stem.to_string()));
+                let file = File {
+                    path,
+                    front: Front::default(),
+                    text,
+                    lang,
+                };
+                folders.push(Folder {
+                    key: path,
+                    front: Front {
+                        title,
+                        ..Front::default()
+                    },
+                    sections: vec![file],
+                });
+                continue;
+            }
+        };
+        let position = match folders.iter().position(|folder| folder.key == key) {
+            Some(position) => position,
+            None => {
+                folders.push(Folder {
+                    key,
+                    front: Front::default(),
+                    sections: vec![],
+                });
+                folders.len() - 1
+            }
+        };
+        let folder = &mut folders[position];
+        if file == INDEX {
+            folder.front = front;
+        } else {
+            folder.sections.push(File {
+                path,
+                front,
+                text,
+                lang,
+            });
+        }
+    }
+    folders.sort_by(|a, b| {
+        let order = |folder: &Folder| folder.front.order.unwrap_or(u32::MAX);
+        order(a).cmp(&order(b)).then(a.key.cmp(b.key))
+    });
+
+    let ids = unique_ids(folders.iter().map(|folder| &folder.front), |id| {
+        id.parse::<id::Doc>().ok().filter(|doc| doc.proj == proj)
+    });
+    let mut next = ids.iter().flatten().map(|doc| doc.value + 1).max();
+    let (mut docs, mut chapters) = (vec![], vec![]);
+    for (i, (folder, id)) in folders.into_iter().zip(ids).enumerate() {
+        let id = id.unwrap_or_else(|| {
+            let value = next.unwrap_or_default();
+            next = Some(value + 1);
+            id::Doc::new(value, proj)
+        });
+        let mut doc = DocHead::new(id, i as u32, folder.front.title());
+        folder.front.apply(
+            &mut doc.aliases,
+            &mut doc.synopsis,
+            &mut doc.label,
+            &mut doc.status,
+        );
+        let sections = read_sections(id, folder.sections, split);
+        docs.push(doc.clone());
+        let heads = sections.iter().map(|sec| sec.head.clone()).collect();
+        chapters.push((doc.body(heads), sections));
+    }
+    Ok((head.body(docs), chapters))
+}
+
+/// The ids of the front matters, unless they are invalid or repeated.
+fn unique_ids<'a, I: Copy + Eq + std::hash::Hash>(
+    fronts: impl Iterator<Item = &'a Front>,
+    parse: impl Fn(&str) -> Option<I>,
+) -> Vec<Option<I>> {
+    let mut seen = HashSet::new();
+    fronts
+        .map(|front| {
+            front
+                .id
+                .as_deref()
+                .and_then(&parse)
+                .filter(|id| seen.insert(*id))
+        })
+        .collect()
+}
+
+/// The sections of the files of a document.
+fn read_sections(doc: id::Doc, mut files: Vec<File>, split: bool) -> Vec<Sec> {
+    files.sort_by(|a, b| {
+        let order = |file: &File| file.front.order.unwrap_or(u32::MAX);
+        order(a).cmp(&order(b)).then(a.path.cmp(b.path))
+    });
+    let ids = unique_ids(files.iter().map(|file| &file.front), |id| {
+        id.parse::<id::Sec>().ok().filter(|sec| sec.doc == doc)
+    });
+    let mut next = ids.iter().flatten().map(|sec| sec.value + 1).max();
+    let mut new_id = || {
+        let value = next.unwrap_or_default();
+        next = Some(value + 1);
+        id::Sec::new(value, doc)
+    };
+    let mut sections: Vec<Sec> = vec![];
+    for (file, id) in files.into_iter().zip(ids) {
+        let mut chunks = match file.lang {
+            MarkupLang::Md if split => split_headings(file.text),
+            _ => vec![(None, file.text.to_string())],
+        };
+        let mut title = file.front.title();
+        let mut front = Some(file.front);
+        if title.is_empty() && chunks.len() > 1 && chunks[0].1.trim().is_empty() {
+            chunks.remove(0);
+            title = chunks[0].0.take().unwrap_or_default();
+        }
+        for (i, (heading, text)) in chunks.into_iter().enumerate() {
+            let order = sections.len() as u32;
+            let sec = match i {
+                0 => {
+                    let id = id.unwrap_or_else(&mut new_id);
+                    let mut head = SecHead::new(id, order, title.clone());
+                    if let Some(front) = front.take() {
+                        front.apply(
+                            &mut head.aliases,
+                            &mut head.synopsis,
+                            &mut head.label,
+                            &mut head.status,
+                        );
+                    }
+                    head
+                }
+                _ => SecHead::new(new_id(), order, heading.unwrap_or_default()),
+            };
+            sections.push(sec.body(Markup::new(text, file.lang)));
+        }
+    }
+    sections
+}
+
+/// Splits the Markdown text at its first level headings, into the text before the first
+/// heading, and the title and the text after each heading.
+fn split_headings(text: &str) -> Vec<(Option<String>, String)> {
+    let mut chunks = vec![];
+    let mut start = 0;
+    let mut title = None;
+    let mut heading: Option<(usize, String)> = None;
+    for (event, range) in Parser::new_ext(text, options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading {
+                level: HeadingLevel::H1,
+                ..
+            }) => heading = Some((range.start, String::new())),
+            Event::Text(content) | Event::Code(content) => {
+                if let Some((_, heading)) = &mut heading {
+                    heading.push_str(&content);
+                }
+            }
+            Event::End(TagEnd::Heading(HeadingLevel::H1)) => {
+                if let Some((heading_start, heading)) = heading.take() {
+                    chunks.push((title.take(), chunk(&text[start..heading_start])));
+                    title = Some(heading.trim().to_string());
+                    start = range.end;
+                }
+            }
+            _ => {}
+        }
+    }
+    chunks.push((title, chunk(&text[start..])));
+    chunks
+}
+
+/// The text of a section split from a file, without blank lines around it.
+fn chunk(text: &str) -> String {
+    let text = text.trim_matches('\n');
+    match text.trim().is_empty() {
+        true => String::new(),
+        false => format!("{}\n", text),
+    }
+}
+
+/// The checksums of the files.
+pub fn snapshot(files: &Files) -> Snapshot {
+    files
+        .iter()
+        .map(|(path, content)| (path.clone(), checksum(content)))
+        .collect()
+}
+
+fn checksum(content: &str) -> u32 {
+    let mut crc = flate2::Crc::new();
+    crc.update(content.as_bytes());
+    crc.sum()
+}
+
+/// The files changed since the last sync, in the project or in the folder.
+pub fn changes(base: &Snapshot, project: &Files, folder: &Files) -> BTreeMap<String, Change> {
+    let paths: std::collections::BTreeSet<&String> = base
+        .keys()
+        .chain(project.keys())
+        .chain(folder.keys())
+        .collect();
+    let mut changes = BTreeMap::new();
+    for path in paths {
+        let (in_project, in_folder) = (project.get(path), folder.get(path));
+        if in_project == in_folder {
+            continue;
+        }
+        let base = base.get(path).copied();
+        let unchanged = |content: Option<&String>| content.map(|c| checksum(c)) == base;
+        let change = match (unchanged(in_project), unchanged(in_folder)) {
+            (true, _) => Change::Folder,
+            (false, true) => Change::Project,
+            (false, false) => Change::Conflict,
+        };
+        changes.insert(path.clone(), change);
+    }
+    changes
+}
+
+/// Reads the files of the markup languages in the folder and its subfolders, besides hidden
+/// ones.
+pub fn read_dir(root: &Path) -> Result<Files, Error> {
+    let mut files = Files::new();
+    let mut folders = vec![(root.to_path_buf(), String::new())];
+    while let Some((folder, prefix)) = folders.pop() {
+        if !folder.exists() {
+            continue;
+        }
+        for entry in std::fs::read_dir(&folder)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+            let path = format!("{}{}", prefix, name);
+            if entry.file_type()?.is_dir() {
+                folders.push((entry.path(), format!("{}/", path)));
+            } else if language(&path).is_some() {
+                files.insert(path, std::fs::read_to_string(entry.path())?);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Writes the files to the folder, removing the files it had but no longer has, and the
+/// folders left empty.
+pub fn write_dir(root: &Path, files: &Files, previous: &Files) -> Result<(), Error> {
+    for (path, content) in files {
+        if previous.get(path) != Some(content) {
+            let path = root.join(path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, content)?;
+        }
+    }
+    for path in previous.keys().filter(|path| !files.contains_key(*path)) {
+        let path = root.join(path);
+        std::fs::remove_file(&path)?;
+        if let Some(parent) = path.parent().filter(|parent| *parent != root) {
+            // only succeeds when the folder is empty.
+            let _ = std::fs::remove_dir(parent);
+        }
+    }
+    Ok(())
+}
+
+/// Syncs the project with the folder both ways.
+///
+/// The files changed in the folder since the last sync are read into the project, and the
+/// ones changed in the project are written to the folder. The folder is then rewritten from
+/// the synced project, naming the added files by their position and title, and its snapshot
+/// is stored with it.
+pub fn sync(
+    root: &Path,
+    proj: &ProjHead,
+    chapters: &[Chapter],
+    split: bool,
+) -> Result<Sync, Error> {
+    let snapshot_path = root.join(SNAPSHOT);
+    let base: Snapshot = match std::fs::read_to_string(&snapshot_path) {
+        Ok(yaml) => {
+            serde_yaml::from_str(&yaml).map_err(|err| Error::Yaml(SNAPSHOT.to_string(), err))?
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Snapshot::new(),
+        Err(err) => return Err(err.into()),
+    };
+    let folder = read_dir(root)?;
+    let project = export(proj, chapters);
+    let changes = changes(&base, &project, &folder);
+    let mut merged = project;
+    for (path, change) in &changes {
+        match (change, folder.get(path)) {
+            (Change::Folder, Some(content)) => {
+                merged.insert(path.clone(), content.clone());
+            }
+            (Change::Folder, None) => {
+                merged.remove(path);
+            }
+            (Change::Conflict, Some(content)) => {
+                std::fs::write(root.join(format!("{}.conflict", path)), content)?;
+            }
+            _ => {}
+        }
+    }
+    let (proj, chapters) = import(&merged, proj.id, split)?;
+    let files = export(&proj.head, &chapters);
+    write_dir(root, &files, &folder)?;
+    let yaml = serde_yaml::to_string(&snapshot(&files)).unwrap_or_default();
+    std::fs::write(snapshot_path, yaml)?;
+    Ok(Sync {
+        proj,
+        chapters,
+        changes,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::chapter;
+
+    fn project() -> (ProjHead, Vec<Chapter>) {
+        let proj = id::Proj::new(1);
+        let mut head = ProjHead::new(proj, 0, "Saga".to_string());
+        head.synopsis = "A long story.".to_string();
+        let (mut doc, mut sections) = chapter(
+            4,
+            "Book One",
+            &[("Arrival", "She *arrived*.\n"), ("", "INT. HOUSE - DAY\n")],
+        );
+        sections[0].head.status = Some("Draft".to_string());
+        sections[1].content.lang = MarkupLang::Fountain;
+        // the first document of the folder.
+        doc.head.order = 0;
+        doc.content = sections.iter().map(|sec| sec.head.clone()).collect();
+        (head, vec![(doc, sections)])
+    }
+
+    #[test]
+    fn round_trips_projects() {
+        let (head, chapters) = project();
+        let files = export(&head, &chapters);
+        let paths: Vec<&str> = files.keys().map(String::as_str).collect();
+        assert_eq!(
+            paths,
+            [
+                "01-book-one/01-arrival.md",
+                "01-book-one/02-section.fountain",
+                "01-book-one/_index.md",
+                "_index.md"
+            ]
+        );
+        assert_eq!(
+            files["01-book-one/01-arrival.md"],
+            "---\nid: '000000010000000400000000'\norder: 0\ntitle: Arrival\nstatus: Draft\n\
+            ---\n\nShe *arrived*.\n"
+        );
+        let arrival = chapters[0].1[0].head.id;
+        assert_eq!(
+            section_path(&chapters, arrival).as_deref(),
+            Some("01-book-one/01-arrival.md")
+        );
+        let (proj, imported) = import(&files, head.id, false).unwrap();
+        assert_eq!(proj.head.synopsis, "A long story.");
+        assert_eq!(proj.content, vec![chapters[0].0.head.clone()]);
+        assert_eq!(imported, chapters);
+    }
+
+    #[test]
+    fn replaces_chapters() {
+        let (head, mut chapters) = project();
+        chapters.push(chapter(5, "Book Two", &[("Return", "Back.\n")]));
+        let mut files = export(&head, &chapters);
+        let (doc, sections) = &mut chapters[0];
+        doc.head.title = "Book Zero".to_string();
+        sections.pop();
+        export_chapter(&mut files, 0, &chapters[0]);
+        assert_eq!(files, export(&head, &chapters));
+    }
+
+    #[test]
+    fn assigns_ids_and_splits() {
+        let (head, chapters) = project();
+        let mut files = export(&head, &chapters);
+        files.insert(
+            "01-book-one/03-notes.md".to_string(),
+            "# One\n\nFirst.\n\n```\n# not a heading\n```\n\nTwo\n===\nSecond.\n".to_string(),
+        );
+        files.insert(
+            "Loose.md".to_string(),
+            "Intro.\n\n# Part\n\nText.".to_string(),
+        );
+        let (_, chapters) = import(&files, head.id, true).unwrap();
+        let titles: Vec<(id::Sec, &str, &str)> = chapters[0]
+            .1
+            .iter()
+            .map(|sec| {
+                (
+                    sec.head.id,
+                    sec.head.title.as_str(),
+                    sec.content.text.as_str(),
+                )
+            })
+            .collect();
+        let doc = chapters[0].0.head.id;
+        assert_eq!(
+            titles[2..],
+            [
+                (
+                    id::Sec::new(2, doc),
+                    "One",
+                    "First.\n\n```\n# not a heading\n```\n"
+                ),
+                (id::Sec::new(3, doc), "Two", "Second.\n"),
+            ]
+        );
+        let (loose, sections) = &chapters[1];
+        assert_eq!(loose.head.id, id::Doc::new(5, head.id));
+        assert_eq!(loose.head.title, "Loose");
+        assert_eq!(sections[0].content.text, "Intro.\n");
+        assert_eq!(sections[1].head.title, "Part");
+    }
+
+    #[test]
+    fn detects_changes_on_both_sides() {
+        let base: Files = [("a", "1"), ("b", "1"), ("c", "1"), ("d", "1")]
+            .map(|(path, content)| (path.to_string(), content.to_string()))
+            .into();
+        let mut project = base.clone();
+        let mut folder = base.clone();
+        project.insert("a".to_string(), "2".to_string());
+        folder.insert("b".to_string(), "2".to_string());
+        project.insert("c".to_string(), "2".to_string());
+        folder.insert("c".to_string(), "3".to_string());
+        folder.remove("d");
+        folder.insert("e".to_string(), "1".to_string());
+        let changes = changes(&snapshot(&base), &project, &folder);
+        let changes: Vec<(&str, Change)> = changes
+            .iter()
+            .map(|(path, change)| (path.as_str(), *change))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("a", Change::Project),
+                ("b", Change::Folder),
+                ("c", Change::Conflict),
+                ("d", Change::Folder),
+                ("e", Change::Folder)
+            ]
+        );
+    }
+
+    #[test]
+    fn syncs_folders() {
+        let root = std::env::temp_dir().join(format!("schling-sync-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let (head, chapters) = project();
+        let synced = sync(&root, &head, &chapters, false).unwrap();
+        assert!(synced
+            .changes
+            .values()
+            .all(|change| *change == Change::Project));
+        let arrival = root.join("01-book-one/01-arrival.md");
+        let content = std::fs::read_to_string(&arrival).unwrap();
+        std::fs::write(
+            &arrival,
+            content.replace("title: Arrival", "title: Landing"),
+        )
+        .unwrap();
+
+        let synced = sync(&root, &head, &synced.chapters, false).unwrap();
+        assert_eq!(
+            synced.changes,
+            BTreeMap::from([("01-book-one/01-arrival.md".to_string(), Change::Folder)])
+        );
+        assert_eq!(synced.chapters[0].1[0].head.title, "Landing");
+        assert!(!arrival.exists());
+        assert!(root.join("01-book-one/01-landing.md").exists());
+        let synced = sync(&root, &head, &synced.chapters, false).unwrap();
+        assert!(synced.changes.is_empty());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
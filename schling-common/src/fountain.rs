@@ -0,0 +1,599 @@
+//! The [Fountain](https://fountain.io/syntax) screenplay syntax.
+
+use std::collections::BTreeSet;
+
+use crate::ast::{Element as AstElement, Kind, Node};
+
+/// An element of a screenplay.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Element {
+    SceneHeading {
+        text: String,
+        number: Option<String>,
+    },
+    Action(String),
+    /// A character cue, opening a block of dialogue.
+    Character {
+        name: String,
+        /// Such as `V.O.` or `CONT'D`.
+        extension: Option<String>,
+        /// Whether the dialogue is spoken at the same time as the previous one.
+        dual: bool,
+    },
+    Parenthetical(String),
+    Dialogue(String),
+    Lyrics(String),
+    Transition(String),
+    Centered(String),
+    Section {
+        level: usize,
+        text: String,
+    },
+    Synopsis(String),
+    PageBreak,
+}
+
+/// A parsed screenplay.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Screenplay {
+    /// The keys and values of the title page.
+    pub title_page: Vec<(String, String)>,
+    pub elements: Vec<Element>,
+}
+
+const SCENE_PREFIXES: &[&str] = &["INT", "EXT", "EST", "INT./EXT", "INT/EXT", "I/E"];
+
+/// Removes the boneyard `/* */` and notes `[[ ]]`, which are not part of the printed screenplay.
+fn strip_comments(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut rest = src;
+    loop {
+        let next = [("/*", "*/"), ("[[", "]]")]
+            .iter()
+            .filter_map(|(open, close)| rest.find(open).map(|pos| (pos, *open, *close)))
+            .min_by_key(|(pos, _, _)| *pos);
+        match next {
+            Some((pos, open, close)) => {
+                out.push_str(&rest[..pos]);
+                let after = &rest[pos + open.len()..];
+                rest = match after.find(close) {
+                    Some(end) => &after[end + close.len()..],
+                    None => "",
+                };
+            }
+            None => {
+                out.push_str(rest);
+                return out;
+            }
+        }
+    }
+}
+
+fn is_upper(line: &str) -> bool {
+    line.chars().any(char::is_alphabetic) && !line.chars().any(char::is_lowercase)
+}
+
+/// Whether the line is a scene heading, either forced by a leading `.` or starting with
+/// `INT`, `EXT` and the like.
+pub fn is_scene_heading(line: &str) -> bool {
+    let line = line.trim();
+    if line.starts_with('.') {
+        return line.len() > 1 && !line.starts_with("..");
+    }
+    let upper = line.to_uppercase();
+    SCENE_PREFIXES.iter().any(|prefix| {
+        upper
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with(['.', ' ']))
+    })
+}
+
+/// Whether the line is a transition, either forced by a leading `>` or in uppercase ending
+/// with `TO:`.
+pub fn is_transition(line: &str) -> bool {
+    let line = line.trim();
+    (line.starts_with('>') && !line.ends_with('<')) || (is_upper(line) && line.ends_with("TO:"))
+}
+
+/// Whether the line is centered text, enclosed in `>` and `<`.
+pub fn is_centered(line: &str) -> bool {
+    let line = line.trim();
+    line.len() > 1 && line.starts_with('>') && line.ends_with('<')
+}
+
+/// Whether the line is a character cue, either forced by a leading `@` or in uppercase.
+///
+/// A cue must be preceded by an empty line and followed by dialogue, which is not checked.
+pub fn is_character(line: &str) -> bool {
+    let line = line.trim();
+    if line.starts_with('@') {
+        return line.len() > 1;
+    }
+    let name = line
+        .split('(')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('^');
+    is_upper(name) && !line.starts_with(['!', '#', '=', '~', '.', '>'])
+}
+
+/// Whether the line is a parenthetical in a block of dialogue.
+pub fn is_parenthetical(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with('(') && line.ends_with(')')
+}
+
+fn parse_character(line: &str) -> Element {
+    let line = line.trim();
+    let line = line.strip_prefix('@').unwrap_or(line);
+    let (line, dual) = match line.strip_suffix('^') {
+        Some(line) => (line.trim_end(), true),
+        None => (line, false),
+    };
+    let (name, extension) = match line.find('(') {
+        Some(pos) => (
+            &line[..pos],
+            Some(line[pos + 1..].trim_end_matches(')').trim().to_string()),
+        ),
+        None => (line, None),
+    };
+    Element::Character {
+        name: name.trim().to_string(),
+        extension,
+        dual,
+    }
+}
+
+fn parse_scene_heading(line: &str) -> Element {
+    let line = line.trim();
+    let line = line.strip_prefix('.').unwrap_or(line);
+    let (text, number) = match line
+        .strip_suffix('#')
+        .and_then(|l| l.rfind('#').map(|p| (l, p)))
+    {
+        Some((line, pos)) => (&line[..pos], Some(line[pos + 1..].to_string())),
+        None => (line, None),
+    };
+    Element::SceneHeading {
+        text: text.trim().to_string(),
+        number,
+    }
+}
+
+/// The key and value of a line of the title page, such as `Title: Brick & Steel`.
+fn parse_title_entry(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    let valid = key.starts_with(char::is_alphabetic)
+        && key.chars().all(|c| c.is_alphabetic() || c == ' ')
+        && !is_upper(key);
+    valid.then(|| (key.trim(), value.trim()))
+}
+
+/// Parses the title page at the start of the text, returning the remaining text.
+fn parse_title_page(src: &str) -> (Vec<(String, String)>, &str) {
+    let mut title_page: Vec<(String, String)> = vec![];
+    let mut end = 0;
+    for line in src.split_inclusive('\n') {
+        let trimmed = line.trim();
+        end += line.len();
+        if trimmed.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = parse_title_entry(line) {
+            title_page.push((key.to_string(), value.to_string()));
+        } else if let (true, Some((_, value))) =
+            (line.starts_with([' ', '\t']), title_page.last_mut())
+        {
+            // indented continuation of the previous value.
+            if !value.is_empty() {
+                value.push('\n');
+            }
+            value.push_str(trimmed);
+        } else {
+            return (vec![], src);
+        }
+    }
+    if title_page.is_empty() {
+        (title_page, src)
+    } else {
+        (title_page, &src[end..])
+    }
+}
+
+/// Parses the screenplay.
+pub fn parse(src: &str) -> Screenplay {
+    let src = strip_comments(&src.replace("\r\n", "\n"));
+    let (title_page, body) = parse_title_page(&src);
+    let lines: Vec<&str> = body.lines().collect();
+    let blank = |pos: usize| lines.get(pos).is_none_or(|line| line.trim().is_empty());
+
+    let mut elements: Vec<Element> = vec![];
+    let mut pos = 0;
+    while pos < lines.len() {
+        let line = lines[pos];
+        let trimmed = line.trim();
+        let before = pos == 0 || blank(pos - 1);
+        let after = blank(pos + 1);
+        pos += 1;
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        let element = if let Some(action) = trimmed.strip_prefix('!') {
+            Element::Action(action.to_string())
+        } else if trimmed.starts_with("===") && trimmed.chars().all(|c| c == '=') {
+            Element::PageBreak
+        } else if let Some(text) = trimmed.strip_prefix('#') {
+            let level = 1 + text.chars().take_while(|c| *c == '#').count();
+            Element::Section {
+                level,
+                text: text.trim_start_matches('#').trim().to_string(),
+            }
+        } else if let Some(text) = trimmed.strip_prefix('=') {
+            Element::Synopsis(text.trim().to_string())
+        } else if let Some(text) = trimmed.strip_prefix('~') {
+            Element::Lyrics(text.trim().to_string())
+        } else if is_centered(trimmed) {
+            let text = &trimmed[1..trimmed.len() - 1];
+            Element::Centered(text.trim().to_string())
+        } else if before && after && is_scene_heading(trimmed) {
+            parse_scene_heading(trimmed)
+        } else if before && after && is_transition(trimmed) {
+            Element::Transition(trimmed.trim_start_matches('>').trim().to_string())
+        } else if before && !after && is_character(trimmed) {
+            elements.push(parse_character(trimmed));
+            // dialogue and parentheticals follow until an empty line.
+            while pos < lines.len() && (!blank(pos) || lines[pos] == "  ") {
+                let line = lines[pos].trim();
+                pos += 1;
+                if is_parenthetical(line) {
+                    elements.push(Element::Parenthetical(line.to_string()));
+                } else if let Some(text) = line.strip_prefix('~') {
+                    elements.push(Element::Lyrics(text.trim().to_string()));
+                } else {
+                    match elements.last_mut() {
+                        Some(Element::Dialogue(dialogue)) => {
+                            dialogue.push('\n');
+                            dialogue.push_str(line);
+                        }
+                        _ => elements.push(Element::Dialogue(line.to_string())),
+                    }
+                }
+            }
+            continue;
+        } else {
+            // consecutive lines of action form a paragraph.
+            match elements.last_mut() {
+                Some(Element::Action(action)) if !before => {
+                    action.push('\n');
+                    action.push_str(line.trim_end());
+                    continue;
+                }
+                _ => Element::Action(line.trim_end().to_string()),
+            }
+        };
+        elements.push(element);
+    }
+    Screenplay {
+        title_page,
+        elements,
+    }
+}
+
+/// The names of the characters speaking in the screenplay.
+pub fn characters(src: &str) -> BTreeSet<String> {
+    parse(src)
+        .elements
+        .into_iter()
+        .filter_map(|element| match element {
+            Element::Character { name, .. } => Some(name),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The names completing the character cue typed on the line.
+pub fn complete_character<'a>(line: &str, names: &'a BTreeSet<String>) -> Vec<&'a str> {
+    let prefix = line.trim_start().trim_start_matches('@').to_uppercase();
+    if prefix.is_empty() || !is_character(line) {
+        return vec![];
+    }
+    names
+        .iter()
+        .filter(|name| name.starts_with(&prefix) && name.len() > prefix.len())
+        .map(String::as_str)
+        .collect()
+}
+
+/// Parses the emphasis of Fountain: `*italic*`, `**bold**`, `***bold italic***` and
+/// `_underline_`, with `\` escaping the delimiters.
+fn parse_inline(text: &str) -> Vec<Node> {
+    let mut nodes = vec![];
+    let mut plain = String::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c == '\\' && rest[1..].starts_with(['*', '_', '\\']) {
+            plain.push_str(&rest[1..2]);
+            rest = &rest[2..];
+            continue;
+        }
+        let delim = ["***", "**", "*", "_"]
+            .into_iter()
+            .filter(|delim| rest.starts_with(delim))
+            .find_map(|delim| {
+                let inner = &rest[delim.len()..];
+                let end = inner.find(delim).filter(|end| *end > 0)?;
+                Some((delim, &inner[..end]))
+            });
+        match delim {
+            Some((delim, inner)) => {
+                if !plain.is_empty() {
+                    nodes.push(Node::Text(std::mem::take(&mut plain)));
+                }
+                let children = parse_inline(inner);
+                let node = match delim {
+                    "***" => element(Kind::Strong, vec![element(Kind::Emphasis, children)]),
+                    "**" => element(Kind::Strong, children),
+                    "*" => element(Kind::Emphasis, children),
+                    _ => element(html("u", None), children),
+                };
+                nodes.push(node);
+                rest = &rest[2 * delim.len() + inner.len()..];
+            }
+            None => {
+                plain.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    if !plain.is_empty() {
+        nodes.push(Node::Text(plain));
+    }
+    nodes
+}
+
+fn html(name: &str, class: Option<&str>) -> Kind {
+    Kind::Html {
+        name: name.to_string(),
+        attrs: class
+            .map(|class| vec![("class".to_string(), format!("screenplay-{}", class))])
+            .unwrap_or_default(),
+    }
+}
+
+fn element(kind: Kind, children: Vec<Node>) -> Node {
+    Node::Element(AstElement { kind, children })
+}
+
+/// A paragraph of the screenplay, with line breaks kept.
+fn paragraph(class: &str, text: &str) -> Node {
+    let mut children = vec![];
+    for (pos, line) in text.split('\n').enumerate() {
+        if pos > 0 {
+            children.push(Node::HardBreak);
+        }
+        children.extend(parse_inline(line));
+    }
+    element(html("p", Some(class)), children)
+}
+
+impl Screenplay {
+    /// Converts the screenplay into the document tree, with classes for the standard layout.
+    pub fn to_ast(&self) -> Vec<Node> {
+        let mut nodes = vec![];
+        if !self.title_page.is_empty() {
+            let entries = self
+                .title_page
+                .iter()
+                .map(|(key, value)| {
+                    let class = format!("title-{}", key.to_lowercase().replace(' ', "-"));
+                    paragraph(&class, value)
+                })
+                .collect();
+            nodes.push(element(html("div", Some("title-page")), entries));
+        }
+
+        // the elements of a block of dialogue, and whether it is dual dialogue.
+        let mut dialogue: Option<(Vec<Node>, bool)> = None;
+        for el in &self.elements {
+            let node = match el {
+                Element::Character {
+                    name,
+                    extension,
+                    dual,
+                } => {
+                    flush(&mut dialogue, &mut nodes);
+                    let cue = match extension {
+                        Some(extension) => format!("{} ({})", name, extension),
+                        None => name.clone(),
+                    };
+                    dialogue = Some((vec![paragraph("character", &cue)], *dual));
+                    continue;
+                }
+                Element::Parenthetical(text) => paragraph("parenthetical", text),
+                Element::Dialogue(text) => paragraph("dialogue", text),
+                Element::Lyrics(text) => paragraph("lyrics", text),
+                _ => {
+                    flush(&mut dialogue, &mut nodes);
+                    match el {
+                        Element::SceneHeading { text, number } => {
+                            let mut node = paragraph("scene-heading", text);
+                            if let (Some(number), Node::Element(heading)) = (number, &mut node) {
+                                if let Kind::Html { attrs, .. } = &mut heading.kind {
+                                    attrs.push(("id".to_string(), format!("scene-{}", number)));
+                                }
+                            }
+                            node
+                        }
+                        Element::Action(text) => paragraph("action", text),
+                        Element::Transition(text) => paragraph("transition", text),
+                        Element::Centered(text) => paragraph("centered", text),
+                        Element::Section { level, text } => {
+                            let kind = Kind::Heading {
+                                level: (*level).min(6) as u8,
+                                id: None,
+                            };
+                            element(kind, parse_inline(text))
+                        }
+                        Element::Synopsis(text) => paragraph("synopsis", text),
+                        Element::PageBreak => element(html("hr", Some("page-break")), vec![]),
+                        _ => continue,
+                    }
+                }
+            };
+            match &mut dialogue {
+                Some((children, _)) => children.push(node),
+                None => nodes.push(node),
+            }
+        }
+        flush(&mut dialogue, &mut nodes);
+        let screenplay = Kind::Html {
+            name: "div".to_string(),
+            attrs: vec![("class".to_string(), "screenplay".to_string())],
+        };
+        vec![element(screenplay, nodes)]
+    }
+}
+
+fn has_class(el: &AstElement, class: &str) -> bool {
+    match &el.kind {
+        Kind::Html { attrs, .. } => attrs
+            .iter()
+            .any(|(attr, value)| attr == "class" && value == class),
+        _ => false,
+    }
+}
+
+/// Adds the open block of dialogue, next to the previous block if it is dual dialogue.
+fn flush(dialogue: &mut Option<(Vec<Node>, bool)>, nodes: &mut Vec<Node>) {
+    if let Some((children, dual)) = dialogue.take() {
+        let block = element(html("div", Some("dialogue-block")), children);
+        match nodes.last_mut() {
+            Some(Node::Element(prev)) if dual && has_class(prev, "screenplay-dialogue-block") => {
+                let prev_block =
+                    std::mem::replace(prev, AstElement::new(html("div", Some("dual"))));
+                prev.children = vec![Node::Element(prev_block), block];
+            }
+            _ => nodes.push(block),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::ToHtml;
+
+    const SCRIPT: &str = "Title: Brick & Steel
+Author: Stu Maschwitz
+
+EXT. BRICK'S PATIO - DAY #1#
+
+A gorgeous day. Steel sits /* cut this */ at the grill.
+He [[too long?]] flips a burger.
+
+STEEL
+(beer raised)
+To retirement.
+
+BRICK ^
+To retirement.
+
+CUT TO:
+
+> THE END <
+
+@McCLANE
+Yippee ki-yay.
+";
+
+    #[test]
+    fn parses_elements() {
+        let screenplay = parse(SCRIPT);
+        assert_eq!(
+            screenplay.title_page,
+            vec![
+                ("Title".to_string(), "Brick & Steel".to_string()),
+                ("Author".to_string(), "Stu Maschwitz".to_string()),
+            ]
+        );
+        assert_eq!(
+            screenplay.elements,
+            vec![
+                Element::SceneHeading {
+                    text: "EXT. BRICK'S PATIO - DAY".to_string(),
+                    number: Some("1".to_string()),
+                },
+                Element::Action(
+                    "A gorgeous day. Steel sits  at the grill.\nHe  flips a burger.".to_string()
+                ),
+                Element::Character {
+                    name: "STEEL".to_string(),
+                    extension: None,
+                    dual: false,
+                },
+                Element::Parenthetical("(beer raised)".to_string()),
+                Element::Dialogue("To retirement.".to_string()),
+                Element::Character {
+                    name: "BRICK".to_string(),
+                    extension: None,
+                    dual: true,
+                },
+                Element::Dialogue("To retirement.".to_string()),
+                Element::Transition("CUT TO:".to_string()),
+                Element::Centered("THE END".to_string()),
+                Element::Character {
+                    name: "McCLANE".to_string(),
+                    extension: None,
+                    dual: false,
+                },
+                Element::Dialogue("Yippee ki-yay.".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn classifies_lines() {
+        assert!(is_scene_heading("INT. HOUSE - NIGHT"));
+        assert!(is_scene_heading("int/ext car"));
+        assert!(is_scene_heading(".FLASHBACK"));
+        assert!(!is_scene_heading("INTERIOR"));
+        assert!(is_transition("SMASH CUT TO:"));
+        assert!(is_transition("> Fade out."));
+        assert!(is_character("HANS (on the radio)"));
+        assert!(!is_character("Hans"));
+        assert_eq!(
+            parse("\nHANS (V.O.)\nHi.").elements[0],
+            Element::Character {
+                name: "HANS".to_string(),
+                extension: Some("V.O.".to_string()),
+                dual: false,
+            }
+        );
+    }
+
+    #[test]
+    fn renders_layout() {
+        let html = parse("\nSTEEL\n*Very* **good** _news_.\n\nBRICK ^\nIndeed.").to_ast();
+        assert_eq!(
+            html.to_html(),
+            "<div class=\"screenplay\"><div class=\"screenplay-dual\">\
+            <div class=\"screenplay-dialogue-block\">\
+            <p class=\"screenplay-character\">STEEL</p>\
+            <p class=\"screenplay-dialogue\"><em>Very</em> <strong>good</strong> <u>news</u>.</p>\
+            </div><div class=\"screenplay-dialogue-block\">\
+            <p class=\"screenplay-character\">BRICK</p>\
+            <p class=\"screenplay-dialogue\">Indeed.</p></div></div></div>"
+        );
+    }
+
+    #[test]
+    fn completes_characters() {
+        let names = characters(SCRIPT);
+        assert_eq!(names.len(), 3);
+        assert_eq!(complete_character("ST", &names), vec!["STEEL"]);
+        assert_eq!(complete_character("@B", &names), vec!["BRICK"]);
+        assert!(complete_character("St", &names).is_empty());
+        assert!(complete_character("STEEL", &names).is_empty());
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt::{Display, Formatter};
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+use syntect::highlighting::ThemeSet;
+use syntect::html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+use crate::ast::{parse_html, Node};
+use crate::sanitize::Policy;
+
+/// The classes of the highlighted tokens are the atoms of their scope with this prefix, such as
+/// `hl-keyword hl-control hl-rust`.
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn themes() -> &'static ThemeSet {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    THEMES.get_or_init(ThemeSet::load_defaults)
+}
+
+/// A bundled color theme of highlighted code.
+#[derive(EnumIter, PartialEq, Debug, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum Theme {
+    InspiredGitHub,
+    OceanLight,
+    SolarizedLight,
+    OceanDark,
+    EightiesDark,
+    MochaDark,
+    SolarizedDark,
+}
+
+impl Theme {
+    /// The default theme of the light mode.
+    pub const LIGHT: Theme = Theme::InspiredGitHub;
+    /// The default theme of the dark mode.
+    pub const DARK: Theme = Theme::OceanDark;
+
+    fn key(self) -> &'static str {
+        match self {
+            Theme::InspiredGitHub => "InspiredGitHub",
+            Theme::OceanLight => "base16-ocean.light",
+            Theme::SolarizedLight => "Solarized (light)",
+            Theme::OceanDark => "base16-ocean.dark",
+            Theme::EightiesDark => "base16-eighties.dark",
+            Theme::MochaDark => "base16-mocha.dark",
+            Theme::SolarizedDark => "Solarized (dark)",
+        }
+    }
+
+    pub fn is_dark(self) -> bool {
+        matches!(
+            self,
+            Theme::OceanDark | Theme::EightiesDark | Theme::MochaDark | Theme::SolarizedDark
+        )
+    }
+
+    /// The style sheet coloring the highlighted code.
+    pub fn css(self) -> String {
+        let theme = &themes().themes[self.key()];
+        css_for_theme_with_class_style(theme, CLASS_STYLE).unwrap_or_default()
+    }
+}
+
+impl Display for Theme {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Theme::InspiredGitHub => "Inspired GitHub",
+            Theme::OceanLight => "Ocean",
+            Theme::SolarizedLight => "Solarized",
+            Theme::OceanDark => "Ocean",
+            Theme::EightiesDark => "Eighties",
+            Theme::MochaDark => "Mocha",
+            Theme::SolarizedDark => "Solarized",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The style sheet of highlighted code using the light theme, or the dark theme when the
+/// user prefers a dark color scheme.
+pub fn stylesheet(light: Theme, dark: Theme) -> String {
+    format!(
+        "{}\n@media (prefers-color-scheme: dark) {{\n{}}}\n",
+        light.css(),
+        dark.css()
+    )
+}
+
+/// Highlights the code of the language, given by its name or file extension, as spans with
+/// the classes of the tokens.
+///
+/// Returns `None` if the language is not bundled.
+pub fn highlight(lang: &str, code: &str) -> Option<Vec<Node>> {
+    let syntaxes = syntaxes();
+    let syntax = syntaxes
+        .find_syntax_by_token(lang)
+        .or_else(|| syntaxes.find_syntax_by_name(lang))?;
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, syntaxes, CLASS_STYLE);
+    for line in LinesWithEndings::from(code) {
+        generator
+            .parse_html_for_line_which_includes_newline(line)
+            .ok()?;
+    }
+    let policy = Policy::text_only()
+        .allow_tags(&["span"])
+        .allow_attributes("span", &["class"]);
+    Some(parse_html(&generator.finalize(), &policy))
+}
+
+/// The content of a code block, highlighted if its language is bundled.
+pub fn code_block(lang: Option<&str>, children: Vec<Node>) -> Vec<Node> {
+    let code: String = children.iter().map(Node::text).collect();
+    lang.and_then(|lang| highlight(lang, &code))
+        .unwrap_or(children)
+}
+
+#[cfg(test)]
+mod test {
+    use strum::IntoEnumIterator;
+
+    use super::*;
+    use crate::ast::{Element, Kind};
+
+    fn classes(nodes: &[Node], out: &mut Vec<(String, String)>) {
+        for node in nodes {
+            if let Node::Element(Element {
+                kind: Kind::Html { attrs, .. },
+                children,
+            }) = node
+            {
+                for child in children {
+                    if let Node::Text(text) = child {
+                        out.push((attrs[0].1.clone(), text.clone()));
+                    }
+                }
+                classes(children, out);
+            }
+        }
+    }
+
+    #[test]
+    fn highlights_code() {
+        let nodes = highlight("rust", "fn main() {\n    let x = \"<a>\";\n}\n").unwrap();
+        let text: String = nodes.iter().map(Node::text).collect();
+        assert_eq!(text, "fn main() {\n    let x = \"<a>\";\n}\n");
+        let mut tokens = vec![];
+        classes(&nodes, &mut tokens);
+        assert!(tokens.contains(&("hl-storage hl-type hl-function hl-rust".into(), "fn".into())));
+        assert!(tokens
+            .iter()
+            .any(|(class, text)| class.starts_with("hl-string") && text == "<a>"));
+
+        assert!(highlight("py", "def f(): pass").is_some());
+        assert!(highlight("Python", "def f(): pass").is_some());
+        assert_eq!(highlight("no-such-language", "x"), None);
+    }
+
+    #[test]
+    fn styles_themes() {
+        for theme in Theme::iter() {
+            assert!(theme.css().contains(".hl-"), "{:?}", theme);
+        }
+        assert!(stylesheet(Theme::LIGHT, Theme::DARK).contains("prefers-color-scheme: dark"));
+    }
+}
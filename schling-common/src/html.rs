@@ -0,0 +1,329 @@
+use std::borrow::Cow;
+
+/// Elements without content or end tag.
+pub const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// A token of a HTML fragment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Token<'a> {
+    Start {
+        /// The lowercase name of the element.
+        name: String,
+        attrs: Vec<(String, String)>,
+        self_closing: bool,
+    },
+    End(String),
+    /// Text with decoded character references.
+    Text(Cow<'a, str>),
+    /// A comment, doctype or processing instruction.
+    Comment(&'a str),
+}
+
+/// Attributes of elements embedded in documents, excluding event handlers.
+pub const ATTRIBUTES: &[&str] = &[
+    "abbr", "align", "alt", "checked", "cite", "class", "colspan", "datetime", "dir", "disabled",
+    "height", "hidden", "href", "id", "lang", "name", "open", "rel", "reversed", "rowspan",
+    "scope", "span", "src", "srcset", "start", "style", "target", "title", "type", "value",
+    "width",
+];
+
+/// Elements laid out as blocks, which end a paragraph.
+pub const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "caption",
+    "colgroup",
+    "dd",
+    "details",
+    "dialog",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hgroup",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "ul",
+];
+
+pub fn is_void(name: &str) -> bool {
+    VOID_ELEMENTS.contains(&name)
+}
+
+pub fn is_block(name: &str) -> bool {
+    BLOCK_ELEMENTS.contains(&name)
+}
+
+/// The static name of a known attribute.
+pub fn attribute_name(name: &str) -> Option<&'static str> {
+    ATTRIBUTES.iter().find(|attr| **attr == name).copied()
+}
+
+/// Splits a fragment of HTML into tags and text, without building a tree.
+///
+/// The tokenizer is lenient: a `<` not starting a tag is text, and an unterminated tag is
+/// dropped.
+pub fn tokenize(src: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    let mut rest = src;
+    while !rest.is_empty() {
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment.find("-->").map_or(comment.len(), |pos| pos + 3);
+            tokens.push(Token::Comment(&rest[..4 + end]));
+            rest = &comment[end..];
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            let end = rest.find('>').map_or(rest.len(), |pos| pos + 1);
+            tokens.push(Token::Comment(&rest[..end]));
+            rest = &rest[end..];
+        } else if let Some((token, len)) = parse_tag(rest) {
+            tokens.push(token);
+            rest = &rest[len..];
+        } else {
+            // text up to the next possible tag, but at least one character.
+            let first = rest.chars().next().map_or(1, char::len_utf8);
+            let end = rest[first..]
+                .find('<')
+                .map_or(rest.len(), |pos| pos + first);
+            tokens.push(Token::Text(decode_entities(&rest[..end])));
+            rest = &rest[end..];
+        }
+    }
+    tokens
+}
+
+fn is_name_char(c: char) -> bool {
+    !c.is_whitespace() && !matches!(c, '/' | '>' | '=' | '"' | '\'' | '<')
+}
+
+/// parses a start or end tag at the beginning of the text, returning its length.
+fn parse_tag(src: &str) -> Option<(Token<'_>, usize)> {
+    let body = src.strip_prefix('<')?;
+    let (end, body) = match body.strip_prefix('/') {
+        Some(body) => (true, body),
+        None => (false, body),
+    };
+    if !body.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let name_len = body.find(|c: char| !is_name_char(c)).unwrap_or(body.len());
+    let name = body[..name_len].to_ascii_lowercase();
+    let mut rest = &body[name_len..];
+    let mut attrs = vec![];
+    let mut self_closing = false;
+
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix('>') {
+            rest = after;
+            break;
+        } else if let Some(after) = rest.strip_prefix("/>") {
+            self_closing = true;
+            rest = after;
+            break;
+        } else if let Some(after) = rest.strip_prefix('/') {
+            rest = after;
+            continue;
+        }
+
+        let len = rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len());
+        if len == 0 {
+            // unterminated tag.
+            return None;
+        }
+        let attr = rest[..len].to_ascii_lowercase();
+        rest = rest[len..].trim_start();
+        let value = match rest.strip_prefix('=') {
+            Some(after) => {
+                let after = after.trim_start();
+                let (value, after) = match after.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let end = after[1..].find(quote)? + 1;
+                        (&after[1..end], &after[end + 1..])
+                    }
+                    _ => {
+                        let end = after
+                            .find(|c: char| c.is_whitespace() || c == '>')
+                            .unwrap_or(after.len());
+                        (&after[..end], &after[end..])
+                    }
+                };
+                rest = after;
+                decode_entities(value).into_owned()
+            }
+            None => String::new(),
+        };
+        attrs.push((attr, value));
+    }
+
+    let len = src.len() - rest.len();
+    let token = if end {
+        Token::End(name)
+    } else {
+        Token::Start {
+            name,
+            attrs,
+            self_closing,
+        }
+    };
+    Some((token, len))
+}
+
+/// Replaces numeric and common named character references by their characters.
+pub fn decode_entities(text: &str) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find('&') {
+        decoded.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let end = rest.find(';').filter(|end| *end <= 32);
+        match end.and_then(|end| decode_entity(&rest[1..end]).map(|c| (c, end))) {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    Cow::Owned(decoded)
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    if let Some(num) = name.strip_prefix('#') {
+        let code = match num.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => num.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    let c = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "shy" => '\u{ad}',
+        "copy" => '©',
+        "reg" => '®',
+        "hellip" => '…',
+        "ndash" => '–',
+        "mdash" => '—',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        _ => return None,
+    };
+    Some(c)
+}
+
+/// Escapes the characters of the text reserved in HTML.
+pub fn escape(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"']) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tokenize_multibyte_text() {
+        assert_eq!(
+            tokenize("↩</a>é"),
+            vec![
+                Token::Text(Cow::Borrowed("↩")),
+                Token::End("a".to_string()),
+                Token::Text(Cow::Borrowed("é"))
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_fragment() {
+        assert_eq!(
+            tokenize("<span class='a' hidden>x &amp; y</SPAN><br/>"),
+            vec![
+                Token::Start {
+                    name: "span".to_string(),
+                    attrs: vec![
+                        ("class".to_string(), "a".to_string()),
+                        ("hidden".to_string(), String::new())
+                    ],
+                    self_closing: false,
+                },
+                Token::Text(Cow::Borrowed("x & y")),
+                Token::End("span".to_string()),
+                Token::Start {
+                    name: "br".to_string(),
+                    attrs: vec![],
+                    self_closing: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_lenient() {
+        assert_eq!(
+            tokenize("1 < 2 <!-- note -->"),
+            vec![
+                Token::Text(Cow::Borrowed("1 ")),
+                Token::Text(Cow::Borrowed("< 2 ")),
+                Token::Comment("<!-- note -->"),
+            ]
+        );
+        assert_eq!(decode_entities("&#x41;&#66;&unknown;"), "AB&unknown;");
+    }
+}
@@ -7,7 +7,7 @@ use std::{fmt::Display, str::FromStr};
 use serde::{de::Visitor, Deserialize, Serialize};
 
 fn is_hex_digit(c: char) -> bool {
-    c.is_digit(16)
+    c.is_ascii_hexdigit()
 }
 
 fn from_hex8(input: &str) -> Result<u32, std::num::ParseIntError> {
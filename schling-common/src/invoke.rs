@@ -24,7 +24,7 @@ impl<I, O> Clone for Invoke<I, O> {
     }
 }
 
-#[allow(clippy::vtable_address_comparisons)]
+#[allow(ambiguous_wide_pointer_comparisons)]
 impl<I, O> PartialEq for Invoke<I, O> {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
@@ -1,4 +1,3 @@
-use closure::closure;
 use multimap::MultiMap;
 use std::{
     collections::HashSet,
@@ -13,7 +12,7 @@ use yew_agent::{Agent, AgentLink, Bridge, Bridged, Context, HandlerId};
 use crate::invoke::Invoke;
 
 /// Inserts the following snippet
-/// ```rust
+/// ```ignore
 /// js_obj.and_then(|obj| obj.dyn_into::<target>.ok())
 /// ```
 #[macro_export]
@@ -61,7 +60,7 @@ pub fn query_parents<N: JsCast, F: Fn(Node) -> Result<N, Node>>(
         }
     }
 
-    return None;
+    None
 }
 
 #[derive(Clone, Default)]
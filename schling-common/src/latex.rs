@@ -0,0 +1,555 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::ast::{Align, Element, Kind, Node, Notes};
+use crate::data::{Doc, Sec};
+use crate::html;
+use crate::sanitize::Policy;
+use crate::toc;
+
+/// The document class of a LaTeX export.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Class {
+    /// A short paper without a table of contents.
+    #[default]
+    Article,
+    /// A longer report with a title page and a table of contents.
+    Report,
+    /// A book with a title page, a table of contents, and pages for two sided printing.
+    Book,
+}
+
+impl Class {
+    /// The preamble of the document up to the title.
+    fn preamble(self) -> &'static str {
+        match self {
+            Class::Article => "\\documentclass[11pt,a4paper]{article}\n",
+            // the sections are the top level, without chapters to number them.
+            Class::Report => {
+                "\\documentclass[11pt,a4paper]{report}\n\
+                \\renewcommand{\\thesection}{\\arabic{section}}\n"
+            }
+            Class::Book => {
+                "\\documentclass[11pt,a4paper,openany]{book}\n\
+                \\renewcommand{\\thesection}{\\arabic{section}}\n"
+            }
+        }
+    }
+
+    /// The front matter of the document, after the start of its body.
+    fn front_matter(self) -> &'static str {
+        match self {
+            Class::Article => "\\maketitle\n\n",
+            Class::Report => "\\maketitle\n\\tableofcontents\n\n",
+            Class::Book => "\\frontmatter\n\\maketitle\n\\tableofcontents\n\\mainmatter\n\n",
+        }
+    }
+}
+
+impl Display for Class {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Class::Article => write!(f, "article"),
+            Class::Report => write!(f, "report"),
+            Class::Book => write!(f, "book"),
+        }
+    }
+}
+
+impl FromStr for Class {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "article" => Ok(Class::Article),
+            "report" => Ok(Class::Report),
+            "book" => Ok(Class::Book),
+            _ => Err(format!("unknown document class: {}", s)),
+        }
+    }
+}
+
+/// The packages of the constructs the content is written with, available in every standard
+/// TeX installation.
+const PACKAGES: &str = "\
+\\usepackage[utf8]{inputenc}
+\\usepackage[T1]{fontenc}
+\\usepackage{lmodern}
+\\usepackage{amsmath}
+\\usepackage{amssymb}
+\\usepackage{booktabs}
+\\usepackage[normalem]{ulem}
+\\usepackage{hyperref}
+";
+
+/// A document written as a LaTeX source file.
+///
+/// The titled sections are numbered `\section`s, and the headings of the content are the
+/// levels below them. The headings of untitled sections start at the level of a section.
+/// Footnotes are set at their first reference, and later references point to their number.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Latex {
+    pub class: Class,
+    pub authors: Vec<String>,
+    pub doc: Doc,
+    pub sections: Vec<Sec>,
+}
+
+impl Latex {
+    pub fn new(doc: Doc, sections: Vec<Sec>, class: Class) -> Self {
+        Self {
+            class,
+            authors: vec![],
+            doc,
+            sections,
+        }
+    }
+
+    /// The source of the document.
+    pub fn to_tex(&self) -> String {
+        let mut notes = Notes::default();
+        let contents: Vec<(&Sec, Vec<Node>)> = self
+            .sections
+            .iter()
+            .map(|sec| {
+                notes.set_scope(&format!("{}-", toc::section_anchor(sec.head.id)));
+                (sec, sec.content.to_ast(&mut notes, &Policy::default()))
+            })
+            .collect();
+        let mut writer = Writer {
+            notes: notes
+                .into_notes()
+                .into_iter()
+                .map(|note| (note.number, note.content))
+                .collect(),
+            ..Writer::default()
+        };
+        let mut tex = String::from(self.class.preamble());
+        tex.push_str(PACKAGES);
+        tex.push_str(&format!(
+            "\n\\title{{{}}}\n\\author{{{}}}\n\\date{{}}\n\n\\begin{{document}}\n",
+            escape(&self.doc.head.title),
+            self.authors
+                .iter()
+                .map(|author| escape(author))
+                .collect::<Vec<_>>()
+                .join(" \\and ")
+        ));
+        tex.push_str(self.class.front_matter());
+        for (sec, nodes) in contents {
+            writer.offset = 0;
+            if !sec.head.title.trim().is_empty() {
+                tex.push_str(&format!(
+                    "\\section{{{}}}\\label{{{}}}\n\n",
+                    escape(&sec.head.title),
+                    toc::section_anchor(sec.head.id)
+                ));
+                writer.offset = 1;
+            }
+            tex.push_str(&writer.blocks(&nodes));
+        }
+        tex.push_str("\\end{document}\n");
+        tex
+    }
+}
+
+/// Escapes the characters of the text with a special meaning in LaTeX.
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\textbackslash{}"),
+            '~' => out.push_str("\\textasciitilde{}"),
+            '^' => out.push_str("\\textasciicircum{}"),
+            '{' | '}' | '$' | '&' | '#' | '%' | '_' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Escapes the characters of a URL with a special meaning in the argument of `\href`.
+fn escape_url(url: &str) -> String {
+    let mut out = String::with_capacity(url.len());
+    for c in url.chars() {
+        match c {
+            '\\' => out.push_str("%5C"),
+            '{' => out.push_str("%7B"),
+            '}' => out.push_str("%7D"),
+            '#' | '%' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The command of a heading, by its level below the section.
+fn heading_command(level: u8) -> &'static str {
+    match level {
+        0 | 1 => "section",
+        2 => "subsection",
+        3 => "subsubsection",
+        4 => "paragraph",
+        _ => "subparagraph",
+    }
+}
+
+fn is_block(node: &Node) -> bool {
+    match node {
+        Node::Rule => true,
+        Node::Element(el) => match &el.kind {
+            Kind::Html { name, .. } => html::is_block(name),
+            kind => !matches!(
+                kind,
+                Kind::Emphasis
+                    | Kind::Strong
+                    | Kind::Strikethrough
+                    | Kind::Link { .. }
+                    | Kind::Image { .. }
+            ),
+        },
+        _ => false,
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    /// The content of the footnotes by number.
+    notes: HashMap<usize, Vec<Node>>,
+    /// Whether a footnote is written, which cannot reference footnotes.
+    footnote: bool,
+    /// The levels the headings of the section are below its title.
+    offset: u8,
+    /// The number of open enumerations, whose counters are numbered by depth.
+    enumerations: usize,
+}
+
+impl Writer {
+    /// Writes the blocks followed by blank lines, and the inline nodes between them as
+    /// paragraphs.
+    fn blocks(&mut self, nodes: &[Node]) -> String {
+        let mut out = String::new();
+        let mut inline: Vec<Node> = vec![];
+        for node in nodes {
+            if is_block(node) {
+                self.flush(&mut inline, &mut out);
+                self.block(node, &mut out);
+            } else {
+                inline.push(node.clone());
+            }
+        }
+        self.flush(&mut inline, &mut out);
+        out
+    }
+
+    fn flush(&mut self, inline: &mut Vec<Node>, out: &mut String) {
+        if !inline.iter().all(|node| node.text().trim().is_empty()) {
+            out.push_str(self.inline(inline).trim());
+            out.push_str("\n\n");
+        }
+        inline.clear();
+    }
+
+    fn block(&mut self, node: &Node, out: &mut String) {
+        let el = match node {
+            Node::Element(el) => el,
+            _ => {
+                out.push_str("\\begin{center}\\rule{0.5\\linewidth}{0.4pt}\\end{center}\n\n");
+                return;
+            }
+        };
+        match &el.kind {
+            Kind::Paragraph => {
+                out.push_str(self.inline(&el.children).trim());
+                out.push_str("\n\n");
+            }
+            Kind::Heading { level, id } => {
+                let command = heading_command(level + self.offset);
+                out.push_str(&format!("\\{}{{{}}}", command, self.inline(&el.children)));
+                if let Some(id) = id {
+                    out.push_str(&format!("\\label{{{}}}", id));
+                }
+                out.push_str("\n\n");
+            }
+            Kind::BlockQuote => {
+                out.push_str("\\begin{quote}\n");
+                out.push_str(self.blocks(&el.children).trim_end());
+                out.push_str("\n\\end{quote}\n\n");
+            }
+            Kind::CodeBlock(_) => {
+                let code: String = el.children.iter().map(Node::text).collect();
+                // the end of the environment cannot appear in its content.
+                let code = code.replace("\\end{verbatim}", "\\end {verbatim}");
+                out.push_str("\\begin{verbatim}\n");
+                out.push_str(code.trim_end_matches('\n'));
+                out.push_str("\n\\end{verbatim}\n\n");
+            }
+            Kind::List(start) => {
+                let environment = match start {
+                    Some(_) => "enumerate",
+                    None => "itemize",
+                };
+                out.push_str(&format!("\\begin{{{}}}\n", environment));
+                if let Some(start) = start {
+                    self.enumerations += 1;
+                    if *start != 1 && self.enumerations <= 4 {
+                        let counter = ["i", "ii", "iii", "iv"][self.enumerations - 1];
+                        out.push_str(&format!(
+                            "\\setcounter{{enum{}}}{{{}}}\n",
+                            counter,
+                            start.saturating_sub(1)
+                        ));
+                    }
+                }
+                for item in &el.children {
+                    let children = match item {
+                        Node::Element(item) => &item.children[..],
+                        node => std::slice::from_ref(node),
+                    };
+                    // the braces end the item, so the text cannot be read as its label.
+                    out.push_str("\\item{} ");
+                    out.push_str(self.blocks(children).trim_end());
+                    out.push('\n');
+                }
+                if start.is_some() {
+                    self.enumerations -= 1;
+                }
+                out.push_str(&format!("\\end{{{}}}\n\n", environment));
+            }
+            Kind::Table => self.table(el, out),
+            _ => out.push_str(&self.blocks(&el.children)),
+        }
+    }
+
+    fn table(&mut self, table: &Element, out: &mut String) {
+        let rows: Vec<&Element> = table
+            .children
+            .iter()
+            .filter_map(|row| match row {
+                Node::Element(row) => Some(row),
+                _ => None,
+            })
+            .collect();
+        let columns = rows.iter().map(|row| row.children.len()).max().unwrap_or(0);
+        if columns == 0 {
+            return;
+        }
+        let aligns: Vec<char> = (0..columns)
+            .map(|i| {
+                let align = rows.iter().find_map(|row| match row.children.get(i) {
+                    Some(Node::Element(Element {
+                        kind: Kind::TableCell { align, .. },
+                        ..
+                    })) => Some(*align),
+                    _ => None,
+                });
+                match align.unwrap_or_default() {
+                    Align::None | Align::Left => 'l',
+                    Align::Center => 'c',
+                    Align::Right => 'r',
+                }
+            })
+            .collect();
+        out.push_str(&format!(
+            "\\begin{{center}}\n\\begin{{tabular}}{{{}}}\n\\toprule\n",
+            aligns.iter().collect::<String>()
+        ));
+        for row in rows {
+            let cells: Vec<String> = row
+                .children
+                .iter()
+                .map(|cell| match cell {
+                    Node::Element(Element {
+                        kind: Kind::TableCell { header: true, .. },
+                        children,
+                    }) => format!("\\textbf{{{}}}", self.inline(children).trim()),
+                    Node::Element(Element {
+                        kind: Kind::TableCell { .. },
+                        children,
+                    }) => self.inline(children).trim().to_string(),
+                    node => self.inline(std::slice::from_ref(node)),
+                })
+                .collect();
+            out.push_str(&cells.join(" & "));
+            out.push_str(" \\\\\n");
+            if row.kind == Kind::TableHead {
+                out.push_str("\\midrule\n");
+            }
+        }
+        out.push_str("\\bottomrule\n\\end{tabular}\n\\end{center}\n\n");
+    }
+
+    fn inline(&mut self, nodes: &[Node]) -> String {
+        let mut out = String::new();
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(&escape(text)),
+                Node::Code(code) => out.push_str(&format!("\\texttt{{{}}}", escape(code))),
+                Node::SoftBreak => out.push('\n'),
+                Node::HardBreak => out.push_str("\\newline\n"),
+                Node::Rule => {}
+                Node::TaskMarker(true) => out.push_str("$\\boxtimes$ "),
+                Node::TaskMarker(false) => out.push_str("$\\square$ "),
+                Node::Math(math) if math.display => {
+                    out.push_str(&format!("\\[{}\\]", math.tex.trim()))
+                }
+                Node::Math(math) => out.push_str(&format!("\\({}\\)", math.tex.trim())),
+                Node::FootnoteRef(_) if self.footnote => {}
+                Node::FootnoteRef(note) => match note.id {
+                    Some(_) => {
+                        let content = self.notes.get(&note.number).cloned().unwrap_or_default();
+                        self.footnote = true;
+                        let content = self.blocks(&content);
+                        self.footnote = false;
+                        out.push_str(&format!(
+                            "\\footnote{{\\label{{note-{}}}{}}}",
+                            note.number,
+                            content.trim()
+                        ));
+                    }
+                    None => out.push_str(&format!(
+                        "\\textsuperscript{{\\ref{{note-{}}}}}",
+                        note.number
+                    )),
+                },
+                Node::Element(el) => out.push_str(&self.element(el)),
+            }
+        }
+        out
+    }
+
+    fn element(&mut self, el: &Element) -> String {
+        let content = self.inline(&el.children);
+        match &el.kind {
+            Kind::Emphasis | Kind::Image { .. } => format!("\\emph{{{}}}", content),
+            Kind::Strong => format!("\\textbf{{{}}}", content),
+            Kind::Strikethrough => format!("\\sout{{{}}}", content),
+            Kind::Link {
+                href: Some(href), ..
+            } => match href.strip_prefix('#') {
+                Some(anchor) => format!("\\hyperref[{}]{{{}}}", anchor, content),
+                None => format!("\\href{{{}}}{{{}}}", escape_url(href), content),
+            },
+            Kind::Html { name, .. } => match name.as_str() {
+                "em" | "i" | "cite" => format!("\\emph{{{}}}", content),
+                "strong" | "b" => format!("\\textbf{{{}}}", content),
+                "del" | "s" => format!("\\sout{{{}}}", content),
+                "code" | "kbd" | "samp" => format!("\\texttt{{{}}}", content),
+                "sup" => format!("\\textsuperscript{{{}}}", content),
+                "sub" => format!("\\textsubscript{{{}}}", content),
+                "br" => "\\newline\n".to_string(),
+                _ => content,
+            },
+            _ => content,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::chapter;
+
+    fn latex(texts: &[(&str, &str)], class: Class) -> Latex {
+        let (doc, sections) = chapter(1, "Notes & Queries", texts);
+        Latex::new(doc, sections, class)
+    }
+
+    fn body(latex: &Latex) -> String {
+        let tex = latex.to_tex();
+        let start =
+            tex.find(latex.class.front_matter()).unwrap() + latex.class.front_matter().len();
+        let end = tex.find("\\end{document}").unwrap();
+        tex[start..end].to_string()
+    }
+
+    #[test]
+    fn escapes_special_characters() {
+        assert_eq!(
+            escape("50% of $5 & #1_x {a} ~^\\"),
+            "50\\% of \\$5 \\& \\#1\\_x \\{a\\} \\textasciitilde{}\\textasciicircum{}\\textbackslash{}"
+        );
+        assert_eq!(escape_url("https://a.b/c%20d#e"), "https://a.b/c\\%20d\\#e");
+    }
+
+    #[test]
+    fn writes_the_preamble() {
+        let mut latex = latex(&[("", "Text.")], Class::Book);
+        latex.authors = vec!["Ada".to_string(), "Grace".to_string()];
+        let tex = latex.to_tex();
+        assert!(tex.starts_with("\\documentclass[11pt,a4paper,openany]{book}\n"));
+        assert!(tex.contains("\\title{Notes \\& Queries}\n\\author{Ada \\and Grace}\n"));
+        assert!(tex.contains("\\mainmatter\n\nText.\n\n\\end{document}\n"));
+        assert_eq!("Report".parse(), Ok(Class::Report));
+        assert!("memoir".parse::<Class>().is_err());
+    }
+
+    #[test]
+    fn maps_sections_and_headings() {
+        let latex = latex(
+            &[
+                ("Intro", "# Scope\n\nIt *is* **so** ~~not~~ `x_1`."),
+                ("", "# Top\n\n## Below"),
+            ],
+            Class::Article,
+        );
+        assert_eq!(
+            body(&latex),
+            "\\section{Intro}\\label{section-0}\n\n\
+            \\subsection{Scope}\\label{section-0-scope}\n\n\
+            It \\emph{is} \\textbf{so} \\sout{not} \\texttt{x\\_1}.\n\n\
+            \\section{Top}\\label{section-1-top}\n\n\
+            \\subsection{Below}\\label{section-1-below}\n\n"
+        );
+    }
+
+    #[test]
+    fn writes_blocks() {
+        let latex = latex(
+            &[(
+                "",
+                "> Quoted\n\n\
+                3. three\n4. [four](https://example.com/#4)\n\n\
+                - [x] done\n  - nested\n\n\
+                | A | B |\n|:-:|--:|\n| 1 | $x^2$ |\n\n\
+                ```\nlet x = {};\n```",
+            )],
+            Class::Article,
+        );
+        assert_eq!(
+            body(&latex),
+            "\\begin{quote}\nQuoted\n\\end{quote}\n\n\
+            \\begin{enumerate}\n\\setcounter{enumi}{2}\n\\item{} three\n\
+            \\item{} \\href{https://example.com/\\#4}{four}\n\\end{enumerate}\n\n\
+            \\begin{itemize}\n\\item{} $\\boxtimes$ done\n\n\
+            \\begin{itemize}\n\\item{} nested\n\\end{itemize}\n\\end{itemize}\n\n\
+            \\begin{center}\n\\begin{tabular}{cr}\n\\toprule\n\
+            \\textbf{A} & \\textbf{B} \\\\\n\\midrule\n1 & \\(x^2\\) \\\\\n\
+            \\bottomrule\n\\end{tabular}\n\\end{center}\n\n\
+            \\begin{verbatim}\nlet x = {};\n\\end{verbatim}\n\n"
+        );
+    }
+
+    #[test]
+    fn sets_footnotes_at_their_references() {
+        let latex = latex(
+            &[
+                ("", "One[^a] and again[^a].\n\n[^a]: A *note*."),
+                ("", "Two[^a].\n\n[^a]: Another."),
+            ],
+            Class::Article,
+        );
+        assert_eq!(
+            body(&latex),
+            "One\\footnote{\\label{note-1}A \\emph{note}.} and again\\textsuperscript{\\ref{note-1}}.\n\n\
+            Two\\footnote{\\label{note-2}Another.}.\n\n"
+        );
+    }
+}
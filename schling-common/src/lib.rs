@@ -1,10 +1,33 @@
+pub mod ast;
+pub mod backup;
+pub mod convert;
+pub mod data;
+pub mod diff;
+pub mod docx;
+pub mod epub;
+pub mod folder;
+pub mod fountain;
+pub mod highlight;
+pub mod html;
 pub mod id;
 #[cfg(feature = "yew-wasm")]
 pub mod invoke;
 #[cfg(feature = "yew-wasm")]
 pub mod key;
+pub mod latex;
+pub mod manuscript;
 pub mod markup;
+pub mod math;
+pub mod pdf;
+pub mod render;
+pub mod rtf;
+pub mod sanitize;
+pub mod scrivener;
+pub mod search;
+pub mod site;
 pub mod source;
+pub mod toc;
+pub mod wiki;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,205 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::data::{Doc, Sec};
+use crate::source::Stats;
+use crate::{docx, pdf};
+
+/// The typeface of a manuscript.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Typeface {
+    /// A monospaced typeface, the classic manuscript look.
+    #[default]
+    Courier,
+    /// A proportional serif typeface.
+    Times,
+}
+
+impl Display for Typeface {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Typeface::Courier => write!(f, "courier"),
+            Typeface::Times => write!(f, "times"),
+        }
+    }
+}
+
+impl FromStr for Typeface {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "courier" => Ok(Typeface::Courier),
+            "times" => Ok(Typeface::Times),
+            _ => Err(format!("unknown typeface: {}", s)),
+        }
+    }
+}
+
+/// A document laid out in the Standard Manuscript Format for submissions.
+///
+/// The first page has the contact information of the author at the top left and the rounded
+/// word count at the top right, then the title and the byline, and the text starts below them.
+/// The text is double spaced in 12 point type on US Letter pages with margins of an inch, and
+/// its paragraphs are indented. The following pages have a header with the surname of the
+/// author, the title and the page number. The sections are separated by `#` scene breaks, and
+/// the text ends with `END`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Manuscript {
+    pub title: String,
+    /// The legal name of the author, heading the contact information.
+    pub author: String,
+    /// The name the work is published under, when it differs from the legal name.
+    pub byline: Option<String>,
+    /// The lines of the contact information after the name, such as the address and email.
+    pub contact: Vec<String>,
+    pub typeface: Typeface,
+    pub language: String,
+    pub doc: Doc,
+    pub sections: Vec<Sec>,
+}
+
+impl Manuscript {
+    pub fn new(doc: Doc, sections: Vec<Sec>, author: &str) -> Self {
+        Self {
+            title: doc.head.title.clone(),
+            author: author.to_string(),
+            byline: None,
+            contact: vec![],
+            typeface: Typeface::default(),
+            language: "en".to_string(),
+            doc,
+            sections,
+        }
+    }
+
+    /// The number of words of the sections, as the editor counts them.
+    pub fn words(&self) -> usize {
+        self.sections
+            .iter()
+            .map(|sec| Stats::of(&sec.content.text).words)
+            .sum()
+    }
+
+    /// The word count of the title page, rounded to the nearest hundred.
+    pub fn word_count(&self) -> String {
+        let words = self.words();
+        let rounded = ((words + 50) / 100 * 100).max(100);
+        format!("about {} words", thousands(rounded))
+    }
+
+    /// The name the work is published under.
+    pub fn byline(&self) -> &str {
+        self.byline
+            .as_deref()
+            .filter(|byline| !byline.trim().is_empty())
+            .unwrap_or(&self.author)
+    }
+
+    /// The header of the pages after the first, before the page number.
+    pub fn header(&self) -> String {
+        let surname = self.byline().split_whitespace().last().unwrap_or_default();
+        format!("{} / {}", surname, self.title)
+    }
+
+    pub fn to_docx(&self) -> Result<Vec<u8>, docx::Error> {
+        docx::manuscript(self)
+    }
+
+    pub fn to_pdf(&self) -> Result<Vec<u8>, pdf::Error> {
+        pdf::manuscript(self)
+    }
+}
+
+/// The number with commas between its groups of thousands.
+fn thousands(number: usize) -> String {
+    let digits = number.to_string();
+    let mut text = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            text.push(',');
+        }
+        text.push(c);
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Read};
+
+    use zip::ZipArchive;
+
+    use super::*;
+    use crate::data::chapter;
+
+    fn manuscript(texts: &[&str]) -> Manuscript {
+        let texts: Vec<(&str, &str)> = texts.iter().map(|text| ("", *text)).collect();
+        let (doc, sections) = chapter(1, "The Long Night", &texts);
+        let mut manuscript = Manuscript::new(doc, sections, "Jane Q. Public");
+        manuscript.byline = Some("J. Q. Writer".to_string());
+        manuscript.contact = vec!["123 Main St".to_string(), "jane@example.com".to_string()];
+        manuscript
+    }
+
+    #[test]
+    fn rounds_word_counts() {
+        let words = "word ".repeat(2349);
+        let mut manuscript = manuscript(&[&words, "*One* more."]);
+        assert_eq!(manuscript.words(), 2351);
+        assert_eq!(manuscript.word_count(), "about 2,400 words");
+        manuscript.sections.truncate(0);
+        assert_eq!(manuscript.word_count(), "about 100 words");
+        assert_eq!(thousands(1_234_500), "1,234,500");
+    }
+
+    #[test]
+    fn uses_the_byline_in_headers() {
+        let mut manuscript = manuscript(&["Text."]);
+        assert_eq!(manuscript.header(), "Writer / The Long Night");
+        manuscript.byline = None;
+        assert_eq!(manuscript.header(), "Public / The Long Night");
+        assert_eq!("Times".parse(), Ok(Typeface::Times));
+    }
+
+    #[test]
+    fn writes_docx() {
+        let manuscript = manuscript(&["It *began*.", "It ended."]);
+        let bytes = manuscript.to_docx().unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+        let mut read = |name: &str| {
+            let mut content = String::new();
+            let mut file = archive.by_name(name).unwrap();
+            file.read_to_string(&mut content).unwrap();
+            content
+        };
+        let document = read("word/document.xml");
+        assert!(document.contains("Jane Q. Public</w:t></w:r><w:r><w:tab/></w:r>"));
+        assert!(document.contains("about 100 words"));
+        assert!(document.contains("by J. Q. Writer"));
+        assert!(document.contains("<w:titlePg/>"));
+        assert!(read("word/header1.xml").contains("Writer / The Long Night / "));
+        assert!(read("word/styles.xml").contains("w:line=\"480\""));
+
+        let (doc, sections) = docx::import(&bytes, manuscript.doc.head.id).unwrap();
+        assert_eq!(doc.head.title, "The Long Night");
+        assert_eq!(
+            sections[0].content.text,
+            "Jane Q. Public about 100 words\n\n123 Main St\n\njane@example.com\n\n\
+            by J. Q. Writer\n\nIt *began*.\n\n---\n\nIt ended.\n\nEND\n"
+        );
+    }
+
+    #[test]
+    fn writes_pdf() {
+        let text = "A sentence of the story goes on for a while. ".repeat(200);
+        let manuscript = manuscript(&[&text, "The end."]);
+        let bytes = manuscript.to_pdf().unwrap();
+        let pdf = String::from_utf8_lossy(&bytes);
+        assert!(pdf.starts_with("%PDF-"));
+        // about 2,000 words double spaced take six pages.
+        assert_eq!(pdf.matches("/Type /Page\n").count(), 6);
+        assert!(pdf.contains("DejaVuSansMono"));
+        assert!(!pdf.contains("LinLibertine"));
+    }
+}
@@ -1,17 +1,23 @@
-#[cfg(feature = "yew-wasm")]
-use gloo_console::error;
-#[cfg(feature = "yew-wasm")]
-use pulldown_cmark::{Alignment, CodeBlockKind, Event, Options, Parser, Tag};
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 use strum_macros::EnumIter;
 #[cfg(feature = "yew-wasm")]
 use web_sys::Element;
 #[cfg(feature = "yew-wasm")]
-use yew::virtual_dom::{VNode, VTag, VText};
+use yew::virtual_dom::{VList, VNode, VTag, VText};
 #[cfg(feature = "yew-wasm")]
-use yew::{html, Classes, Component, Context, Html, NodeRef, Properties};
+use yew::{html, Component, Context, Html, NodeRef, Properties};
 
+#[cfg(feature = "yew-wasm")]
+use crate::ast::{self, parse_markdown, Align, Kind, Node, Note, Notes};
+#[cfg(feature = "yew-wasm")]
+use crate::highlight;
+#[cfg(feature = "yew-wasm")]
+use crate::html as dom;
+#[cfg(feature = "yew-wasm")]
+use crate::math::Math;
+#[cfg(feature = "yew-wasm")]
+use crate::sanitize::Policy;
 #[cfg(feature = "yew-wasm")]
 use crate::source::ToDom;
 
@@ -76,10 +82,12 @@ impl Display for Markup {
     }
 }
 
-#[derive(EnumIter, PartialEq, Debug, Eq, Clone, Copy, Serialize, Deserialize)]
+#[derive(EnumIter, PartialEq, Debug, Default, Eq, Clone, Copy, Serialize, Deserialize)]
 pub enum MarkupLang {
     Html,
+    #[default]
     Md,
+    Fountain,
 }
 
 impl MarkupLang {
@@ -88,17 +96,12 @@ impl MarkupLang {
     }
 }
 
-impl Default for MarkupLang {
-    fn default() -> Self {
-        MarkupLang::Md
-    }
-}
-
 impl Display for MarkupLang {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let lang = match self {
             MarkupLang::Html => "html",
             MarkupLang::Md => "md",
+            MarkupLang::Fountain => "fountain",
         };
         write!(f, "{}", lang)
     }
@@ -107,20 +110,36 @@ impl Display for MarkupLang {
 #[cfg(feature = "yew-wasm")]
 impl ToDom for Markup {
     fn to_dom(self) -> Html {
-        match self.lang {
-            MarkupLang::Html => html!(<RawHtml inner_html={self.text}/>),
-            MarkupLang::Md => render_markdown(self.text.as_str()),
+        let mut notes = Notes::default();
+        let content = self.to_dom_with(&mut notes, &Policy::default());
+        html! {
+        <>
+            {content}
+            {notes.to_dom()}
+        </>
         }
     }
 }
 
+#[cfg(feature = "yew-wasm")]
+impl Markup {
+    /// Renders the markup, collecting its footnotes into `notes` instead of appending them,
+    /// and removing the HTML not allowed by the policy.
+    pub fn to_dom_with(self, notes: &mut Notes, policy: &Policy) -> Html {
+        self.to_ast(notes, policy).to_dom()
+    }
+}
+
 #[cfg(feature = "yew-wasm")]
 #[derive(Debug, Clone, Eq, PartialEq, Properties)]
 pub struct RawHtmlProps {
     pub inner_html: String,
+    #[prop_or_default]
+    pub policy: Policy,
 }
 
-/// Embeds the content of the `inner_html` string into the VDOM.
+/// Embeds the content of the `inner_html` string into the VDOM, after removing the elements
+/// and attributes not allowed by the `policy`.
 #[cfg(feature = "yew-wasm")]
 pub struct RawHtml {
     props: RawHtmlProps,
@@ -161,195 +180,402 @@ impl Component for RawHtml {
 
     fn rendered(&mut self, _ctx: &Context<Self>, _first_render: bool) {
         let el = self.node_ref.cast::<Element>().unwrap();
-        el.set_inner_html(&self.props.inner_html);
+        el.set_inner_html(&self.props.policy.sanitize(&self.props.inner_html));
     }
 }
 
-/// Original author of this code is [Nathan Ringo](https://github.com/remexre)
-/// Source: https://github.com/acmumn/mentoring/blob/master/web-client/src/view/markdown.rs
+#[cfg(feature = "yew-wasm")]
+#[derive(Debug, Clone, Eq, PartialEq, Properties)]
+pub struct FormulaProps {
+    pub math: Math,
+}
 
-/// Adds a class to the VTag.
-/// You can also provide multiple classes separated by ascii whitespaces.
-///
-/// Note that this has a complexity of O(n),
-/// where n is the number of classes already in VTag plus
-/// the number of classes to be added.
+/// Embeds a formula as MathML, which the VDOM cannot create in its namespace.
 #[cfg(feature = "yew-wasm")]
-fn add_class(vtag: &mut VTag, class: impl Into<Classes>) {
-    let mut classes: Classes = vtag
-        .attributes
-        .iter()
-        .find(|(k, _)| *k == "class")
-        .map(|(_, v)| Classes::from(v.to_owned()))
-        .unwrap_or_default();
-    classes.push(class);
-    vtag.add_attribute("class", classes.to_string());
+pub struct Formula {
+    node_ref: NodeRef,
 }
 
-/// Renders a string of Markdown to HTML with the default options (footnotes
-/// disabled, tables enabled).
 #[cfg(feature = "yew-wasm")]
-pub fn render_markdown(src: &str) -> Html {
-    let mut elems = vec![];
-    let mut spine = vec![];
-
-    macro_rules! add_child {
-        ($child:expr) => {{
-            let l = spine.len();
-            assert_ne!(l, 0);
-            spine[l - 1].add_child($child);
-        }};
-    }
-
-    let mut options = Options::empty();
-    options.insert(Options::ENABLE_TABLES);
-    options.insert(Options::ENABLE_SMART_PUNCTUATION);
-    options.insert(Options::ENABLE_STRIKETHROUGH);
-
-    for ev in Parser::new_ext(src, options) {
-        match ev {
-            Event::Start(tag) => {
-                spine.push(make_tag(tag));
+impl Component for Formula {
+    type Message = ();
+    type Properties = FormulaProps;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            node_ref: NodeRef::default(),
+        }
+    }
+
+    fn view(&self, _ctx: &Context<Self>) -> Html {
+        html! {
+            <span class="math" ref={self.node_ref.clone()}/>
+        }
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, _first_render: bool) {
+        let el = self.node_ref.cast::<Element>().unwrap();
+        el.set_inner_html(&ctx.props().math.render());
+    }
+}
+
+// Original author of this code is [Nathan Ringo](https://github.com/remexre)
+// Source: https://github.com/acmumn/mentoring/blob/master/web-client/src/view/markdown.rs
+
+#[cfg(feature = "yew-wasm")]
+fn add_children(tag: &mut VTag, nodes: Vec<Node>) {
+    for node in nodes {
+        tag.add_child(node.to_dom());
+    }
+}
+
+#[cfg(feature = "yew-wasm")]
+fn with_children(name: &'static str, nodes: Vec<Node>) -> VTag {
+    let mut tag = VTag::new(name);
+    add_children(&mut tag, nodes);
+    tag
+}
+
+#[cfg(feature = "yew-wasm")]
+impl ToDom for Node {
+    fn to_dom(self) -> Html {
+        match self {
+            Node::Text(text) => VText::new(text).into(),
+            Node::Code(code) => html!(<code>{code}</code>),
+            Node::SoftBreak => VText::new("\n").into(),
+            Node::HardBreak => VTag::new("br").into(),
+            Node::Rule => VTag::new("hr").into(),
+            Node::TaskMarker(checked) => {
+                let mut el = VTag::new("input");
+                el.add_attribute("disabled", "");
+                el.add_attribute("type", "checkbox");
+                el.set_checked(checked);
+                el.into()
             }
-            Event::End(tag) => {
-                // TODO Verify stack end.
-                let l = spine.len();
-                assert!(l >= 1);
-                let mut top = spine.pop().unwrap();
-                if let Tag::CodeBlock(_) = tag {
-                    let mut pre = VTag::new("pre");
-                    pre.add_child(top.into());
-                    top = pre;
-                } else if let Tag::Table(aligns) = tag {
-                    for r in top.children_mut().iter_mut().flat_map(|ch| ch.iter_mut()) {
-                        if let VNode::VTag(ref mut vtag) = r {
-                            for (i, c) in vtag
-                                .children_mut()
-                                .iter_mut()
-                                .flat_map(|ch| ch.iter_mut())
-                                .enumerate()
-                            {
-                                if let VNode::VTag(ref mut vtag) = c {
-                                    match aligns[i] {
-                                        Alignment::None => {}
-                                        Alignment::Left => add_class(vtag, "text-left"),
-                                        Alignment::Center => add_class(vtag, "text-center"),
-                                        Alignment::Right => add_class(vtag, "text-right"),
-                                    }
-                                }
-                            }
-                        }
-                    }
-                } else if let Tag::TableHead = tag {
-                    for c in top.children_mut().iter_mut().flat_map(|ch| ch.iter_mut()) {
-                        if let VNode::VTag(ref mut vtag) = c {
-                            // TODO
-                            //                            vtag.tag = "th".into();
-                            vtag.add_attribute("scope", "col");
+            Node::Math(math) => match math.to_mathml() {
+                Ok(_) => html!(<Formula {math}/>),
+                Err(err) => {
+                    let mut el = VTag::new("span");
+                    el.add_attribute("class", "math-error");
+                    el.add_attribute("title", err.to_string());
+                    el.add_child(VText::new(math.delimited()).into());
+                    el.into()
+                }
+            },
+            Node::FootnoteRef(note) => {
+                let mut a = VTag::new("a");
+                a.add_attribute("href", format!("#{}", note.target));
+                a.add_child(VText::new(note.number.to_string()).into());
+                let mut el = VTag::new("sup");
+                el.add_attribute("class", "footnote-reference");
+                if let Some(id) = note.id {
+                    el.add_attribute("id", id);
+                }
+                el.add_child(a.into());
+                el.into()
+            }
+            Node::Element(el) => el.to_dom(),
+        }
+    }
+}
+
+#[cfg(feature = "yew-wasm")]
+impl ToDom for ast::Element {
+    fn to_dom(self) -> Html {
+        let children = self.children;
+        let tag = match self.kind {
+            Kind::Paragraph => with_children("p", children),
+            Kind::Heading { level, id } => {
+                let mut el = VTag::new(format!("h{}", level));
+                if let Some(id) = id {
+                    el.add_attribute("id", id);
+                }
+                add_children(&mut el, children);
+                el
+            }
+            Kind::BlockQuote => with_children("blockquote", children),
+            Kind::CodeBlock(lang) => {
+                let children = highlight::code_block(lang.as_deref(), children);
+                let mut code = with_children("code", children);
+                if let Some(lang) = lang {
+                    code.add_attribute("class", format!("language-{}", lang));
+                }
+                let mut el = VTag::new("pre");
+                el.add_child(code.into());
+                el
+            }
+            Kind::List(None) => with_children("ul", children),
+            Kind::List(Some(1)) => with_children("ol", children),
+            Kind::List(Some(start)) => {
+                let mut el = VTag::new("ol");
+                el.add_attribute("start", start.to_string());
+                add_children(&mut el, children);
+                el
+            }
+            Kind::Item => with_children("li", children),
+            Kind::Table => {
+                let mut el = VTag::new("table");
+                let mut body = VTag::new("tbody");
+                for child in children {
+                    match child {
+                        Node::Element(head) if head.kind == Kind::TableHead => {
+                            el.add_child(head.to_dom())
                         }
+                        row => body.add_child(row.to_dom()),
                     }
                 }
-                if l == 1 {
-                    elems.push(top);
-                } else {
-                    spine[l - 2].add_child(top.into());
+                el.add_child(body.into());
+                el
+            }
+            Kind::TableHead => {
+                let mut el = VTag::new("thead");
+                el.add_child(with_children("tr", children).into());
+                el
+            }
+            Kind::TableRow => with_children("tr", children),
+            Kind::TableCell { header, align } => {
+                let mut el = with_children(if header { "th" } else { "td" }, children);
+                match align {
+                    Align::Left => el.add_attribute("style", "text-align: left"),
+                    Align::Center => el.add_attribute("style", "text-align: center"),
+                    Align::Right => el.add_attribute("style", "text-align: right"),
+                    Align::None => {}
+                }
+                el
+            }
+            Kind::Emphasis => with_children("em", children),
+            Kind::Strong => with_children("strong", children),
+            Kind::Strikethrough => with_children("del", children),
+            Kind::Link { href, title } => {
+                let mut el = VTag::new("a");
+                if let Some(href) = href {
+                    el.add_attribute("href", href);
+                }
+                if !title.is_empty() {
+                    el.add_attribute("title", title);
                 }
+                add_children(&mut el, children);
+                el
             }
-            Event::Text(text) => add_child!(VText::new(text.to_string()).into()),
-            Event::Rule => add_child!(VTag::new("hr").into()),
-            Event::SoftBreak => add_child!(VText::new("\n").into()),
-            Event::HardBreak => add_child!(VTag::new("br").into()),
-            _ => error!(format!("Unknown event: {:#?}", ev)),
-        }
+            Kind::Image { src, title } => {
+                let mut el = VTag::new("img");
+                if let Some(src) = src {
+                    el.add_attribute("src", src);
+                }
+                el.add_attribute("alt", children.iter().map(Node::text).collect::<String>());
+                if !title.is_empty() {
+                    el.add_attribute("title", title);
+                }
+                el
+            }
+            Kind::Html { name, attrs } => {
+                let mut el = VTag::new(name);
+                for (attr, value) in attrs {
+                    match dom::attribute_name(&attr) {
+                        Some("checked") => el.set_checked(true),
+                        Some("value") => el.set_value(value),
+                        Some(attr) => el.add_attribute(attr, value),
+                        None => {}
+                    }
+                }
+                add_children(&mut el, children);
+                el
+            }
+        };
+        tag.into()
     }
+}
+
+#[cfg(feature = "yew-wasm")]
+impl ToDom for Note {
+    fn to_dom(self) -> Html {
+        let mut el = VTag::new("li");
+        el.add_attribute("class", "footnote-definition");
+        el.add_attribute("id", self.id);
+        el.add_attribute("value", self.number.to_string());
+        add_children(&mut el, self.content);
+        let mut back = VTag::new("a");
+        back.add_attribute("class", "footnote-backref");
+        back.add_attribute("href", format!("#{}", self.back));
+        back.add_child(VText::new("↩").into());
+        el.add_child(back.into());
+        el.into()
+    }
+}
 
-    if elems.len() == 1 {
-        VNode::VTag(Box::new(elems.pop().unwrap()))
-    } else {
+#[cfg(feature = "yew-wasm")]
+impl ToDom for Notes {
+    fn to_dom(self) -> Html {
+        if self.is_empty() {
+            return html!();
+        }
         html! {
-            { for elems.into_iter() }
+        <section class="footnotes">
+            <ol>
+                {for self.into_notes().into_iter().map(Note::to_dom)}
+            </ol>
+        </section>
         }
     }
 }
 
 #[cfg(feature = "yew-wasm")]
-fn make_tag(t: Tag) -> VTag {
-    match t {
-        Tag::Paragraph => VTag::new("p"),
-        Tag::Heading(n, _, _) => VTag::new(n.to_string()),
-        Tag::BlockQuote => {
-            let mut el = VTag::new("blockquote");
-            el.add_attribute("class", "blockquote");
-            el
+impl ToDom for Vec<Node> {
+    fn to_dom(self) -> Html {
+        let mut nodes: Vec<VNode> = self.into_iter().map(Node::to_dom).collect();
+        if nodes.len() == 1 {
+            nodes.pop().unwrap_or_default()
+        } else {
+            VNode::VList(VList::with_children(nodes, None))
         }
-        Tag::CodeBlock(code_block_kind) => {
-            let mut el = VTag::new("code");
-
-            if let CodeBlockKind::Fenced(lang) = code_block_kind {
-                // Different color schemes may be used for different code blocks,
-                // but a different library (likely js based at the moment) would be necessary to actually provide the
-                // highlighting support by locating the language classes and applying dom transforms
-                // on their contents.
-                match lang.as_ref() {
-                    "html" => el.add_attribute("class", "html-language"),
-                    "rust" => el.add_attribute("class", "rust-language"),
-                    "java" => el.add_attribute("class", "java-language"),
-                    "c" => el.add_attribute("class", "c-language"),
-                    _ => {} // Add your own language highlighting support
-                };
-            }
+    }
+}
 
-            el
-        }
-        Tag::List(None) => VTag::new("ul"),
-        Tag::List(Some(1)) => VTag::new("ol"),
-        Tag::List(Some(ref start)) => {
-            let mut el = VTag::new("ol");
-            el.add_attribute("start", start.to_string());
-            el
-        }
-        Tag::Item => VTag::new("li"),
-        Tag::Table(_) => {
-            let mut el = VTag::new("table");
-            el.add_attribute("class", "table");
-            el
-        }
-        Tag::TableHead => VTag::new("th"),
-        Tag::TableRow => VTag::new("tr"),
-        Tag::TableCell => VTag::new("td"),
-        Tag::Emphasis => {
-            let mut el = VTag::new("span");
-            el.add_attribute("class", "font-italic");
-            el
-        }
-        Tag::Strong => {
-            let mut el = VTag::new("span");
-            el.add_attribute("class", "font-weight-bold");
-            el
-        }
-        Tag::Link(_link_type, ref href, ref title) => {
-            let mut el = VTag::new("a");
-            el.add_attribute("href", href.to_string());
-            let title = title.clone().into_string();
-            if !title.is_empty() {
-                el.add_attribute("title", title);
+/// Renders a string of Markdown to HTML, followed by its footnotes.
+#[cfg(feature = "yew-wasm")]
+pub fn render_markdown(src: &str) -> Html {
+    let mut notes = Notes::default();
+    let content = render_markdown_with(src, &mut notes, &Policy::default());
+    html! {
+    <>
+        {content}
+        {notes.to_dom()}
+    </>
+    }
+}
+
+/// Renders a string of Markdown to HTML, collecting its footnotes into `notes` and removing
+/// the embedded HTML and URLs not allowed by the policy.
+#[cfg(feature = "yew-wasm")]
+pub fn render_markdown_with(src: &str, notes: &mut Notes, policy: &Policy) -> Html {
+    parse_markdown(src, notes, policy).to_dom()
+}
+
+#[cfg(all(test, feature = "yew-wasm"))]
+mod test {
+    use super::*;
+    use crate::render::ToHtml;
+
+    /// Serializes the VDOM in the format of pulldown-cmark's HTML renderer.
+    fn to_html(node: &VNode, out: &mut String) {
+        match node {
+            VNode::VTag(tag) => {
+                let mut tag = tag.clone();
+                out.push('<');
+                out.push_str(tag.tag());
+                for (key, value) in tag.attributes.iter() {
+                    out.push_str(&format!(" {}=\"{}\"", key, dom::escape(value)));
+                }
+                if tag.checked() {
+                    out.push_str(" checked=\"\"");
+                }
+                if dom::is_void(tag.tag()) {
+                    out.push_str(" />");
+                    return;
+                }
+                out.push('>');
+                for child in tag.children().iter() {
+                    to_html(child, out);
+                }
+                out.push_str(&format!("</{}>", tag.tag()));
             }
-            el
-        }
-        Tag::Image(_link_type, ref src, ref title) => {
-            let mut el = VTag::new("img");
-            el.add_attribute("src", src.to_string());
-            let title = title.clone().into_string();
-            if !title.is_empty() {
-                el.add_attribute("title", title);
+            VNode::VText(text) => out.push_str(&dom::escape(&text.text)),
+            VNode::VList(list) => {
+                for child in list.iter() {
+                    to_html(child, out);
+                }
             }
-            el
+            _ => {}
         }
-        Tag::FootnoteDefinition(ref _footnote_id) => VTag::new("span"), // Footnotes are not rendered as anything special
-        Tag::Strikethrough => {
-            let mut el = VTag::new("span");
-            el.add_attribute("class", "text-decoration-strikethrough");
-            el
+    }
+
+    fn normalize(html: &str) -> String {
+        html.replace('\n', "").replace(" />", "/>")
+    }
+
+    #[test]
+    fn matches_html_renderer() {
+        let sources = [
+            "Some *em*, **strong**, ~~gone~~ and `code` \"quoted\".",
+            "A [link](https://example.org \"Title\") and ![an *image*](cat.png).",
+            "# Heading\n\nText  \nbreak\n\n---\n\n> quote\n> more",
+            "```rust info\nfn main() {}\n```\n\n    indented",
+            "- a\n- b\n\n3. c\n\n- [ ] open\n- [x] done",
+            "| a | b | c |\n|:--|:-:|--:|\n| 1 | 2 | 3 |",
+            "Inline <span class=\"note\">html</span> text.\n\n<div title=\"b\">\n<p>raw</p>\n</div>",
+        ];
+        for src in sources {
+            let nodes = parse_markdown(src, &mut Notes::default(), &Policy::default());
+            let mut actual = String::new();
+            to_html(&nodes.clone().to_dom(), &mut actual);
+            assert_eq!(
+                normalize(&actual),
+                normalize(&nodes.to_html()),
+                "source: {}",
+                src
+            );
         }
     }
+
+    fn render(src: &str) -> String {
+        let mut html = String::new();
+        to_html(&render_markdown(src), &mut html);
+        normalize(&html)
+    }
+
+    #[test]
+    fn footnotes() {
+        assert_eq!(
+            render("B[^b] and a[^a], b[^b].\n\n[^a]: Note a.\n[^b]: Note b."),
+            "<p>B<sup class=\"footnote-reference\" id=\"fnref-b\"><a href=\"#fn-b\">1</a></sup> \
+            and a<sup class=\"footnote-reference\" id=\"fnref-a\"><a href=\"#fn-a\">2</a></sup>, \
+            b<sup class=\"footnote-reference\"><a href=\"#fn-b\">1</a></sup>.</p>\
+            <section class=\"footnotes\"><ol>\
+            <li class=\"footnote-definition\" id=\"fn-b\" value=\"1\"><p>Note b.</p>\
+            <a class=\"footnote-backref\" href=\"#fnref-b\">↩</a></li>\
+            <li class=\"footnote-definition\" id=\"fn-a\" value=\"2\"><p>Note a.</p>\
+            <a class=\"footnote-backref\" href=\"#fnref-a\">↩</a></li>\
+            </ol></section>"
+        );
+    }
+
+    #[test]
+    fn sanitizes_html() {
+        assert_eq!(
+            render("Text <img src=x onerror=alert(1)> <script>alert(1)</script> end"),
+            "<p>Text <img src=\"x\"/>  end</p>"
+        );
+        assert_eq!(
+            render("<div onclick=\"alert(1)\">\n<iframe src=\"x\"></iframe>\n</div>"),
+            "<div></div>"
+        );
+        assert_eq!(
+            render("[click](javascript:alert(1)) ![x](data:text/html,x)"),
+            "<p><a>click</a> <img alt=\"x\"/></p>"
+        );
+    }
+
+    #[test]
+    fn endnotes() {
+        let mut notes = Notes::new("s0-");
+        let mut html = String::new();
+        to_html(
+            &render_markdown_with("One[^1].\n\n[^1]: First.", &mut notes, &Policy::default()),
+            &mut html,
+        );
+        notes.set_scope("s1-");
+        to_html(
+            &render_markdown_with("Two[^1].\n\n[^1]: Second.", &mut notes, &Policy::default()),
+            &mut html,
+        );
+        assert_eq!(
+            normalize(&html),
+            "<p>One<sup class=\"footnote-reference\" id=\"fnref-s0-1\"><a href=\"#fn-s0-1\">1</a></sup>.</p>\
+            <p>Two<sup class=\"footnote-reference\" id=\"fnref-s1-1\"><a href=\"#fn-s1-1\">2</a></sup>.</p>"
+        );
+        let mut html = String::new();
+        to_html(&notes.to_dom(), &mut html);
+        assert!(html.contains("id=\"fn-s0-1\" value=\"1\""));
+        assert!(html.find("First").unwrap() < html.find("Second").unwrap());
+    }
 }
@@ -0,0 +1,986 @@
+use std::fmt::{Display, Formatter};
+
+use crate::html;
+
+/// A formula in TeX notation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Math {
+    pub tex: String,
+    /// Whether the formula is displayed on its own line, instead of inline.
+    pub display: bool,
+}
+
+/// The reason a formula cannot be rendered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MathError(pub String);
+
+impl Display for MathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+type Result<T> = std::result::Result<T, MathError>;
+
+fn error<T>(message: impl Into<String>) -> Result<T> {
+    Err(MathError(message.into()))
+}
+
+impl Math {
+    pub fn new(tex: &str, display: bool) -> Self {
+        Self {
+            tex: tex.to_string(),
+            display,
+        }
+    }
+
+    /// The formula as a MathML `math` element.
+    pub fn to_mathml(&self) -> Result<String> {
+        let nodes = TexParser::new(&self.tex).parse()?;
+        let mut out = String::from("<math xmlns=\"http://www.w3.org/1998/Math/MathML\"");
+        if self.display {
+            out.push_str(" display=\"block\"");
+        }
+        out.push('>');
+        write_row(&nodes, &mut Style::new(self.display), &mut out);
+        out.push_str("</math>");
+        Ok(out)
+    }
+
+    /// The formula as MathML, or an inline error showing the source of a malformed formula.
+    pub fn render(&self) -> String {
+        self.to_mathml().unwrap_or_else(|err| {
+            format!(
+                "<span class=\"math-error\" title=\"{}\">{}</span>",
+                html::escape(&err.0),
+                html::escape(&self.delimited())
+            )
+        })
+    }
+
+    /// The source of the formula with its Markdown delimiters.
+    pub fn delimited(&self) -> String {
+        let delimiter = if self.display { "$$" } else { "$" };
+        format!("{0}{1}{0}", delimiter, self.tex)
+    }
+}
+
+/// A node of a formula.
+#[derive(Clone, Debug, PartialEq)]
+enum M {
+    Row(Vec<M>),
+    Ident(String),
+    Number(String),
+    /// An operator, which takes its limits below and above in display style.
+    Op {
+        text: String,
+        limits: bool,
+        stretchy: bool,
+    },
+    Text(String),
+    Space(&'static str),
+    Frac(Box<M>, Box<M>, bool),
+    Sqrt(Box<M>, Option<Box<M>>),
+    Scripts {
+        base: Box<M>,
+        sub: Option<Box<M>>,
+        sup: Option<Box<M>>,
+    },
+    Accent(Box<M>, &'static str, bool),
+    Under(Box<M>, &'static str),
+    Variant(&'static str, Box<M>),
+    Table {
+        rows: Vec<Vec<M>>,
+        align: &'static str,
+    },
+}
+
+fn op(text: &str) -> M {
+    M::Op {
+        text: text.to_string(),
+        limits: false,
+        stretchy: false,
+    }
+}
+
+fn fence(text: &str) -> M {
+    M::Op {
+        text: text.to_string(),
+        limits: false,
+        stretchy: true,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token<'a> {
+    Char(char),
+    Command(&'a str),
+    Open,
+    Close,
+    Sup,
+    Sub,
+    Prime,
+    Column,
+    NewRow,
+}
+
+/// The deepest nesting of groups and arguments, which keeps the recursive parser from
+/// overflowing the stack.
+const MAX_DEPTH: usize = 200;
+
+struct TexParser<'a> {
+    src: &'a str,
+    pos: usize,
+    /// The nesting of the atom being parsed.
+    depth: usize,
+}
+
+const GREEK: &[(&str, char)] = &[
+    ("alpha", 'α'),
+    ("beta", 'β'),
+    ("gamma", 'γ'),
+    ("delta", 'δ'),
+    ("epsilon", 'ϵ'),
+    ("varepsilon", 'ε'),
+    ("zeta", 'ζ'),
+    ("eta", 'η'),
+    ("theta", 'θ'),
+    ("vartheta", 'ϑ'),
+    ("iota", 'ι'),
+    ("kappa", 'κ'),
+    ("lambda", 'λ'),
+    ("mu", 'μ'),
+    ("nu", 'ν'),
+    ("xi", 'ξ'),
+    ("pi", 'π'),
+    ("varpi", 'ϖ'),
+    ("rho", 'ρ'),
+    ("varrho", 'ϱ'),
+    ("sigma", 'σ'),
+    ("varsigma", 'ς'),
+    ("tau", 'τ'),
+    ("upsilon", 'υ'),
+    ("phi", 'ϕ'),
+    ("varphi", 'φ'),
+    ("chi", 'χ'),
+    ("psi", 'ψ'),
+    ("omega", 'ω'),
+    ("Gamma", 'Γ'),
+    ("Delta", 'Δ'),
+    ("Theta", 'Θ'),
+    ("Lambda", 'Λ'),
+    ("Xi", 'Ξ'),
+    ("Pi", 'Π'),
+    ("Sigma", 'Σ'),
+    ("Upsilon", 'Υ'),
+    ("Phi", 'Φ'),
+    ("Psi", 'Ψ'),
+    ("Omega", 'Ω'),
+];
+
+/// Symbols written as identifiers.
+const SYMBOLS: &[(&str, &str)] = &[
+    ("infty", "∞"),
+    ("partial", "∂"),
+    ("nabla", "∇"),
+    ("hbar", "ℏ"),
+    ("ell", "ℓ"),
+    ("emptyset", "∅"),
+    ("varnothing", "∅"),
+    ("aleph", "ℵ"),
+    ("Re", "ℜ"),
+    ("Im", "ℑ"),
+    ("angle", "∠"),
+    ("prime", "′"),
+];
+
+const OPERATORS: &[(&str, &str)] = &[
+    ("cdot", "⋅"),
+    ("times", "×"),
+    ("div", "÷"),
+    ("pm", "±"),
+    ("mp", "∓"),
+    ("ast", "∗"),
+    ("star", "⋆"),
+    ("circ", "∘"),
+    ("bullet", "∙"),
+    ("oplus", "⊕"),
+    ("otimes", "⊗"),
+    ("leq", "≤"),
+    ("le", "≤"),
+    ("geq", "≥"),
+    ("ge", "≥"),
+    ("neq", "≠"),
+    ("ne", "≠"),
+    ("ll", "≪"),
+    ("gg", "≫"),
+    ("approx", "≈"),
+    ("equiv", "≡"),
+    ("sim", "∼"),
+    ("simeq", "≃"),
+    ("cong", "≅"),
+    ("propto", "∝"),
+    ("in", "∈"),
+    ("notin", "∉"),
+    ("ni", "∋"),
+    ("subset", "⊂"),
+    ("subseteq", "⊆"),
+    ("supset", "⊃"),
+    ("supseteq", "⊇"),
+    ("cup", "∪"),
+    ("cap", "∩"),
+    ("setminus", "∖"),
+    ("to", "→"),
+    ("rightarrow", "→"),
+    ("leftarrow", "←"),
+    ("gets", "←"),
+    ("leftrightarrow", "↔"),
+    ("Rightarrow", "⇒"),
+    ("Leftarrow", "⇐"),
+    ("Leftrightarrow", "⇔"),
+    ("implies", "⟹"),
+    ("iff", "⟺"),
+    ("mapsto", "↦"),
+    ("uparrow", "↑"),
+    ("downarrow", "↓"),
+    ("forall", "∀"),
+    ("exists", "∃"),
+    ("neg", "¬"),
+    ("lnot", "¬"),
+    ("land", "∧"),
+    ("wedge", "∧"),
+    ("lor", "∨"),
+    ("vee", "∨"),
+    ("perp", "⊥"),
+    ("parallel", "∥"),
+    ("mid", "∣"),
+    ("cdots", "⋯"),
+    ("ldots", "…"),
+    ("dots", "…"),
+    ("vdots", "⋮"),
+    ("ddots", "⋱"),
+    ("colon", ":"),
+    ("langle", "⟨"),
+    ("rangle", "⟩"),
+    ("lfloor", "⌊"),
+    ("rfloor", "⌋"),
+    ("lceil", "⌈"),
+    ("rceil", "⌉"),
+    ("vert", "|"),
+    ("Vert", "‖"),
+];
+
+/// Operators taking their limits below and above in display style.
+const LARGE_OPERATORS: &[(&str, &str)] = &[
+    ("sum", "∑"),
+    ("prod", "∏"),
+    ("coprod", "∐"),
+    ("bigcup", "⋃"),
+    ("bigcap", "⋂"),
+    ("bigoplus", "⨁"),
+    ("bigotimes", "⨂"),
+];
+
+/// Integrals, which take their limits as scripts.
+const INTEGRALS: &[(&str, &str)] = &[("int", "∫"), ("iint", "∬"), ("iiint", "∭"), ("oint", "∮")];
+
+const FUNCTIONS: &[&str] = &[
+    "arccos", "arcsin", "arctan", "arg", "cos", "cosh", "cot", "coth", "csc", "deg", "det", "dim",
+    "exp", "gcd", "hom", "ker", "lg", "ln", "log", "sec", "sin", "sinh", "tan", "tanh",
+];
+
+/// Functions taking their limits below in display style.
+const LIMIT_FUNCTIONS: &[&str] = &["inf", "lim", "liminf", "limsup", "max", "min", "Pr", "sup"];
+
+const SPACES: &[(&str, &str)] = &[
+    (",", "0.1667em"),
+    (":", "0.2222em"),
+    (">", "0.2222em"),
+    (";", "0.2778em"),
+    (" ", "0.25em"),
+    ("!", "-0.1667em"),
+    ("quad", "1em"),
+    ("qquad", "2em"),
+];
+
+const ACCENTS: &[(&str, &str, bool)] = &[
+    ("hat", "^", false),
+    ("widehat", "^", true),
+    ("bar", "¯", false),
+    ("overline", "‾", true),
+    ("vec", "→", false),
+    ("overrightarrow", "→", true),
+    ("dot", "˙", false),
+    ("ddot", "¨", false),
+    ("tilde", "~", false),
+    ("widetilde", "~", true),
+];
+
+const VARIANTS: &[(&str, &str)] = &[
+    ("mathrm", "normal"),
+    ("mathbf", "bold"),
+    ("boldsymbol", "bold-italic"),
+    ("mathit", "italic"),
+    ("mathbb", "double-struck"),
+    ("mathcal", "script"),
+    ("mathscr", "script"),
+    ("mathfrak", "fraktur"),
+    ("mathsf", "sans-serif"),
+    ("mathtt", "monospace"),
+];
+
+fn lookup<T: Copy>(table: &[(&str, T)], name: &str) -> Option<T> {
+    table
+        .iter()
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| *value)
+}
+
+impl<'a> TexParser<'a> {
+    fn new(src: &'a str) -> Self {
+        Self {
+            src,
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    fn skip_space(&mut self) {
+        let rest = &self.src[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<Token<'a>> {
+        let pos = self.pos;
+        let token = self.next();
+        self.pos = pos;
+        token
+    }
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        self.skip_space();
+        let rest = &self.src[self.pos..];
+        let c = rest.chars().next()?;
+        self.pos += c.len_utf8();
+        let token = match c {
+            '{' => Token::Open,
+            '}' => Token::Close,
+            '^' => Token::Sup,
+            '_' => Token::Sub,
+            '\'' => Token::Prime,
+            '&' => Token::Column,
+            '\\' => {
+                let name = &rest[1..];
+                let len = name
+                    .find(|c: char| !c.is_ascii_alphabetic())
+                    .unwrap_or(name.len());
+                let len = match len {
+                    0 => name.chars().next().map_or(0, char::len_utf8),
+                    len => len,
+                };
+                self.pos += len;
+                match &name[..len] {
+                    "\\" => Token::NewRow,
+                    "" => Token::Char('\\'),
+                    name => Token::Command(name),
+                }
+            }
+            c => Token::Char(c),
+        };
+        Some(token)
+    }
+
+    fn parse(mut self) -> Result<Vec<M>> {
+        let nodes = self.row(&[])?;
+        match self.next() {
+            None => Ok(nodes),
+            Some(Token::Close) => error("unexpected }"),
+            Some(Token::Column) | Some(Token::NewRow) => {
+                error("& and \\\\ are only allowed in environments")
+            }
+            Some(Token::Command(command)) => error(format!("unexpected \\{}", command)),
+            Some(_) => error("unexpected input"),
+        }
+    }
+
+    /// Parses nodes up to the end of a group or row, or one of the commands.
+    fn row(&mut self, until: &[&str]) -> Result<Vec<M>> {
+        let mut nodes = vec![];
+        loop {
+            match self.peek() {
+                None | Some(Token::Close) | Some(Token::Column) | Some(Token::NewRow) => break,
+                Some(Token::Command(command)) if until.contains(&command) => break,
+                Some(Token::Sup) | Some(Token::Sub) | Some(Token::Prime) => {
+                    let base = nodes.pop().unwrap_or(M::Row(vec![]));
+                    nodes.push(self.scripts(base)?);
+                }
+                Some(_) => {
+                    let atom = self.atom()?;
+                    nodes.push(atom);
+                }
+            }
+        }
+        Ok(nodes)
+    }
+
+    fn scripts(&mut self, base: M) -> Result<M> {
+        let (base, mut sub, mut sup) = match base {
+            M::Scripts { base, sub, sup } => (base, sub, sup),
+            base => (Box::new(base), None, None),
+        };
+        loop {
+            match self.peek() {
+                Some(Token::Sub) => {
+                    self.next();
+                    if sub.is_some() {
+                        return error("double subscript");
+                    }
+                    sub = Some(Box::new(self.argument("_")?));
+                }
+                Some(Token::Sup) => {
+                    self.next();
+                    if sup.is_some() {
+                        return error("double superscript");
+                    }
+                    sup = Some(Box::new(self.argument("^")?));
+                }
+                Some(Token::Prime) => {
+                    self.next();
+                    let prime = op("′");
+                    sup = Some(Box::new(match sup {
+                        Some(sup) => M::Row(vec![*sup, prime]),
+                        None => prime,
+                    }));
+                }
+                _ => break,
+            }
+        }
+        Ok(M::Scripts { base, sub, sup })
+    }
+
+    /// Parses the argument of a command: a group or a single atom.
+    fn argument(&mut self, command: &str) -> Result<M> {
+        match self.peek() {
+            None | Some(Token::Close) | Some(Token::Column) | Some(Token::NewRow) => {
+                error(format!("missing argument of {}", command))
+            }
+            Some(Token::Sup) | Some(Token::Sub) | Some(Token::Prime) => {
+                error(format!("missing argument of {}", command))
+            }
+            Some(_) => self.atom(),
+        }
+    }
+
+    fn group(&mut self) -> Result<M> {
+        let nodes = self.row(&[])?;
+        match self.next() {
+            Some(Token::Close) => Ok(M::Row(nodes)),
+            _ => error("missing }"),
+        }
+    }
+
+    /// The text of a group, without parsing it as a formula.
+    fn text_group(&mut self, command: &str) -> Result<String> {
+        if self.next() != Some(Token::Open) {
+            return error(format!("missing argument of \\{}", command));
+        }
+        let rest = &self.src[self.pos..];
+        let mut depth = 0;
+        for (i, c) in rest.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => {
+                    self.pos += i + 1;
+                    return Ok(rest[..i].to_string());
+                }
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        error("missing }")
+    }
+
+    /// The optional argument between brackets.
+    fn optional(&mut self) -> Result<Option<M>> {
+        if self.peek() != Some(Token::Char('[')) {
+            return Ok(None);
+        }
+        self.next();
+        let mut nodes = vec![];
+        loop {
+            match self.peek() {
+                Some(Token::Char(']')) => {
+                    self.next();
+                    return Ok(Some(M::Row(nodes)));
+                }
+                None => return error("missing ]"),
+                Some(Token::Sup) | Some(Token::Sub) | Some(Token::Prime) => {
+                    let base = nodes.pop().unwrap_or(M::Row(vec![]));
+                    nodes.push(self.scripts(base)?);
+                }
+                Some(_) => {
+                    let atom = self.atom()?;
+                    nodes.push(atom);
+                }
+            }
+        }
+    }
+
+    fn delimiter(&mut self, command: &str) -> Result<M> {
+        let text = match self.next() {
+            Some(Token::Char('.')) => String::new(),
+            Some(Token::Char(c)) if "()[]|/".contains(c) => c.to_string(),
+            Some(Token::Char('<')) => "⟨".to_string(),
+            Some(Token::Char('>')) => "⟩".to_string(),
+            Some(Token::Command("{")) => "{".to_string(),
+            Some(Token::Command("}")) => "}".to_string(),
+            Some(Token::Command("|")) => "‖".to_string(),
+            Some(Token::Command(name)) => match lookup(OPERATORS, name) {
+                Some(text) => text.to_string(),
+                None => return error(format!("unknown delimiter \\{} after \\{}", name, command)),
+            },
+            _ => return error(format!("missing delimiter after \\{}", command)),
+        };
+        Ok(fence(&text))
+    }
+
+    fn atom(&mut self) -> Result<M> {
+        if self.depth == MAX_DEPTH {
+            return error("too deeply nested");
+        }
+        self.depth += 1;
+        let atom = self.token_atom();
+        self.depth -= 1;
+        atom
+    }
+
+    fn token_atom(&mut self) -> Result<M> {
+        let token = match self.next() {
+            Some(token) => token,
+            None => return error("unexpected end"),
+        };
+        match token {
+            Token::Open => self.group(),
+            Token::Char(c) if c.is_ascii_digit() || c == '.' => {
+                let mut number = c.to_string();
+                while let Some(Token::Char(c)) = self.peek_adjacent() {
+                    if c.is_ascii_digit() || (c == '.' && !number.contains('.')) {
+                        self.next();
+                        number.push(c);
+                    } else {
+                        break;
+                    }
+                }
+                if number == "." {
+                    Ok(op("."))
+                } else {
+                    Ok(M::Number(number))
+                }
+            }
+            Token::Char(c) if c.is_alphabetic() => Ok(M::Ident(c.to_string())),
+            Token::Char('-') => Ok(op("−")),
+            Token::Char('*') => Ok(op("∗")),
+            Token::Char('~') => Ok(M::Space("0.25em")),
+            Token::Char(c) => Ok(op(&c.to_string())),
+            Token::Command(name) => self.command(name),
+            Token::Close => error("unexpected }"),
+            Token::Sup | Token::Sub | Token::Prime => error("missing base of a script"),
+            Token::Column | Token::NewRow => error("& and \\\\ are only allowed in environments"),
+        }
+    }
+
+    /// The next token if it is not preceded by white space.
+    fn peek_adjacent(&mut self) -> Option<Token<'a>> {
+        if self.src[self.pos..].starts_with(char::is_whitespace) {
+            None
+        } else {
+            self.peek()
+        }
+    }
+
+    fn command(&mut self, name: &'a str) -> Result<M> {
+        if let Some(c) = lookup(GREEK, name) {
+            return Ok(if c.is_uppercase() {
+                M::Variant("normal", Box::new(M::Ident(c.to_string())))
+            } else {
+                M::Ident(c.to_string())
+            });
+        }
+        if let Some(text) = lookup(SYMBOLS, name) {
+            return Ok(M::Ident(text.to_string()));
+        }
+        if let Some(text) = lookup(OPERATORS, name) {
+            return Ok(op(text));
+        }
+        if let Some(text) = lookup(LARGE_OPERATORS, name) {
+            return Ok(M::Op {
+                text: text.to_string(),
+                limits: true,
+                stretchy: false,
+            });
+        }
+        if let Some(text) = lookup(INTEGRALS, name) {
+            return Ok(op(text));
+        }
+        if FUNCTIONS.contains(&name) {
+            return Ok(M::Ident(name.to_string()));
+        }
+        if LIMIT_FUNCTIONS.contains(&name) {
+            return Ok(M::Op {
+                text: name.to_string(),
+                limits: true,
+                stretchy: false,
+            });
+        }
+        if let Some(width) = lookup(SPACES, name) {
+            return Ok(M::Space(width));
+        }
+        if let Some((_, accent, stretchy)) = ACCENTS.iter().find(|(key, _, _)| *key == name) {
+            let base = self.argument(&format!("\\{}", name))?;
+            return Ok(M::Accent(Box::new(base), accent, *stretchy));
+        }
+        if let Some(variant) = lookup(VARIANTS, name) {
+            let base = self.argument(&format!("\\{}", name))?;
+            return Ok(M::Variant(variant, Box::new(base)));
+        }
+        match name {
+            "{" | "}" | "%" | "$" | "#" | "&" | "_" => Ok(op(name)),
+            "|" => Ok(op("‖")),
+            "frac" | "dfrac" | "tfrac" => {
+                let num = self.argument("\\frac")?;
+                let den = self.argument("\\frac")?;
+                Ok(M::Frac(Box::new(num), Box::new(den), true))
+            }
+            "binom" => {
+                let n = self.argument("\\binom")?;
+                let k = self.argument("\\binom")?;
+                Ok(M::Row(vec![
+                    fence("("),
+                    M::Frac(Box::new(n), Box::new(k), false),
+                    fence(")"),
+                ]))
+            }
+            "sqrt" => {
+                let index = self.optional()?;
+                let base = self.argument("\\sqrt")?;
+                Ok(M::Sqrt(Box::new(base), index.map(Box::new)))
+            }
+            "text" | "textrm" | "mbox" => Ok(M::Text(self.text_group(name)?)),
+            "operatorname" => Ok(M::Ident(self.text_group(name)?)),
+            "underline" => {
+                let base = self.argument("\\underline")?;
+                Ok(M::Under(Box::new(base), "_"))
+            }
+            "left" => {
+                let open = self.delimiter("left")?;
+                let inner = self.row(&["right"])?;
+                if self.next() != Some(Token::Command("right")) {
+                    return error("missing \\right");
+                }
+                let close = self.delimiter("right")?;
+                let mut nodes = vec![open];
+                nodes.extend(inner);
+                nodes.push(close);
+                Ok(M::Row(nodes))
+            }
+            "right" => error("\\right without \\left"),
+            "begin" => self.environment(),
+            "end" => error("\\end without \\begin"),
+            name => error(format!("unknown command \\{}", name)),
+        }
+    }
+
+    fn environment(&mut self) -> Result<M> {
+        let name = self.text_group("begin")?;
+        let (open, close, align) = match name.as_str() {
+            "matrix" | "smallmatrix" => ("", "", "center"),
+            "pmatrix" => ("(", ")", "center"),
+            "bmatrix" => ("[", "]", "center"),
+            "Bmatrix" => ("{", "}", "center"),
+            "vmatrix" => ("|", "|", "center"),
+            "Vmatrix" => ("‖", "‖", "center"),
+            "cases" => ("{", "", "left"),
+            "aligned" | "align" | "align*" | "split" | "gathered" => ("", "", "right left"),
+            _ => return error(format!("unknown environment {}", name)),
+        };
+        let mut rows = vec![];
+        let mut row = vec![];
+        loop {
+            let cell = self.row(&["end"])?;
+            row.push(M::Row(cell));
+            match self.next() {
+                Some(Token::Column) => {}
+                Some(Token::NewRow) => rows.push(std::mem::take(&mut row)),
+                Some(Token::Command("end")) => {
+                    let end = self.text_group("end")?;
+                    if end != name {
+                        return error(format!("\\begin{{{}}} ended by \\end{{{}}}", name, end));
+                    }
+                    break;
+                }
+                Some(Token::Close) => return error("unexpected }"),
+                _ => return error(format!("missing \\end{{{}}}", name)),
+            }
+        }
+        // a trailing \\ does not start a row.
+        if !(row.len() == 1 && row[0] == M::Row(vec![])) || rows.is_empty() {
+            rows.push(row);
+        }
+        let table = M::Table { rows, align };
+        if open.is_empty() && close.is_empty() {
+            Ok(table)
+        } else {
+            Ok(M::Row(vec![fence(open), table, fence(close)]))
+        }
+    }
+}
+
+/// The style of the formula being written.
+struct Style {
+    display: bool,
+    variant: Option<&'static str>,
+}
+
+impl Style {
+    fn new(display: bool) -> Self {
+        Self {
+            display,
+            variant: None,
+        }
+    }
+}
+
+fn write_row(nodes: &[M], style: &mut Style, out: &mut String) {
+    if nodes.len() == 1 {
+        write_node(&nodes[0], style, out);
+    } else {
+        out.push_str("<mrow>");
+        for node in nodes {
+            write_node(node, style, out);
+        }
+        out.push_str("</mrow>");
+    }
+}
+
+fn write_token(name: &str, text: &str, attrs: &str, style: &Style, out: &mut String) {
+    out.push_str(&format!("<{}{}", name, attrs));
+    if let Some(variant) = style.variant {
+        out.push_str(&format!(" mathvariant=\"{}\"", variant));
+    }
+    out.push_str(&format!(">{}</{}>", html::escape(text), name));
+}
+
+fn write_node(node: &M, style: &mut Style, out: &mut String) {
+    match node {
+        M::Row(nodes) => write_row(nodes, style, out),
+        M::Ident(text) => write_token("mi", text, "", style, out),
+        M::Number(text) => write_token("mn", text, "", style, out),
+        M::Op { text, stretchy, .. } => {
+            let attrs = if *stretchy { "" } else { " stretchy=\"false\"" };
+            if !text.is_empty() || *stretchy {
+                out.push_str(&format!("<mo{}>{}</mo>", attrs, html::escape(text)));
+            }
+        }
+        M::Text(text) => out.push_str(&format!("<mtext>{}</mtext>", html::escape(text))),
+        M::Space(width) => out.push_str(&format!("<mspace width=\"{}\"/>", width)),
+        M::Frac(num, den, line) => {
+            out.push_str(if *line {
+                "<mfrac>"
+            } else {
+                "<mfrac linethickness=\"0\">"
+            });
+            write_node(num, style, out);
+            write_node(den, style, out);
+            out.push_str("</mfrac>");
+        }
+        M::Sqrt(base, None) => {
+            out.push_str("<msqrt>");
+            write_node(base, style, out);
+            out.push_str("</msqrt>");
+        }
+        M::Sqrt(base, Some(index)) => {
+            out.push_str("<mroot>");
+            write_node(base, style, out);
+            write_node(index, style, out);
+            out.push_str("</mroot>");
+        }
+        M::Scripts { base, sub, sup } => {
+            let limits = style.display && matches!(**base, M::Op { limits: true, .. });
+            let name = match (sub.is_some(), sup.is_some(), limits) {
+                (true, true, true) => "munderover",
+                (true, false, true) => "munder",
+                (false, true, true) => "mover",
+                (true, true, false) => "msubsup",
+                (true, false, false) => "msub",
+                (false, true, false) => "msup",
+                (false, false, _) => return write_node(base, style, out),
+            };
+            out.push_str(&format!("<{}>", name));
+            write_node(base, style, out);
+            for script in [sub, sup].into_iter().flatten() {
+                write_node(script, style, out);
+            }
+            out.push_str(&format!("</{}>", name));
+        }
+        M::Accent(base, accent, stretchy) => {
+            out.push_str("<mover accent=\"true\">");
+            write_node(base, style, out);
+            let stretchy = if *stretchy { "" } else { " stretchy=\"false\"" };
+            out.push_str(&format!("<mo{}>{}</mo></mover>", stretchy, accent));
+        }
+        M::Under(base, accent) => {
+            out.push_str("<munder accentunder=\"true\">");
+            write_node(base, style, out);
+            out.push_str(&format!("<mo>{}</mo></munder>", accent));
+        }
+        M::Variant(variant, base) => {
+            let outer = style.variant.replace(variant);
+            write_node(base, style, out);
+            style.variant = outer;
+        }
+        M::Table { rows, align } => {
+            out.push_str(&format!("<mtable columnalign=\"{}\">", align));
+            for row in rows {
+                out.push_str("<mtr>");
+                for cell in row {
+                    out.push_str("<mtd>");
+                    write_node(cell, style, out);
+                    out.push_str("</mtd>");
+                }
+                out.push_str("</mtr>");
+            }
+            out.push_str("</mtable>");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ast::{parse_markdown, Node, Notes};
+    use crate::sanitize::Policy;
+
+    fn inner(tex: &str) -> String {
+        let math = Math::new(tex, false).to_mathml().unwrap();
+        let start = math.find('>').unwrap() + 1;
+        math[start..math.len() - "</math>".len()].to_string()
+    }
+
+    fn err(tex: &str) -> String {
+        Math::new(tex, false).to_mathml().unwrap_err().0
+    }
+
+    #[test]
+    fn converts_formulas() {
+        assert_eq!(
+            inner("x^2 + 1.5"),
+            "<mrow><msup><mi>x</mi><mn>2</mn></msup><mo stretchy=\"false\">+</mo>\
+            <mn>1.5</mn></mrow>"
+        );
+        assert_eq!(
+            inner("\\frac{a}{b_{i}}"),
+            "<mfrac><mi>a</mi><msub><mi>b</mi><mi>i</mi></msub></mfrac>"
+        );
+        assert_eq!(
+            inner("\\sqrt[3]{\\alpha}"),
+            "<mroot><mi>α</mi><mn>3</mn></mroot>"
+        );
+        assert_eq!(
+            inner("\\mathbf{v}'"),
+            "<msup><mi mathvariant=\"bold\">v</mi><mo stretchy=\"false\">′</mo></msup>"
+        );
+        assert_eq!(
+            inner("\\left( x \\right]"),
+            "<mrow><mo>(</mo><mi>x</mi><mo>]</mo></mrow>"
+        );
+        assert_eq!(
+            inner("\\text{if } x<y"),
+            "<mrow><mtext>if </mtext><mi>x</mi><mo stretchy=\"false\">&lt;</mo><mi>y</mi></mrow>"
+        );
+        assert_eq!(
+            inner("\\begin{pmatrix} 1 & 0 \\\\ 0 & 1 \\end{pmatrix}"),
+            "<mrow><mo>(</mo><mtable columnalign=\"center\">\
+            <mtr><mtd><mn>1</mn></mtd><mtd><mn>0</mn></mtd></mtr>\
+            <mtr><mtd><mn>0</mn></mtd><mtd><mn>1</mn></mtd></mtr>\
+            </mtable><mo>)</mo></mrow>"
+        );
+    }
+
+    #[test]
+    fn places_limits() {
+        let sum = "\\sum_{i=1}^n i";
+        assert!(Math::new(sum, true)
+            .to_mathml()
+            .unwrap()
+            .starts_with("<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\"><mrow><munderover>"));
+        assert!(inner(sum).starts_with("<mrow><msubsup><mo stretchy=\"false\">∑</mo>"));
+        assert!(inner("\\int_0^1").starts_with("<msubsup>"));
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert_eq!(err("\\frac{a}"), "missing argument of \\frac");
+        assert_eq!(err("{x"), "missing }");
+        assert_eq!(err("x}"), "unexpected }");
+        assert_eq!(err("x^1^2"), "double superscript");
+        assert_eq!(err("\\foo"), "unknown command \\foo");
+        assert_eq!(err("\\left( x"), "missing \\right");
+        assert_eq!(err("a & b"), "& and \\\\ are only allowed in environments");
+        let nested = format!("{}x{}", "{".repeat(10_000), "}".repeat(10_000));
+        assert_eq!(err(&nested), "too deeply nested");
+        assert_eq!(err(&"\\sqrt".repeat(10_000)), "too deeply nested");
+        assert_eq!(
+            Math::new("\\frac{<a>}", true).render(),
+            "<span class=\"math-error\" title=\"missing argument of \\frac\">$$\\frac{&lt;a&gt;}$$</span>"
+        );
+    }
+
+    #[test]
+    fn renders_markdown() {
+        use crate::markup::Markup;
+
+        let markup =
+            Markup::md_str("*Area* $\\pi r^2$, $\\frac{1}$ and `$x$`\n\n$$\ne^{i\\pi} = -1\n$$");
+        assert_eq!(
+            markup.render_html(&Policy::default()),
+            "<p><em>Area</em> <math xmlns=\"http://www.w3.org/1998/Math/MathML\"><mrow>\
+            <mi>π</mi><msup><mi>r</mi><mn>2</mn></msup></mrow></math>, \
+            <span class=\"math-error\" title=\"missing argument of \\frac\">$\\frac{1}$</span> and <code>$x$</code></p>\n\
+            <p><math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\"><mrow>\
+            <msup><mi>e</mi><mrow><mi>i</mi><mi>π</mi></mrow></msup><mo stretchy=\"false\">=</mo>\
+            <mo stretchy=\"false\">−</mo><mn>1</mn></mrow></math></p>\n"
+        );
+    }
+
+    fn formulas(src: &str) -> Vec<Math> {
+        fn collect(nodes: &[Node], formulas: &mut Vec<Math>) {
+            for node in nodes {
+                match node {
+                    Node::Math(math) => formulas.push(math.clone()),
+                    Node::Element(el) => collect(&el.children, formulas),
+                    _ => {}
+                }
+            }
+        }
+        let mut formulas = vec![];
+        let nodes = parse_markdown(src, &mut Notes::default(), &Policy::default());
+        collect(&nodes, &mut formulas);
+        formulas
+    }
+
+    #[test]
+    fn parses_formulas() {
+        assert_eq!(
+            formulas("Let $x_1$ cost $5 and $10.\n\n`$y$`, \\$z$ and\n\n$$\n a*b \n$$\n"),
+            vec![Math::new("x_1", false), Math::new("a*b", true)]
+        );
+        assert_eq!(
+            formulas("```\n$a$\n```\n<span title=\"$b$\">$ c $</span>"),
+            vec![]
+        );
+        assert_eq!(
+            formulas("[$x$](https://example.org/$a$/$b$) and $a*b*c$"),
+            vec![Math::new("x", false), Math::new("a*b*c", false)]
+        );
+    }
+}
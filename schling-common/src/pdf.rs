@@ -0,0 +1,1672 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::str::FromStr;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use pdf_writer::types::{CidFontType, FontFlags, SystemInfo, UnicodeCmap};
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+
+use crate::ast::{Element, Kind, Node, Notes};
+use crate::data::{Doc, Sec};
+use crate::epub::Chapter;
+use crate::manuscript::{Manuscript, Typeface};
+use crate::sanitize::Policy;
+use crate::wiki::Titles;
+
+/// The trim size of the pages of a book.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PageSize {
+    /// ISO A5, 148 × 210 mm.
+    A5,
+    /// The US trade paperback, 6 × 9 in.
+    #[default]
+    SixByNine,
+    /// US Letter, 8.5 × 11 in.
+    Letter,
+}
+
+impl PageSize {
+    /// The width and height in points.
+    pub fn dimensions(self) -> (f32, f32) {
+        match self {
+            PageSize::A5 => (419.53, 595.28),
+            PageSize::SixByNine => (432.0, 648.0),
+            PageSize::Letter => (612.0, 792.0),
+        }
+    }
+
+    /// The margins usual for the size, wider at the spine than at the outer edge.
+    pub fn margins(self) -> Margins {
+        match self {
+            PageSize::A5 => Margins {
+                inner: 54.0,
+                outer: 40.0,
+                top: 50.0,
+                bottom: 54.0,
+            },
+            PageSize::SixByNine => Margins {
+                inner: 58.0,
+                outer: 42.0,
+                top: 54.0,
+                bottom: 58.0,
+            },
+            PageSize::Letter => Margins {
+                inner: 90.0,
+                outer: 72.0,
+                top: 72.0,
+                bottom: 72.0,
+            },
+        }
+    }
+
+    /// The size of the body text in points.
+    fn font_size(self) -> f32 {
+        match self {
+            PageSize::A5 => 10.0,
+            PageSize::SixByNine => 10.5,
+            PageSize::Letter => 12.0,
+        }
+    }
+}
+
+impl Display for PageSize {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PageSize::A5 => write!(f, "a5"),
+            PageSize::SixByNine => write!(f, "6x9"),
+            PageSize::Letter => write!(f, "letter"),
+        }
+    }
+}
+
+impl FromStr for PageSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "a5" => Ok(PageSize::A5),
+            "6x9" | "6×9" => Ok(PageSize::SixByNine),
+            "letter" => Ok(PageSize::Letter),
+            _ => Err(format!("unknown page size: {}", s)),
+        }
+    }
+}
+
+/// The margins of the pages in points.
+///
+/// The margins are mirrored: the inner margin is on the left of the recto (odd) pages, and on
+/// the right of the verso (even) pages.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Margins {
+    pub inner: f32,
+    pub outer: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+/// The reason a PDF cannot be typeset.
+#[derive(Debug)]
+pub enum Error {
+    /// The book has no chapters, so there would be nothing after the title page.
+    Empty,
+    /// One of the bundled fonts cannot be subset to the glyphs of the text.
+    Font(subsetter::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Empty => write!(f, "the book has no chapters"),
+            Error::Font(err) => write!(f, "cannot embed a font: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<subsetter::Error> for Error {
+    fn from(err: subsetter::Error) -> Self {
+        Error::Font(err)
+    }
+}
+
+/// A print-ready PDF book of documents, typeset with embedded fonts.
+///
+/// The book starts with a title page, and every chapter on a recto page, opening with a drop
+/// cap. The other pages have running headers with the title of the book on the verso pages,
+/// and the title of the chapter on the recto pages. Paragraphs leave no single line at the
+/// bottom or the top of a page, and headings stay with the text that follows them.
+///
+/// As with the EPUB export, images are replaced by their alternative text. Formulas are set
+/// as their TeX source.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Book {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub language: String,
+    pub size: PageSize,
+    pub margins: Margins,
+    pub chapters: Vec<Chapter>,
+}
+
+impl Book {
+    pub fn new(title: &str, size: PageSize) -> Self {
+        Self {
+            title: title.to_string(),
+            authors: vec![],
+            language: "en".to_string(),
+            size,
+            margins: size.margins(),
+            chapters: vec![],
+        }
+    }
+
+    pub fn with_chapter(mut self, doc: Doc, sections: Vec<Sec>) -> Self {
+        self.chapters.push(Chapter { doc, sections });
+        self
+    }
+
+    /// The book as the bytes of a PDF file.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        if self.chapters.is_empty() {
+            return Err(Error::Empty);
+        }
+        let mut setter = Typesetter::new(self, None);
+        let mut items = vec![];
+        for (i, chapter) in self.chapters.iter().enumerate() {
+            setter.chapter(
+                i,
+                &chapter.doc.head.title,
+                &self.blocks(chapter),
+                &mut items,
+            );
+        }
+        let mut pages = vec![setter.title_page(), Page::new(PageKind::Blank, None)];
+        paginate(&mut pages, items, setter.body_height());
+        let contents: Vec<Vec<u8>> = pages
+            .iter()
+            .enumerate()
+            .map(|(i, page)| setter.content(i + 1, page))
+            .collect();
+        setter.write(&pages, &contents)
+    }
+
+    /// The blocks of the chapter, with its footnotes at the end.
+    fn blocks(&self, chapter: &Chapter) -> Vec<Block> {
+        let mut titles = Titles::default();
+        for chapter in &self.chapters {
+            titles.insert_doc(&chapter.doc.head);
+            for sec in &chapter.sections {
+                titles.insert_sec(&sec.head);
+            }
+        }
+        let mut notes = Notes::default().with_titles(titles, chapter.doc.head.id);
+        let mut blocks = Blocks::default();
+        for (i, sec) in chapter.sections.iter().enumerate() {
+            if i > 0 {
+                blocks.blocks.push(Block::SceneBreak);
+            }
+            notes.set_scope(&format!("section-{}-", sec.head.id));
+            blocks.nodes(&sec.content.to_ast(&mut notes, &Policy::default()));
+        }
+        if !notes.is_empty() {
+            blocks
+                .blocks
+                .push(Block::Heading(vec![Run::new("Notes", Face::Regular)]));
+            blocks.inset += 1;
+            for note in notes.into_notes() {
+                blocks.label = Some(format!("{}.", note.number));
+                blocks.nodes(&note.content);
+            }
+        }
+        blocks.blocks
+    }
+}
+
+/// Typesets the manuscript in the Standard Manuscript Format.
+pub(crate) fn manuscript(manuscript: &Manuscript) -> Result<Vec<u8>, Error> {
+    let mut book = Book::new(&manuscript.title, PageSize::Letter)
+        .with_chapter(manuscript.doc.clone(), manuscript.sections.clone());
+    book.authors = vec![manuscript.byline().to_string()];
+    book.language = manuscript.language.clone();
+    book.margins = Margins {
+        inner: 72.0,
+        outer: 72.0,
+        top: 72.0,
+        bottom: 72.0,
+    };
+    let mut setter = Typesetter::new(&book, Some(manuscript));
+    let blocks = book.blocks(&book.chapters[0]);
+    let mut items = vec![];
+    setter.story(&blocks, &mut items);
+    let mut pages = vec![setter.front_page(manuscript)];
+    paginate(&mut pages, items, setter.body_height());
+    let contents: Vec<Vec<u8>> = pages
+        .iter()
+        .enumerate()
+        .map(|(i, page)| setter.content(i + 1, page))
+        .collect();
+    setter.write(&pages, &contents)
+}
+
+/// The font faces of the book.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Face {
+    Regular,
+    Italic,
+    Bold,
+    BoldItalic,
+    Mono,
+}
+
+impl Face {
+    const ALL: [Face; 5] = [
+        Face::Regular,
+        Face::Italic,
+        Face::Bold,
+        Face::BoldItalic,
+        Face::Mono,
+    ];
+
+    fn italic(self) -> Self {
+        match self {
+            Face::Regular => Face::Italic,
+            Face::Bold => Face::BoldItalic,
+            face => face,
+        }
+    }
+
+    fn bold(self) -> Self {
+        match self {
+            Face::Regular => Face::Bold,
+            Face::Italic => Face::BoldItalic,
+            face => face,
+        }
+    }
+
+    /// The name of the font in the resources of the pages.
+    fn resource(self) -> &'static [u8] {
+        match self {
+            Face::Regular => b"F0",
+            Face::Italic => b"F1",
+            Face::Bold => b"F2",
+            Face::BoldItalic => b"F3",
+            Face::Mono => b"F4",
+        }
+    }
+
+    /// The name of the font of the face, whose text is monospaced in manuscripts set in
+    /// Courier.
+    fn postscript_name(self, mono: bool) -> &'static str {
+        match (self, mono) {
+            (Face::Regular, false) => "LinLibertineO",
+            (Face::Italic, false) => "LinLibertineOI",
+            (Face::Bold, false) => "LinLibertineOB",
+            (Face::BoldItalic, false) => "LinLibertineOBI",
+            (Face::Italic, true) => "DejaVuSansMono-Oblique",
+            (Face::Bold, true) => "DejaVuSansMono-Bold",
+            (Face::BoldItalic, true) => "DejaVuSansMono-BoldOblique",
+            (Face::Regular | Face::Mono, _) => "DejaVuSansMono",
+        }
+    }
+
+    /// The position of the font in the fonts bundled by `typst_assets`.
+    fn asset(self, mono: bool) -> usize {
+        match (self, mono) {
+            (Face::Regular, false) => 0,
+            (Face::Bold, false) => 1,
+            (Face::BoldItalic, false) => 2,
+            (Face::Italic, false) => 3,
+            (Face::Bold, true) => 10,
+            (Face::BoldItalic, true) => 11,
+            (Face::Italic, true) => 12,
+            (Face::Regular | Face::Mono, _) => 13,
+        }
+    }
+}
+
+/// A font with the glyphs used by the book, and their characters.
+struct Font {
+    name: &'static str,
+    data: &'static [u8],
+    face: ttf_parser::Face<'static>,
+    used: BTreeMap<u16, char>,
+}
+
+impl Font {
+    fn load(face: Face, mono: bool) -> Self {
+        let data = typst_assets::fonts()
+            .nth(face.asset(mono))
+            .expect("the fonts are bundled");
+        Self {
+            name: face.postscript_name(mono),
+            data,
+            face: ttf_parser::Face::parse(data, 0).expect("the bundled fonts are valid"),
+            used: BTreeMap::from([(0, '\u{FFFD}')]),
+        }
+    }
+
+    /// Scales a length in font units to a font size.
+    fn scale(&self, units: f32, size: f32) -> f32 {
+        units / self.face.units_per_em() as f32 * size
+    }
+
+    fn advance(&self, glyph: u16) -> f32 {
+        self.face
+            .glyph_hor_advance(ttf_parser::GlyphId(glyph))
+            .unwrap_or(0) as f32
+    }
+
+    /// The height of the capital letters relative to the font size.
+    fn cap_height(&self) -> f32 {
+        let units = self.face.capital_height().unwrap_or(self.face.ascender());
+        self.scale(units as f32, 1.0)
+    }
+}
+
+/// A piece of text set in one font, at a horizontal offset.
+#[derive(Clone, Debug, PartialEq)]
+struct Frag {
+    face: Face,
+    size: f32,
+    x: f32,
+    /// The offset of the baseline above the baseline of the line.
+    rise: f32,
+    glyphs: Vec<u16>,
+}
+
+/// A word or a space of a paragraph, or a forced line break.
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(Vec<Frag>, f32),
+    Space(f32),
+    Break,
+}
+
+/// A line of a page, whose fragments are offset from the left edge of the text block.
+#[derive(Clone, Debug, PartialEq)]
+struct Line {
+    frags: Vec<Frag>,
+    height: f32,
+    /// The offset of the baseline below the top of the line.
+    baseline: f32,
+    /// Whether the line must be on the same page as the following line.
+    keep: bool,
+}
+
+/// A line of a paragraph that must not end up alone at the top or the bottom of a page.
+const WIDOWS: usize = 2;
+const ORPHANS: usize = 2;
+
+/// The number of lines beside a drop cap.
+const DROP_LINES: usize = 3;
+
+/// The piece of a chapter, in the order they are laid out on the pages.
+#[derive(Clone, Debug, PartialEq)]
+enum Item {
+    /// The start of a chapter, on a new recto page.
+    Chapter(usize),
+    Line(Line),
+    /// Vertical space, which is dropped at the top of a page.
+    Space(f32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PageKind {
+    Title,
+    Blank,
+    /// The first page of a chapter, without running header.
+    Opener,
+    Body,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Page {
+    kind: PageKind,
+    chapter: Option<usize>,
+    /// The lines, by their offset from the top of the text block.
+    lines: Vec<(f32, Line)>,
+}
+
+impl Page {
+    fn new(kind: PageKind, chapter: Option<usize>) -> Self {
+        Self {
+            kind,
+            chapter,
+            lines: vec![],
+        }
+    }
+}
+
+/// Distributes the items on pages of the given height, after the lines of the existing pages.
+///
+/// When a line does not fit, the lines at the end of the page which must stay with it move to
+/// the next page as well, unless that would leave the page empty.
+fn paginate(pages: &mut Vec<Page>, items: Vec<Item>, height: f32) {
+    let mut y = pages
+        .last()
+        .and_then(|page| page.lines.last())
+        .map_or(0.0, |(y, line)| y + line.height);
+    let mut chapter = pages.last().and_then(|page| page.chapter);
+    for item in items {
+        match item {
+            Item::Chapter(i) => {
+                chapter = Some(i);
+                if pages.len() % 2 == 1 {
+                    pages.push(Page::new(PageKind::Blank, None));
+                }
+                pages.push(Page::new(PageKind::Opener, chapter));
+                y = height / 4.0;
+            }
+            Item::Space(space) => {
+                if pages.last().is_some_and(|page| !page.lines.is_empty()) {
+                    y += space;
+                }
+            }
+            Item::Line(line) => {
+                let page = pages.last_mut().expect("chapters start a page");
+                if y + line.height > height && !page.lines.is_empty() {
+                    let mut moved = vec![];
+                    while page.lines.len() > 1 && page.lines.last().is_some_and(|(_, l)| l.keep) {
+                        moved.extend(page.lines.pop().map(|(_, line)| line));
+                    }
+                    pages.push(Page::new(PageKind::Body, chapter));
+                    y = 0.0;
+                    for line in moved.into_iter().rev() {
+                        let height = line.height;
+                        pages.last_mut().unwrap().lines.push((y, line));
+                        y += height;
+                    }
+                }
+                let height = line.height;
+                pages.last_mut().unwrap().lines.push((y, line));
+                y += height;
+            }
+        }
+    }
+}
+
+/// A piece of text in one face.
+#[derive(Clone, Debug, PartialEq)]
+struct Run {
+    text: String,
+    face: Face,
+    /// Whether the text is a superscript, such as a footnote reference.
+    sup: bool,
+}
+
+impl Run {
+    fn new(text: &str, face: Face) -> Self {
+        Self {
+            text: text.to_string(),
+            face,
+            sup: false,
+        }
+    }
+}
+
+/// A block of a chapter.
+#[derive(Clone, Debug, PartialEq)]
+enum Block {
+    /// A paragraph of running text, indented by a number of steps, such as in quotes and lists.
+    Text {
+        runs: Vec<Run>,
+        inset: u8,
+        /// The label hanging in front of the paragraph, such as the marker of a list item.
+        label: Option<String>,
+    },
+    Heading(Vec<Run>),
+    Code(String),
+    SceneBreak,
+}
+
+/// The elements of embedded HTML which hold blocks.
+const HTML_BLOCKS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "header",
+    "li",
+    "ol",
+    "p",
+    "section",
+    "ul",
+];
+
+/// Converts the document tree to blocks.
+#[derive(Default)]
+struct Blocks {
+    blocks: Vec<Block>,
+    /// The inline content of the current paragraph.
+    runs: Vec<Run>,
+    inset: u8,
+    label: Option<String>,
+}
+
+impl Blocks {
+    fn nodes(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            self.node(node);
+        }
+        self.flush();
+    }
+
+    fn node(&mut self, node: &Node) {
+        let el = match node {
+            Node::Element(el) if is_block(el) => el,
+            Node::Rule => {
+                self.flush();
+                self.blocks.push(Block::SceneBreak);
+                return;
+            }
+            node => {
+                self.inline(node, Face::Regular, false);
+                return;
+            }
+        };
+        self.flush();
+        match &el.kind {
+            Kind::Heading { .. } => {
+                let mut runs = vec![];
+                std::mem::swap(&mut runs, &mut self.runs);
+                for child in &el.children {
+                    self.inline(child, Face::Regular, false);
+                }
+                std::mem::swap(&mut runs, &mut self.runs);
+                self.blocks.push(Block::Heading(runs));
+            }
+            Kind::CodeBlock(_) => {
+                let code: String = el.children.iter().map(Node::text).collect();
+                self.blocks
+                    .push(Block::Code(code.trim_end_matches('\n').to_string()));
+            }
+            Kind::BlockQuote => {
+                self.inset += 1;
+                self.nodes(&el.children);
+                self.inset -= 1;
+            }
+            Kind::List(start) => {
+                self.inset += 1;
+                for (i, item) in el.children.iter().enumerate() {
+                    self.label = Some(match start {
+                        Some(start) => format!("{}.", start + i as u64),
+                        None => "•".to_string(),
+                    });
+                    match item {
+                        Node::Element(item) => self.nodes(&item.children),
+                        node => self.nodes(std::slice::from_ref(node)),
+                    }
+                }
+                self.label = None;
+                self.inset -= 1;
+            }
+            Kind::Table => {
+                for row in &el.children {
+                    if let Node::Element(row) = row {
+                        for (i, cell) in row.children.iter().enumerate() {
+                            if i > 0 {
+                                self.runs.push(Run::new(" | ", Face::Regular));
+                            }
+                            let face = match cell {
+                                Node::Element(Element {
+                                    kind: Kind::TableCell { header: true, .. },
+                                    ..
+                                }) => Face::Bold,
+                                _ => Face::Regular,
+                            };
+                            self.inline(cell, face, false);
+                        }
+                    }
+                    self.flush();
+                }
+            }
+            _ => self.nodes(&el.children),
+        }
+    }
+
+    fn inline(&mut self, node: &Node, face: Face, sup: bool) {
+        let mut push = |text: &str, face: Face| {
+            self.runs.push(Run {
+                text: text.to_string(),
+                face,
+                sup,
+            })
+        };
+        match node {
+            Node::Text(text) => push(text, face),
+            Node::Code(code) => push(code, Face::Mono),
+            Node::SoftBreak => push(" ", face),
+            Node::HardBreak => push("\n", face),
+            Node::TaskMarker(checked) => push(if *checked { "[x] " } else { "[ ] " }, face),
+            Node::Math(math) => push(&math.tex, face.italic()),
+            Node::FootnoteRef(note) => self.runs.push(Run {
+                text: note.number.to_string(),
+                face,
+                sup: true,
+            }),
+            Node::Rule => {}
+            Node::Element(el) => {
+                let (face, sup) = match &el.kind {
+                    Kind::Emphasis | Kind::Image { .. } => (face.italic(), sup),
+                    Kind::Strong => (face.bold(), sup),
+                    Kind::Html { name, .. } => match name.as_str() {
+                        "br" => {
+                            push("\n", face);
+                            return;
+                        }
+                        "em" | "i" | "cite" => (face.italic(), sup),
+                        "strong" | "b" => (face.bold(), sup),
+                        "code" | "kbd" | "samp" => (Face::Mono, sup),
+                        "sup" => (face, true),
+                        _ => (face, sup),
+                    },
+                    _ => (face, sup),
+                };
+                for child in &el.children {
+                    self.inline(child, face, sup);
+                }
+            }
+        }
+    }
+
+    /// Ends the current paragraph.
+    fn flush(&mut self) {
+        if self.runs.iter().all(|run| run.text.trim().is_empty()) {
+            self.runs.clear();
+            return;
+        }
+        self.blocks.push(Block::Text {
+            runs: std::mem::take(&mut self.runs),
+            inset: self.inset,
+            label: self.label.take(),
+        });
+    }
+}
+
+fn is_block(el: &Element) -> bool {
+    match &el.kind {
+        Kind::Html { name, .. } => HTML_BLOCKS.contains(&name.as_str()),
+        Kind::Emphasis
+        | Kind::Strong
+        | Kind::Strikethrough
+        | Kind::Link { .. }
+        | Kind::Image { .. } => false,
+        _ => true,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Align {
+    Justify,
+    Left,
+    Center,
+}
+
+/// Lays out the text of a book with its fonts.
+struct Typesetter<'a> {
+    book: &'a Book,
+    /// The manuscript laid out in the Standard Manuscript Format instead of as a book.
+    manuscript: Option<&'a Manuscript>,
+    fonts: Vec<Font>,
+    /// The size of the body text.
+    size: f32,
+    leading: f32,
+}
+
+impl<'a> Typesetter<'a> {
+    fn new(book: &'a Book, manuscript: Option<&'a Manuscript>) -> Self {
+        let size = book.size.font_size();
+        let mono = manuscript.is_some_and(|manuscript| manuscript.typeface == Typeface::Courier);
+        Self {
+            book,
+            manuscript,
+            fonts: Face::ALL
+                .into_iter()
+                .map(|face| Font::load(face, mono))
+                .collect(),
+            size,
+            leading: if manuscript.is_some() {
+                size * 2.0
+            } else {
+                size * 1.35
+            },
+        }
+    }
+
+    fn font(&self, face: Face) -> &Font {
+        &self.fonts[face as usize]
+    }
+
+    fn text_width(&self) -> f32 {
+        self.book.size.dimensions().0 - self.book.margins.inner - self.book.margins.outer
+    }
+
+    fn body_height(&self) -> f32 {
+        self.book.size.dimensions().1 - self.book.margins.top - self.book.margins.bottom
+    }
+
+    /// The glyph of the character, from the face or a fallback, with its advance.
+    fn glyph(&mut self, face: Face, c: char, size: f32) -> (Face, u16, f32) {
+        for face in [face, Face::Regular, Face::Mono] {
+            let font = &mut self.fonts[face as usize];
+            if let Some(glyph) = font.face.glyph_index(c) {
+                font.used.entry(glyph.0).or_insert(c);
+                let advance = font.scale(font.advance(glyph.0), size);
+                return (face, glyph.0, advance);
+            }
+        }
+        (face, 0, 0.0)
+    }
+
+    /// Splits the runs into words and spaces at the size.
+    fn tokens(&mut self, runs: &[Run], size: f32) -> Vec<Token> {
+        let mut tokens = vec![];
+        let mut word: Vec<Frag> = vec![];
+        let mut width = 0.0;
+        let end_word = |tokens: &mut Vec<Token>, word: &mut Vec<Frag>, width: &mut f32| {
+            if !word.is_empty() {
+                tokens.push(Token::Word(std::mem::take(word), *width));
+                *width = 0.0;
+            }
+        };
+        for run in runs {
+            let (size, rise) = if run.sup {
+                (size * 0.65, size * 0.35)
+            } else {
+                (size, 0.0)
+            };
+            for c in run.text.chars() {
+                if c == '\n' {
+                    end_word(&mut tokens, &mut word, &mut width);
+                    tokens.push(Token::Break);
+                } else if c.is_whitespace() && c != '\u{A0}' {
+                    end_word(&mut tokens, &mut word, &mut width);
+                    if !matches!(tokens.last(), None | Some(Token::Space(_) | Token::Break)) {
+                        let (_, _, advance) = self.glyph(run.face, ' ', size);
+                        tokens.push(Token::Space(advance));
+                    }
+                } else {
+                    let (face, glyph, advance) = self.glyph(run.face, c, size);
+                    match word.last_mut() {
+                        Some(frag)
+                            if frag.face == face && frag.size == size && frag.rise == rise =>
+                        {
+                            frag.glyphs.push(glyph)
+                        }
+                        _ => word.push(Frag {
+                            face,
+                            size,
+                            x: width,
+                            rise,
+                            glyphs: vec![glyph],
+                        }),
+                    }
+                    width += advance;
+                }
+            }
+        }
+        end_word(&mut tokens, &mut word, &mut width);
+        if let Some(Token::Space(_)) = tokens.last() {
+            tokens.pop();
+        }
+        tokens
+    }
+
+    /// Sets the runs in one line, aligned in the width.
+    fn single_line(&mut self, runs: &[Run], size: f32, width: f32, align: Align) -> Vec<Frag> {
+        let tokens = self.tokens(runs, size);
+        break_lines(tokens, |_| f32::INFINITY, Align::Left)
+            .into_iter()
+            .flatten()
+            .map(|(frag, natural)| {
+                let offset = match align {
+                    Align::Center => (width - natural) / 2.0,
+                    _ => 0.0,
+                };
+                Frag {
+                    x: frag.x + offset,
+                    ..frag
+                }
+            })
+            .collect()
+    }
+
+    /// Lines of the runs in the text width, whose lines all stay together.
+    fn display(&mut self, runs: &[Run], face: Face, size: f32) -> Vec<Line> {
+        let runs: Vec<Run> = runs
+            .iter()
+            .map(|run| Run {
+                face: match face {
+                    Face::Bold => run.face.bold(),
+                    _ => run.face,
+                },
+                ..run.clone()
+            })
+            .collect();
+        let tokens = self.tokens(&runs, size);
+        let width = self.text_width();
+        break_lines(tokens, |_| width, Align::Center)
+            .into_iter()
+            .map(|frags| Line {
+                frags: frags.into_iter().map(|(frag, _)| frag).collect(),
+                height: (size * 1.3).max(self.leading),
+                baseline: size,
+                keep: true,
+            })
+            .collect()
+    }
+
+    /// The lines of a paragraph, optionally opening with a drop cap.
+    fn paragraph(
+        &mut self,
+        runs: &[Run],
+        inset: u8,
+        label: Option<&str>,
+        indent: bool,
+        drop_cap: bool,
+    ) -> Vec<Line> {
+        let step = self.size * 1.5;
+        let left = step * inset as f32;
+        let width = self.text_width() - left - if label.is_some() { 0.0 } else { left / 2.0 };
+        let mut runs = runs.to_vec();
+        let mut cap = None;
+        if drop_cap {
+            cap = self.drop_cap(&mut runs);
+        }
+        let cap_width = cap.as_ref().map_or(0.0, |(_, width)| *width);
+        let (first, align) = match (indent, self.manuscript) {
+            (false, None) => (0.0, Align::Justify),
+            (true, None) => (step, Align::Justify),
+            // half an inch.
+            (true, Some(_)) => (36.0, Align::Left),
+            (false, Some(_)) => (0.0, Align::Left),
+        };
+        let tokens = self.tokens(&runs, self.size);
+        let lines = break_lines(
+            tokens,
+            |i| {
+                let mut available = width;
+                if i < DROP_LINES {
+                    available -= cap_width;
+                }
+                if i == 0 {
+                    available -= first;
+                }
+                available
+            },
+            align,
+        );
+        let count = lines.len();
+        let mut lines: Vec<Line> = lines
+            .into_iter()
+            .enumerate()
+            .map(|(i, frags)| {
+                let mut offset = left;
+                if i < DROP_LINES {
+                    offset += cap_width;
+                }
+                if i == 0 {
+                    offset += first;
+                }
+                Line {
+                    frags: frags
+                        .into_iter()
+                        .map(|(frag, _)| Frag {
+                            x: frag.x + offset,
+                            ..frag
+                        })
+                        .collect(),
+                    height: self.leading,
+                    baseline: self.size,
+                    keep: (cap.is_some() && i + 1 < DROP_LINES)
+                        || i + 1 < ORPHANS
+                        || (i + WIDOWS >= count && i + 1 < count),
+                }
+            })
+            .collect();
+        if let (Some((frags, _)), Some(line)) = (cap, lines.first_mut()) {
+            line.frags.extend(frags);
+        }
+        if let (Some(label), Some(line)) = (label, lines.first_mut()) {
+            let runs = [Run::new(label, Face::Regular)];
+            let frags = self.single_line(&runs, self.size, 0.0, Align::Left);
+            let label_width = frags
+                .last()
+                .map_or(0.0, |frag| frag.x + self.frag_width(frag));
+            line.frags.extend(frags.into_iter().map(|frag| Frag {
+                x: frag.x + left - label_width - self.size * 0.5,
+                ..frag
+            }));
+        }
+        if drop_cap && count < DROP_LINES {
+            if let Some(line) = lines.last_mut() {
+                line.height += (DROP_LINES - count) as f32 * self.leading;
+            }
+        }
+        lines
+    }
+
+    /// Takes the first letter, with its opening punctuation, from the runs as a drop cap
+    /// spanning the first lines, with the width it takes from them.
+    fn drop_cap(&mut self, runs: &mut [Run]) -> Option<(Vec<Frag>, f32)> {
+        let run = runs.iter_mut().find(|run| !run.text.trim().is_empty())?;
+        let text = run.text.trim_start();
+        let end = text
+            .char_indices()
+            .find(|(_, c)| c.is_alphanumeric())
+            .map(|(i, c)| i + c.len_utf8())?;
+        let letter = text[..end].to_string();
+        run.text = text[end..].to_string();
+        let cap_height = self.font(Face::Regular).cap_height();
+        let size = ((DROP_LINES - 1) as f32 * self.leading + cap_height * self.size) / cap_height;
+        let mut frags =
+            self.single_line(&[Run::new(&letter, Face::Regular)], size, 0.0, Align::Left);
+        let width = frags
+            .last()
+            .map_or(0.0, |frag| frag.x + self.frag_width(frag));
+        for frag in &mut frags {
+            frag.rise = -((DROP_LINES - 1) as f32) * self.leading;
+        }
+        Some((frags, width + self.size * 0.3))
+    }
+
+    fn frag_width(&self, frag: &Frag) -> f32 {
+        let font = self.font(frag.face);
+        frag.glyphs
+            .iter()
+            .map(|glyph| font.scale(font.advance(*glyph), frag.size))
+            .sum()
+    }
+
+    /// The lines of a code block, wrapped at the text width.
+    fn code(&mut self, code: &str) -> Vec<Line> {
+        let size = self.size * 0.85;
+        let width = self.text_width() - self.size * 1.5;
+        let mut lines = vec![];
+        for source in code.split('\n') {
+            let mut frag = Frag {
+                face: Face::Mono,
+                size,
+                x: self.size * 1.5,
+                rise: 0.0,
+                glyphs: vec![],
+            };
+            let mut x = 0.0;
+            for c in source.chars() {
+                let c = if c == '\t' { ' ' } else { c };
+                let (_, glyph, advance) = self.glyph(Face::Mono, c, size);
+                if x + advance > width && !frag.glyphs.is_empty() {
+                    lines.push(vec![frag.clone()]);
+                    frag.glyphs.clear();
+                    x = 0.0;
+                }
+                frag.glyphs.push(glyph);
+                x += advance;
+            }
+            lines.push(vec![frag]);
+        }
+        let count = lines.len();
+        lines
+            .into_iter()
+            .enumerate()
+            .map(|(i, frags)| Line {
+                frags,
+                height: size * 1.3,
+                baseline: size,
+                keep: i + 1 < ORPHANS || (i + WIDOWS >= count && i + 1 < count),
+            })
+            .collect()
+    }
+
+    /// Lays out the chapter after the items of the previous chapters.
+    fn chapter(&mut self, index: usize, title: &str, blocks: &[Block], items: &mut Vec<Item>) {
+        items.push(Item::Chapter(index));
+        let size = self.size * 2.0;
+        items.extend(
+            self.display(&[Run::new(title, Face::Regular)], Face::Regular, size)
+                .into_iter()
+                .map(Item::Line),
+        );
+        items.push(Item::Space(self.leading * 2.0));
+        let mut first = true;
+        let mut indent = false;
+        for block in blocks {
+            match block {
+                Block::Text { runs, inset, label } => {
+                    if !indent {
+                        items.push(Item::Space(self.leading * 0.5));
+                    }
+                    let plain = *inset == 0 && label.is_none();
+                    let lines = self.paragraph(
+                        runs,
+                        *inset,
+                        label.as_deref(),
+                        indent && plain,
+                        first && plain,
+                    );
+                    items.extend(lines.into_iter().map(Item::Line));
+                    first = false;
+                    indent = plain;
+                    if !plain {
+                        items.push(Item::Space(self.leading * 0.5));
+                    }
+                }
+                Block::Heading(runs) => {
+                    items.push(Item::Space(self.leading));
+                    let lines = self.display(runs, Face::Bold, self.size * 1.2);
+                    items.extend(lines.into_iter().map(Item::Line));
+                    indent = false;
+                }
+                Block::Code(code) => {
+                    items.push(Item::Space(self.leading * 0.5));
+                    let lines = self.code(code);
+                    items.extend(lines.into_iter().map(Item::Line));
+                    items.push(Item::Space(self.leading * 0.5));
+                    indent = false;
+                }
+                Block::SceneBreak => {
+                    items.push(Item::Space(self.leading * 0.5));
+                    items.push(self.mark("*  *  *"));
+                    items.push(Item::Space(self.leading * 0.5));
+                    indent = false;
+                }
+            }
+        }
+    }
+
+    /// A centered line of the text, such as a scene break.
+    fn mark(&mut self, text: &str) -> Item {
+        let width = self.text_width();
+        let frags = self.single_line(
+            &[Run::new(text, Face::Regular)],
+            self.size,
+            width,
+            Align::Center,
+        );
+        Item::Line(Line {
+            frags,
+            height: self.leading,
+            baseline: self.size,
+            keep: true,
+        })
+    }
+
+    /// Lays out the text of a manuscript, whose paragraphs are all indented, and whose scenes
+    /// and end are marked without extra space.
+    fn story(&mut self, blocks: &[Block], items: &mut Vec<Item>) {
+        for block in blocks {
+            match block {
+                Block::Text { runs, inset, label } => {
+                    let plain = *inset == 0 && label.is_none();
+                    let lines = self.paragraph(runs, *inset, label.as_deref(), plain, false);
+                    items.extend(lines.into_iter().map(Item::Line));
+                }
+                Block::Heading(runs) => {
+                    let lines = self.display(runs, Face::Regular, self.size);
+                    items.extend(lines.into_iter().map(Item::Line));
+                }
+                Block::Code(code) => {
+                    let lines = self.code(code);
+                    items.extend(lines.into_iter().map(Item::Line));
+                }
+                Block::SceneBreak => items.push(self.mark("#")),
+            }
+        }
+        items.push(self.mark("END"));
+    }
+
+    /// The first page of a manuscript, with the contact information and the word count at the
+    /// top, then the title and the byline a third down the page.
+    fn front_page(&mut self, manuscript: &Manuscript) -> Page {
+        let mut page = Page::new(PageKind::Opener, Some(0));
+        let width = self.text_width();
+        let single = self.size * 1.2;
+        let contact = std::iter::once(&manuscript.author).chain(&manuscript.contact);
+        for (i, text) in contact.enumerate() {
+            let mut frags = self.single_line(
+                &[Run::new(text, Face::Regular)],
+                self.size,
+                width,
+                Align::Left,
+            );
+            if i == 0 {
+                let count = [Run::new(&manuscript.word_count(), Face::Regular)];
+                let count = self.single_line(&count, self.size, width, Align::Left);
+                let count_width = count
+                    .last()
+                    .map_or(0.0, |frag| frag.x + self.frag_width(frag));
+                frags.extend(count.into_iter().map(|frag| Frag {
+                    x: frag.x + width - count_width,
+                    ..frag
+                }));
+            }
+            let line = Line {
+                frags,
+                height: single,
+                baseline: self.size,
+                keep: true,
+            };
+            page.lines.push((i as f32 * single, line));
+        }
+        let mut y = self.body_height() / 3.0;
+        let title = [Run::new(&manuscript.title, Face::Regular)];
+        let byline = [Run::new(
+            &format!("by {}", manuscript.byline()),
+            Face::Regular,
+        )];
+        let mut lines = self.display(&title, Face::Regular, self.size);
+        lines.extend(self.display(&byline, Face::Regular, self.size));
+        if let Some(last) = lines.last_mut() {
+            // a blank line before the text.
+            last.height += self.leading;
+        }
+        for line in lines {
+            let height = line.height;
+            page.lines.push((y, line));
+            y += height;
+        }
+        page
+    }
+
+    /// The title page with the title and the authors of the book.
+    fn title_page(&mut self) -> Page {
+        let mut page = Page::new(PageKind::Title, None);
+        let mut y = self.body_height() / 4.0;
+        let title = [Run::new(&self.book.title, Face::Regular)];
+        let authors = [Run::new(&self.book.authors.join(", "), Face::Italic)];
+        let mut lines = self.display(&title, Face::Regular, self.size * 2.4);
+        if let Some(last) = lines.last_mut() {
+            last.height += self.leading * 3.0;
+        }
+        lines.extend(self.display(&authors, Face::Regular, self.size * 1.3));
+        for line in lines {
+            let height = line.height;
+            page.lines.push((y, line));
+            y += height;
+        }
+        page
+    }
+
+    /// The content stream of the page with the number, with its running header and page
+    /// number.
+    fn content(&mut self, number: usize, page: &Page) -> Vec<u8> {
+        let height = self.book.size.dimensions().1;
+        let margins = self.book.margins;
+        let recto = number % 2 == 1;
+        let left = if recto { margins.inner } else { margins.outer };
+        let top = height - margins.top;
+        let text_width = self.text_width();
+
+        let mut content = Content::new();
+        content.begin_text();
+        for (y, line) in &page.lines {
+            for frag in &line.frags {
+                show(
+                    &mut content,
+                    frag,
+                    left + frag.x,
+                    top - y - line.baseline + frag.rise,
+                );
+            }
+        }
+        if let Some(manuscript) = self.manuscript {
+            if number > 1 {
+                let header = format!("{} / {}", manuscript.header(), number);
+                let frags = self.single_line(
+                    &[Run::new(&header, Face::Regular)],
+                    self.size,
+                    text_width,
+                    Align::Left,
+                );
+                let header_width = frags
+                    .last()
+                    .map_or(0.0, |frag| frag.x + self.frag_width(frag));
+                for frag in &frags {
+                    show(
+                        &mut content,
+                        frag,
+                        left + text_width - header_width + frag.x,
+                        height - margins.top * 0.5 - self.size * 0.5,
+                    );
+                }
+            }
+        } else if page.kind == PageKind::Body {
+            let header = match page.chapter {
+                Some(chapter) if recto => self.book.chapters[chapter].doc.head.title.clone(),
+                _ => self.book.title.clone(),
+            };
+            let frags = self.single_line(
+                &[Run::new(&header, Face::Italic)],
+                self.size * 0.85,
+                text_width,
+                Align::Center,
+            );
+            for frag in &frags {
+                show(
+                    &mut content,
+                    frag,
+                    left + frag.x,
+                    height - margins.top * 0.6,
+                );
+            }
+        }
+        if self.manuscript.is_none() && matches!(page.kind, PageKind::Body | PageKind::Opener) {
+            let folio = [Run::new(&number.to_string(), Face::Regular)];
+            let frags = self.single_line(&folio, self.size * 0.9, text_width, Align::Center);
+            for frag in &frags {
+                show(&mut content, frag, left + frag.x, margins.bottom * 0.5);
+            }
+        }
+        content.end_text();
+        content.finish()
+    }
+
+    /// Writes the document with the pages and their contents, embedding the used glyphs of the
+    /// fonts.
+    fn write(&self, pages: &[Page], contents: &[Vec<u8>]) -> Result<Vec<u8>, Error> {
+        let (width, height) = self.book.size.dimensions();
+        let mut refs = Refs::default();
+        let catalog = refs.next();
+        let tree = refs.next();
+        let outline = refs.next();
+        let info = refs.next();
+        let faces: Vec<(Face, Ref)> = Face::ALL
+            .into_iter()
+            .filter(|face| self.font(*face).used.len() > 1)
+            .map(|face| (face, refs.next()))
+            .collect();
+        let page_refs: Vec<(Ref, Ref)> = pages.iter().map(|_| (refs.next(), refs.next())).collect();
+
+        let mut pdf = Pdf::new();
+        pdf.pages(tree)
+            .kids(page_refs.iter().map(|(page, _)| *page))
+            .count(pages.len() as i32);
+        for ((page, content), data) in page_refs.iter().zip(contents) {
+            let mut writer = pdf.page(*page);
+            writer
+                .parent(tree)
+                .media_box(Rect::new(0.0, 0.0, width, height))
+                .contents(*content);
+            let mut resources = writer.resources();
+            let mut fonts = resources.fonts();
+            for (face, font) in &faces {
+                fonts.pair(Name(face.resource()), *font);
+            }
+            fonts.finish();
+            resources.finish();
+            writer.finish();
+            pdf.stream(*content, &deflate(data))
+                .filter(Filter::FlateDecode);
+        }
+
+        for (face, font) in &faces {
+            self.embed(&mut pdf, &mut refs, *face, *font)?;
+        }
+
+        let openers: Vec<(usize, Ref)> = pages
+            .iter()
+            .zip(&page_refs)
+            .filter(|(page, _)| page.kind == PageKind::Opener)
+            .filter_map(|(page, (page_ref, _))| Some((page.chapter?, *page_ref)))
+            .collect();
+        let items: Vec<Ref> = openers.iter().map(|_| refs.next()).collect();
+        let mut writer = pdf.outline(outline);
+        if let (Some(first), Some(last)) = (items.first(), items.last()) {
+            writer.first(*first).last(*last);
+        }
+        writer.count(items.len() as i32);
+        writer.finish();
+        for (i, ((chapter, page), item)) in openers.iter().zip(&items).enumerate() {
+            let title = &self.book.chapters[*chapter].doc.head.title;
+            let mut writer = pdf.outline_item(*item);
+            writer.title(TextStr(title)).parent(outline);
+            if i > 0 {
+                writer.prev(items[i - 1]);
+            }
+            if let Some(next) = items.get(i + 1) {
+                writer.next(*next);
+            }
+            writer.dest().page(*page).xyz(0.0, height, None);
+        }
+
+        pdf.catalog(catalog)
+            .pages(tree)
+            .outlines(outline)
+            .lang(TextStr(&self.book.language));
+        let mut writer = pdf.document_info(info);
+        writer.title(TextStr(&self.book.title));
+        let authors = self.book.authors.join(", ");
+        if !authors.is_empty() {
+            writer.author(TextStr(&authors));
+        }
+        writer.creator(TextStr("Schling"));
+        writer.finish();
+        Ok(pdf.finish())
+    }
+
+    /// Embeds the subset of the font with the used glyphs as a composite font.
+    fn embed(&self, pdf: &mut Pdf, refs: &mut Refs, face: Face, id: Ref) -> Result<(), Error> {
+        let font = self.font(face);
+        let glyphs: Vec<u16> = font.used.keys().copied().collect();
+        let subset = subsetter::subset(font.data, 0, subsetter::Profile::pdf(&glyphs))?;
+        let name = format!("{}+{}", subset_tag(&glyphs), font.name);
+        let info = SystemInfo {
+            registry: Str(b"Adobe"),
+            ordering: Str(b"Identity"),
+            supplement: 0,
+        };
+        let (cid, descriptor, cmap, file) = (refs.next(), refs.next(), refs.next(), refs.next());
+
+        pdf.type0_font(id)
+            .base_font(Name(name.as_bytes()))
+            .encoding_predefined(Name(b"Identity-H"))
+            .descendant_font(cid)
+            .to_unicode(cmap);
+
+        let mut writer = pdf.cid_font(cid);
+        writer
+            .subtype(CidFontType::Type2)
+            .base_font(Name(name.as_bytes()))
+            .system_info(info)
+            .font_descriptor(descriptor)
+            .default_width(0.0)
+            .cid_to_gid_map_predefined(Name(b"Identity"));
+        let mut widths = writer.widths();
+        for glyph in &glyphs {
+            widths.consecutive(*glyph, [font.scale(font.advance(*glyph), 1000.0)]);
+        }
+        widths.finish();
+        writer.finish();
+
+        let ttf = &font.face;
+        let units = |value: i16| font.scale(value as f32, 1000.0);
+        let bbox = ttf.global_bounding_box();
+        let mut flags = FontFlags::NON_SYMBOLIC;
+        flags |= if ttf.is_monospaced() {
+            FontFlags::FIXED_PITCH
+        } else {
+            FontFlags::SERIF
+        };
+        if ttf.is_italic() {
+            flags |= FontFlags::ITALIC;
+        }
+        pdf.font_descriptor(descriptor)
+            .name(Name(name.as_bytes()))
+            .flags(flags)
+            .bbox(Rect::new(
+                units(bbox.x_min),
+                units(bbox.y_min),
+                units(bbox.x_max),
+                units(bbox.y_max),
+            ))
+            .italic_angle(ttf.italic_angle())
+            .ascent(units(ttf.ascender()))
+            .descent(units(ttf.descender()))
+            .cap_height(units(ttf.capital_height().unwrap_or(ttf.ascender())))
+            .stem_v(if ttf.is_bold() { 120.0 } else { 80.0 })
+            .font_file2(file);
+
+        let mut unicode = UnicodeCmap::new(Name(b"Custom"), info);
+        for (glyph, c) in &font.used {
+            unicode.pair(*glyph, *c);
+        }
+        pdf.stream(cmap, &unicode.finish());
+        pdf.stream(file, &deflate(&subset))
+            .filter(Filter::FlateDecode)
+            .pair(Name(b"Length1"), subset.len() as i32);
+        Ok(())
+    }
+}
+
+/// Breaks the tokens into lines of the widths by their index, returning the fragments of each
+/// line with the natural width of the line.
+fn break_lines(
+    tokens: Vec<Token>,
+    width: impl Fn(usize) -> f32,
+    align: Align,
+) -> Vec<Vec<(Frag, f32)>> {
+    let mut lines = vec![];
+    let mut words: Vec<(f32, Vec<Frag>, f32)> = vec![];
+    let mut natural = 0.0;
+    let mut space = 0.0;
+    for token in tokens {
+        match token {
+            Token::Space(advance) => space = advance,
+            Token::Word(frags, advance) => {
+                let gap = if words.is_empty() { 0.0 } else { space };
+                if !words.is_empty() && natural + gap + advance > width(lines.len()) {
+                    let available = width(lines.len());
+                    lines.push(set_line(
+                        std::mem::take(&mut words),
+                        natural,
+                        available,
+                        align,
+                        false,
+                    ));
+                    natural = 0.0;
+                    words.push((0.0, frags, advance));
+                    natural += advance;
+                } else {
+                    words.push((gap, frags, advance));
+                    natural += gap + advance;
+                }
+                space = 0.0;
+            }
+            Token::Break => {
+                let available = width(lines.len());
+                lines.push(set_line(
+                    std::mem::take(&mut words),
+                    natural,
+                    available,
+                    align,
+                    true,
+                ));
+                natural = 0.0;
+                space = 0.0;
+            }
+        }
+    }
+    if !words.is_empty() || lines.is_empty() {
+        let available = width(lines.len());
+        lines.push(set_line(words, natural, available, align, true));
+    }
+    lines
+}
+
+/// Positions the words of a line, stretching the spaces of justified lines but the last.
+fn set_line(
+    words: Vec<(f32, Vec<Frag>, f32)>,
+    natural: f32,
+    width: f32,
+    align: Align,
+    last: bool,
+) -> Vec<(Frag, f32)> {
+    let extra = (width - natural).max(0.0);
+    let (mut x, stretch) = match align {
+        Align::Justify if !last && words.len() > 1 => (0.0, extra / (words.len() - 1) as f32),
+        Align::Center if width.is_finite() => (extra / 2.0, 0.0),
+        _ => (0.0, 0.0),
+    };
+    let mut line = vec![];
+    for (i, (gap, frags, advance)) in words.into_iter().enumerate() {
+        if i > 0 {
+            x += gap + stretch;
+        }
+        for frag in frags {
+            line.push((
+                Frag {
+                    x: frag.x + x,
+                    ..frag
+                },
+                natural,
+            ));
+        }
+        x += advance;
+    }
+    line
+}
+
+fn show(content: &mut Content, frag: &Frag, x: f32, y: f32) {
+    let text: Vec<u8> = frag
+        .glyphs
+        .iter()
+        .flat_map(|glyph| glyph.to_be_bytes())
+        .collect();
+    content
+        .set_font(Name(frag.face.resource()), frag.size)
+        .set_text_matrix([1.0, 0.0, 0.0, 1.0, x, y])
+        .show(Str(&text));
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder.write_all(data).expect("writing to memory");
+    encoder.finish().expect("writing to memory")
+}
+
+/// The tag of an embedded subset of a font, six letters derived from its glyphs.
+fn subset_tag(glyphs: &[u16]) -> String {
+    let mut hash: u32 = 2166136261;
+    for glyph in glyphs {
+        for byte in glyph.to_be_bytes() {
+            hash = (hash ^ byte as u32).wrapping_mul(16777619);
+        }
+    }
+    (0..6)
+        .map(|i| (b'A' + (hash >> (i * 5) & 31) as u8 % 26) as char)
+        .collect()
+}
+
+/// Allocates the ids of the indirect objects.
+#[derive(Default)]
+struct Refs(i32);
+
+impl Refs {
+    fn next(&mut self) -> Ref {
+        self.0 += 1;
+        Ref::new(self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::chapter;
+
+    fn line(height: f32, keep: bool) -> Item {
+        Item::Line(Line {
+            frags: vec![],
+            height,
+            baseline: height,
+            keep,
+        })
+    }
+
+    fn book(content: &str) -> Book {
+        let (doc, sections) = chapter(1, "The Beginning", &[("", content)]);
+        Book::new("A Book", PageSize::A5).with_chapter(doc, sections)
+    }
+
+    #[test]
+    fn parses_page_sizes() {
+        for size in [PageSize::A5, PageSize::SixByNine, PageSize::Letter] {
+            assert_eq!(size.to_string().parse::<PageSize>(), Ok(size));
+        }
+        assert_eq!("6×9".parse::<PageSize>(), Ok(PageSize::SixByNine));
+        assert!("b5".parse::<PageSize>().is_err());
+        assert_eq!(PageSize::SixByNine.dimensions(), (432.0, 648.0));
+    }
+
+    #[test]
+    fn keeps_widows_and_orphans() {
+        let book = book("Text.");
+        let mut setter = Typesetter::new(&book, None);
+        let runs = [Run::new(&"word ".repeat(100), Face::Regular)];
+        let lines = setter.paragraph(&runs, 0, None, true, false);
+        let keep: Vec<bool> = lines.iter().map(|line| line.keep).collect();
+        let count = keep.len();
+        assert!(count > 4);
+        assert!(keep[0] && !keep[1] && keep[count - 2] && !keep[count - 1]);
+
+        // five lines fit a page: the single first line of a paragraph moves to the next page.
+        let paragraph = |lines: usize| -> Vec<Item> {
+            (0..lines)
+                .map(|i| {
+                    line(
+                        10.0,
+                        i + 1 < ORPHANS || (i + WIDOWS >= lines && i + 1 < lines),
+                    )
+                })
+                .collect()
+        };
+        let mut items = vec![Item::Chapter(0)];
+        items.extend(paragraph(4));
+        items.extend(paragraph(3));
+        let mut pages = vec![];
+        paginate(&mut pages, items, 50.0 * 4.0 / 3.0);
+        let counts: Vec<usize> = pages.iter().map(|page| page.lines.len()).collect();
+        assert_eq!(counts, [4, 3]);
+
+        // the single last line of a paragraph takes the line before it to the next page.
+        let mut items = vec![Item::Chapter(0)];
+        items.extend(paragraph(6));
+        let mut pages = vec![];
+        paginate(&mut pages, items, 50.0 * 4.0 / 3.0);
+        let counts: Vec<usize> = pages.iter().map(|page| page.lines.len()).collect();
+        assert_eq!(counts, [4, 2]);
+    }
+
+    #[test]
+    fn opens_chapters_with_drop_caps() {
+        let text = format!("Once upon a time, {}.", "there was a sentence ".repeat(20));
+        let book = book(&text);
+        let mut setter = Typesetter::new(&book, None);
+        let blocks = book.blocks(&book.chapters[0]);
+        let mut items = vec![];
+        setter.chapter(0, "The Beginning", &blocks, &mut items);
+        let lines: Vec<&Line> = items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Line(line) => Some(line),
+                _ => None,
+            })
+            .collect();
+        // the title and the paragraph, whose first line holds the large initial.
+        let cap = lines[1]
+            .frags
+            .iter()
+            .find(|frag| frag.size > setter.size * 2.0)
+            .unwrap();
+        assert_eq!(cap.glyphs.len(), 1);
+        assert!(cap.rise < 0.0);
+        assert!(lines[1..4].iter().all(|line| line.frags[0].x > 0.0));
+        assert!(lines[1].keep && lines[2].keep);
+    }
+
+    #[test]
+    fn writes_book() {
+        let book =
+            book("Some *text* with a note.[^1]\n\n---\n\nAfter the break.\n\n[^1]: The note.");
+        let bytes = book.to_bytes().unwrap();
+        let pdf = String::from_utf8_lossy(&bytes);
+        assert!(pdf.starts_with("%PDF-1.7"));
+        // the title page, a blank verso page and the chapter.
+        assert!(pdf.contains("/Type /Pages\n  /Kids [") && pdf.contains("/Count 3"));
+        assert!(pdf.contains("/FontFile2"));
+        assert!(pdf.contains("+LinLibertineOI"));
+        assert!(pdf.contains("/Title (The Beginning)"));
+
+        let empty = Book::new("Nothing", PageSize::Letter);
+        assert!(matches!(empty.to_bytes(), Err(Error::Empty)));
+    }
+}
@@ -0,0 +1,309 @@
+use crate::ast::{Align, Element, Kind, Node, Note, Notes};
+use crate::highlight;
+use crate::html;
+use crate::markup::Markup;
+use crate::sanitize::Policy;
+
+/// Serializes the document tree to an HTML string, with void elements in XHTML syntax.
+pub trait ToHtml {
+    fn write_html(&self, out: &mut String);
+
+    fn to_html(&self) -> String {
+        let mut out = String::new();
+        self.write_html(&mut out);
+        out
+    }
+}
+
+impl ToHtml for [Node] {
+    fn write_html(&self, out: &mut String) {
+        for node in self {
+            node.write_html(out);
+        }
+    }
+}
+
+impl ToHtml for Vec<Node> {
+    fn write_html(&self, out: &mut String) {
+        self.as_slice().write_html(out);
+    }
+}
+
+impl ToHtml for Node {
+    fn write_html(&self, out: &mut String) {
+        match self {
+            Node::Text(text) => out.push_str(&html::escape(text)),
+            Node::Code(code) => {
+                out.push_str("<code>");
+                out.push_str(&html::escape(code));
+                out.push_str("</code>");
+            }
+            Node::SoftBreak => out.push('\n'),
+            Node::HardBreak => out.push_str("<br />\n"),
+            Node::Rule => out.push_str("<hr />\n"),
+            Node::TaskMarker(checked) => {
+                out.push_str("<input disabled=\"\" type=\"checkbox\"");
+                if *checked {
+                    out.push_str(" checked=\"\"");
+                }
+                out.push_str("/>\n");
+            }
+            Node::Math(math) => out.push_str(&math.render()),
+            Node::FootnoteRef(note) => {
+                out.push_str("<sup class=\"footnote-reference\"");
+                if let Some(id) = &note.id {
+                    push_attr(out, "id", id);
+                }
+                out.push_str("><a");
+                push_attr(out, "href", &format!("#{}", note.target));
+                out.push_str(&format!(">{}</a></sup>", note.number));
+            }
+            Node::Element(el) => el.write_html(out),
+        }
+    }
+}
+
+fn push_attr(out: &mut String, name: &str, value: &str) {
+    out.push_str(&format!(" {}=\"{}\"", name, html::escape(value)));
+}
+
+fn open(out: &mut String, name: &str) {
+    out.push_str(&format!("<{}>", name));
+}
+
+fn close(out: &mut String, name: &str) {
+    out.push_str(&format!("</{}>", name));
+}
+
+impl ToHtml for Element {
+    fn write_html(&self, out: &mut String) {
+        let name = match &self.kind {
+            Kind::Paragraph => "p",
+            Kind::Heading { level, id } => {
+                let name = format!("h{}", level);
+                out.push_str(&format!("<{}", name));
+                if let Some(id) = id {
+                    push_attr(out, "id", id);
+                }
+                out.push('>');
+                self.children.write_html(out);
+                close(out, &name);
+                out.push('\n');
+                return;
+            }
+            Kind::BlockQuote => "blockquote",
+            Kind::CodeBlock(lang) => {
+                out.push_str("<pre><code");
+                if let Some(lang) = lang {
+                    push_attr(out, "class", &format!("language-{}", lang));
+                }
+                out.push('>');
+                highlight::code_block(lang.as_deref(), self.children.clone()).write_html(out);
+                out.push_str("</code></pre>\n");
+                return;
+            }
+            Kind::List(None) => "ul",
+            Kind::List(Some(1)) => "ol",
+            Kind::List(Some(start)) => {
+                out.push_str("<ol");
+                push_attr(out, "start", &start.to_string());
+                out.push('>');
+                self.children.write_html(out);
+                out.push_str("</ol>\n");
+                return;
+            }
+            Kind::Item => "li",
+            Kind::Table => {
+                out.push_str("<table>");
+                let (head, body) = match self.children.split_first() {
+                    Some((head @ Node::Element(el), body)) if el.kind == Kind::TableHead => {
+                        (Some(head), body)
+                    }
+                    _ => (None, &self.children[..]),
+                };
+                if let Some(head) = head {
+                    head.write_html(out);
+                }
+                out.push_str("<tbody>");
+                body.write_html(out);
+                out.push_str("</tbody></table>\n");
+                return;
+            }
+            Kind::TableHead => {
+                out.push_str("<thead><tr>");
+                self.children.write_html(out);
+                out.push_str("</tr></thead>");
+                return;
+            }
+            Kind::TableRow => "tr",
+            Kind::TableCell { header, align } => {
+                let name = if *header { "th" } else { "td" };
+                out.push_str(&format!("<{}", name));
+                match align {
+                    Align::Left => push_attr(out, "style", "text-align: left"),
+                    Align::Center => push_attr(out, "style", "text-align: center"),
+                    Align::Right => push_attr(out, "style", "text-align: right"),
+                    Align::None => {}
+                }
+                out.push('>');
+                self.children.write_html(out);
+                close(out, name);
+                return;
+            }
+            Kind::Emphasis => "em",
+            Kind::Strong => "strong",
+            Kind::Strikethrough => "del",
+            Kind::Link { href, title } => {
+                out.push_str("<a");
+                if let Some(href) = href {
+                    push_attr(out, "href", href);
+                }
+                if !title.is_empty() {
+                    push_attr(out, "title", title);
+                }
+                out.push('>');
+                self.children.write_html(out);
+                close(out, "a");
+                return;
+            }
+            Kind::Image { src, title } => {
+                out.push_str("<img");
+                if let Some(src) = src {
+                    push_attr(out, "src", src);
+                }
+                let alt: String = self.children.iter().map(Node::text).collect();
+                push_attr(out, "alt", &alt);
+                if !title.is_empty() {
+                    push_attr(out, "title", title);
+                }
+                out.push_str(" />");
+                return;
+            }
+            Kind::Html { name, attrs } => {
+                out.push_str(&format!("<{}", name));
+                for (attr, value) in attrs {
+                    push_attr(out, attr, value);
+                }
+                if html::is_void(name) {
+                    out.push_str(" />");
+                } else {
+                    out.push('>');
+                    self.children.write_html(out);
+                    close(out, name);
+                }
+                return;
+            }
+        };
+        open(out, name);
+        self.children.write_html(out);
+        close(out, name);
+        if !matches!(
+            self.kind,
+            Kind::Emphasis | Kind::Strong | Kind::Strikethrough
+        ) {
+            out.push('\n');
+        }
+    }
+}
+
+impl ToHtml for Note {
+    fn write_html(&self, out: &mut String) {
+        out.push_str("<li class=\"footnote-definition\"");
+        push_attr(out, "id", &self.id);
+        push_attr(out, "value", &self.number.to_string());
+        out.push('>');
+        self.content.write_html(out);
+        out.push_str("<a class=\"footnote-backref\"");
+        push_attr(out, "href", &format!("#{}", self.back));
+        out.push_str(">↩</a></li>\n");
+    }
+}
+
+impl ToHtml for Notes {
+    fn write_html(&self, out: &mut String) {
+        if self.is_empty() {
+            return;
+        }
+        out.push_str("<section class=\"footnotes\"><ol>\n");
+        for note in self.clone().into_notes() {
+            note.write_html(out);
+        }
+        out.push_str("</ol></section>\n");
+    }
+}
+
+impl Markup {
+    /// Renders the markup to an HTML string followed by its footnotes, removing the HTML not
+    /// allowed by the policy.
+    pub fn render_html(&self, policy: &Policy) -> String {
+        self.render_html_with(Notes::default(), policy)
+    }
+
+    /// Renders the markup followed by its footnotes, in the scope and with the titles of the
+    /// notes.
+    pub fn render_html_with(&self, mut notes: Notes, policy: &Policy) -> String {
+        let mut out = self.to_ast(&mut notes, policy).to_html();
+        notes.write_html(&mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pulldown_cmark::Parser;
+
+    use super::*;
+    use crate::ast::{options, parse_markdown};
+
+    fn normalize(html: &str) -> String {
+        html.replace('\n', "").replace(" />", "/>")
+    }
+
+    fn assert_golden(src: &str) {
+        let mut expected = String::new();
+        pulldown_cmark::html::push_html(&mut expected, Parser::new_ext(src, options()));
+        let actual = parse_markdown(src, &mut Notes::default(), &Policy::default()).to_html();
+        assert_eq!(normalize(&actual), normalize(&expected), "source: {}", src);
+    }
+
+    #[test]
+    fn golden() {
+        assert_golden("Some *em*, **strong**, ~~gone~~ and `code` \"quoted\".");
+        assert_golden("A [link](https://example.org \"Title\") and ![an *image*](cat.png).");
+        assert_golden("Hard  \nbreak\nsoft break");
+        assert_golden("# Heading\n\n## Sub\n\nText\n\n---\n\n> quote\n> more");
+        assert_golden("```unknown info\nfn main() {}\n```\n\n    indented");
+        assert_golden("- a\n- b\n\n3. c\n4. d\n\n1. e");
+        assert_golden("- [ ] open\n- [x] done");
+        assert_golden("| a | b | c |\n|:--|:-:|--:|\n| 1 | 2 | 3 |\n| 4 | 5 | 6 |");
+        assert_golden("| head only |\n|---|");
+        assert_golden("Inline <span class=\"note\">html</span> text.");
+        assert_golden("<div title=\"block\">\n<p>raw</p>\n</div>");
+    }
+
+    #[test]
+    fn notes_and_sanitizing() {
+        let markup = Markup::md_str("a[^1] <b onclick=\"x\">b</b>\n\n[^1]: Note.");
+        assert_eq!(
+            normalize(&markup.render_html(&Policy::default())),
+            "<p>a<sup class=\"footnote-reference\" id=\"fnref-1\"><a href=\"#fn-1\">1</a></sup> \
+            <b>b</b></p>\
+            <section class=\"footnotes\"><ol>\
+            <li class=\"footnote-definition\" id=\"fn-1\" value=\"1\"><p>Note.</p>\
+            <a class=\"footnote-backref\" href=\"#fnref-1\">↩</a></li>\
+            </ol></section>"
+        );
+        assert_eq!(
+            Markup::html_str("<p onclick=\"x\">a<br>b</p>").render_html(&Policy::default()),
+            "<p>a<br />b</p>"
+        );
+    }
+
+    #[test]
+    fn highlights_code_blocks() {
+        let html = Markup::md_str("```rust\nfn main() {}\n```").render_html(&Policy::default());
+        assert!(html
+            .starts_with("<pre><code class=\"language-rust\"><span class=\"hl-source hl-rust\">"));
+        assert!(html.contains("<span class=\"hl-storage hl-type hl-function hl-rust\">fn</span>"));
+    }
+}
@@ -0,0 +1,522 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use crate::ast::{Element, Kind, Node, NoteRef};
+use crate::docx::{self, Format, Part, Piece, MONOSPACE_FONTS};
+
+/// The destinations whose content is not text, skipped without notice.
+const IGNORED: &[&str] = &[
+    "colortbl",
+    "colorschememapping",
+    "datastore",
+    "expandedcolortbl",
+    "footer",
+    "footerf",
+    "footerl",
+    "footerr",
+    "generator",
+    "header",
+    "headerf",
+    "headerl",
+    "headerr",
+    "info",
+    "latentstyles",
+    "listoverridetable",
+    "listtable",
+    "mmathPr",
+    "rsidtbl",
+    "stylesheet",
+    "themedata",
+    "xmlnstbl",
+];
+
+/// The destinations with content that cannot be converted, and what they are called in the
+/// report.
+const SKIPPED: &[(&str, &str)] = &[
+    ("annotation", "a comment"),
+    ("NeXTGraphic", "an image"),
+    ("object", "an embedded object"),
+    ("pict", "an image"),
+    ("shppict", "an image"),
+];
+
+/// The reason an RTF document cannot be read.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The data does not start with an RTF header.
+    NotRtf,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NotRtf => write!(f, "not an RTF document"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// An RTF document converted to Markdown.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Converted {
+    pub text: String,
+    /// What the document contains but the text lacks, such as images, once each.
+    pub skipped: Vec<String>,
+}
+
+/// Reads an RTF document as Markdown.
+///
+/// The paragraphs keep their bold, italic, struck and monospaced text, line breaks, links,
+/// list items and footnotes. Lines of only `#` or `* * *` are scene breaks. Tables become
+/// paragraphs of their rows.
+pub fn to_markdown(data: &[u8]) -> Result<Converted, Error> {
+    if !data.starts_with(b"{\\rtf") {
+        return Err(Error::NotRtf);
+    }
+    let mut reader = Reader::default();
+    reader.read(data);
+    reader.paragraph();
+    Ok(Converted {
+        text: reader.part.markdown().trim_start().to_string(),
+        skipped: reader.skipped,
+    })
+}
+
+/// Where the text of a group goes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Destination {
+    #[default]
+    Text,
+    Skip,
+    FontTable,
+    /// The instruction of a field, such as the target of a hyperlink.
+    Instruction,
+    /// The marker of a list item, such as its number.
+    ListText,
+}
+
+/// The state of a group, restored at its end.
+#[derive(Clone, Debug)]
+struct State {
+    format: Format,
+    destination: Destination,
+    /// The number of characters following a Unicode character, for readers without Unicode.
+    fallback: usize,
+    /// The number of fallback characters left to skip.
+    skip: usize,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            format: Format::default(),
+            destination: Destination::Text,
+            fallback: 1,
+            skip: 0,
+        }
+    }
+}
+
+/// A field, which is a hyperlink when its instruction is one.
+struct Field {
+    depth: usize,
+    instruction: String,
+    /// The first piece of its result.
+    start: usize,
+}
+
+/// A footnote being read, with the content around it.
+struct Footnote {
+    depth: usize,
+    part: Part,
+    pieces: Vec<Piece>,
+}
+
+#[derive(Default)]
+struct Reader {
+    part: Part,
+    pieces: Vec<Piece>,
+    state: State,
+    stack: Vec<State>,
+    /// Whether the next control word starts the group, naming its destination.
+    group_start: bool,
+    /// Whether the destination of the group is marked as ignorable by `\*`.
+    ignorable: bool,
+    /// Whether the fonts of the font table are monospaced, by number.
+    fonts: HashMap<i32, bool>,
+    font: Option<(i32, String)>,
+    fields: Vec<Field>,
+    footnotes: Vec<Footnote>,
+    /// The list level of the paragraph, when it is a list item.
+    list: Option<usize>,
+    /// The marker of the list item, such as its number.
+    marker: String,
+    skipped: Vec<String>,
+}
+
+impl Reader {
+    fn read(&mut self, data: &[u8]) {
+        let mut i = 0;
+        while i < data.len() {
+            match data[i] {
+                b'{' => {
+                    self.stack.push(self.state.clone());
+                    self.group_start = true;
+                    self.ignorable = false;
+                    i += 1;
+                }
+                b'}' => {
+                    self.close();
+                    i += 1;
+                }
+                b'\\' => i = self.control(data, i + 1),
+                b'\r' | b'\n' => i += 1,
+                _ => {
+                    let end = data[i..]
+                        .iter()
+                        .position(|b| matches!(b, b'{' | b'}' | b'\\' | b'\r' | b'\n'))
+                        .map_or(data.len(), |pos| i + pos);
+                    let text = String::from_utf8_lossy(&data[i..end]).into_owned();
+                    self.text(&text);
+                    self.group_start = false;
+                    i = end;
+                }
+            }
+        }
+    }
+
+    /// Reads the control word or symbol after a backslash, returning the position after it.
+    fn control(&mut self, data: &[u8], start: usize) -> usize {
+        let Some(&first) = data.get(start) else {
+            return start;
+        };
+        if !first.is_ascii_alphabetic() {
+            let mut end = start + 1;
+            match first {
+                b'\'' => {
+                    let hex = data.get(start + 1..start + 3).unwrap_or_default();
+                    if let Some(byte) = std::str::from_utf8(hex)
+                        .ok()
+                        .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    {
+                        self.text(&windows_1252(byte).to_string());
+                    }
+                    end = start + 3;
+                }
+                b'*' => self.ignorable = true,
+                b'~' => self.text("\u{a0}"),
+                b'_' => self.text("-"),
+                b'\\' | b'{' | b'}' => self.text(&(first as char).to_string()),
+                b'\r' | b'\n' => self.word("par", None),
+                _ => {}
+            }
+            return end;
+        }
+        let mut end = start;
+        while data.get(end).is_some_and(u8::is_ascii_alphabetic) {
+            end += 1;
+        }
+        let word = String::from_utf8_lossy(&data[start..end]).into_owned();
+        let digits = end;
+        if data.get(end) == Some(&b'-') {
+            end += 1;
+        }
+        while data.get(end).is_some_and(u8::is_ascii_digit) {
+            end += 1;
+        }
+        let param = std::str::from_utf8(&data[digits..end])
+            .ok()
+            .and_then(|param| param.parse::<i32>().ok());
+        if data.get(end) == Some(&b' ') {
+            end += 1;
+        }
+        self.word(&word, param);
+        end
+    }
+
+    fn word(&mut self, word: &str, param: Option<i32>) {
+        let group_start = std::mem::take(&mut self.group_start);
+        if self.state.destination == Destination::Skip {
+            return;
+        }
+        if group_start {
+            if let Some((_, what)) = SKIPPED.iter().find(|(name, _)| *name == word) {
+                self.skip(what);
+                return;
+            }
+            if IGNORED.contains(&word) {
+                self.state.destination = Destination::Skip;
+                return;
+            }
+        }
+        let on = param != Some(0);
+        match word {
+            "fonttbl" => self.state.destination = Destination::FontTable,
+            "f" if self.state.destination == Destination::FontTable => {
+                self.font = Some((param.unwrap_or_default(), String::new()));
+            }
+            "fmodern" if self.state.destination == Destination::FontTable => {
+                if let Some((number, _)) = self.font {
+                    self.fonts.insert(number, true);
+                }
+            }
+            "f" => {
+                let number = param.unwrap_or_default();
+                self.state.format.code = self.fonts.get(&number).copied().unwrap_or(false);
+            }
+            "b" => self.state.format.bold = on,
+            "i" => self.state.format.italic = on,
+            "strike" | "striked" => self.state.format.strike = on,
+            "plain" => self.state.format = Format::default(),
+            "pard" => self.list = None,
+            "ls" => self.list = Some(self.list.unwrap_or_default()),
+            "ilvl" => self.list = Some(param.unwrap_or_default().max(0) as usize),
+            "listtext" | "pntext" => self.state.destination = Destination::ListText,
+            "par" | "sect" | "page" | "row" => self.paragraph(),
+            "line" => self.node(Node::HardBreak),
+            "tab" | "cell" => self.text(" "),
+            "trowd" => self.report("a table, as paragraphs"),
+            "u" => {
+                let code = param.unwrap_or_default();
+                let code = if code < 0 { code + 0x10000 } else { code };
+                if let Some(c) = char::from_u32(code as u32) {
+                    self.text(&c.to_string());
+                }
+                self.state.skip = self.state.fallback;
+            }
+            "uc" => self.state.fallback = param.unwrap_or(1).max(0) as usize,
+            "emdash" => self.text("—"),
+            "endash" => self.text("–"),
+            "lquote" => self.text("‘"),
+            "rquote" => self.text("’"),
+            "ldblquote" => self.text("“"),
+            "rdblquote" => self.text("”"),
+            "bullet" => self.text("•"),
+            "emspace" | "enspace" | "qmspace" => self.text(" "),
+            "footnote" => {
+                self.footnotes.push(Footnote {
+                    depth: self.stack.len(),
+                    part: std::mem::take(&mut self.part),
+                    pieces: std::mem::take(&mut self.pieces),
+                });
+                self.state.format = Format::default();
+            }
+            "field" => self.fields.push(Field {
+                depth: self.stack.len(),
+                instruction: String::new(),
+                start: self.pieces.len(),
+            }),
+            "fldinst" => self.state.destination = Destination::Instruction,
+            "fldrslt" => {
+                self.state.destination = Destination::Text;
+                if let Some(field) = self.fields.last_mut() {
+                    field.start = self.pieces.len();
+                }
+            }
+            // the number of a footnote is its reference.
+            "chftn" => {}
+            _ if group_start && self.ignorable => self.state.destination = Destination::Skip,
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        self.group_start = false;
+        let mut text = text;
+        while self.state.skip > 0 && !text.is_empty() {
+            let len = text.chars().next().map_or(0, char::len_utf8);
+            text = &text[len..];
+            self.state.skip -= 1;
+        }
+        if text.is_empty() {
+            return;
+        }
+        match self.state.destination {
+            Destination::Text => {
+                let format = self.state.format;
+                match self.pieces.last_mut() {
+                    Some(Piece::Text(last, content)) if *last == format => content.push_str(text),
+                    _ => self.pieces.push(Piece::Text(format, text.to_string())),
+                }
+            }
+            Destination::FontTable => {
+                if let Some((number, name)) = &mut self.font {
+                    name.push_str(text);
+                    if let Some(name) = name.strip_suffix(';') {
+                        let name = name.trim().to_lowercase();
+                        let monospace = MONOSPACE_FONTS.iter().any(|font| name.starts_with(font));
+                        let entry = self.fonts.entry(*number).or_default();
+                        *entry = *entry || monospace;
+                        self.font = None;
+                    }
+                }
+            }
+            Destination::Instruction => {
+                if let Some(field) = self.fields.last_mut() {
+                    field.instruction.push_str(text);
+                }
+            }
+            Destination::ListText => self.marker.push_str(text),
+            Destination::Skip => {}
+        }
+    }
+
+    fn node(&mut self, node: Node) {
+        if self.state.destination == Destination::Text {
+            self.pieces.push(Piece::Node(node));
+        }
+    }
+
+    /// Skips the group, reporting what it contains.
+    fn skip(&mut self, what: &str) {
+        self.state.destination = Destination::Skip;
+        self.report(what);
+    }
+
+    fn report(&mut self, what: &str) {
+        if !self.skipped.iter().any(|skipped| skipped == what) {
+            self.skipped.push(what.to_string());
+        }
+    }
+
+    /// Ends the group, and the field or footnote it contains.
+    fn close(&mut self) {
+        let depth = self.stack.len();
+        if self.fields.last().is_some_and(|field| field.depth == depth) {
+            if let Some(field) = self.fields.pop() {
+                self.end_field(field);
+            }
+        }
+        if self
+            .footnotes
+            .last()
+            .is_some_and(|note| note.depth == depth)
+        {
+            if let Some(note) = self.footnotes.pop() {
+                self.paragraph();
+                let content = std::mem::replace(&mut self.part, note.part).blocks;
+                self.pieces = note.pieces;
+                self.part.notes.push(content);
+                self.pieces.push(Piece::Node(Node::FootnoteRef(NoteRef {
+                    number: self.part.notes.len(),
+                    target: String::new(),
+                    id: None,
+                })));
+            }
+        }
+        if let Some(state) = self.stack.pop() {
+            self.state = state;
+        }
+        self.group_start = false;
+    }
+
+    fn end_field(&mut self, field: Field) {
+        let result = self.pieces.split_off(field.start.min(self.pieces.len()));
+        let instruction = field.instruction.trim();
+        let href = instruction
+            .strip_prefix("HYPERLINK")
+            .and_then(|rest| rest.split('"').nth(1))
+            .map(str::to_string);
+        match href {
+            // the comments of Scrivener are links around the commented text.
+            Some(href) if href.starts_with("scrivcmt:") => {
+                self.report("a comment");
+                self.pieces.extend(result);
+            }
+            Some(href) => self.pieces.push(Piece::Node(Node::Element(Element {
+                kind: Kind::Link {
+                    href: Some(href),
+                    title: String::new(),
+                },
+                children: docx::group(result),
+            }))),
+            None => self.pieces.extend(result),
+        }
+    }
+
+    /// Ends the paragraph, as a list item when it has a marker.
+    fn paragraph(&mut self) {
+        let mut nodes = docx::group(std::mem::take(&mut self.pieces));
+        docx::trim_start(&mut nodes);
+        let marker = std::mem::take(&mut self.marker);
+        let text: String = nodes.iter().map(Node::text).collect();
+        let blocks = &mut self.part.blocks;
+        if text.trim().is_empty()
+            && !nodes
+                .iter()
+                .any(|node| matches!(node, Node::FootnoteRef(_)))
+        {
+            // empty paragraphs only add space.
+        } else if let Some(level) = self.list.filter(|_| !marker.trim().is_empty()) {
+            let digits: String = marker
+                .trim()
+                .chars()
+                .take_while(char::is_ascii_digit)
+                .collect();
+            docx::push_item(blocks, level, digits.parse().ok(), nodes);
+        } else if matches!(text.trim(), "* * *" | "***" | "#" | "⁂") {
+            blocks.push(Node::Rule);
+        } else {
+            blocks.push(docx::paragraph_node(nodes));
+        }
+    }
+}
+
+/// The character of a byte in the Windows-1252 code page.
+fn windows_1252(byte: u8) -> char {
+    const HIGH: [char; 32] = [
+        '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž',
+        '\u{8f}', '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}',
+        'ž', 'Ÿ',
+    ];
+    match byte {
+        0x80..=0x9f => HIGH[(byte - 0x80) as usize],
+        byte => byte as char,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn markdown(rtf: &str) -> Converted {
+        to_markdown(rtf.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn reads_formatting() {
+        let converted = markdown(
+            "{\\rtf1\\ansi\\ansicpg1252{\\fonttbl\\f0\\fswiss Helvetica;\\f1\\fmodern Courier;}\n\
+            {\\colortbl;\\red255\\green255\\blue255;}\n\
+            \\pard\\f0 It was \\b dark\\b0  and \\i stormy\\i0 , caf\\'e9 \\u8212\\'97 see \\f1 code\\f0 .\\par\n\
+            \\par\n\
+            {\\field{\\*\\fldinst{HYPERLINK \"https://example.com\"}}{\\fldrslt the site}}\\line next\\par\n\
+            #\\par\n\
+            {\\*\\unknown skipped}\\strike gone\\strike0\\par}",
+        );
+        assert_eq!(
+            converted.text,
+            "It was **dark** and *stormy*, café — see `code`.\n\n\
+            [the site](https://example.com)\\\nnext\n\n---\n\n~~gone~~\n"
+        );
+        assert!(converted.skipped.is_empty());
+        assert_eq!(to_markdown(b"plain"), Err(Error::NotRtf));
+    }
+
+    #[test]
+    fn reads_footnotes_and_lists() {
+        let converted = markdown(
+            "{\\rtf1\\ansi\n\
+            Said{\\super\\chftn{\\footnote\\pard\\plain {\\super\\chftn} A \\i note\\i0 .}}.\\par\n\
+            \\pard\\ls1\\ilvl0{\\listtext\t1.\t}First\\par\n\
+            \\pard\\ls1\\ilvl0{\\listtext\t2.\t}Second\\par\n\
+            {\\pict\\pngblip 89504e47}\\pard Done.\\par}",
+        );
+        assert_eq!(
+            converted.text,
+            "Said[^1].\n\n1. First\n2. Second\n\nDone.\n\n[^1]: A *note*.\n"
+        );
+        assert_eq!(converted.skipped, ["an image"]);
+    }
+}
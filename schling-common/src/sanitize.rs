@@ -0,0 +1,467 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::html::{self, Token};
+
+/// Elements whose content is removed together with the element.
+const STRIP_CONTENT: &[&str] = &[
+    "script",
+    "style",
+    "template",
+    "noscript",
+    "iframe",
+    "object",
+    "embed",
+    "textarea",
+    "title",
+    "xmp",
+    "noembed",
+    "noframes",
+    "plaintext",
+    "svg",
+    "math",
+];
+
+/// Attributes holding a URL, checked against the allowed schemes.
+const URL_ATTRIBUTES: &[&str] = &["href", "src", "cite"];
+
+/// The side the `style` attribute aligns the text to, the only style allowed as it is the
+/// alignment of table columns.
+pub fn text_align(style: &str) -> Option<&'static str> {
+    let (property, value) = style.trim().trim_end_matches(';').split_once(':')?;
+    if !property.trim().eq_ignore_ascii_case("text-align") {
+        return None;
+    }
+    ["left", "center", "right"]
+        .into_iter()
+        .find(|side| value.trim().eq_ignore_ascii_case(side))
+}
+
+/// The elements, attributes and URL schemes allowed in HTML content.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Policy {
+    tags: HashSet<String>,
+    /// Attributes allowed on every allowed element.
+    attributes: HashSet<String>,
+    tag_attributes: HashMap<String, HashSet<String>>,
+    schemes: HashSet<String>,
+}
+
+fn set(names: &[&str]) -> HashSet<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+impl Default for Policy {
+    /// Allows the elements of formatted documents, with links and images.
+    fn default() -> Self {
+        Self::strict()
+            .allow_tags(&[
+                "a",
+                "abbr",
+                "caption",
+                "col",
+                "colgroup",
+                "dd",
+                "details",
+                "div",
+                "dl",
+                "dt",
+                "figcaption",
+                "figure",
+                "h1",
+                "h2",
+                "h3",
+                "h4",
+                "h5",
+                "h6",
+                "hr",
+                "img",
+                "li",
+                "ol",
+                "pre",
+                "q",
+                "span",
+                "summary",
+                "table",
+                "tbody",
+                "td",
+                "tfoot",
+                "th",
+                "thead",
+                "time",
+                "tr",
+                "ul",
+            ])
+            .allow_attributes("*", &["class", "id", "title", "lang", "dir"])
+            .allow_attributes("a", &["href"])
+            .allow_attributes("img", &["src", "alt", "width", "height"])
+            .allow_attributes("td", &["colspan", "rowspan", "align", "style"])
+            .allow_attributes("th", &["colspan", "rowspan", "align", "scope", "style"])
+            .allow_attributes("col", &["span"])
+            .allow_attributes("ol", &["start", "reversed"])
+            .allow_attributes("time", &["datetime"])
+            .allow_attributes("q", &["cite"])
+            .allow_attributes("blockquote", &["cite"])
+            .allow_attributes("del", &["cite", "datetime"])
+            .allow_attributes("ins", &["cite", "datetime"])
+            .allow_attributes("details", &["open"])
+    }
+}
+
+impl Policy {
+    /// Allows inline formatting and paragraphs only, without any attributes.
+    pub fn strict() -> Self {
+        Self {
+            tags: set(&[
+                "b",
+                "blockquote",
+                "br",
+                "cite",
+                "code",
+                "del",
+                "dfn",
+                "em",
+                "i",
+                "ins",
+                "kbd",
+                "mark",
+                "p",
+                "s",
+                "samp",
+                "small",
+                "strong",
+                "sub",
+                "sup",
+                "u",
+            ]),
+            attributes: HashSet::new(),
+            tag_attributes: HashMap::new(),
+            schemes: set(&["http", "https", "mailto"]),
+        }
+    }
+
+    /// Removes all elements, keeping their text.
+    pub fn text_only() -> Self {
+        Self {
+            tags: HashSet::new(),
+            ..Self::strict()
+        }
+    }
+
+    pub fn allow_tags(mut self, tags: &[&str]) -> Self {
+        self.tags.extend(tags.iter().map(|tag| tag.to_string()));
+        self
+    }
+
+    pub fn deny_tags(mut self, tags: &[&str]) -> Self {
+        for tag in tags {
+            self.tags.remove(*tag);
+        }
+        self
+    }
+
+    /// Allows the attributes on the element, or on all elements with the tag `*`.
+    ///
+    /// Event handlers are never allowed.
+    pub fn allow_attributes(mut self, tag: &str, attributes: &[&str]) -> Self {
+        let attributes = attributes.iter().filter(|attr| !attr.starts_with("on"));
+        let allowed = match tag {
+            "*" => &mut self.attributes,
+            tag => self.tag_attributes.entry(tag.to_string()).or_default(),
+        };
+        allowed.extend(attributes.map(|attr| attr.to_string()));
+        self
+    }
+
+    pub fn allow_schemes(mut self, schemes: &[&str]) -> Self {
+        self.schemes
+            .extend(schemes.iter().map(|scheme| scheme.to_ascii_lowercase()));
+        self
+    }
+
+    pub fn allows_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+
+    /// Whether the content of the element is dropped with the element.
+    pub fn strips_content(&self, tag: &str) -> bool {
+        STRIP_CONTENT.contains(&tag) && !self.allows_tag(tag)
+    }
+
+    /// Whether the URL is relative or uses an allowed scheme.
+    pub fn allows_url(&self, url: &str) -> bool {
+        // browsers ignore control characters and whitespace in the scheme.
+        let url: String = url
+            .chars()
+            .filter(|c| !c.is_control() && !c.is_whitespace())
+            .collect();
+        match url.find([':', '/', '?', '#']) {
+            Some(pos) if url[pos..].starts_with(':') => {
+                self.schemes.contains(&url[..pos].to_ascii_lowercase())
+            }
+            _ => true,
+        }
+    }
+
+    fn allows_attribute(&self, tag: &str, attr: &str, value: &str) -> bool {
+        let allowed = self.attributes.contains(attr)
+            || self
+                .tag_attributes
+                .get(tag)
+                .is_some_and(|attrs| attrs.contains(attr));
+        allowed
+            && (!URL_ATTRIBUTES.contains(&attr) || self.allows_url(value))
+            && (attr != "style" || text_align(value).is_some())
+    }
+
+    /// The allowed attributes of the element.
+    pub fn filter_attributes(
+        &self,
+        tag: &str,
+        attrs: Vec<(String, String)>,
+    ) -> Vec<(String, String)> {
+        attrs
+            .into_iter()
+            .filter(|(attr, value)| self.allows_attribute(tag, attr, value))
+            .collect()
+    }
+
+    /// Removes the elements and attributes not allowed from the HTML fragment.
+    ///
+    /// The result is serialized again from the tokens of the fragment, so that browsers parse
+    /// the same elements as the sanitizer.
+    pub fn sanitize(&self, src: &str) -> String {
+        let mut out = String::with_capacity(src.len());
+        let mut filter = Filter::default();
+        for token in html::tokenize(src) {
+            match filter.apply(self, token) {
+                Some(Token::Start {
+                    name,
+                    attrs,
+                    self_closing: _,
+                }) => {
+                    out.push('<');
+                    out.push_str(&name);
+                    for (attr, value) in attrs {
+                        out.push_str(&format!(" {}=\"{}\"", attr, html::escape(&value)));
+                    }
+                    out.push('>');
+                }
+                Some(Token::End(name)) => out.push_str(&format!("</{}>", name)),
+                Some(Token::Text(text)) => out.push_str(&html::escape(&text)),
+                Some(Token::Comment(_)) | None => {}
+            }
+        }
+        out
+    }
+}
+
+/// Applies a policy to a stream of tokens, which may span several fragments.
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    /// The element whose content is stripped, and the depth of its nested elements.
+    strip: Option<(String, usize)>,
+}
+
+impl Filter {
+    /// Whether the content of a removed element is being stripped.
+    pub fn is_stripping(&self) -> bool {
+        self.strip.is_some()
+    }
+
+    /// The token if it is allowed, with its attributes filtered.
+    pub fn apply<'a>(&mut self, policy: &Policy, token: Token<'a>) -> Option<Token<'a>> {
+        if let Some((tag, depth)) = &mut self.strip {
+            match &token {
+                Token::Start { name, .. } if name == tag => *depth += 1,
+                Token::End(name) if name == tag && *depth == 0 => self.strip = None,
+                Token::End(name) if name == tag => *depth -= 1,
+                _ => {}
+            }
+            return None;
+        }
+        match token {
+            Token::Start {
+                name,
+                attrs,
+                self_closing,
+            } => {
+                if policy.strips_content(&name) {
+                    if !self_closing && !html::is_void(&name) {
+                        self.strip = Some((name, 0));
+                    }
+                    None
+                } else if policy.allows_tag(&name) {
+                    let attrs = policy.filter_attributes(&name, attrs);
+                    Some(Token::Start {
+                        name,
+                        attrs,
+                        self_closing,
+                    })
+                } else {
+                    None
+                }
+            }
+            Token::End(name) if policy.allows_tag(&name) => Some(Token::End(name)),
+            Token::End(_) | Token::Comment(_) => None,
+            text => Some(text),
+        }
+    }
+}
+
+/// Sanitizes the HTML fragment with the default policy.
+pub fn sanitize(src: &str) -> String {
+    Policy::default().sanitize(src)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Known XSS payloads, mostly from the OWASP filter evasion cheat sheet.
+    const PAYLOADS: &[&str] = &[
+        "<script>alert(1)</script>",
+        "<SCRIPT SRC=http://xss.example/xss.js></SCRIPT>",
+        "<script>document.write('<p>')</script>",
+        "<scr<script>ipt>alert(1)</script>",
+        "<img src=x onerror=alert(1)>",
+        "<IMG SRC=\"javascript:alert('XSS');\">",
+        "<IMG SRC=JaVaScRiPt:alert('XSS')>",
+        "<IMG SRC=&#106;&#97;&#118;&#97;&#115;&#99;&#114;&#105;&#112;&#116;&#58;alert(1)>",
+        "<IMG SRC=&#x6A&#x61&#x76&#x61&#x73&#x63&#x72&#x69&#x70&#x74&#x3A;alert(1)>",
+        "<IMG SRC=\"jav\tascript:alert('XSS');\">",
+        "<IMG SRC=\"jav&#x0A;ascript:alert('XSS');\">",
+        "<IMG SRC=\" &#14;  javascript:alert('XSS');\">",
+        "<a href=\"javascript:alert(1)\">x</a>",
+        "<a href=\"vbscript:msgbox(1)\">x</a>",
+        "<a href=\"data:text/html;base64,PHNjcmlwdD5hbGVydCgxKTwvc2NyaXB0Pg==\">x</a>",
+        "<a href=\"  JAVASCRIPT:alert(1)\">x</a>",
+        "<a href=\"java&#09;script:alert(1)\">x</a>",
+        "<a href=x onclick=alert(1)>x</a>",
+        "<div onmouseover=\"alert(1)\">x</div>",
+        "<body onload=alert(1)>",
+        "<svg/onload=alert(1)>",
+        "<svg><script>alert(1)</script></svg>",
+        "<math><mi xlink:href=\"javascript:alert(1)\">x</mi></math>",
+        "<iframe src=\"javascript:alert(1)\"></iframe>",
+        "<iframe srcdoc=\"<script>alert(1)</script>\"></iframe>",
+        "<object data=\"javascript:alert(1)\"></object>",
+        "<embed src=\"javascript:alert(1)\">",
+        "<style>@import 'http://xss.example/xss.css';</style>",
+        "<div style=\"background:url(javascript:alert(1))\">x</div>",
+        "<span style=\"width: expression(alert(1))\">x</span>",
+        "<link rel=stylesheet href=\"http://xss.example/xss.css\">",
+        "<meta http-equiv=\"refresh\" content=\"0;url=javascript:alert(1)\">",
+        "<base href=\"javascript:alert(1)//\">",
+        "<form action=\"javascript:alert(1)\"><input type=submit></form>",
+        "<button formaction=\"javascript:alert(1)\">x</button>",
+        "<input onfocus=alert(1) autofocus>",
+        "<details open ontoggle=alert(1)>",
+        "<video><source onerror=\"alert(1)\"></video>",
+        "<audio src=x onerror=alert(1)>",
+        "<marquee onstart=alert(1)>",
+        "<isindex type=image src=1 onerror=alert(1)>",
+        "<table background=\"javascript:alert(1)\">",
+        "<!--<img src=\"--><img src=x onerror=alert(1)//\">",
+        "<![CDATA[<script>alert(1)</script>]]>",
+        "<img \"\"\"><script>alert(1)</script>\">",
+        "<img src=`x` onerror=alert(1)>",
+        "<a href=\"#\" onclick = \"alert(1)\">x</a>",
+        "<img/src=x/onerror=alert(1)>",
+        "<textarea><script>alert(1)</script></textarea>",
+        "<title><script>alert(1)</script></title>",
+        "<noscript><p title=\"</noscript><img src=x onerror=alert(1)>\"></noscript>",
+        "<template><script>alert(1)</script></template>",
+        "&lt;script&gt;alert(1)&lt;/script&gt;",
+        "<p>unterminated <img src=x onerror=alert(1)",
+    ];
+
+    /// Whether the output contains an element or attribute which could run scripts.
+    fn assert_harmless(payload: &str, output: &str) {
+        let policy = Policy::default();
+        for token in html::tokenize(output) {
+            match token {
+                Token::Start { name, attrs, .. } => {
+                    assert!(policy.allows_tag(&name), "{} in {}", name, output);
+                    for (attr, value) in attrs {
+                        assert!(!attr.starts_with("on"), "{} in {}", attr, output);
+                        assert!(
+                            (attr != "style" || text_align(&value).is_some()) && attr != "srcdoc",
+                            "{} in {}",
+                            attr,
+                            output
+                        );
+                        if URL_ATTRIBUTES.contains(&attr.as_str()) {
+                            assert!(policy.allows_url(&value), "{} in {}", value, output);
+                        }
+                    }
+                }
+                Token::Comment(comment) => panic!("{} from {}", comment, payload),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn xss_corpus() {
+        for payload in PAYLOADS {
+            let output = sanitize(payload);
+            assert_harmless(payload, &output);
+            // sanitizing again does not change the result.
+            assert_eq!(sanitize(&output), output, "payload: {}", payload);
+        }
+    }
+
+    #[test]
+    fn keeps_alignments_only() {
+        assert_eq!(
+            sanitize("<table><tr><td style=\"Text-Align:right;\">1</td></tr></table>"),
+            "<table><tr><td style=\"Text-Align:right;\">1</td></tr></table>"
+        );
+        assert_eq!(
+            sanitize("<td style=\"text-align: left; color: red\">1</td><p style=\"text-align: left\">2</p>"),
+            "<td>1</td><p>2</p>"
+        );
+    }
+
+    #[test]
+    fn strips_scripts() {
+        assert_eq!(sanitize("a<script>alert(1)</script>b"), "ab");
+        assert_eq!(sanitize("<img src=x onerror=alert(1)>"), "<img src=\"x\">");
+        assert_eq!(
+            sanitize("<a href=\"JaVaScRiPt:alert(1)\">x</a>"),
+            "<a>x</a>"
+        );
+        assert_eq!(
+            sanitize("&lt;script&gt; &amp; <b>bold</b>"),
+            "&lt;script&gt; &amp; <b>bold</b>"
+        );
+    }
+
+    #[test]
+    fn keeps_documents() {
+        let src = "<h2 id=\"a\">Title</h2><p>Some <em>text</em> and \
+            <a href=\"https://example.org/?q=1#x\">a link</a>.</p><img src=\"cat.png\" alt=\"Cat\">";
+        assert_eq!(sanitize(src), src);
+        assert_eq!(
+            sanitize("<a href=\"../other#s\">x</a>"),
+            "<a href=\"../other#s\">x</a>"
+        );
+    }
+
+    #[test]
+    fn configurable() {
+        let src = "<h1>Title</h1><p><a href=\"ftp://example.org\">x</a></p>";
+        assert_eq!(Policy::strict().sanitize(src), "Title<p>x</p>");
+        assert_eq!(Policy::text_only().sanitize(src), "Titlex");
+        assert_eq!(
+            Policy::default().deny_tags(&["h1"]).sanitize(src),
+            "Title<p><a>x</a></p>"
+        );
+        assert_eq!(Policy::default().allow_schemes(&["ftp"]).sanitize(src), src);
+        let policy = Policy::strict().allow_attributes("p", &["class", "onclick"]);
+        assert_eq!(
+            policy.sanitize("<p class=\"x\" onclick=\"y\">z</p>"),
+            "<p class=\"x\">z</p>"
+        );
+    }
+}
@@ -0,0 +1,514 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+
+use roxmltree::{Document, Node as Xml};
+use zip::ZipArchive;
+
+use crate::data::{Doc, DocHead, Sec, SecHead};
+use crate::id;
+use crate::markup::Markup;
+use crate::rtf;
+
+/// The reason a Scrivener project cannot be read.
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Zip(zip::result::ZipError),
+    Xml(roxmltree::Error),
+    /// The project lacks its binder, the `.scrivx` file.
+    Missing,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "cannot read the project: {}", err),
+            Error::Zip(err) => write!(f, "cannot read the project: {}", err),
+            Error::Xml(err) => write!(f, "malformed binder: {}", err),
+            Error::Missing => write!(f, "the project has no .scrivx binder"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(err: zip::result::ZipError) -> Self {
+        Error::Zip(err)
+    }
+}
+
+impl From<roxmltree::Error> for Error {
+    fn from(err: roxmltree::Error) -> Self {
+        Error::Xml(err)
+    }
+}
+
+/// The documents read from a Scrivener project, and what could not be converted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Import {
+    /// The title of the project, the name of its binder.
+    pub title: String,
+    pub chapters: Vec<(Doc, Vec<Sec>)>,
+    pub report: Vec<String>,
+}
+
+/// Reads a `.scriv` project folder.
+///
+/// The documents get consecutive ids from the first one.
+pub fn import_dir(folder: &Path, first: id::Doc) -> Result<Import, Error> {
+    let mut binder = None;
+    for entry in std::fs::read_dir(folder)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "scrivx") {
+            binder = Some(path);
+            break;
+        }
+    }
+    let binder = binder.ok_or(Error::Missing)?;
+    let title = binder
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let xml = std::fs::read_to_string(&binder)?;
+    let files = Files::Dir(folder.to_path_buf());
+    Importer::new(files, first).import(title, &xml)
+}
+
+/// Reads a zip archive of a `.scriv` project folder, as uploaded.
+pub fn import_zip(data: &[u8], first: id::Doc) -> Result<Import, Error> {
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+    let binder = archive
+        .file_names()
+        .filter(|name| name.ends_with(".scrivx") && !name.starts_with("__MACOSX/"))
+        .min_by_key(|name| name.len())
+        .map(str::to_string)
+        .ok_or(Error::Missing)?;
+    let (prefix, file) = match binder.rsplit_once('/') {
+        Some((folder, file)) => (format!("{}/", folder), file),
+        None => (String::new(), binder.as_str()),
+    };
+    let title = file.trim_end_matches(".scrivx").to_string();
+    let mut xml = String::new();
+    archive.by_name(&binder)?.read_to_string(&mut xml)?;
+    let files = Files::Zip(archive, prefix);
+    Importer::new(files, first).import(title, &xml)
+}
+
+/// The files of a project, by their path in the project folder.
+enum Files<'a> {
+    Dir(PathBuf),
+    Zip(ZipArchive<Cursor<&'a [u8]>>, String),
+}
+
+impl Files<'_> {
+    fn read(&mut self, path: &str) -> Result<Option<Vec<u8>>, Error> {
+        let mut data = vec![];
+        match self {
+            Files::Dir(folder) => match std::fs::read(folder.join(path)) {
+                Ok(content) => data = content,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                Err(err) => return Err(err.into()),
+            },
+            Files::Zip(archive, prefix) => match archive.by_name(&format!("{}{}", prefix, path)) {
+                Ok(mut file) => {
+                    file.read_to_end(&mut data)?;
+                }
+                Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+                Err(err) => return Err(err.into()),
+            },
+        }
+        Ok(Some(data))
+    }
+}
+
+/// The child elements of the node with the name.
+fn elements<'a, 'i>(node: Xml<'a, 'i>, name: &'static str) -> impl Iterator<Item = Xml<'a, 'i>> {
+    node.children()
+        .filter(move |child| child.is_element() && child.has_tag_name(name))
+}
+
+fn element<'a, 'i>(node: Xml<'a, 'i>, name: &'static str) -> Option<Xml<'a, 'i>> {
+    elements(node, name).next()
+}
+
+/// The items of the binder below the item.
+fn children<'a, 'i>(item: Xml<'a, 'i>) -> impl Iterator<Item = Xml<'a, 'i>> {
+    element(item, "Children")
+        .into_iter()
+        .flat_map(|children| elements(children, "BinderItem"))
+}
+
+fn kind<'a>(item: Xml<'a, '_>) -> &'a str {
+    item.attribute("Type").unwrap_or_default()
+}
+
+fn title(item: Xml) -> String {
+    element(item, "Title")
+        .and_then(|title| title.text())
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// The names of the labels or the statuses of the settings, by id.
+fn names(settings: Option<Xml>, list: &'static str, name: &'static str) -> HashMap<String, String> {
+    settings
+        .and_then(|settings| element(settings, list))
+        .into_iter()
+        .flat_map(|list| elements(list, name))
+        .filter_map(|item| {
+            let id = item.attribute("ID")?;
+            // the negative ids are for no label or status.
+            let text = item.text()?.trim();
+            (!id.starts_with('-') && !text.is_empty()).then(|| (id.to_string(), text.to_string()))
+        })
+        .collect()
+}
+
+/// Reads the binder of a project into documents of sections.
+struct Importer<'a> {
+    files: Files<'a>,
+    first: id::Doc,
+    labels: HashMap<String, String>,
+    statuses: HashMap<String, String>,
+    chapters: Vec<(Doc, Vec<Sec>)>,
+    report: Vec<String>,
+}
+
+impl<'a> Importer<'a> {
+    fn new(files: Files<'a>, first: id::Doc) -> Self {
+        Self {
+            files,
+            first,
+            labels: HashMap::new(),
+            statuses: HashMap::new(),
+            chapters: vec![],
+            report: vec![],
+        }
+    }
+
+    /// Maps the folders of the binder to documents, and the text items in them to sections.
+    ///
+    /// The text items at the top of the draft and research folders make a document of their
+    /// own, titled by the folder. The trash is left out.
+    fn import(mut self, title: String, xml: &str) -> Result<Import, Error> {
+        let project = Document::parse(xml)?;
+        let root = project.root_element();
+        self.labels = names(element(root, "LabelSettings"), "Labels", "Label");
+        self.statuses = names(element(root, "StatusSettings"), "StatusItems", "Status");
+        let binder = element(root, "Binder").ok_or(Error::Missing)?;
+        for item in elements(binder, "BinderItem") {
+            match kind(item) {
+                "TrashFolder" => {}
+                "DraftFolder" | "ResearchFolder" => {
+                    let mut loose = None;
+                    for child in children(item) {
+                        match kind(child) {
+                            "Folder" => self.document(child)?,
+                            "Text" => {
+                                let chapter = match loose {
+                                    Some(chapter) => chapter,
+                                    None => self.new_document(item)?,
+                                };
+                                loose = Some(chapter);
+                                self.section(child, chapter)?;
+                                self.contents(child, chapter)?;
+                            }
+                            _ => self.skip(child),
+                        }
+                    }
+                }
+                "Folder" | "Text" => self.document(item)?,
+                _ => self.skip(item),
+            }
+        }
+        Ok(Import {
+            title,
+            chapters: self.chapters,
+            report: self.report,
+        })
+    }
+
+    /// Adds the document of the folder, starting with its own text.
+    fn document(&mut self, folder: Xml) -> Result<(), Error> {
+        let chapter = self.new_document(folder)?;
+        if let Some(text) = self.text(folder)? {
+            let sec = self.new_section(chapter, String::new(), text);
+            self.push(chapter, sec);
+        }
+        self.contents(folder, chapter)
+    }
+
+    /// Adds the items below the item to the document, flattening the folders.
+    fn contents(&mut self, item: Xml, chapter: usize) -> Result<(), Error> {
+        for child in children(item) {
+            match kind(child) {
+                "Text" => {}
+                "Folder" => self.report.push(format!(
+                    "flattened the folder “{}” into the sections of “{}”",
+                    title(child),
+                    self.chapters[chapter].0.head.title
+                )),
+                _ => {
+                    self.skip(child);
+                    continue;
+                }
+            }
+            self.section(child, chapter)?;
+            self.contents(child, chapter)?;
+        }
+        Ok(())
+    }
+
+    fn new_document(&mut self, item: Xml) -> Result<usize, Error> {
+        let index = self.chapters.len();
+        let id = id::Doc::new(self.first.value + index as u32, self.first.proj);
+        let mut head = DocHead::new(id, index as u32, title(item));
+        self.metadata(item, &mut head.synopsis, &mut head.label, &mut head.status)?;
+        self.chapters.push((head.body(vec![]), vec![]));
+        Ok(index)
+    }
+
+    /// Adds the section of the text item to the document.
+    fn section(&mut self, item: Xml, chapter: usize) -> Result<(), Error> {
+        let text = self.text(item)?.unwrap_or_default();
+        let mut sec = self.new_section(chapter, title(item), text);
+        let head = &mut sec.head;
+        self.metadata(item, &mut head.synopsis, &mut head.label, &mut head.status)?;
+        self.push(chapter, sec);
+        Ok(())
+    }
+
+    fn new_section(&self, chapter: usize, title: String, text: String) -> Sec {
+        let (doc, sections) = &self.chapters[chapter];
+        let order = sections.len() as u32;
+        let head = SecHead::new(id::Sec::new(order, doc.head.id), order, title);
+        head.body(Markup::md_str(&text))
+    }
+
+    fn push(&mut self, chapter: usize, sec: Sec) {
+        let (doc, sections) = &mut self.chapters[chapter];
+        doc.content.push(sec.head.clone());
+        sections.push(sec);
+    }
+
+    /// The Markdown text of the item, when it has any.
+    fn text(&mut self, item: Xml) -> Result<Option<String>, Error> {
+        let Some(data) = self.file(item, "content.rtf", ".rtf")? else {
+            return Ok(None);
+        };
+        if self.file(item, "notes.rtf", "_notes.rtf")?.is_some() {
+            self.report
+                .push(format!("skipped the notes of “{}”", title(item)));
+        }
+        match rtf::to_markdown(&data) {
+            Ok(converted) => {
+                for what in converted.skipped {
+                    self.report
+                        .push(format!("skipped {} in “{}”", what, title(item)));
+                }
+                Ok(Some(converted.text).filter(|text| !text.trim().is_empty()))
+            }
+            Err(err) => {
+                self.report.push(format!(
+                    "cannot read the text of “{}”: {}",
+                    title(item),
+                    err
+                ));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Reads the synopsis, the label and the status of the item.
+    fn metadata(
+        &mut self,
+        item: Xml,
+        synopsis: &mut String,
+        label: &mut Option<String>,
+        status: &mut Option<String>,
+    ) -> Result<(), Error> {
+        let text = match self.file(item, "synopsis.txt", "_synopsis.txt")? {
+            Some(data) => String::from_utf8_lossy(&data).into_owned(),
+            None => element(item, "Synopsis")
+                .and_then(|el| el.text())
+                .unwrap_or_default()
+                .to_string(),
+        };
+        *synopsis = text.trim().to_string();
+        let metadata = element(item, "MetaData");
+        let id = |name| {
+            metadata
+                .and_then(|metadata| element(metadata, name))
+                .and_then(|el| el.text())
+                .map(str::trim)
+        };
+        *label = id("LabelID").and_then(|id| self.labels.get(id)).cloned();
+        *status = id("StatusID").and_then(|id| self.statuses.get(id)).cloned();
+        Ok(())
+    }
+
+    /// Reads a file of the item: in its data folder since Scrivener 3, or else next to the
+    /// files of the other items, named by its numeric id and the suffix.
+    fn file(&mut self, item: Xml, name: &str, suffix: &str) -> Result<Option<Vec<u8>>, Error> {
+        let path = match (item.attribute("UUID"), item.attribute("ID")) {
+            (Some(uuid), _) => format!("Files/Data/{}/{}", uuid, name),
+            (None, Some(id)) => format!("Files/Docs/{}{}", id, suffix),
+            (None, None) => return Ok(None),
+        };
+        self.files.read(&path)
+    }
+
+    fn skip(&mut self, item: Xml) {
+        let what = match kind(item) {
+            "PDF" => "PDF file",
+            "Image" => "image",
+            "WebArchive" => "web page",
+            _ => "file",
+        };
+        self.report
+            .push(format!("skipped the {} “{}”", what, title(item)));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    use super::*;
+
+    const BINDER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ScrivenerProject Version="2.0">
+  <Binder>
+    <BinderItem UUID="D" Type="DraftFolder">
+      <Title>Manuscript</Title>
+      <Children>
+        <BinderItem UUID="C1" Type="Folder">
+          <Title>Chapter One</Title>
+          <MetaData><LabelID>0</LabelID><StatusID>1</StatusID></MetaData>
+          <Children>
+            <BinderItem UUID="S1" Type="Text">
+              <Title>Arrival</Title>
+              <MetaData><LabelID>-1</LabelID><StatusID>0</StatusID></MetaData>
+            </BinderItem>
+            <BinderItem UUID="F" Type="Folder">
+              <Title>Flashback</Title>
+              <Children>
+                <BinderItem UUID="S2" Type="Text"><Title>Years Ago</Title></BinderItem>
+              </Children>
+            </BinderItem>
+            <BinderItem UUID="P" Type="PDF"><Title>Map</Title></BinderItem>
+          </Children>
+        </BinderItem>
+        <BinderItem UUID="S3" Type="Text"><Title>Epilogue</Title></BinderItem>
+      </Children>
+    </BinderItem>
+    <BinderItem UUID="T" Type="TrashFolder">
+      <Title>Trash</Title>
+      <Children><BinderItem UUID="X" Type="Text"><Title>Cut</Title></BinderItem></Children>
+    </BinderItem>
+  </Binder>
+  <LabelSettings>
+    <Labels><Label ID="-1">No Label</Label><Label ID="0">Mira</Label></Labels>
+  </LabelSettings>
+  <StatusSettings>
+    <StatusItems><Status ID="-1">No Status</Status><Status ID="0">To Do</Status><Status ID="1">First Draft</Status></StatusItems>
+  </StatusSettings>
+</ScrivenerProject>"#;
+
+    fn project() -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        let mut add = |name: &str, content: &str| {
+            zip.start_file(
+                format!("Novel.scriv/{}", name),
+                SimpleFileOptions::default(),
+            )
+            .unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        };
+        add("Novel.scrivx", BINDER);
+        add(
+            "Files/Data/S1/content.rtf",
+            "{\\rtf1 She \\i arrived\\i0 .\\par}",
+        );
+        add("Files/Data/S1/synopsis.txt", "Mira comes to town.\n");
+        add("Files/Data/S1/notes.rtf", "{\\rtf1 Research the trains.}");
+        add(
+            "Files/Data/S2/content.rtf",
+            "{\\rtf1 Long ago.{\\pict\\pngblip 00}\\par}",
+        );
+        add("Files/Data/S3/content.rtf", "{\\rtf1 The end.}");
+        add("Files/Data/X/content.rtf", "{\\rtf1 Cut.}");
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn maps_folders_to_documents() {
+        let first = id::Doc::new(3, id::Proj::new(1));
+        let import = import_zip(&project(), first).unwrap();
+        assert_eq!(import.title, "Novel");
+        let titles: Vec<(&str, Vec<&str>)> = import
+            .chapters
+            .iter()
+            .map(|(doc, sections)| {
+                let sections = sections.iter().map(|sec| sec.head.title.as_str()).collect();
+                (doc.head.title.as_str(), sections)
+            })
+            .collect();
+        assert_eq!(
+            titles,
+            [
+                ("Chapter One", vec!["Arrival", "Flashback", "Years Ago"]),
+                ("Manuscript", vec!["Epilogue"])
+            ]
+        );
+        let (doc, sections) = &import.chapters[0];
+        assert_eq!(doc.head.id, first);
+        assert_eq!(doc.head.label.as_deref(), Some("Mira"));
+        assert_eq!(doc.head.status.as_deref(), Some("First Draft"));
+        assert_eq!(
+            doc.content,
+            sections
+                .iter()
+                .map(|sec| sec.head.clone())
+                .collect::<Vec<_>>()
+        );
+        let arrival = &sections[0];
+        assert_eq!(arrival.head.id, id::Sec::new(0, first));
+        assert_eq!(arrival.head.synopsis, "Mira comes to town.");
+        assert_eq!(arrival.head.label, None);
+        assert_eq!(arrival.head.status.as_deref(), Some("To Do"));
+        assert_eq!(arrival.content.text, "She *arrived*.\n");
+        assert_eq!(sections[2].head.order, 2);
+        let (epilogue, _) = &import.chapters[1];
+        assert_eq!(epilogue.head.id, id::Doc::new(4, first.proj));
+    }
+
+    #[test]
+    fn reports_what_is_skipped() {
+        let first = id::Doc::new(0, id::Proj::new(1));
+        let import = import_zip(&project(), first).unwrap();
+        assert_eq!(
+            import.report,
+            [
+                "skipped the notes of “Arrival”",
+                "flattened the folder “Flashback” into the sections of “Chapter One”",
+                "skipped an image in “Years Ago”",
+                "skipped the PDF file “Map”"
+            ]
+        );
+        assert!(matches!(import_zip(b"PK", first), Err(Error::Zip(_))));
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+use rust_stemmers::{Algorithm, Stemmer};
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+#[cfg(feature = "yew-wasm")]
+use yew::{html, Html};
+
+use crate::id;
+#[cfg(feature = "yew-wasm")]
+use crate::source::ToDom;
+use crate::source::{unquote, Symbol, SymbolIter};
+
+/// BM25 term frequency saturation.
+const K1: f32 = 1.2;
+/// BM25 document length normalization.
+const B: f32 = 0.75;
+/// Weight of a term occurring in the title compared to the content.
+const TITLE_BOOST: u32 = 3;
+
+/// The language used to stem the words of a text.
+#[derive(EnumIter, PartialEq, Debug, Default, Eq, Clone, Copy, Hash, Serialize, Deserialize)]
+pub enum Lang {
+    #[default]
+    English,
+    German,
+}
+
+impl Lang {
+    fn stemmer(self) -> Stemmer {
+        match self {
+            Lang::English => Stemmer::create(Algorithm::English),
+            Lang::German => Stemmer::create(Algorithm::German),
+        }
+    }
+}
+
+impl Display for Lang {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let lang = match self {
+            Lang::English => "english",
+            Lang::German => "german",
+        };
+        write!(f, "{}", lang)
+    }
+}
+
+/// A word of a text, normalized to its stem.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    /// The lowercase stem of the word.
+    pub term: String,
+    /// The byte range of the word in the source text.
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Splits the text into stemmed tokens, recursing into quotes.
+pub fn tokenize(text: &str, lang: Lang) -> Vec<Token> {
+    let stemmer = lang.stemmer();
+    let mut tokens = vec![];
+    collect_tokens(text, text, &stemmer, &mut tokens);
+    tokens
+}
+
+fn collect_tokens(source: &str, text: &str, stemmer: &Stemmer, tokens: &mut Vec<Token>) {
+    for symbol in SymbolIter::new(text) {
+        match symbol {
+            Symbol::Ident(word) => {
+                let start = word.as_ptr() as usize - source.as_ptr() as usize;
+                let term = stemmer.stem(&word.to_lowercase()).into_owned();
+                tokens.push(Token {
+                    term,
+                    start,
+                    end: start + word.len(),
+                });
+            }
+            Symbol::Quote(quote) => collect_tokens(source, unquote(quote), stemmer, tokens),
+            Symbol::Mark(mark) => collect_tokens(source, mark.as_str(), stemmer, tokens),
+            Symbol::Trivia(_) => {}
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    /// Weighted number of terms in the section.
+    len: u32,
+    terms: HashSet<String>,
+}
+
+/// Inverted index of the sections of a project.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Index {
+    pub lang: Lang,
+    entries: HashMap<id::Sec, Entry>,
+    postings: HashMap<String, HashMap<id::Sec, u32>>,
+}
+
+/// A section matching a query.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Hit {
+    pub id: id::Sec,
+    pub score: f32,
+    /// The excerpt of the section matching the query, empty unless requested.
+    #[serde(default)]
+    pub snippet: Snippet,
+}
+
+impl Index {
+    pub fn new(lang: Lang) -> Self {
+        Self {
+            lang,
+            ..Self::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds the section to the index, replacing the previous version of the section.
+    pub fn insert(&mut self, id: id::Sec, title: &str, content: &str) {
+        self.remove(id);
+
+        let mut freq: HashMap<String, u32> = HashMap::new();
+        for token in tokenize(title, self.lang) {
+            *freq.entry(token.term).or_default() += TITLE_BOOST;
+        }
+        for token in tokenize(content, self.lang) {
+            *freq.entry(token.term).or_default() += 1;
+        }
+
+        let len = freq.values().sum();
+        let terms = freq.keys().cloned().collect();
+        for (term, count) in freq {
+            self.postings.entry(term).or_default().insert(id, count);
+        }
+        self.entries.insert(id, Entry { len, terms });
+    }
+
+    /// Removes the section from the index.
+    pub fn remove(&mut self, id: id::Sec) {
+        if let Some(entry) = self.entries.remove(&id) {
+            for term in entry.terms {
+                if let Some(posting) = self.postings.get_mut(&term) {
+                    posting.remove(&id);
+                    if posting.is_empty() {
+                        self.postings.remove(&term);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Ranks the sections containing any word of the query using BM25.
+    pub fn search(&self, query: &str) -> Vec<Hit> {
+        let count = self.entries.len() as f32;
+        if count == 0.0 {
+            return vec![];
+        }
+        let avg_len = self.entries.values().map(|e| e.len as f32).sum::<f32>() / count;
+
+        let terms: HashSet<String> = tokenize(query, self.lang)
+            .into_iter()
+            .map(|t| t.term)
+            .collect();
+        let mut scores: HashMap<id::Sec, f32> = HashMap::new();
+        for term in terms {
+            let posting = match self.postings.get(&term) {
+                Some(posting) => posting,
+                None => continue,
+            };
+            let n = posting.len() as f32;
+            let idf = ((count - n + 0.5) / (n + 0.5) + 1.0).ln();
+            for (id, freq) in posting {
+                let freq = *freq as f32;
+                let len = self.entries[id].len as f32;
+                let norm = freq + K1 * (1.0 - B + B * len / avg_len);
+                *scores.entry(*id).or_default() += idf * freq * (K1 + 1.0) / norm;
+            }
+        }
+
+        let mut hits: Vec<Hit> = scores
+            .into_iter()
+            .map(|(id, score)| Hit {
+                id,
+                score,
+                snippet: Snippet::default(),
+            })
+            .collect();
+        hits.sort_by(|lhs, rhs| {
+            rhs.score
+                .partial_cmp(&lhs.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(lhs.id.value.cmp(&rhs.id.value))
+        });
+        hits
+    }
+}
+
+/// A part of a snippet, either matched by the query or surrounding context.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fragment {
+    pub text: String,
+    pub highlight: bool,
+}
+
+/// An excerpt of a text with the words matching a query highlighted.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snippet {
+    pub fragments: Vec<Fragment>,
+}
+
+impl Snippet {
+    /// Extracts the part of the `text` with the first match of the query, surrounded by at most
+    /// `context` words on either side.
+    pub fn build(text: &str, query: &str, lang: Lang, context: usize) -> Self {
+        let terms: HashSet<String> = tokenize(query, lang).into_iter().map(|t| t.term).collect();
+        let tokens = tokenize(text, lang);
+        let first = tokens
+            .iter()
+            .position(|t| terms.contains(&t.term))
+            .unwrap_or(0);
+        let from = first.saturating_sub(context);
+        let to = (first + context + 1).min(tokens.len());
+        if from >= to {
+            return Self::default();
+        }
+
+        let mut snippet = Self::default();
+        let mut pos = tokens[from].start;
+        for token in &tokens[from..to] {
+            snippet.push(&text[pos..token.start], false);
+            snippet.push(&text[token.start..token.end], terms.contains(&token.term));
+            pos = token.end;
+        }
+        snippet
+    }
+
+    /// Parses a text where highlighted parts are enclosed in the `start` and `stop` markers,
+    /// such as the output of PostgreSQL's `ts_headline`.
+    pub fn from_marked(text: &str, start: &str, stop: &str) -> Self {
+        let mut snippet = Self::default();
+        let mut rest = text;
+        while let Some(begin) = rest.find(start) {
+            snippet.push(&rest[..begin], false);
+            rest = &rest[begin + start.len()..];
+            let end = rest.find(stop).unwrap_or(rest.len());
+            snippet.push(&rest[..end], true);
+            rest = &rest[(end + stop.len()).min(rest.len())..];
+        }
+        snippet.push(rest, false);
+        snippet
+    }
+
+    fn push(&mut self, text: &str, highlight: bool) {
+        if text.is_empty() {
+            return;
+        }
+        match self.fragments.last_mut() {
+            Some(last) if last.highlight == highlight => last.text.push_str(text),
+            _ => self.fragments.push(Fragment {
+                text: text.to_string(),
+                highlight,
+            }),
+        }
+    }
+}
+
+impl Display for Snippet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for fragment in &self.fragments {
+            write!(f, "{}", fragment.text)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "yew-wasm")]
+impl ToDom for Snippet {
+    fn to_dom(self) -> Html {
+        html! {
+        <span class="search-snippet">
+            {for self.fragments.into_iter().map(|fragment| if fragment.highlight {
+                html!(<mark>{fragment.text}</mark>)
+            } else {
+                html!({fragment.text})
+            })}
+        </span>
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sec(value: u32) -> id::Sec {
+        id::Sec::new(value, id::Doc::new(0, id::Proj::new(0)))
+    }
+
+    #[test]
+    fn stems_by_language() {
+        let terms: Vec<String> = tokenize("Running runners", Lang::English)
+            .into_iter()
+            .map(|t| t.term)
+            .collect();
+        assert_eq!(terms, vec!["run", "runner"]);
+
+        let terms: Vec<String> = tokenize("Häuser Hauses", Lang::German)
+            .into_iter()
+            .map(|t| t.term)
+            .collect();
+        assert_eq!(terms, vec!["haus", "haus"]);
+    }
+
+    #[test]
+    fn ranks_and_updates() {
+        let mut index = Index::new(Lang::English);
+        index.insert(sec(0), "Harbour", "The ships sailed into the harbour.");
+        index.insert(sec(1), "Market", "A ship was seen from the market.");
+        index.insert(sec(2), "Night", "Nothing happened.");
+
+        let hits: Vec<id::Sec> = index.search("ships").into_iter().map(|h| h.id).collect();
+        assert_eq!(hits, vec![sec(0), sec(1)]);
+
+        index.insert(sec(0), "Harbour", "Empty water.");
+        let hits: Vec<id::Sec> = index.search("ship").into_iter().map(|h| h.id).collect();
+        assert_eq!(hits, vec![sec(1)]);
+
+        index.remove(sec(1));
+        assert!(index.search("ship").is_empty());
+        assert_eq!(index.len(), 2);
+    }
+
+    #[test]
+    fn highlights_snippets() {
+        let snippet = Snippet::build("one two three sailing four five", "sail", Lang::English, 1);
+        assert_eq!(snippet.to_string(), "three sailing four");
+        assert_eq!(
+            snippet.fragments[1],
+            Fragment {
+                text: "sailing".to_string(),
+                highlight: true
+            }
+        );
+
+        let snippet = Snippet::from_marked("a <b>ship</b> sails", "<b>", "</b>");
+        assert_eq!(snippet.fragments.len(), 3);
+        assert!(snippet.fragments[1].highlight);
+    }
+}
@@ -0,0 +1,573 @@
+use std::fmt::{Display, Formatter};
+use std::io::{Cursor, Seek, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::ast::Notes;
+use crate::data::{Doc, Sec};
+use crate::epub::{self, Chapter};
+use crate::highlight;
+use crate::html::escape;
+use crate::sanitize::Policy;
+use crate::toc::{self, Anchors};
+use crate::wiki::{Target, Titles};
+
+/// The template of the pages of the site, unless it is replaced.
+///
+/// The placeholders `{{lang}}`, `{{title}}`, `{{site}}`, `{{root}}`, `{{content}}` and
+/// `{{nav}}` are replaced by the language of the site, the title of the page and of the site,
+/// the relative path to the root of the site, the content of the page and its navigation.
+pub const DEFAULT_TEMPLATE: &str = "\
+<!DOCTYPE html>
+<html lang=\"{{lang}}\">
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>{{title}}</title>
+<link rel=\"stylesheet\" href=\"{{root}}style.css\">
+<link rel=\"alternate\" type=\"application/atom+xml\" title=\"{{site}}\" href=\"{{root}}feed.xml\">
+</head>
+<body>
+<header class=\"site\"><a href=\"{{root}}index.html\">{{site}}</a></header>
+<main>
+{{content}}</main>
+{{nav}}</body>
+</html>
+";
+
+/// The style sheet of the site, unless it is replaced.
+pub const DEFAULT_STYLE: &str = "\
+body { font-family: Georgia, serif; line-height: 1.6; max-width: 40em; margin: 0 auto; padding: 1em; }
+header.site { font-variant: small-caps; margin-bottom: 2em; }
+header.site a { color: inherit; text-decoration: none; }
+nav.pages { display: flex; justify-content: space-between; gap: 1em; margin: 3em 0 1em; }
+nav.contents ol { list-style: none; padding-left: 1em; }
+p.document { font-variant: small-caps; margin: 0; }
+blockquote { margin: 1em 2em; }
+pre { white-space: pre-wrap; font-size: 0.85em; }
+img { max-width: 100%; }
+table { border-collapse: collapse; margin: 1em auto; }
+th, td { border: 1px solid #888; padding: 0.2em 0.5em; }
+.footnotes { font-size: 0.9em; margin-top: 2em; }
+.math-error, .wiki-link.missing { color: #b00; }
+";
+
+/// The number of the latest sections in the feed.
+const FEED_ENTRIES: usize = 20;
+
+/// The look of the pages of a site.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Theme {
+    /// The HTML template of the pages, see [`DEFAULT_TEMPLATE`].
+    pub template: String,
+    pub style: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            template: DEFAULT_TEMPLATE.to_string(),
+            style: DEFAULT_STYLE.to_string(),
+        }
+    }
+}
+
+/// A file of a site, by its path relative to the root of the site.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct File {
+    pub path: String,
+    pub content: String,
+}
+
+/// The reason a site cannot be written.
+#[derive(Debug)]
+pub enum Error {
+    /// The site has no documents to publish.
+    Empty,
+    Io(std::io::Error),
+    Zip(zip::result::ZipError),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Empty => write!(f, "the site has no documents"),
+            Error::Io(err) => write!(f, "cannot write the site: {}", err),
+            Error::Zip(err) => write!(f, "cannot write the site: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(err: zip::result::ZipError) -> Self {
+        Error::Zip(err)
+    }
+}
+
+/// A static website publishing the documents of a project.
+///
+/// The home page has the table of contents of the documents and their sections. Each document
+/// has a folder with an index page listing its sections, and a page for each section, linking
+/// to the previous and the next section in reading order across documents. Wiki links point
+/// to the pages of their targets. The Atom feed `feed.xml` has the latest sections.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Site {
+    pub title: String,
+    pub authors: Vec<String>,
+    /// The BCP 47 language tag of the content.
+    pub language: String,
+    pub description: Option<String>,
+    /// The absolute URL the site is published at, for the links of the feed.
+    pub base_url: String,
+    /// The time of the last update of the feed, as `YYYY-MM-DDThh:mm:ssZ`.
+    pub updated: String,
+    pub theme: Theme,
+    pub chapters: Vec<Chapter>,
+}
+
+/// A page of a section, in reading order.
+struct Page<'a> {
+    path: String,
+    title: String,
+    chapter: usize,
+    sec: &'a Sec,
+}
+
+impl Site {
+    pub fn new(title: &str, base_url: &str) -> Self {
+        let mut base_url = base_url.trim().to_string();
+        if !base_url.is_empty() && !base_url.ends_with('/') {
+            base_url.push('/');
+        }
+        Self {
+            title: title.to_string(),
+            authors: vec![],
+            language: "en".to_string(),
+            description: None,
+            base_url,
+            updated: epub::timestamp(std::time::SystemTime::now()),
+            theme: Theme::default(),
+            chapters: vec![],
+        }
+    }
+
+    pub fn with_chapter(mut self, doc: Doc, sections: Vec<Sec>) -> Self {
+        self.chapters.push(Chapter { doc, sections });
+        self
+    }
+
+    /// The files of the site.
+    pub fn files(&self) -> Result<Vec<File>, Error> {
+        if self.chapters.is_empty() {
+            return Err(Error::Empty);
+        }
+        let folders = self.folders();
+        let pages = self.pages(&folders);
+        let mut style = self.theme.style.clone();
+        style.push_str(&highlight::Theme::LIGHT.css());
+        let mut files = vec![
+            File {
+                path: "index.html".to_string(),
+                content: self.home(&folders),
+            },
+            File {
+                path: "style.css".to_string(),
+                content: style,
+            },
+        ];
+        for (i, chapter) in self.chapters.iter().enumerate() {
+            files.push(File {
+                path: format!("{}/index.html", folders[i]),
+                content: self.document(chapter, pages.iter().filter(|page| page.chapter == i)),
+            });
+        }
+        let mut titles = Titles::default();
+        for chapter in &self.chapters {
+            titles.insert_doc(&chapter.doc.head);
+            for sec in &chapter.sections {
+                titles.insert_sec(&sec.head);
+            }
+        }
+        let rendered: Vec<String> = pages
+            .iter()
+            .map(|page| self.content(page, &pages, &folders, &titles))
+            .collect();
+        for (i, page) in pages.iter().enumerate() {
+            let doc = &self.chapters[page.chapter].doc;
+            let mut content = format!(
+                "<article>\n<p class=\"document\"><a href=\"index.html\">{}</a></p>\n\
+                <h1>{}</h1>\n",
+                escape(&doc.head.title),
+                escape(&page.title)
+            );
+            content.push_str(&rendered[i]);
+            content.push_str("</article>\n");
+            let mut nav = String::from("<nav class=\"pages\">");
+            match i.checked_sub(1).map(|i| &pages[i]) {
+                Some(prev) => nav.push_str(&format!(
+                    "<a rel=\"prev\" href=\"../{}\">← {}</a>",
+                    prev.path,
+                    escape(&prev.title)
+                )),
+                None => nav.push_str("<span></span>"),
+            }
+            nav.push_str("<a href=\"../index.html\">Contents</a>");
+            match pages.get(i + 1) {
+                Some(next) => nav.push_str(&format!(
+                    "<a rel=\"next\" href=\"../{}\">{} →</a>",
+                    next.path,
+                    escape(&next.title)
+                )),
+                None => nav.push_str("<span></span>"),
+            }
+            nav.push_str("</nav>\n");
+            let title = format!("{} – {}", page.title, doc.head.title);
+            files.push(File {
+                path: page.path.clone(),
+                content: self.fill(&title, "../", &content, &nav),
+            });
+        }
+        files.push(File {
+            path: "feed.xml".to_string(),
+            content: self.feed(&pages, &rendered),
+        });
+        Ok(files)
+    }
+
+    /// Writes the files of the site into the folder.
+    pub fn write_to(&self, folder: &Path) -> Result<(), Error> {
+        for file in self.files()? {
+            let path = folder.join(&file.path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, file.content)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the files of the site as a zip archive.
+    pub fn write<W: Write + Seek>(&self, writer: W) -> Result<W, Error> {
+        let files = self.files()?;
+        let mut zip = ZipWriter::new(writer);
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        for file in files {
+            zip.start_file(file.path, options)?;
+            zip.write_all(file.content.as_bytes())?;
+        }
+        Ok(zip.finish()?)
+    }
+
+    /// The site as the bytes of a zip archive.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.write(Cursor::new(vec![]))?.into_inner())
+    }
+
+    /// The folders of the documents, the slugs of their titles.
+    fn folders(&self) -> Vec<String> {
+        let mut anchors = Anchors::new("");
+        self.chapters
+            .iter()
+            .map(|chapter| anchors.anchor(&chapter.doc.head.title))
+            .collect()
+    }
+
+    /// The pages of the sections, named by the slugs of their titles or their position.
+    fn pages(&self, folders: &[String]) -> Vec<Page<'_>> {
+        let mut pages = vec![];
+        for (i, chapter) in self.chapters.iter().enumerate() {
+            let mut anchors = Anchors::new("");
+            // the index page of the document.
+            anchors.anchor("index");
+            for (j, sec) in chapter.sections.iter().enumerate() {
+                let title = match sec.head.title.trim() {
+                    "" => format!("Part {}", j + 1),
+                    title => title.to_string(),
+                };
+                pages.push(Page {
+                    path: format!("{}/{}.html", folders[i], anchors.anchor(&title)),
+                    title,
+                    chapter: i,
+                    sec,
+                });
+            }
+        }
+        pages
+    }
+
+    /// The page with the template filled in.
+    fn fill(&self, title: &str, root: &str, content: &str, nav: &str) -> String {
+        let template = &self.theme.template;
+        let mut out = String::new();
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find("{{") {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let Some(end) = after.find("}}") else {
+                rest = &rest[start..];
+                break;
+            };
+            match after[..end].trim() {
+                "lang" => out.push_str(&escape(&self.language)),
+                "title" => out.push_str(&escape(title)),
+                "site" => out.push_str(&escape(&self.title)),
+                "root" => out.push_str(root),
+                "content" => out.push_str(content),
+                "nav" => out.push_str(nav),
+                _ => out.push_str(&rest[start..start + 2 + end + 2]),
+            }
+            rest = &after[end + 2..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// The home page with the table of contents.
+    fn home(&self, folders: &[String]) -> String {
+        let mut content = format!("<h1>{}</h1>\n", escape(&self.title));
+        if !self.authors.is_empty() {
+            content.push_str(&format!(
+                "<p class=\"authors\">{}</p>\n",
+                escape(&self.authors.join(", "))
+            ));
+        }
+        if let Some(description) = &self.description {
+            content.push_str(&format!(
+                "<p class=\"description\">{}</p>\n",
+                escape(description)
+            ));
+        }
+        content.push_str("<nav class=\"contents\"><h2>Contents</h2>\n<ol>\n");
+        let folders = folders.iter();
+        for (chapter, folder) in self.chapters.iter().zip(folders) {
+            content.push_str(&format!(
+                "<li><a href=\"{}/index.html\">{}</a></li>\n",
+                folder,
+                escape(&chapter.doc.head.title)
+            ));
+        }
+        content.push_str("</ol>\n</nav>\n");
+        self.fill(&self.title, "", &content, "")
+    }
+
+    /// The index page of a document with the table of contents of its sections.
+    fn document<'a>(&self, chapter: &Chapter, pages: impl Iterator<Item = &'a Page<'a>>) -> String {
+        let mut content = format!("<h1>{}</h1>\n", escape(&chapter.doc.head.title));
+        content.push_str("<nav class=\"contents\"><ol>\n");
+        let mut first = None;
+        for page in pages {
+            first = first.or(Some(page));
+            let file = page.path.rsplit('/').next().unwrap_or_default();
+            let entries = toc::contents([page.sec]);
+            content.push_str(&format!(
+                "<li><a href=\"{}\">{}</a>",
+                file,
+                escape(&page.title)
+            ));
+            if let Some(entry) = entries.first() {
+                write_entries(&mut content, file, &entry.children);
+            }
+            content.push_str("</li>\n");
+        }
+        content.push_str("</ol>\n</nav>\n");
+        let nav = match first {
+            Some(first) => format!(
+                "<nav class=\"pages\"><a href=\"../index.html\">Contents</a>\
+                <a rel=\"next\" href=\"../{}\">Start reading →</a></nav>\n",
+                first.path
+            ),
+            None => String::new(),
+        };
+        self.fill(&chapter.doc.head.title, "../", &content, &nav)
+    }
+
+    /// The HTML of a section with its footnotes, and its wiki links pointing to the pages.
+    fn content(&self, page: &Page, pages: &[Page], folders: &[String], titles: &Titles) -> String {
+        let doc = self.chapters[page.chapter].doc.head.id;
+        let anchor = toc::section_anchor(page.sec.head.id);
+        let notes = Notes::new(&format!("{}-", anchor)).with_titles(titles.clone(), doc);
+        let mut html = page.sec.content.render_html_with(notes, &Policy::default());
+        for (chapter, folder) in self.chapters.iter().zip(folders) {
+            let target = Target::Doc(chapter.doc.head.id);
+            html = html.replace(
+                &format!("href=\"{}\" data-target=\"{}\"", target.href(), target),
+                &format!("href=\"../{}/index.html\"", folder),
+            );
+        }
+        for other in pages {
+            let target = Target::Sec(other.sec.head.id);
+            html = html.replace(
+                &format!("href=\"{}\" data-target=\"{}\"", target.href(), target),
+                &format!("href=\"../{}\"", other.path),
+            );
+        }
+        html
+    }
+
+    /// The Atom feed of the latest sections, with their rendered content.
+    fn feed(&self, pages: &[Page], rendered: &[String]) -> String {
+        let mut feed = format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+            <feed xmlns=\"http://www.w3.org/2005/Atom\" xml:lang=\"{}\">\n\
+            <title>{}</title>\n<id>{}</id>\n<updated>{}</updated>\n\
+            <link href=\"{}\" />\n<link rel=\"self\" href=\"{}feed.xml\" />\n",
+            escape(&self.language),
+            escape(&self.title),
+            escape(&self.base_url),
+            escape(&self.updated),
+            escape(&self.base_url),
+            escape(&self.base_url)
+        );
+        let authors: Vec<&str> = match self.authors.is_empty() {
+            true => vec![&self.title],
+            false => self.authors.iter().map(String::as_str).collect(),
+        };
+        for author in authors {
+            feed.push_str(&format!(
+                "<author><name>{}</name></author>\n",
+                escape(author)
+            ));
+        }
+        if let Some(description) = &self.description {
+            feed.push_str(&format!("<subtitle>{}</subtitle>\n", escape(description)));
+        }
+        for (page, html) in pages.iter().zip(rendered).rev().take(FEED_ENTRIES) {
+            let url = format!("{}{}", self.base_url, page.path);
+            let doc = &self.chapters[page.chapter].doc;
+            feed.push_str(&format!(
+                "<entry>\n<title>{}</title>\n<id>{}</id>\n<link href=\"{}\" />\n\
+                <updated>{}</updated>\n<content type=\"html\" xml:base=\"{}\">{}</content>\n\
+                </entry>\n",
+                escape(&format!("{}: {}", doc.head.title, page.title)),
+                escape(&url),
+                escape(&url),
+                escape(&self.updated),
+                escape(&url),
+                escape(html)
+            ));
+        }
+        feed.push_str("</feed>\n");
+        feed
+    }
+}
+
+/// Writes the nested entries of the headings of a page.
+fn write_entries(out: &mut String, file: &str, entries: &[toc::Entry]) {
+    if entries.is_empty() {
+        return;
+    }
+    out.push_str("<ol>");
+    for entry in entries {
+        out.push_str(&format!(
+            "<li><a href=\"{}#{}\">{}</a>",
+            file,
+            entry.anchor,
+            escape(&entry.title)
+        ));
+        write_entries(out, file, &entry.children);
+        out.push_str("</li>");
+    }
+    out.push_str("</ol>");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::chapter;
+
+    fn site() -> Site {
+        let (one, first) = chapter(
+            1,
+            "Book One",
+            &[("Index", "See [[The End]].\n\n## Storm"), ("", "Rain.")],
+        );
+        let (two, second) = chapter(2, "Book One", &[("The End", "Back to [[Book One]].")]);
+        let mut site = Site::new("Saga", "https://example.com/saga");
+        site.updated = "2024-01-02T03:04:05Z".to_string();
+        site.with_chapter(one, first).with_chapter(two, second)
+    }
+
+    fn file<'a>(files: &'a [File], path: &str) -> &'a str {
+        &files.iter().find(|file| file.path == path).unwrap().content
+    }
+
+    #[test]
+    fn names_the_pages() {
+        let files = site().files().unwrap();
+        let paths: Vec<&str> = files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "index.html",
+                "style.css",
+                "book-one/index.html",
+                "book-one-1/index.html",
+                "book-one/index-1.html",
+                "book-one/part-2.html",
+                "book-one-1/the-end.html",
+                "feed.xml"
+            ]
+        );
+        let home = file(&files, "index.html");
+        assert!(home.contains("<title>Saga</title>"));
+        assert!(home.contains("<li><a href=\"book-one-1/index.html\">Book One</a></li>"));
+        let document = file(&files, "book-one/index.html");
+        assert!(document.contains("href=\"index-1.html#section-0-storm\">Storm</a>"));
+        assert!(document.contains("<a rel=\"next\" href=\"../book-one/index-1.html\">"));
+        assert!(matches!(Site::new("Saga", "").files(), Err(Error::Empty)));
+    }
+
+    #[test]
+    fn links_the_pages() {
+        let files = site().files().unwrap();
+        let part = file(&files, "book-one/part-2.html");
+        assert!(part.contains("<title>Part 2 – Book One</title>"));
+        assert!(part.contains("<link rel=\"stylesheet\" href=\"../style.css\">"));
+        assert!(part.contains("<a rel=\"prev\" href=\"../book-one/index-1.html\">← Index</a>"));
+        assert!(part.contains("<a rel=\"next\" href=\"../book-one-1/the-end.html\">The End →</a>"));
+        let first = file(&files, "book-one/index-1.html");
+        assert!(first.contains("href=\"../book-one-1/the-end.html\">The End</a>."));
+        assert!(!first.contains("rel=\"prev\""));
+        let last = file(&files, "book-one-1/the-end.html");
+        assert!(last.contains("href=\"../book-one/index.html\">Book One</a>."));
+        assert!(!last.contains("rel=\"next\""));
+    }
+
+    #[test]
+    fn fills_the_theme() {
+        let mut site = site();
+        site.theme = Theme {
+            template: "<{{ title }}|{{root}}|{{unknown}}|{{nav}}{{content".to_string(),
+            style: "p {}".to_string(),
+        };
+        let files = site.files().unwrap();
+        assert_eq!(file(&files, "index.html"), "<Saga||{{unknown}}|{{content");
+        assert!(file(&files, "style.css").starts_with("p {}"));
+    }
+
+    #[test]
+    fn writes_the_feed() {
+        let files = site().files().unwrap();
+        let feed = file(&files, "feed.xml");
+        assert!(feed.contains("<link rel=\"self\" href=\"https://example.com/saga/feed.xml\" />"));
+        assert!(feed.contains("<author><name>Saga</name></author>"));
+        let latest = feed.find("<title>Book One: The End</title>").unwrap();
+        let earliest = feed.find("<title>Book One: Index</title>").unwrap();
+        assert!(latest < earliest);
+        assert!(feed.contains("<id>https://example.com/saga/book-one/part-2.html</id>"));
+        assert!(feed.contains("<updated>2024-01-02T03:04:05Z</updated>"));
+        assert!(feed.contains("&lt;p&gt;Rain.&lt;/p&gt;"));
+        roxmltree::Document::parse(feed).unwrap();
+    }
+}
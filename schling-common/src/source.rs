@@ -1,20 +1,21 @@
-use std::str::FromStr;
-
 use self::{Symbol::*, Trivia::*};
 use nom::{
     branch::alt,
     bytes::complete::{is_not, tag, take_while, take_while1},
-    character::complete::{anychar, char},
-    combinator::{map, verify},
-    error::{FromExternalError, ParseError},
+    character::complete::anychar,
+    combinator::{map, recognize, verify},
+    error::{ErrorKind, FromExternalError, ParseError},
     sequence::{delimited, preceded},
     IResult,
 };
-use paste::paste;
+use unicode_bidi::{bidi_class, BidiClass};
 use unicode_categories::{self, UnicodeCategories};
 #[cfg(feature = "yew-wasm")]
 use yew::prelude::*;
 
+use crate::fountain;
+use crate::markup::MarkupLang;
+
 #[cfg(feature = "yew-wasm")]
 pub trait ToDom {
     /// Retrieves the com representation.
@@ -26,8 +27,196 @@ pub struct Text<'a> {
     pub symbols: Vec<Symbol<'a>>,
 }
 
+impl<'a> Text<'a> {
+    pub fn parse(text: &'a str) -> Self {
+        Self::parse_as(text, Mode::Prose)
+    }
+
+    pub fn parse_as(text: &'a str, mode: Mode) -> Self {
+        Self {
+            symbols: SymbolIter::with_mode(text, mode).collect(),
+        }
+    }
+
+    pub fn lines(&self) -> LineIter<'_> {
+        LineIter {
+            symbols: &self.symbols,
+            id: 0,
+            after_break: false,
+        }
+    }
+}
+
+/// The syntax recognized by the tokenizer in addition to prose.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Prose,
+    Md,
+    Html,
+    Fountain,
+}
+
+impl From<MarkupLang> for Mode {
+    fn from(lang: MarkupLang) -> Self {
+        match lang {
+            MarkupLang::Html => Mode::Html,
+            MarkupLang::Md => Mode::Md,
+            MarkupLang::Fountain => Mode::Fountain,
+        }
+    }
+}
+
 pub struct SymbolIter<'s> {
     text: &'s str,
+    mode: Mode,
+    /// Whether only indentation or block quote markers precede the current position in the line.
+    line_start: bool,
+    /// Whether the previous symbol is a word.
+    after_word: bool,
+    /// The delimiter of the open fenced code block.
+    fence: Option<&'s str>,
+    /// Whether an HTML comment or a Fountain boneyard is open.
+    comment: bool,
+    /// Whether the current line contains only white space so far.
+    line_blank: bool,
+    /// Whether the previous line is empty, or the current line is the first one.
+    prev_blank: bool,
+}
+
+impl<'s> SymbolIter<'s> {
+    pub fn new(text: &'s str) -> Self {
+        Self::with_mode(text, Mode::Prose)
+    }
+
+    pub fn with_mode(text: &'s str, mode: Mode) -> Self {
+        Self {
+            text,
+            mode,
+            line_start: true,
+            after_word: false,
+            fence: None,
+            comment: false,
+            line_blank: true,
+            prev_blank: true,
+        }
+    }
+
+    fn parse_md(&mut self) -> IResult<&'s str, Symbol<'s>, ()> {
+        let i = self.text;
+        if let Some(fence) = self.fence {
+            return match parse_line_content::<()>(i) {
+                Ok((rest, line)) if self.line_start => {
+                    if is_fence(line, fence) {
+                        self.fence = None;
+                    }
+                    Ok((rest, Symbol::Mark(Mark::Fence(line))))
+                }
+                _ => parse_symbol(i),
+            };
+        }
+        if self.line_start {
+            if let Ok((rest, line)) = parse_fence::<()>(i) {
+                let line_trim = line.trim_start();
+                let len = line_trim
+                    .find(|c| !matches!(c, '`' | '~'))
+                    .unwrap_or(line_trim.len());
+                self.fence = Some(&line_trim[..len]);
+                return Ok((rest, Symbol::Mark(Mark::Fence(line))));
+            }
+            let block = alt((
+                map(parse_heading::<()>, Mark::Heading),
+                map(parse_block_quote, Mark::BlockQuote),
+                map(parse_list_marker, Mark::ListMarker),
+            ))(i);
+            if let Ok((rest, mark)) = block {
+                return Ok((rest, Symbol::Mark(mark)));
+            }
+        }
+        let underscore = !self.after_word;
+        alt((
+            map(parse_code_span, Mark::Code),
+            map(parse_strong, Mark::Strong),
+            map(
+                verify(parse_emphasis, move |s: &str| {
+                    underscore || !s.starts_with('_')
+                }),
+                Mark::Emphasis,
+            ),
+            map(parse_link, Mark::Link),
+        ))(i)
+        .map(|(rest, mark)| (rest, Symbol::Mark(mark)))
+        .or_else(|_: nom::Err<()>| parse_symbol_except(MD_MARKS)(i))
+    }
+
+    fn parse_html(&mut self) -> IResult<&'s str, Symbol<'s>, ()> {
+        let i = self.text;
+        if self.comment || i.starts_with("<!--") {
+            if let Ok((_, line)) = parse_line_content::<()>(i) {
+                let end = line.find("-->").map(|pos| pos + 3);
+                self.comment = end.is_none();
+                let end = end.unwrap_or(line.len());
+                return Ok((&i[end..], Symbol::Mark(Mark::Comment(&i[..end]))));
+            }
+            return parse_symbol(i);
+        }
+        alt((map(parse_tag, Mark::Tag), map(parse_entity, Mark::Entity)))(i)
+            .map(|(rest, mark)| (rest, Symbol::Mark(mark)))
+            .or_else(|_: nom::Err<()>| parse_symbol_except(HTML_MARKS)(i))
+    }
+
+    fn parse_fountain(&mut self) -> IResult<&'s str, Symbol<'s>, ()> {
+        let i = self.text;
+        if self.comment || i.starts_with("/*") {
+            if let Ok((_, line)) = parse_line_content::<()>(i) {
+                let end = line.find("*/").map(|pos| pos + 2);
+                self.comment = end.is_none();
+                let end = end.unwrap_or(line.len());
+                return Ok((&i[end..], Symbol::Mark(Mark::Comment(&i[..end]))));
+            }
+            return parse_symbol(i);
+        }
+        if i.starts_with("[[") {
+            if let Ok((_, line)) = parse_line_content::<()>(i) {
+                let end = line.find("]]").map_or(line.len(), |pos| pos + 2);
+                return Ok((&i[end..], Symbol::Mark(Mark::Comment(&i[..end]))));
+            }
+        }
+        if self.line_start {
+            if let Ok((rest, line)) = parse_line_content::<()>(i) {
+                let next_blank = parse_new_line::<()>(rest).map_or(true, |(next, _)| {
+                    next.lines().next().is_none_or(|l| l.trim().is_empty())
+                });
+                let trimmed = line.trim_start();
+                let mark = if trimmed.starts_with('#') {
+                    Some(Mark::Heading(line))
+                } else if trimmed.starts_with('=') && !trimmed.starts_with("===") {
+                    Some(Mark::Comment(line))
+                } else if fountain::is_centered(line) {
+                    Some(Mark::Centered(line))
+                } else if self.prev_blank && next_blank && fountain::is_scene_heading(line) {
+                    Some(Mark::Scene(line))
+                } else if self.prev_blank && next_blank && fountain::is_transition(line) {
+                    Some(Mark::Transition(line))
+                } else if self.prev_blank && !next_blank && fountain::is_character(line) {
+                    Some(Mark::Cue(line))
+                } else if !self.prev_blank && fountain::is_parenthetical(line) {
+                    Some(Mark::Parenthetical(line))
+                } else {
+                    None
+                };
+                if let Some(mark) = mark {
+                    return Ok((rest, Symbol::Mark(mark)));
+                }
+            }
+        }
+        alt((
+            map(parse_strong, Mark::Strong),
+            map(parse_emphasis, Mark::Emphasis),
+        ))(i)
+        .map(|(rest, mark)| (rest, Symbol::Mark(mark)))
+        .or_else(|_: nom::Err<()>| parse_symbol_except(FOUNTAIN_MARKS)(i))
+    }
 }
 
 impl<'s> Iterator for SymbolIter<'s> {
@@ -37,9 +226,29 @@ impl<'s> Iterator for SymbolIter<'s> {
         if self.text.is_empty() {
             return None;
         }
-        match parse_symbol::<()>(self.text) {
+        let parsed = match self.mode {
+            Mode::Prose => parse_symbol::<()>(self.text),
+            Mode::Md => self.parse_md(),
+            Mode::Html => self.parse_html(),
+            Mode::Fountain => self.parse_fountain(),
+        };
+        match parsed {
             Ok((text, symbol)) => {
                 self.text = text;
+                self.line_start = match symbol {
+                    Trivia(Line(_)) => true,
+                    Trivia(Space(_)) | Symbol::Mark(Mark::BlockQuote(_)) => self.line_start,
+                    _ => false,
+                };
+                self.after_word = matches!(symbol, Ident(_));
+                match symbol {
+                    Trivia(Line(_)) => {
+                        self.prev_blank = self.line_blank;
+                        self.line_blank = true;
+                    }
+                    Trivia(Space(_)) => {}
+                    _ => self.line_blank = false,
+                }
                 Some(symbol)
             }
             Err(_) => {
@@ -53,6 +262,8 @@ impl<'s> Iterator for SymbolIter<'s> {
 pub struct LineIter<'s> {
     symbols: &'s [Symbol<'s>],
     id: usize,
+    /// Whether the previous line ended with a line break, so that an empty line follows.
+    after_break: bool,
 }
 
 impl<'s> Iterator for LineIter<'s> {
@@ -61,7 +272,13 @@ impl<'s> Iterator for LineIter<'s> {
     fn next(&mut self) -> Option<Self::Item> {
         let symbols = self.symbols;
         if symbols.is_empty() {
-            return None;
+            // the empty line after a trailing line break.
+            if !std::mem::take(&mut self.after_break) {
+                return None;
+            }
+            let id = self.id;
+            self.id = id + 1;
+            return Some(Line { id, text: symbols });
         }
 
         let mut pos = 0;
@@ -72,7 +289,9 @@ impl<'s> Iterator for LineIter<'s> {
             pos += 1;
         }
         let symbols = self.symbols;
-        self.symbols = &symbols[pos..];
+        // skip the line break.
+        self.after_break = pos < symbols.len();
+        self.symbols = &symbols[(pos + 1).min(symbols.len())..];
         let id = self.id;
         self.id = id + 1;
 
@@ -99,14 +318,122 @@ impl<'s> Line<'s> {
     pub fn code_id(&self) -> String {
         format!("LC{}", self.number())
     }
+
+    /// The base direction of the line, given by its first strong character.
+    pub fn direction(&self) -> Direction {
+        self.text
+            .iter()
+            .find_map(|symbol| Direction::of(symbol.as_str()))
+            .unwrap_or_default()
+    }
+
+    /// Splits the line into runs of the same direction, neutral symbols such as spaces and
+    /// punctuation take the direction of the surrounding runs, or the base direction between
+    /// runs of different directions.
+    pub fn runs(&self) -> Vec<BidiRun<'s>> {
+        let base = self.direction();
+        let strong: Vec<Option<Direction>> = self
+            .text
+            .iter()
+            .map(|symbol| Direction::of(symbol.as_str()))
+            .collect();
+        let mut next = vec![None; strong.len()];
+        for pos in (0..strong.len().saturating_sub(1)).rev() {
+            next[pos] = strong[pos + 1].or(next[pos + 1]);
+        }
+
+        let mut runs: Vec<BidiRun<'s>> = vec![];
+        let mut prev = None;
+        let mut start = 0;
+        for (pos, dir) in strong.iter().enumerate() {
+            let dir = match dir {
+                Some(dir) => *dir,
+                None if prev.is_some() && prev == next[pos] => prev.unwrap_or(base),
+                None => base,
+            };
+            prev = strong[pos].or(prev);
+            match runs.last_mut() {
+                Some(run) if run.dir == dir => run.symbols = &self.text[start..=pos],
+                _ => {
+                    start = pos;
+                    runs.push(BidiRun {
+                        dir,
+                        symbols: &self.text[pos..=pos],
+                    });
+                }
+            }
+        }
+        runs
+    }
 }
 
 #[cfg(feature = "yew-wasm")]
 impl<'s> ToDom for Line<'s> {
+    fn to_dom(self) -> Html {
+        let dir = self.direction();
+        html! {
+        <span class="source-line" dir={dir.to_string()}>
+            {for self.runs().into_iter().map(|run| if run.dir == dir {
+                run.to_dom()
+            } else {
+                html!(<bdi dir={run.dir.to_string()}>{run.to_dom()}</bdi>)
+            })}
+        </span>
+        }
+    }
+}
+
+/// The writing direction of a text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Direction {
+    #[default]
+    Ltr,
+    Rtl,
+}
+
+impl Direction {
+    /// The direction of the first strongly directional character, if any.
+    pub fn of(text: &str) -> Option<Direction> {
+        text.chars().find_map(Direction::of_char)
+    }
+
+    fn of_char(c: char) -> Option<Direction> {
+        match bidi_class(c) {
+            BidiClass::L => Some(Direction::Ltr),
+            BidiClass::R | BidiClass::AL => Some(Direction::Rtl),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Direction::Ltr => write!(f, "ltr"),
+            Direction::Rtl => write!(f, "rtl"),
+        }
+    }
+}
+
+/// Consecutive symbols of a line with the same direction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BidiRun<'s> {
+    pub dir: Direction,
+    pub symbols: &'s [Symbol<'s>],
+}
+
+impl<'s> BidiRun<'s> {
+    pub fn text(&self) -> String {
+        self.symbols.iter().map(|s| s.as_str()).collect()
+    }
+}
+
+#[cfg(feature = "yew-wasm")]
+impl<'s> ToDom for BidiRun<'s> {
     fn to_dom(self) -> Html {
         html! {
         <>
-            {for self.text.iter().map(|symbol| symbol.to_dom())}
+            {for self.symbols.iter().map(|symbol| symbol.to_dom())}
         </>
         }
     }
@@ -114,17 +441,20 @@ impl<'s> ToDom for Line<'s> {
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Symbol<'a> {
+    /// A quoted text, including its delimiters.
     Quote(&'a str),
     Trivia(Trivia<'a>),
     Ident(&'a str),
+    Mark(Mark<'a>),
 }
 
 impl<'a> Symbol<'a> {
     pub fn as_str(&self) -> &str {
         match self {
-            Quote(value) => *value,
+            Quote(value) => value,
             Trivia(value) => value.as_str(),
-            Ident(value) => *value,
+            Ident(value) => value,
+            Symbol::Mark(value) => value.as_str(),
         }
     }
 }
@@ -142,6 +472,82 @@ impl<'a> ToDom for Symbol<'a> {
             Ident(value) => html! {
                 <span class="source-ident">{value}</span>
             },
+            Symbol::Mark(value) => html! {
+                <span class={value.class()}>{value.as_str()}</span>
+            },
+        }
+    }
+}
+
+/// A construct of the markup language of the text.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mark<'a> {
+    /// A Markdown heading, including the leading `#`.
+    Heading(&'a str),
+    Emphasis(&'a str),
+    Strong(&'a str),
+    /// A Markdown link or image.
+    Link(&'a str),
+    ListMarker(&'a str),
+    BlockQuote(&'a str),
+    /// A Markdown code span.
+    Code(&'a str),
+    /// A line of a fenced Markdown code block, including the delimiters.
+    Fence(&'a str),
+    /// An HTML tag.
+    Tag(&'a str),
+    Comment(&'a str),
+    /// An HTML character reference.
+    Entity(&'a str),
+    /// A Fountain scene heading.
+    Scene(&'a str),
+    /// A Fountain character cue.
+    Cue(&'a str),
+    Parenthetical(&'a str),
+    Transition(&'a str),
+    Centered(&'a str),
+}
+
+impl<'a> Mark<'a> {
+    pub fn as_str(&self) -> &'a str {
+        match *self {
+            Mark::Heading(s)
+            | Mark::Emphasis(s)
+            | Mark::Strong(s)
+            | Mark::Link(s)
+            | Mark::ListMarker(s)
+            | Mark::BlockQuote(s)
+            | Mark::Code(s)
+            | Mark::Fence(s)
+            | Mark::Tag(s)
+            | Mark::Comment(s)
+            | Mark::Entity(s)
+            | Mark::Scene(s)
+            | Mark::Cue(s)
+            | Mark::Parenthetical(s)
+            | Mark::Transition(s)
+            | Mark::Centered(s) => s,
+        }
+    }
+
+    pub fn class(&self) -> &'static str {
+        match self {
+            Mark::Heading(_) => "source-heading",
+            Mark::Emphasis(_) => "source-emphasis",
+            Mark::Strong(_) => "source-strong",
+            Mark::Link(_) => "source-link",
+            Mark::ListMarker(_) => "source-list-marker",
+            Mark::BlockQuote(_) => "source-block-quote",
+            Mark::Code(_) => "source-code",
+            Mark::Fence(_) => "source-fence",
+            Mark::Tag(_) => "source-tag",
+            Mark::Comment(_) => "source-comment",
+            Mark::Entity(_) => "source-entity",
+            Mark::Scene(_) => "source-scene",
+            Mark::Cue(_) => "source-cue",
+            Mark::Parenthetical(_) => "source-parenthetical",
+            Mark::Transition(_) => "source-transition",
+            Mark::Centered(_) => "source-centered",
         }
     }
 }
@@ -159,6 +565,14 @@ fn escape_literal<'a, Error: ParseError<&'a str>>(
 }
 
 fn parse_quote<'a, E>(i: &'a str) -> IResult<&'a str, &'a str, E>
+where
+    E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+{
+    recognize(parse_quoted)(i)
+}
+
+/// parses a quote, yielding the text between the delimiters.
+fn parse_quoted<'a, E>(i: &'a str) -> IResult<&'a str, &'a str, E>
 where
     E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 {
@@ -181,18 +595,26 @@ where
     alt((tag("\r\n"), tag("\n"), tag("\u{2028}")))(i)
 }
 
+/// parses white space other than line breaks, such as tabs and spaces of all widths.
 fn parse_space<'a, E>(i: &'a str) -> IResult<&'a str, &'a str, E>
 where
     E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 {
-    take_while1(|c: char| c.is_separator_paragraph() || c.is_separator_space())(i)
+    take_while1(|c: char| {
+        c.is_separator_paragraph()
+            || c.is_separator_space()
+            || (c.is_whitespace() && !matches!(c, '\r' | '\n' | '\u{2028}'))
+    })(i)
 }
 
-fn parse_punctuation<'a, E>(i: &'a str) -> IResult<&'a str, &'a str, E>
+/// parses punctuation, stopping at the characters starting a construct of the markup language.
+fn parse_punctuation_except<'a, E>(
+    marks: &'static [char],
+) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str, E>
 where
     E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 {
-    take_while1(|c: char| c.is_punctuation() || c.is_symbol())(i)
+    take_while1(move |c: char| (c.is_punctuation() || c.is_symbol()) && !marks.contains(&c))
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -212,40 +634,475 @@ impl<'a> Trivia<'a> {
     }
 }
 
-fn parse_trivia<'a, E>(i: &'a str) -> IResult<&'a str, Trivia<'a>, E>
+fn parse_trivia_except<'a, E>(
+    marks: &'static [char],
+) -> impl FnMut(&'a str) -> IResult<&'a str, Trivia<'a>, E>
 where
     E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 {
     alt((
         map(parse_new_line, Line),
         map(parse_space, Space),
-        map(parse_punctuation, Symbol),
-    ))(i)
+        map(parse_punctuation_except(marks), Symbol),
+    ))
+}
+
+/// parses any single character not recognized otherwise, such as control characters.
+fn parse_unknown<'a, E>(i: &'a str) -> IResult<&'a str, Trivia<'a>, E>
+where
+    E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+{
+    map(recognize(anychar), Symbol)(i)
 }
 
 fn parse_ident<'a, E>(i: &'a str) -> IResult<&'a str, &'a str, E>
 where
     E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 {
-    // starts with at least one text, follows with multiple text, numbers, or dash.
-    preceded(
-        take_while1(|c: char| c.is_letter() || c.is_mark()),
+    // starts with at least one text or number, follows with multiple text, numbers, or dash.
+    // scripts written without spaces are segmented by `parse_cjk`.
+    let (_, word) = recognize(preceded(
+        take_while1(|c: char| {
+            (c.is_letter() || c.is_mark() || c.is_number()) && Script::of(c).is_none()
+        }),
         take_while(|c: char| {
-            c.is_letter() || c.is_mark() || c.is_punctuation_dash() || c.is_number()
+            (c.is_letter() || c.is_mark() || c.is_punctuation_dash() || c.is_number())
+                && Script::of(c).is_none()
         }),
-    )(i)
+    ))(i)?;
+    // ends where the direction changes, such as latin directly followed by arabic letters.
+    let dir = Direction::of(word);
+    let end = word
+        .char_indices()
+        .find(|(_, c)| matches!(Direction::of_char(*c), Some(d) if Some(d) != dir))
+        .map_or(word.len(), |(pos, _)| pos);
+    Ok((&i[end..], &i[..end]))
+}
+
+/// A script of the east asian languages, which are not necessarily separated by spaces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Script {
+    Han,
+    Hiragana,
+    Katakana,
+    Hangul,
+}
+
+impl Script {
+    pub fn of(c: char) -> Option<Script> {
+        match c {
+            '\u{3005}' | '\u{3007}' | '\u{3021}'..='\u{3029}' | '\u{3038}'..='\u{303B}' => {
+                Some(Script::Han)
+            }
+            '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}' => {
+                Some(Script::Han)
+            }
+            '\u{20000}'..='\u{2FA1F}' | '\u{30000}'..='\u{3134F}' => Some(Script::Han),
+            '\u{3041}'..='\u{309F}' => Some(Script::Hiragana),
+            '\u{30A0}'..='\u{30FF}' | '\u{31F0}'..='\u{31FF}' | '\u{FF66}'..='\u{FF9F}' => {
+                Some(Script::Katakana)
+            }
+            '\u{1100}'..='\u{11FF}' | '\u{3131}'..='\u{318E}' | '\u{A960}'..='\u{A97F}' => {
+                Some(Script::Hangul)
+            }
+            '\u{AC00}'..='\u{D7AF}' | '\u{D7B0}'..='\u{D7FF}' | '\u{FFA0}'..='\u{FFDC}' => {
+                Some(Script::Hangul)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether each character is counted as a word, because words are not separated by spaces.
+    pub fn is_logographic(self) -> bool {
+        !matches!(self, Script::Hangul)
+    }
+
+    fn continues(self, c: char) -> bool {
+        match Script::of(c) {
+            Some(script) => script == self,
+            // prolonged sound mark and combining marks continue any run.
+            None => c.is_mark(),
+        }
+    }
+}
+
+const PROLONGED_SOUND_MARK: char = '\u{30FC}';
+
+/// parses a single Han character, or a run of Hiragana, Katakana or Hangul.
+fn parse_cjk<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
+    let first = match i.chars().next() {
+        Some(c) => c,
+        None => return fail(i),
+    };
+    let script = match Script::of(first) {
+        Some(script) => script,
+        None => return fail(i),
+    };
+    let rest = &i[first.len_utf8()..];
+    let len = match script {
+        Script::Han => rest.find(|c: char| !c.is_mark()),
+        Script::Hiragana | Script::Katakana => {
+            rest.find(|c: char| !script.continues(c) && c != PROLONGED_SOUND_MARK)
+        }
+        Script::Hangul => rest.find(|c: char| !script.continues(c)),
+    }
+    .unwrap_or(rest.len());
+    let end = first.len_utf8() + len;
+    Ok((&i[end..], &i[..end]))
+}
+
+/// Statistics of a text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of words, counting each character of logographic scripts as a word.
+    pub words: usize,
+    /// The number of characters, excluding white space.
+    pub chars: usize,
+}
+
+impl Stats {
+    pub fn of(text: &str) -> Self {
+        let mut stats = Self::default();
+        stats.count(text);
+        stats
+    }
+
+    fn count(&mut self, text: &str) {
+        for symbol in SymbolIter::new(text) {
+            match symbol {
+                Ident(word) => {
+                    let chars = word.chars().filter(|c| !c.is_mark()).count();
+                    self.chars += word.chars().count();
+                    self.words += match word.chars().next().and_then(Script::of) {
+                        Some(script) if script.is_logographic() => chars,
+                        _ => 1,
+                    };
+                }
+                Quote(quote) => {
+                    let inner = unquote(quote);
+                    self.chars += quote.chars().count() - inner.chars().count();
+                    self.count(inner);
+                }
+                Trivia(Symbol(s)) => self.chars += s.chars().count(),
+                // prose has no marks of a markup language.
+                Trivia(_) | Symbol::Mark(_) => {}
+            }
+        }
+    }
+}
+
+/// The text between the delimiters of a quote.
+pub fn unquote(quote: &str) -> &str {
+    parse_quoted::<()>(quote).map_or(quote, |(_, inner)| inner)
+}
+
+/// The byte offset of the start of the next word after `pos`, or the end of the text.
+pub fn next_word(text: &str, pos: usize) -> usize {
+    word_starts(text)
+        .find(|start| *start > pos)
+        .unwrap_or(text.len())
+}
+
+/// The byte offset of the start of the word before `pos`, or the start of the text.
+pub fn prev_word(text: &str, pos: usize) -> usize {
+    word_starts(text)
+        .take_while(|start| *start < pos)
+        .last()
+        .unwrap_or(0)
+}
+
+fn word_starts(text: &str) -> impl Iterator<Item = usize> + '_ {
+    SymbolIter::new(text).filter_map(move |symbol| match symbol {
+        Ident(word) => Some(word.as_ptr() as usize - text.as_ptr() as usize),
+        _ => None,
+    })
+}
+
+/// The byte offsets of the starts of the lines of the text.
+pub fn line_starts(text: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    for (pos, c) in text.char_indices() {
+        if matches!(c, '\n' | '\u{2028}') {
+            starts.push(pos + c.len_utf8());
+        }
+    }
+    starts
+}
+
+/// The byte offset of the next character after `pos` in the logical order of the text, or of
+/// the one before it when not `forward`, keeping combining marks with their base character.
+pub fn step(text: &str, pos: usize, forward: bool) -> usize {
+    // a carriage return and a line feed make a single line break.
+    let starts =
+        |(i, c): &(usize, char)| !(c.is_mark() || *c == '\n' && text[..*i].ends_with('\r'));
+    match forward {
+        true => text
+            .char_indices()
+            .skip_while(|(i, _)| *i <= pos)
+            .find(starts)
+            .map_or(text.len(), |(i, _)| i),
+        false => text[..pos]
+            .char_indices()
+            .rev()
+            .find(starts)
+            .map_or(0, |(i, _)| i),
+    }
+}
+
+/// The base direction of the line holding `pos`, given by its first strong character.
+pub fn direction_at(text: &str, pos: usize) -> Direction {
+    let starts = line_starts(text);
+    let line = starts.partition_point(|start| *start <= pos);
+    let end = starts.get(line).copied().unwrap_or(text.len());
+    Direction::of(&text[starts[line - 1]..end]).unwrap_or_default()
+}
+
+/// Indents the lines from the one at `start` to the one at `end` by `unit`, returning the text
+/// with the offsets moved along.
+pub fn indent(text: &str, start: usize, end: usize, unit: &str) -> (String, usize, usize) {
+    edit_lines(text, start, end, |_| (0, unit))
+}
+
+/// Removes a tab or up to `width` spaces from the start of the lines from the one at `start` to
+/// the one at `end`, returning the text with the offsets moved along.
+pub fn outdent(text: &str, start: usize, end: usize, width: usize) -> (String, usize, usize) {
+    edit_lines(text, start, end, |line| {
+        let removed = match line.starts_with('\t') {
+            true => 1,
+            false => line.bytes().take(width).take_while(|b| *b == b' ').count(),
+        };
+        (removed, "")
+    })
+}
+
+/// Replaces the start of each line from the one at `start` to the one at `end`, the edit gives
+/// the number of bytes removed and the text inserted for the rest of the text from a line.
+fn edit_lines<'e>(
+    text: &str,
+    start: usize,
+    end: usize,
+    edit: impl Fn(&str) -> (usize, &'e str),
+) -> (String, usize, usize) {
+    let starts = line_starts(text);
+    let line_of = |pos: usize| starts.partition_point(|start| *start <= pos) - 1;
+    let mut result = String::with_capacity(text.len());
+    let mut done = 0;
+    let (mut new_start, mut new_end) = (start, end);
+    for &line in &starts[line_of(start)..=line_of(end)] {
+        let (removed, inserted) = edit(&text[line..]);
+        result.push_str(&text[done..line]);
+        result.push_str(inserted);
+        done = line + removed;
+        for (pos, new) in [(start, &mut new_start), (end, &mut new_end)] {
+            if pos >= line {
+                *new = *new + inserted.len() - removed.min(pos - line);
+            }
+        }
+    }
+    result.push_str(&text[done..]);
+    (result, new_start, new_end)
 }
 
 /// parses any recognized symbol.
 fn parse_symbol<'a, E>(i: &'a str) -> IResult<&'a str, Symbol<'a>, E>
+where
+    E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+{
+    parse_symbol_except(&[])(i)
+}
+
+/// parses any recognized symbol, splitting punctuation at the characters starting a construct of
+/// the markup language.
+fn parse_symbol_except<'a, E>(
+    marks: &'static [char],
+) -> impl FnMut(&'a str) -> IResult<&'a str, Symbol<'a>, E>
 where
     E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 {
     alt((
-        map(parse_trivia, Trivia),
+        map(parse_trivia_except(marks), Trivia),
         map(parse_quote, Quote),
+        map(parse_cjk, Ident),
         map(parse_ident, Ident),
-    ))(i)
+        map(parse_unknown, Trivia),
+    ))
+}
+
+const MD_MARKS: &[char] = &['*', '_', '`', '[', '!'];
+const HTML_MARKS: &[char] = &['<', '&'];
+const FOUNTAIN_MARKS: &[char] = &['*', '_', '/', '['];
+
+fn fail<'a, T, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, T, E> {
+    Err(nom::Err::Error(E::from_error_kind(i, ErrorKind::Verify)))
+}
+
+/// parses the remainder of the line, excluding the line break.
+fn parse_line_content<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
+    take_while1(|c: char| !matches!(c, '\r' | '\n' | '\u{2028}'))(i)
+}
+
+/// parses a line opening a fenced code block, with at least three backticks or tildes.
+fn parse_fence<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
+    let (rest, line) = parse_line_content(i)?;
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() < 4 && (trimmed.starts_with("```") || trimmed.starts_with("~~~"))
+    {
+        Ok((rest, line))
+    } else {
+        fail(i)
+    }
+}
+
+/// whether the line closes the fenced code block opened by `fence`.
+fn is_fence(line: &str, fence: &str) -> bool {
+    let line = line.trim();
+    let delim = fence.chars().next().unwrap_or('`');
+    line.len() >= fence.len() && line.chars().all(|c| c == delim)
+}
+
+/// parses a Markdown ATX heading until the end of the line.
+fn parse_heading<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
+    let level = i.chars().take_while(|c| *c == '#').count();
+    let after = &i[level..];
+    if (1..=6).contains(&level) && (after.is_empty() || after.starts_with([' ', '\t', '\r', '\n']))
+    {
+        let end = after.find(['\r', '\n']).unwrap_or(after.len());
+        Ok((&after[end..], &i[..level + end]))
+    } else {
+        fail(i)
+    }
+}
+
+/// parses the `>` marker of a block quote and the optional space following it.
+fn parse_block_quote<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
+    match i.strip_prefix('>') {
+        Some(rest) => {
+            let len = if rest.starts_with(' ') { 2 } else { 1 };
+            Ok((&i[len..], &i[..len]))
+        }
+        None => fail(i),
+    }
+}
+
+/// parses a bullet or ordered list item marker, followed by a space.
+fn parse_list_marker<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
+    let digits = i.chars().take_while(|c| c.is_ascii_digit()).count();
+    let len = match (digits, i[digits..].chars().next()) {
+        (0, Some('-' | '*' | '+')) => 1,
+        (1..=9, Some('.' | ')')) => digits + 1,
+        _ => return fail(i),
+    };
+    if i[len..].starts_with([' ', '\t']) {
+        Ok((&i[len..], &i[..len]))
+    } else {
+        fail(i)
+    }
+}
+
+/// parses a Markdown code span, delimited by the same number of backticks on a single line.
+fn parse_code_span<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
+    let ticks = i.chars().take_while(|c| *c == '`').count();
+    if ticks == 0 {
+        return fail(i);
+    }
+    let (_, line) = parse_line_content(i)?;
+    match line[ticks..].find(&i[..ticks]) {
+        Some(pos) if pos > 0 => {
+            let end = ticks + pos + ticks;
+            Ok((&i[end..], &i[..end]))
+        }
+        _ => fail(i),
+    }
+}
+
+/// parses text enclosed by the `delim`, not starting or ending with white space, on a single line.
+fn parse_enclosed<'a, E: ParseError<&'a str>>(
+    delim: &'static str,
+) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str, E> {
+    move |i: &'a str| {
+        let body = match i.strip_prefix(delim) {
+            Some(body) if body.starts_with(|c: char| !c.is_whitespace()) => body,
+            _ => return fail(i),
+        };
+        let (_, line) = parse_line_content(body)?;
+        let closing = line
+            .match_indices(delim)
+            .map(|(pos, _)| pos)
+            .find(|pos| *pos > 0 && !line[..*pos].ends_with(char::is_whitespace));
+        match closing {
+            Some(pos) => {
+                let end = delim.len() + pos + delim.len();
+                Ok((&i[end..], &i[..end]))
+            }
+            None => fail(i),
+        }
+    }
+}
+
+fn parse_strong<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
+    alt((parse_enclosed("**"), parse_enclosed("__")))(i)
+}
+
+fn parse_emphasis<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
+    alt((parse_enclosed("*"), parse_enclosed("_")))(i)
+}
+
+/// parses an inline Markdown link `[text](destination)` or image `![alt](source)`.
+fn parse_link<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
+    let start = if i.starts_with("![") { 1 } else { 0 };
+    if !i[start..].starts_with('[') {
+        return fail(i);
+    }
+    let (_, line) = parse_line_content(i)?;
+    let close = match line[start..].find("](") {
+        Some(pos) => start + pos,
+        None => return fail(i),
+    };
+    match line[close..].find(')') {
+        Some(pos) => {
+            let end = close + pos + 1;
+            Ok((&i[end..], &i[..end]))
+        }
+        None => fail(i),
+    }
+}
+
+/// parses an HTML start or end tag on a single line.
+fn parse_tag<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
+    let body = match i.strip_prefix('<') {
+        Some(body) => body.strip_prefix('/').unwrap_or(body),
+        None => return fail(i),
+    };
+    if !body.starts_with(|c: char| c.is_ascii_alphabetic() || c == '!') {
+        return fail(i);
+    }
+    let (_, line) = parse_line_content(i)?;
+    match line.find('>') {
+        Some(pos) => Ok((&i[pos + 1..], &i[..pos + 1])),
+        None => fail(i),
+    }
+}
+
+/// parses a named, decimal or hexadecimal HTML character reference.
+fn parse_entity<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
+    let body = match i.strip_prefix('&') {
+        Some(body) => body,
+        None => return fail(i),
+    };
+    let name = body
+        .strip_prefix("#x")
+        .or_else(|| body.strip_prefix("#X"))
+        .or_else(|| body.strip_prefix('#'))
+        .unwrap_or(body);
+    let len = name
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .count();
+    if len == 0 || !name[len..].starts_with(';') {
+        return fail(i);
+    }
+    let end = i.len() - name.len() + len + 1;
+    Ok((&i[end..], &i[..end]))
 }
 
 #[cfg(test)]
@@ -257,4 +1114,284 @@ mod test {
             assert_eq!($($parse)*(&format!("{}{}",$match, $tailing)), Ok(($tailing, $match)));
         };
     }
+
+    #[test]
+    fn parse_ident_whole_word() {
+        assert_parse!("well-known", " words", parse_ident::<()>);
+        assert_parse!("1984", ",", parse_ident::<()>);
+    }
+
+    #[test]
+    fn parse_space_excludes_line() {
+        assert_parse!(" \t", "\n", parse_space::<()>);
+    }
+
+    #[test]
+    fn parse_any_text() {
+        let text = Text::parse("tab\there\u{7}\n");
+        assert_eq!(
+            text.symbols,
+            vec![
+                Ident("tab"),
+                Trivia(Space("\t")),
+                Ident("here"),
+                Trivia(Symbol("\u{7}")),
+                Trivia(Line("\n")),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_words_with_digits() {
+        assert_parse!("3D", " model", parse_ident::<()>);
+        assert_parse!("2nd", ".", parse_ident::<()>);
+        assert_parse!("1-2", " times", parse_ident::<()>);
+        assert_parse!("x2", "", parse_ident::<()>);
+        assert_parse!("٣", " ", parse_ident::<()>);
+    }
+
+    #[test]
+    fn parse_space_of_all_widths() {
+        // the no-break, em and ideographic spaces, a vertical tab and a form feed.
+        assert_parse!("\u{a0}\u{2003}\u{3000}\u{b}\u{c}", "a", parse_space::<()>);
+        assert_parse!(" ", "\r\n", parse_space::<()>);
+        assert_parse!("\t", "\u{2028}", parse_space::<()>);
+        let text = Text::parse("a\u{a0}b\r\nc\u{2028}d");
+        assert_eq!(
+            text.symbols,
+            vec![
+                Ident("a"),
+                Trivia(Space("\u{a0}")),
+                Ident("b"),
+                Trivia(Line("\r\n")),
+                Ident("c"),
+                Trivia(Line("\u{2028}")),
+                Ident("d"),
+            ]
+        );
+    }
+
+    fn marks(text: &str, mode: Mode) -> Vec<Mark<'_>> {
+        SymbolIter::with_mode(text, mode)
+            .filter_map(|symbol| match symbol {
+                Symbol::Mark(mark) => Some(mark),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lines_skip_line_breaks() {
+        let text = Text::parse("one\ntwo\n\nfour");
+        let lines: Vec<usize> = text.lines().map(|line| line.text.len()).collect();
+        assert_eq!(lines, vec![1, 1, 0, 1]);
+    }
+
+    #[test]
+    fn lines_end_with_trailing_line_break() {
+        let text = Text::parse("one\ntwo\n");
+        let lines: Vec<(usize, usize)> = text
+            .lines()
+            .map(|line| (line.number(), line.text.len()))
+            .collect();
+        assert_eq!(lines, vec![(1, 1), (2, 1), (3, 0)]);
+        assert_eq!(Text::parse("\n").lines().count(), 2);
+        assert_eq!(Text::parse("").lines().count(), 0);
+    }
+
+    #[test]
+    fn parse_md_blocks() {
+        assert_eq!(
+            marks(
+                "# Title\n> - item\n1. first\n```rust\nlet a = *b*;\n```\n",
+                Mode::Md
+            ),
+            vec![
+                Mark::Heading("# Title"),
+                Mark::BlockQuote("> "),
+                Mark::ListMarker("-"),
+                Mark::ListMarker("1."),
+                Mark::Fence("```rust"),
+                Mark::Fence("let a = *b*;"),
+                Mark::Fence("```"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_md_inlines() {
+        assert_eq!(
+            marks(
+                "Some *em*, **strong**, `code`, [link](url) and snake_case_name.",
+                Mode::Md
+            ),
+            vec![
+                Mark::Emphasis("*em*"),
+                Mark::Strong("**strong**"),
+                Mark::Code("`code`"),
+                Mark::Link("[link](url)"),
+            ]
+        );
+        assert!(marks("2 * 3 * 4", Mode::Md).is_empty());
+    }
+
+    #[test]
+    fn parse_html_marks() {
+        assert_eq!(
+            marks(
+                "<p class=\"a\">Fish &amp; chips<!-- open\nclosed --></p>",
+                Mode::Html
+            ),
+            vec![
+                Mark::Tag("<p class=\"a\">"),
+                Mark::Entity("&amp;"),
+                Mark::Comment("<!-- open"),
+                Mark::Comment("closed -->"),
+                Mark::Tag("</p>"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_fountain_marks() {
+        assert_eq!(
+            marks(
+                "INT. HOUSE - DAY\n\nHe *runs*. /* cut\nthis */\n\nANNA\n(quietly)\nHi.\n\nCUT TO:\n",
+                Mode::Fountain
+            ),
+            vec![
+                Mark::Scene("INT. HOUSE - DAY"),
+                Mark::Emphasis("*runs*"),
+                Mark::Comment("/* cut"),
+                Mark::Comment("this */"),
+                Mark::Cue("ANNA"),
+                Mark::Parenthetical("(quietly)"),
+                Mark::Transition("CUT TO:"),
+            ]
+        );
+    }
+
+    #[test]
+    fn segment_cjk() {
+        let words: Vec<&str> = SymbolIter::new("東京タワーへ行きました。서울 여행abc")
+            .filter_map(|symbol| match symbol {
+                Ident(word) => Some(word),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            words,
+            vec![
+                "東",
+                "京",
+                "タワー",
+                "へ",
+                "行",
+                "きました",
+                "서울",
+                "여행",
+                "abc"
+            ]
+        );
+    }
+
+    #[test]
+    fn count_words() {
+        assert_eq!(Stats::of("Two words."), Stats { words: 2, chars: 9 });
+        assert_eq!(Stats::of("日本語です"), Stats { words: 5, chars: 5 });
+        assert_eq!(Stats::of("한국어 문장"), Stats { words: 2, chars: 5 });
+    }
+
+    #[test]
+    fn jump_words() {
+        let text = "私は猫";
+        assert_eq!(next_word(text, 0), 3);
+        assert_eq!(next_word(text, 3), 6);
+        assert_eq!(prev_word(text, 9), 6);
+        assert_eq!(next_word("a b", 2), 3);
+    }
+
+    #[test]
+    fn count_quote_delimiters() {
+        assert_eq!(Stats::of("\"Hi\" »Ja«"), Stats { words: 2, chars: 8 });
+        assert_eq!(Stats::of("<<Oui>>"), Stats { words: 1, chars: 7 });
+        assert_eq!(parse_quote::<()>("<<Oui>>!"), Ok(("!", "<<Oui>>")));
+        assert_eq!(unquote("<<Oui>>"), "Oui");
+        assert_eq!(unquote("»Ja«"), "Ja");
+    }
+
+    #[test]
+    fn step_logically() {
+        // a Hebrew word with its vowel points.
+        let text = "ab שָׁלוֹם\r\nc";
+        let mut pos = 3;
+        let mut stops = vec![];
+        while pos < text.len() {
+            pos = step(text, pos, true);
+            stops.push(pos);
+        }
+        assert_eq!(stops, [9, 11, 15, 17, 19, 20]);
+        assert_eq!(step(text, 9, false), 3);
+        assert_eq!(step(text, 19, false), 17);
+        assert_eq!(step(text, 0, false), 0);
+        assert_eq!(direction_at(text, 4), Direction::Ltr);
+        assert_eq!(direction_at("a\nשלום b", 3), Direction::Rtl);
+    }
+
+    #[test]
+    fn indent_lines() {
+        let text = "one\n\ttwo\n  three";
+        assert_eq!(
+            indent(text, 1, 6, "\t"),
+            ("\tone\n\t\ttwo\n  three".to_string(), 2, 8)
+        );
+        assert_eq!(
+            outdent(text, 5, 15, 4),
+            ("one\ntwo\nthree".to_string(), 4, 12)
+        );
+        assert_eq!(outdent(text, 0, 0, 4), (text.to_string(), 0, 0));
+        assert_eq!(indent("a\n", 2, 2, "  "), ("a\n  ".to_string(), 4, 4));
+    }
+
+    fn runs(text: &str) -> Vec<(Direction, String)> {
+        let text = Text::parse(text);
+        let line = text.lines().next().unwrap_or_default();
+        line.runs()
+            .iter()
+            .map(|run| (run.dir, run.text()))
+            .collect()
+    }
+
+    #[test]
+    fn detect_bidi_runs() {
+        use Direction::*;
+        assert_eq!(runs("plain text."), vec![(Ltr, "plain text.".to_string())]);
+        assert_eq!(
+            runs("He said שלום עולם to me."),
+            vec![
+                (Ltr, "He said ".to_string()),
+                (Rtl, "שלום עולם".to_string()),
+                (Ltr, " to me.".to_string()),
+            ]
+        );
+        assert_eq!(
+            runs("مرحبا, Hello world! سلام"),
+            vec![
+                (Rtl, "مرحبا, ".to_string()),
+                (Ltr, "Hello world".to_string()),
+                (Rtl, "! سلام".to_string()),
+            ]
+        );
+        assert_eq!(
+            runs("abcשלום"),
+            vec![(Ltr, "abc".to_string()), (Rtl, "שלום".to_string())]
+        );
+    }
+
+    #[test]
+    fn line_direction() {
+        let text = Text::parse("«שלום» hello\n123 text");
+        let dirs: Vec<Direction> = text.lines().map(|line| line.direction()).collect();
+        assert_eq!(dirs, vec![Direction::Rtl, Direction::Ltr]);
+    }
 }
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::{Element, Kind, Node, Notes};
+use crate::data::Sec;
+use crate::id;
+use crate::markup::Markup;
+use crate::sanitize::Policy;
+
+/// The anchor name of a heading: its lowercase words, joined by dashes.
+pub fn slug(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if (c.is_whitespace() || c == '-' || c == '_') && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        "heading".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+/// Assigns unique ids to the headings of a section.
+///
+/// The ids are the slugs of the headings with the prefix of the section, and a counter for
+/// repeated headings, so that they only change when the heading itself changes.
+#[derive(Clone, Debug, Default)]
+pub struct Anchors {
+    prefix: String,
+    used: HashMap<String, usize>,
+}
+
+impl Anchors {
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// The id of the next heading with the text.
+    pub fn anchor(&mut self, text: &str) -> String {
+        let slug = slug(text);
+        let count = self.used.entry(slug.clone()).or_default();
+        *count += 1;
+        match *count {
+            1 => format!("{}{}", self.prefix, slug),
+            n => format!("{}{}-{}", self.prefix, slug, n - 1),
+        }
+    }
+}
+
+/// A heading of a section.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Heading {
+    pub level: u8,
+    pub title: String,
+    pub anchor: String,
+}
+
+/// Sets the ids of the headings without one, and returns the headings in document order.
+pub fn anchor_headings(nodes: &mut [Node], anchors: &mut Anchors) -> Vec<Heading> {
+    let mut headings = vec![];
+    collect_headings(nodes, anchors, &mut headings);
+    headings
+}
+
+fn collect_headings(nodes: &mut [Node], anchors: &mut Anchors, headings: &mut Vec<Heading>) {
+    for node in nodes {
+        let Node::Element(Element { kind, children }) = node else {
+            continue;
+        };
+        match kind {
+            Kind::Heading { level, id } => {
+                let title: String = children.iter().map(Node::text).collect();
+                let title = title.trim().to_string();
+                let anchor = id.get_or_insert_with(|| anchors.anchor(&title)).clone();
+                headings.push(Heading {
+                    level: *level,
+                    title,
+                    anchor,
+                });
+            }
+            _ => collect_headings(children, anchors, headings),
+        }
+    }
+}
+
+impl Markup {
+    /// The headings of the markup, with the anchors of the headings rendered in `scope`.
+    pub fn headings(&self, scope: &str) -> Vec<Heading> {
+        let mut nodes = self.to_ast(&mut Notes::new(scope), &Policy::default());
+        anchor_headings(&mut nodes, &mut Anchors::new(scope))
+    }
+}
+
+/// An entry of a table of contents, with the entries of the headings below it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub title: String,
+    pub anchor: String,
+    pub children: Vec<Entry>,
+}
+
+impl Entry {
+    pub fn new(title: &str, anchor: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            anchor: anchor.to_string(),
+            children: vec![],
+        }
+    }
+
+    /// Nests the headings below the entry by their levels.
+    ///
+    /// A heading skipping levels is nested below the previous heading of a higher level.
+    pub fn with_headings(self, headings: &[Heading]) -> Self {
+        let mut spine: Vec<(u8, Entry)> = vec![(0, self)];
+        for heading in headings {
+            while spine.len() > 1 && spine[spine.len() - 1].0 >= heading.level {
+                close(&mut spine);
+            }
+            spine.push((heading.level, Entry::new(&heading.title, &heading.anchor)));
+        }
+        while spine.len() > 1 {
+            close(&mut spine);
+        }
+        let (_, entry) = spine.pop().expect("the entry stays on the spine");
+        entry
+    }
+}
+
+/// The anchor of a section in a document.
+pub fn section_anchor(id: id::Sec) -> String {
+    format!("section-{}", id.value)
+}
+
+/// The anchor of a document.
+pub fn document_anchor(id: id::Doc) -> String {
+    format!("document-{}", id.value)
+}
+
+/// The table of contents of a document, with the headings of each section below its title.
+///
+/// The headings of a section are anchored in the scope of the section anchor followed by a
+/// dash, as when rendering the sections of the document.
+pub fn contents<'a>(sections: impl IntoIterator<Item = &'a Sec>) -> Vec<Entry> {
+    sections
+        .into_iter()
+        .map(|sec| {
+            let anchor = section_anchor(sec.head.id);
+            let headings = sec.content.headings(&format!("{}-", anchor));
+            Entry::new(&sec.head.title, &anchor).with_headings(&headings)
+        })
+        .collect()
+}
+
+fn close(spine: &mut Vec<(u8, Entry)>) {
+    if let Some((_, entry)) = spine.pop() {
+        if let Some((_, parent)) = spine.last_mut() {
+            parent.children.push(entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slug("Hello, World!"), "hello-world");
+        assert_eq!(slug("  Über  die_Brücke - 2 "), "über-die-brücke-2");
+        assert_eq!(slug("?!"), "heading");
+
+        let mut anchors = Anchors::new("section-1-");
+        assert_eq!(anchors.anchor("Intro"), "section-1-intro");
+        assert_eq!(anchors.anchor("Intro"), "section-1-intro-1");
+        assert_eq!(anchors.anchor("Other"), "section-1-other");
+    }
+
+    #[test]
+    fn anchors_headings() {
+        let markup = Markup::md_str("# Intro\n\n> ## Quoted *heading*\n\n# Intro\n\ntext");
+        assert_eq!(
+            markup.render_html(&Policy::default()),
+            "<h1 id=\"intro\">Intro</h1>\n\
+            <blockquote><h2 id=\"quoted-heading\">Quoted <em>heading</em></h2>\n</blockquote>\n\
+            <h1 id=\"intro-1\">Intro</h1>\n<p>text</p>\n"
+        );
+        assert_eq!(
+            markup.headings("s-"),
+            vec![
+                Heading {
+                    level: 1,
+                    title: "Intro".to_string(),
+                    anchor: "s-intro".to_string()
+                },
+                Heading {
+                    level: 2,
+                    title: "Quoted heading".to_string(),
+                    anchor: "s-quoted-heading".to_string()
+                },
+                Heading {
+                    level: 1,
+                    title: "Intro".to_string(),
+                    anchor: "s-intro-1".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn document_contents() {
+        let doc = id::Doc::new(0, id::Proj::new(0));
+        let sec = |value, title: &str, text: &str| {
+            crate::data::SecHead::new(id::Sec::new(value, doc), value, title.to_string())
+                .body(Markup::md_str(text))
+        };
+        let sections = [sec(1, "One", "## Start\n\ntext"), sec(2, "Two", "text")];
+        let mut one = Entry::new("One", "section-1");
+        one.children = vec![Entry::new("Start", "section-1-start")];
+        assert_eq!(
+            contents(&sections),
+            vec![one, Entry::new("Two", "section-2")]
+        );
+    }
+
+    #[test]
+    fn nests_entries() {
+        let heading = |level, title: &str| Heading {
+            level,
+            title: title.to_string(),
+            anchor: slug(title),
+        };
+        let entry = Entry::new("Section", "section").with_headings(&[
+            heading(2, "A"),
+            heading(4, "A.1"),
+            heading(3, "A.2"),
+            heading(1, "B"),
+        ]);
+        let mut a = Entry::new("A", "a");
+        a.children = vec![Entry::new("A.1", "a1"), Entry::new("A.2", "a2")];
+        let mut expected = Entry::new("Section", "section");
+        expected.children = vec![a, Entry::new("B", "b")];
+        assert_eq!(entry, expected);
+    }
+}
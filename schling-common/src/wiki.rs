@@ -0,0 +1,363 @@
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
+use crate::ast::{placeholder, verbatim_ranges, Element, Kind, Node};
+use crate::data::{DocHead, Sec, SecHead};
+use crate::id;
+use crate::markup::MarkupLang;
+use crate::toc;
+
+/// A wiki link `[[Target]]` or `[[Target|label]]` of Markdown, whose target is the title of a
+/// section or document, or `Document/Section`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WikiLink {
+    pub target: String,
+    pub label: Option<String>,
+}
+
+/// The section or document a wiki link refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    Doc(id::Doc),
+    Sec(id::Sec),
+}
+
+impl Target {
+    /// The in-app link to the target.
+    pub fn href(self) -> String {
+        match self {
+            Target::Doc(id) => format!("#{}", toc::document_anchor(id)),
+            Target::Sec(id) => format!("#{}", toc::section_anchor(id)),
+        }
+    }
+
+    pub fn doc(self) -> id::Doc {
+        match self {
+            Target::Doc(id) => id,
+            Target::Sec(id) => id.doc,
+        }
+    }
+}
+
+impl Display for Target {
+    /// The id of the target.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Doc(id) => write!(f, "{}", id),
+            Target::Sec(id) => write!(f, "{}", id),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    target: Target,
+    title: String,
+    aliases: Vec<String>,
+}
+
+/// The titles of the documents and sections of a project, resolving wiki links.
+///
+/// A link resolves by the current titles first, then by the previous titles, so that links
+/// keep working when their target is renamed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Titles {
+    entries: Vec<Entry>,
+}
+
+/// Compares titles ignoring case and repeated white space.
+fn normalize(title: &str) -> String {
+    title
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+impl Titles {
+    pub fn insert_doc(&mut self, head: &DocHead) {
+        self.insert(Target::Doc(head.id), &head.title, &head.aliases);
+    }
+
+    pub fn insert_sec(&mut self, head: &SecHead) {
+        self.insert(Target::Sec(head.id), &head.title, &head.aliases);
+    }
+
+    fn insert(&mut self, target: Target, title: &str, aliases: &[String]) {
+        self.entries.retain(|entry| entry.target != target);
+        self.entries.push(Entry {
+            target,
+            title: title.to_string(),
+            aliases: aliases.to_vec(),
+        });
+    }
+
+    /// The current title of the target.
+    pub fn title(&self, target: Target) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|entry| entry.target == target)
+            .map(|entry| entry.title.as_str())
+    }
+
+    fn find(&self, name: &str, filter: impl Fn(Target) -> bool) -> Option<Target> {
+        let name = normalize(name);
+        let candidates = || self.entries.iter().filter(|entry| filter(entry.target));
+        candidates()
+            .find(|entry| normalize(&entry.title) == name)
+            .or_else(|| {
+                candidates().find(|entry| entry.aliases.iter().any(|a| normalize(a) == name))
+            })
+            .map(|entry| entry.target)
+    }
+
+    fn find_sec(&self, name: &str, doc: Option<id::Doc>) -> Option<Target> {
+        self.find(name, |target| {
+            matches!(target, Target::Sec(_)) && doc.is_none_or(|doc| target.doc() == doc)
+        })
+    }
+
+    /// Resolves the target of a link in a section of the document `from`.
+    ///
+    /// A title refers to a section of the same document, then to a section of another
+    /// document, then to a document.
+    pub fn resolve(&self, link: &str, from: id::Doc) -> Option<Target> {
+        let found = self
+            .find_sec(link, Some(from))
+            .or_else(|| self.find_sec(link, None))
+            .or_else(|| self.find(link, |target| matches!(target, Target::Doc(_))));
+        if found.is_some() {
+            return found;
+        }
+        link.match_indices('/').find_map(|(i, _)| {
+            let doc = self.find(&link[..i], |target| matches!(target, Target::Doc(_)))?;
+            self.find_sec(&link[i + 1..], Some(doc.doc()))
+        })
+    }
+}
+
+/// The wiki links of the Markdown source, outside of code and HTML, with their ranges.
+fn scan(src: &str) -> Vec<(Range<usize>, WikiLink)> {
+    let verbatim = verbatim_ranges(src);
+    let bytes = src.as_bytes();
+    let mut links = vec![];
+    let mut pos = 0;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\\' => pos += 2,
+            b'[' if src[pos..].starts_with("[[")
+                && !verbatim.iter().any(|range| range.contains(&pos)) =>
+            {
+                let start = pos + 2;
+                let inner = src[start..]
+                    .find("]]")
+                    .map(|len| &src[start..start + len])
+                    .filter(|inner| !inner.contains(['[', ']', '\n']))
+                    .filter(|inner| !inner.trim().is_empty());
+                match inner {
+                    Some(inner) => {
+                        let (target, label) = match inner.split_once('|') {
+                            Some((target, label)) => (target, Some(label.trim().to_string())),
+                            None => (inner, None),
+                        };
+                        let end = start + inner.len() + 2;
+                        let link = WikiLink {
+                            target: target.trim().to_string(),
+                            label: label.filter(|label| !label.is_empty()),
+                        };
+                        links.push((pos..end, link));
+                        pos = end;
+                    }
+                    None => pos = start,
+                }
+            }
+            _ => pos += 1,
+        }
+    }
+    links
+}
+
+/// The wiki links of the Markdown source.
+pub fn links(src: &str) -> Vec<WikiLink> {
+    scan(src).into_iter().map(|(_, link)| link).collect()
+}
+
+/// Replaces the wiki links of the Markdown source by placeholders of their nodes, linking to
+/// their targets when the titles of the project are known.
+pub(crate) fn extract(
+    src: &str,
+    inlines: &mut Vec<Node>,
+    titles: Option<(&Titles, id::Doc)>,
+) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut copied = 0;
+    for (range, link) in scan(src) {
+        out.push_str(&src[copied..range.start]);
+        out.push_str(&placeholder(inlines, node(&link, titles)));
+        copied = range.end;
+    }
+    out.push_str(&src[copied..]);
+    out
+}
+
+fn element(name: &str, attrs: &[(&str, String)], text: String) -> Node {
+    let attrs = attrs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.clone()))
+        .collect();
+    let mut el = Element::new(Kind::Html {
+        name: name.to_string(),
+        attrs,
+    });
+    el.children.push(Node::Text(text));
+    el.into()
+}
+
+/// A link to the target, labeled by its current title, or a marker of a missing target.
+fn node(link: &WikiLink, titles: Option<(&Titles, id::Doc)>) -> Node {
+    let label = || link.label.clone().unwrap_or_else(|| link.target.clone());
+    let Some((titles, from)) = titles else {
+        return element("span", &[("class", "wiki-link".to_string())], label());
+    };
+    match titles.resolve(&link.target, from) {
+        Some(target) => {
+            let text = link
+                .label
+                .clone()
+                .or_else(|| titles.title(target).map(str::to_string))
+                .unwrap_or_else(label);
+            let attrs = [
+                ("class", "wiki-link".to_string()),
+                ("href", target.href()),
+                ("data-target", target.to_string()),
+            ];
+            element("a", &attrs, text)
+        }
+        None => {
+            let attrs = [
+                ("class", "wiki-link missing".to_string()),
+                (
+                    "title",
+                    format!("Nothing is titled \u{201c}{}\u{201d}", link.target),
+                ),
+            ];
+            element("span", &attrs, label())
+        }
+    }
+}
+
+/// The heads of the Markdown sections linking to the target, in the order of the sections.
+pub fn backlinks<'a>(
+    target: Target,
+    sections: impl IntoIterator<Item = &'a Sec>,
+    titles: &Titles,
+) -> Vec<&'a SecHead> {
+    sections
+        .into_iter()
+        .filter(|sec| sec.content.lang == MarkupLang::Md && target != Target::Sec(sec.head.id))
+        .filter(|sec| {
+            let from = sec.head.id.doc;
+            links(&sec.content.text)
+                .iter()
+                .any(|link| titles.resolve(&link.target, from) == Some(target))
+        })
+        .map(|sec| &sec.head)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ast::Notes;
+    use crate::data::chapter;
+    use crate::markup::Markup;
+    use crate::sanitize::Policy;
+
+    fn project() -> (Titles, Vec<Sec>) {
+        let (novel, mut sections) = chapter(
+            1,
+            "Novel",
+            &[
+                ("Intro", "See [[Setting]] and [[Draft/Intro|the draft]]."),
+                ("Setting", "Back to [[intro]], `[[Setting]]`."),
+            ],
+        );
+        let (draft, draft_sections) = chapter(2, "Draft", &[("Intro", "Nothing to see.")]);
+        sections.extend(draft_sections);
+        let mut titles = Titles::default();
+        titles.insert_doc(&novel.head);
+        titles.insert_doc(&draft.head);
+        for sec in &sections {
+            titles.insert_sec(&sec.head);
+        }
+        (titles, sections)
+    }
+
+    #[test]
+    fn resolves_links() {
+        let (mut titles, sections) = project();
+        let doc = sections[0].head.id.doc;
+        let intro = Target::Sec(sections[0].head.id);
+        let setting = Target::Sec(sections[1].head.id);
+        let draft_intro = Target::Sec(sections[2].head.id);
+        assert_eq!(titles.resolve("  INTRO ", doc), Some(intro));
+        assert_eq!(
+            titles.resolve("Intro", draft_intro.doc()),
+            Some(draft_intro)
+        );
+        assert_eq!(titles.resolve("Draft/Intro", doc), Some(draft_intro));
+        assert_eq!(titles.resolve("Novel", doc), Some(Target::Doc(doc)));
+        assert_eq!(titles.resolve("Novel/Nothing", doc), None);
+
+        let renamed = sections[1].head.own_with_title("Places".to_string());
+        assert_eq!(renamed.aliases, vec!["Setting".to_string()]);
+        titles.insert_sec(&renamed);
+        assert_eq!(titles.resolve("Setting", doc), Some(setting));
+        assert_eq!(titles.resolve("Places", doc), Some(setting));
+        assert_eq!(titles.title(setting), Some("Places"));
+    }
+
+    #[test]
+    fn renders_links() {
+        let (titles, sections) = project();
+        let doc = sections[0].head.id.doc;
+        let markup = Markup::md_str("[[Setting]], [[Draft/Intro|draft]], [[Gone]] and `[[x]]`");
+        let mut notes = Notes::default().with_titles(titles, doc);
+        let html = crate::render::ToHtml::to_html(&markup.to_ast(&mut notes, &Policy::default()));
+        assert_eq!(
+            html,
+            "<p><a class=\"wiki-link\" href=\"#section-1\" data-target=\"000000010000000100000001\">\
+            Setting</a>, <a class=\"wiki-link\" href=\"#section-0\" \
+            data-target=\"000000010000000200000000\">draft</a>, \
+            <span class=\"wiki-link missing\" title=\"Nothing is titled “Gone”\">Gone</span> \
+            and <code>[[x]]</code></p>\n"
+        );
+        assert_eq!(
+            markup.render_html(&Policy::default()),
+            "<p><span class=\"wiki-link\">Setting</span>, <span class=\"wiki-link\">draft</span>, \
+            <span class=\"wiki-link\">Gone</span> and <code>[[x]]</code></p>\n"
+        );
+    }
+
+    #[test]
+    fn finds_backlinks() {
+        let (titles, sections) = project();
+        let heads = |target| {
+            backlinks(target, &sections, &titles)
+                .into_iter()
+                .map(|head| head.id)
+                .collect::<Vec<_>>()
+        };
+        let ids: Vec<id::Sec> = sections.iter().map(|sec| sec.head.id).collect();
+        assert_eq!(heads(Target::Sec(ids[0])), vec![ids[1]]);
+        assert_eq!(heads(Target::Sec(ids[1])), vec![ids[0]]);
+        assert_eq!(heads(Target::Sec(ids[2])), vec![ids[0]]);
+        assert_eq!(
+            links("[[a|b]] [[ ]] [[c\nd]] \\[[e]]"),
+            vec![WikiLink {
+                target: "a".to_string(),
+                label: Some("b".to_string())
+            }]
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use closure::closure;
+use gloo_file::{callbacks::FileReader, Blob, File, ObjectUrl};
+use schling_common::{backup::Backup, id};
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlAnchorElement, HtmlInputElement};
+use yew::prelude::*;
+
+use crate::data;
+
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct Props {
+    pub id: id::Doc,
+}
+
+/// Saves the archive as a file in the downloads of the browser.
+fn download(bytes: &[u8], name: &str) {
+    let url = ObjectUrl::from(Blob::new_with_options(bytes, Some("application/zip")));
+    let link = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.create_element("a").ok())
+        .and_then(|link| link.dyn_into::<HtmlAnchorElement>().ok());
+    if let Some(link) = link {
+        link.set_href(&url);
+        link.set_download(name);
+        link.click();
+    }
+}
+
+/// Downloads the project as a backup archive, and restores it from one.
+#[function_component(ProjBackup)]
+pub fn proj_backup(props: &Props) -> Html {
+    let Props { id } = props.clone();
+    let error = use_state(|| None::<String>);
+    // the pending read of the archive, cancelled when dropped.
+    let reader = use_mut_ref(|| None::<FileReader>);
+
+    let onclick = Callback::from(closure!(clone error, |_: MouseEvent| {
+        let backup = data::backup(id);
+        let name = match backup.proj.head.title.trim() {
+            "" => "schling.backup.zip".to_string(),
+            title => format!("{}.backup.zip", title),
+        };
+        match backup.to_bytes() {
+            Ok(bytes) => download(&bytes, &name),
+            Err(e) => error.set(Some(e.to_string())),
+        }
+    }));
+
+    let onchange = Callback::from(closure!(clone error, |e: Event| {
+        let Some(file) = e
+            .target_dyn_into::<HtmlInputElement>()
+            .and_then(|input| input.files())
+            .and_then(|files| files.get(0))
+        else {
+            return;
+        };
+        let error = error.clone();
+        let read = gloo_file::callbacks::read_as_bytes(&File::from(file), move |bytes| {
+            let restored = bytes
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| Backup::read(&bytes).map_err(|e| e.to_string()));
+            match restored {
+                Ok(backup) if backup.proj.head.id == id.proj => {
+                    data::restore(backup);
+                    if let Some(window) = web_sys::window() {
+                        let _ = window.location().reload();
+                    }
+                }
+                Ok(_) => error.set(Some("The backup is not one of this project.".to_string())),
+                Err(e) => error.set(Some(e)),
+            }
+        });
+        *reader.borrow_mut() = Some(read);
+    }));
+
+    html! {
+    <div class="box">
+        <div class="buttons">
+            <button class="button is-small" {onclick}>{"Download backup"}</button>
+            <label class="button is-small">
+                {"Restore from backup"}
+                <input class="is-hidden" type="file" accept=".zip,application/zip" {onchange}/>
+            </label>
+        </div>
+        { for error.as_ref().map(|e| html! { <p class="help is-danger">{e}</p> }) }
+    </div>
+    }
+}
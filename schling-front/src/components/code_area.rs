@@ -1,10 +1,15 @@
-use gloo_console::warn;
+use std::collections::BTreeSet;
+
+use closure::closure;
 use schling_common::{
-    key::{KeyRegistrar, Shortcut},
-    source::{Line, Text, ToDom},
+    fountain,
+    key::query_parents,
+    markup::MarkupLang,
+    source::{self, Direction, Line, Text, ToDom},
 };
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlElement, Node};
 use yew::prelude::*;
-use yew_agent::Dispatched;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum IndentUsing {
@@ -27,7 +32,7 @@ impl IndentUsing {
     }
 }
 
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct CodeStyle {
     indent_width: u32,
     indent_kind: IndentUsing,
@@ -35,16 +40,41 @@ pub struct CodeStyle {
     tailing_line_break: bool,
 }
 
+impl Default for CodeStyle {
+    fn default() -> Self {
+        Self {
+            indent_width: 4,
+            indent_kind: IndentUsing::default(),
+            bracket_pairs: vec![],
+            tailing_line_break: false,
+        }
+    }
+}
+
+impl CodeStyle {
+    /// The text inserted by a level of indentation.
+    fn indent_unit(&self) -> String {
+        let count = match self.indent_kind {
+            IndentUsing::Spaces => self.indent_width as usize,
+            IndentUsing::Tabs => 1,
+        };
+        self.indent_kind.char().to_string().repeat(count)
+    }
+}
+
 pub enum SourceChanged {
-    Change(Text),
-    Insert(Text),
-    Complete(Text),
+    Change(String),
+    Insert(String),
+    Complete(String),
 }
 
 #[derive(Clone, Debug, PartialEq, Properties)]
 pub struct Props {
     #[prop_or_default]
-    pub value: Text,
+    pub value: String,
+    /// The markup language highlighted in addition to prose.
+    #[prop_or_default]
+    pub lang: MarkupLang,
     #[prop_or_default]
     pub id: String,
     #[prop_or_default]
@@ -52,6 +82,11 @@ pub struct Props {
     #[prop_or_default]
     pub placeholder: String,
     #[prop_or_default]
+    pub style: CodeStyle,
+    /// The character names suggested when typing a Fountain character cue.
+    #[prop_or_default]
+    pub completions: BTreeSet<String>,
+    #[prop_or_default]
     pub onchange: Callback<(Event, SourceChanged)>,
 }
 
@@ -59,40 +94,117 @@ pub struct Props {
 pub fn code_area(props: &Props) -> Html {
     let Props {
         value,
+        lang,
         id,
         class,
         placeholder,
+        style,
+        completions,
         onchange,
     } = props.clone();
+    let table = use_node_ref();
+    // the selection to restore once the changed value is rendered.
+    let restore = use_mut_ref(|| None::<(usize, usize)>);
 
-    use_effect(move || {
-        let mut hotkeys = KeyRegistrar::dispatcher();
-        warn!("reg hotkeys");
-        hotkeys.send(Shortcut::new_always(
-            "Tab",
-            Callback::from(|e: KeyboardEvent| {
-                indent(e.clone());
-                e.prevent_default();
-                e.set_cancel_bubble(true);
-            }),
-        ));
-        hotkeys.send(Shortcut::new_always(
-            "Shift Tab",
-            Callback::from(|e: KeyboardEvent| {
-                outdent(e.clone());
-                e.prevent_default();
-                e.set_cancel_bubble(true);
-            }),
-        ));
-        || {}
-    });
+    use_effect_with_deps(
+        closure!(clone table, clone restore, |value: &String| {
+            if let (Some((start, end)), Some(table)) =
+                (restore.borrow_mut().take(), table.cast::<HtmlElement>())
+            {
+                select(&table, value, start, end);
+            }
+            || {}
+        }),
+        value.clone(),
+    );
 
+    let onkeydown = Callback::from(
+        closure!(clone value, clone table, clone onchange, |e: KeyboardEvent| {
+            let Some(table) = table.cast::<HtmlElement>() else {
+                return;
+            };
+            let Some((start, end)) = selection(&table, &value) else {
+                return;
+            };
+            let change = |(text, start, end): (String, usize, usize)| {
+                *restore.borrow_mut() = Some((start, end));
+                onchange.emit((e.clone().into(), SourceChanged::Change(text)));
+            };
+            match (e.key().as_str(), e.ctrl_key(), e.shift_key()) {
+                ("Tab", _, false) => change(source::indent(&value, start, end, &style.indent_unit())),
+                ("Tab", _, true) => change(source::outdent(
+                    &value,
+                    start,
+                    end,
+                    style.indent_width as usize,
+                )),
+                ("ArrowLeft", true, false) => {
+                    let pos = source::prev_word(&value, start);
+                    select(&table, &value, pos, pos);
+                }
+                ("ArrowRight", true, false) => {
+                    let pos = source::next_word(&value, end);
+                    select(&table, &value, pos, pos);
+                }
+                // moves in the logical order of the text, right meaning backwards in right to
+                // left lines.
+                (key @ ("ArrowLeft" | "ArrowRight"), false, false) => {
+                    let rtl = source::direction_at(&value, start) == Direction::Rtl;
+                    let forward = (key == "ArrowRight") != rtl;
+                    let pos = match (start == end, forward) {
+                        (true, _) => source::step(&value, start, forward),
+                        (false, true) => end,
+                        (false, false) => start,
+                    };
+                    select(&table, &value, pos, pos);
+                }
+                ("Backspace", true, _) => {
+                    let from = match start == end {
+                        true => source::prev_word(&value, start),
+                        false => start,
+                    };
+                    change((format!("{}{}", &value[..from], &value[end..]), from, from));
+                }
+                _ => return,
+            }
+            e.prevent_default();
+        }),
+    );
+
+    let suggestions = match lang {
+        MarkupLang::Fountain => render_completions(&value, &completions, &onchange),
+        _ => html! {},
+    };
     html! {
-        <table {id} {class}>
+        <>
+        <table {id} {class} ref={table} tabindex="0" {onkeydown}>
             <tbody>
-                {for value.symbols().map(render_line)}
+                {for Text::parse_as(&value, lang.into()).lines().map(render_line)}
             </tbody>
         </table>
+        {suggestions}
+        </>
+    }
+}
+
+/// Suggests the known character names completing the cue on the last line.
+fn render_completions(
+    value: &str,
+    completions: &BTreeSet<String>,
+    onchange: &Callback<(Event, SourceChanged)>,
+) -> Html {
+    let line = value.lines().last().unwrap_or_default();
+    let typed = line.trim_start().trim_start_matches('@').len();
+    html! {
+        <div class="tags completions">
+        { for fountain::complete_character(line, completions).into_iter().map(|name| {
+            let rest = name[typed..].to_string();
+            let onclick = onchange.reform(move |e: MouseEvent| {
+                (e.into(), SourceChanged::Complete(rest.clone()))
+            });
+            html!(<button class="tag is-link is-light" {onclick}>{name}</button>)
+        }) }
+        </div>
     }
 }
 
@@ -105,10 +217,86 @@ fn render_line(line: Line) -> Html {
     }
 }
 
-fn indent(e: KeyboardEvent) {
-    warn!("indent")
+/// The selection as byte offsets into the value, when it is inside the table.
+fn selection(table: &HtmlElement, value: &str) -> Option<(usize, usize)> {
+    let selection = web_sys::window()?.get_selection().ok()??;
+    let anchor = offset(
+        table,
+        value,
+        selection.anchor_node()?,
+        selection.anchor_offset(),
+    )?;
+    let focus = offset(
+        table,
+        value,
+        selection.focus_node()?,
+        selection.focus_offset(),
+    )?;
+    Some((anchor.min(focus), anchor.max(focus)))
+}
+
+/// The byte offset into the value of a position in the code of a line.
+fn offset(table: &HtmlElement, value: &str, node: Node, offset: u32) -> Option<usize> {
+    if !table.contains(Some(&node)) {
+        return None;
+    }
+    let cell: HtmlElement = query_parents(Some(node.clone()), |node| {
+        let code = node
+            .dyn_ref::<HtmlElement>()
+            .is_some_and(|element| element.id().starts_with("LC"));
+        match code {
+            true => Ok(node.unchecked_into()),
+            false => Err(node),
+        }
+    })?;
+    let number: usize = cell.id()["LC".len()..].parse().ok()?;
+    let range = web_sys::window()?.document()?.create_range().ok()?;
+    range.select_node_contents(&cell).ok()?;
+    range.set_end(&node, offset).ok()?;
+    let column = String::from(range.to_string()).len();
+    let start = *source::line_starts(value).get(number.checked_sub(1)?)?;
+    Some((start + column).min(value.len()))
 }
 
-fn outdent(e: KeyboardEvent) {
-    warn!("outdent")
+/// Selects the text between the byte offsets into the value.
+fn select(table: &HtmlElement, value: &str, start: usize, end: usize) {
+    let (Some(start), Some(end)) = (position(table, value, start), position(table, value, end))
+    else {
+        return;
+    };
+    if let Some(selection) = web_sys::window().and_then(|window| window.get_selection().ok()?) {
+        let _ = selection.set_base_and_extent(&start.0, start.1, &end.0, end.1);
+    }
+}
+
+/// The node and the offset within it of a byte offset into the value.
+fn position(table: &HtmlElement, value: &str, pos: usize) -> Option<(Node, u32)> {
+    let starts = source::line_starts(value);
+    let line = starts.partition_point(|start| *start <= pos) - 1;
+    let cell: Node = table
+        .query_selector(&format!("#LC{}", line + 1))
+        .ok()??
+        .into();
+    let mut column = pos - starts[line];
+    text_position(&cell, &mut column).or(Some((cell, 0)))
+}
+
+/// The text node holding the byte `column` of the text of the node, counting down the bytes of
+/// the texts before it.
+fn text_position(node: &Node, column: &mut usize) -> Option<(Node, u32)> {
+    if node.node_type() == Node::TEXT_NODE {
+        let text = node.text_content().unwrap_or_default();
+        if *column <= text.len() {
+            let offset = text
+                .get(..*column)
+                .map_or(0, |text| text.encode_utf16().count());
+            return Some((node.clone(), offset as u32));
+        }
+        *column -= text.len();
+        return None;
+    }
+    let children = node.child_nodes();
+    (0..children.length())
+        .filter_map(|i| children.item(i))
+        .find_map(|child| text_position(&child, column))
 }
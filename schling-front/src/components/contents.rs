@@ -0,0 +1,93 @@
+use schling_common::toc::Entry;
+use wasm_bindgen::{closure::Closure, JsCast};
+use yew::prelude::*;
+
+/// The distance from the top of the window, in pixels, above which a heading is scrolled
+/// past.
+const SPY_OFFSET: f64 = 80.0;
+
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct Props {
+    pub entries: Vec<Entry>,
+}
+
+fn anchors(entries: &[Entry], out: &mut Vec<String>) {
+    for entry in entries {
+        out.push(entry.anchor.clone());
+        anchors(&entry.children, out);
+    }
+}
+
+/// The anchor of the last heading scrolled past, or of the first one.
+fn current(anchors: &[String]) -> Option<String> {
+    let document = web_sys::window()?.document()?;
+    let mut current = anchors.first().cloned();
+    for anchor in anchors {
+        match document.get_element_by_id(anchor) {
+            Some(el) if el.get_bounding_client_rect().top() <= SPY_OFFSET => {
+                current = Some(anchor.clone())
+            }
+            Some(_) => break,
+            None => {}
+        }
+    }
+    current
+}
+
+/// A collapsible table of contents, highlighting the entry being read.
+#[function_component(Contents)]
+pub fn contents(props: &Props) -> Html {
+    let active = use_state_eq(String::new);
+    let mut all = vec![];
+    anchors(&props.entries, &mut all);
+    {
+        let active = active.clone();
+        use_effect_with_deps(
+            move |all: &Vec<String>| {
+                let all = all.clone();
+                let spy = Closure::<dyn Fn()>::new(move || {
+                    if let Some(anchor) = current(&all) {
+                        active.set(anchor);
+                    }
+                });
+                let window = web_sys::window();
+                if let Some(window) = &window {
+                    let _ = window
+                        .add_event_listener_with_callback("scroll", spy.as_ref().unchecked_ref());
+                }
+                move || {
+                    if let Some(window) = window {
+                        let _ = window.remove_event_listener_with_callback(
+                            "scroll",
+                            spy.as_ref().unchecked_ref(),
+                        );
+                    }
+                }
+            },
+            all,
+        );
+    }
+    html! {
+    <ul class="menu-list">
+        { for props.entries.iter().map(|entry| render_entry(entry, &active)) }
+    </ul>
+    }
+}
+
+fn render_entry(entry: &Entry, active: &str) -> Html {
+    let class = (entry.anchor == active).then_some("is-active");
+    let link = html! {
+        <a {class} href={format!("#{}", entry.anchor)}>{&entry.title}</a>
+    };
+    if entry.children.is_empty() {
+        return html!(<li>{link}</li>);
+    }
+    html! {
+    <li>
+        <details open=true>
+            <summary>{link}</summary>
+            <ul>{ for entry.children.iter().map(|child| render_entry(child, active)) }</ul>
+        </details>
+    </li>
+    }
+}
@@ -0,0 +1,59 @@
+use schling_common::{ast::Notes, id, sanitize::Policy, source::ToDom};
+use yew::prelude::*;
+
+use super::sec::section_id;
+use crate::data::{self, Head};
+
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct Props {
+    pub id: id::Doc,
+}
+
+/// Renders all sections of the document for reading, with the footnotes of every section
+/// numbered continuously and collected at the end as endnotes.
+#[function_component(DocView)]
+pub fn doc_view(props: &Props) -> Html {
+    let Props { id } = props.clone();
+    let document = match id.load() {
+        Some(document) => document,
+        None => return html!(),
+    };
+    let sections: Vec<data::Sec> = document
+        .content
+        .iter()
+        .filter_map(|s| s.id.load())
+        .collect();
+
+    let (titles, _) = data::titles(id);
+    let mut notes = Notes::default().with_titles(titles, id);
+    let content: Vec<Html> = sections
+        .into_iter()
+        .map(|section| {
+            let id = section.head.id;
+            notes.set_scope(&format!("{}-", section_id(id)));
+            html! {
+            <section id={section_id(id)}>
+                <h2>{section.head.title}</h2>
+                {section.content.to_dom_with(&mut notes, &Policy::default())}
+            </section>
+            }
+        })
+        .collect();
+    let endnotes = if notes.is_empty() {
+        html!()
+    } else {
+        html! {
+        <>
+            <h2>{"Notes"}</h2>
+            {notes.to_dom()}
+        </>
+        }
+    };
+    html! {
+    <article class="box content">
+        <h1>{document.head.title}</h1>
+        {content}
+        {endnotes}
+    </article>
+    }
+}
@@ -1,10 +1,17 @@
+pub mod backup;
 pub mod code_area;
+pub mod contents;
+pub mod doc;
+pub mod search;
 pub mod sec;
 pub mod sec_list;
+pub mod theme;
 
 use schling_common::{markup::Markup, source::ToDom};
 use yew::prelude::*;
 
+use theme::CodeTheme;
+
 #[macro_export]
 macro_rules! pass_thru {
     ($props:ident.$name:ident, $($closure:tt)*) => {
@@ -47,6 +54,7 @@ pub fn navbar() -> Html {
                 <a class="navbar-item">{"Home"}</a>
             </div>
             <div class="navbar_end">
+                <CodeTheme/>
             </div>
         </div>
     </nav>
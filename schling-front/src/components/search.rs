@@ -0,0 +1,95 @@
+use closure::closure;
+use schling_common::{
+    id,
+    search::{Hit, Lang, Snippet},
+    source::ToDom,
+};
+use strum::IntoEnumIterator;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use super::sec::section_id;
+use crate::data::{self, Head};
+
+/// Number of words shown around the first match of a result.
+const SNIPPET_CONTEXT: usize = 8;
+const MAX_RESULTS: usize = 20;
+
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct Props {
+    /// A document of the project searched, which is searched alone when the project is not
+    /// stored.
+    pub id: id::Doc,
+}
+
+fn search(proj: id::Proj, query: &str) -> Vec<(data::Sec, Hit)> {
+    let index = data::index(proj);
+    index
+        .search(query)
+        .into_iter()
+        .take(MAX_RESULTS)
+        .filter_map(|mut hit| {
+            let section = hit.id.load()?;
+            hit.snippet = Snippet::build(&section.content.text, query, index.lang, SNIPPET_CONTEXT);
+            Some((section, hit))
+        })
+        .collect()
+}
+
+#[function_component(Search)]
+pub fn search_box(props: &Props) -> Html {
+    let Props { id } = props.clone();
+    let results = use_state(Vec::default);
+
+    let lang = use_state(|| data::index(id.proj).lang);
+
+    let oninput = Callback::from(closure!(clone results, |e: InputEvent| {
+        if let Some(target) = e.target_dyn_into::<HtmlInputElement>() {
+            let query = target.value();
+            results.set(if query.trim().is_empty() { vec![] } else { search(id.proj, &query) });
+        }
+    }));
+    let onchange = Callback::from(closure!(clone lang, |e: Event| {
+        if let Some(target) = e.target_dyn_into::<HtmlSelectElement>() {
+            let value = target.value();
+            if let Some(selected) = Lang::iter().find(|lang| lang.to_string() == value) {
+                lang.set(data::set_index_lang(id, selected).lang);
+            }
+        }
+    }));
+    html! {
+    <div class="box">
+        <div class="field has-addons">
+            <div class="control has-icons-left is-expanded">
+                <input class="input is-rounded" type="search" placeholder="Search" {oninput}/>
+                <span class="icon is-left"><i class="fa fa-search"></i></span>
+            </div>
+            <div class="control select is-rounded" title="Language of the words searched">
+                <select {onchange}>
+                { for Lang::iter().map(|option| html! {
+                    <option value={option.to_string()} selected={option == *lang}>{language_name(option)}</option>
+                }) }
+                </select>
+            </div>
+        </div>
+        { for results.iter().cloned().map(render_result) }
+    </div>
+    }
+}
+
+fn language_name(lang: Lang) -> &'static str {
+    match lang {
+        Lang::English => "English",
+        Lang::German => "Deutsch",
+    }
+}
+
+fn render_result((section, hit): (data::Sec, Hit)) -> Html {
+    let href = format!("#{}", section_id(hit.id));
+    html! {
+    <a class="search-result" {href}>
+        <span class="has-text-weight-bold">{section.head.title}</span>
+        <p>{hit.snippet.to_dom()}</p>
+    </a>
+    }
+}
@@ -1,10 +1,19 @@
 use closure::closure;
-use schling_common::source::ToDom;
-use schling_common::{id, markup::Markup, source::Text};
-use web_sys::{HtmlInputElement, HtmlTextAreaElement};
+use schling_common::source::{Stats, ToDom};
+use schling_common::{
+    ast::Notes,
+    convert::Loss,
+    id,
+    markup::{Markup, MarkupLang},
+    sanitize::Policy,
+    toc,
+    wiki::{self, Target},
+};
+use strum::IntoEnumIterator;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
 use yew::prelude::*;
 
-use crate::code_area::CodeArea;
+use crate::code_area::{CodeArea, SourceChanged};
 use crate::data::{self, Head};
 
 #[derive(Clone, Debug, PartialEq, Properties)]
@@ -12,14 +21,8 @@ pub struct Props {
     pub id: id::Sec,
 }
 
-const SECTION_PREFIX: &str = "section-";
-
-fn section_id(id: id::Sec) -> String {
-    format!("{}{}", SECTION_PREFIX, id.value)
-}
-
-fn section_content_id(id: id::Sec) -> String {
-    format!("{}content-{}", SECTION_PREFIX, id.value)
+pub fn section_id(id: id::Sec) -> String {
+    toc::section_anchor(id)
 }
 
 #[derive(PartialEq)]
@@ -63,12 +66,12 @@ impl Reducible for State {
             }
             Action::UpdTitle(title) => Self {
                 edit: self.edit,
-                section: self.id().update(self.section.own_with_title(title)),
+                section: save(self.section.own_with_title(title)),
             }
             .into(),
             Action::UpdContent(content) => Self {
                 edit: self.edit,
-                section: self.id().update(self.section.own_with_content(content)),
+                section: save(self.section.own_with_content(content)),
             }
             .into(),
             Action::ViewKeyPress(e) => {
@@ -98,6 +101,13 @@ enum Action {
     ViewKeyPress(KeyboardEvent),
 }
 
+/// Stores the section and updates the search index.
+fn save(section: data::Sec) -> data::Sec {
+    data::reindex(&section);
+    let id = section.head.id;
+    id.update(section)
+}
+
 fn new_section(id: id::Sec) -> data::Sec {
     data::SecHead::new(id, id.value, format!("Section {}", id.value)).body(Markup::md_str(""))
 }
@@ -109,44 +119,119 @@ pub fn section(props: &Props) -> Html {
         edit: false,
         section: id.load().unwrap_or_else(|| new_section(id)),
     });
+    // the constructs lost by the last conversion of the section format.
+    let losses = use_state(Vec::<Loss>::new);
     let data::Sec { head, content } = state.section.clone();
 
     let title = head.title;
     if state.edit {
-        let content = Text { text: content.text };
+        let stats = Stats::of(&content.text);
         let upd_title = Callback::from(closure!(clone state, |e: Event| {
             if let Some(target) = e.target_dyn_into::<HtmlInputElement>() {
             let value = target.value();
             state.dispatch(Action::UpdTitle(value));}
         }));
-        let upd_content = Callback::from(closure!(clone state, |e: Event| {
-            if let Some(target) = e.target_dyn_into::<HtmlTextAreaElement>(){
-            let value = Markup::md(target.value());
-            state.dispatch(Action::UpdContent(value));}
+        let lang = content.lang;
+        let upd_content = Callback::from(closure!(clone state, clone content, |(_, change)| {
+            let text = match change {
+                SourceChanged::Change(text) => text,
+                SourceChanged::Insert(text) | SourceChanged::Complete(text) => {
+                    format!("{}{}", content.text, text)
+                }
+            };
+            state.dispatch(Action::UpdContent(Markup::new(text, lang)));
+        }));
+        let upd_lang = Callback::from(closure!(clone state, clone content, |e: Event| {
+            if let Some(target) = e.target_dyn_into::<HtmlSelectElement>() {
+            let lang = MarkupLang::iter().find(|lang| lang.to_string() == target.value());
+            let lang = lang.unwrap_or_default();
+            state.dispatch(Action::UpdContent(lang.with_text(content.text.clone())));}
         }));
+        let convert = |target: MarkupLang| {
+            Callback::from(closure!(clone state, clone content, clone losses, |_| {
+                if let Some(conversion) = content.convert(target) {
+                    losses.set(conversion.losses);
+                    state.dispatch(Action::UpdContent(conversion.markup));
+                }
+            }))
+        };
+        let conversions = MarkupLang::iter()
+            .filter(|target| *target != lang && lang.converts_to(*target))
+            .map(|target| {
+                html! {
+                    <button class="button is-small is-rounded" onclick={convert(target)}>
+                        {format!("Convert to {}", target)}
+                    </button>
+                }
+            });
+        let report = if losses.is_empty() {
+            html! {}
+        } else {
+            html! {
+            <div class="notification is-warning is-light">
+                <p>{"The conversion could not keep everything:"}</p>
+                <ul>{ for losses.iter().map(|loss| html!(<li>{loss.to_string()}</li>)) }</ul>
+            </div>
+            }
+        };
+        let completions = match lang {
+            MarkupLang::Fountain => data::characters(id.doc),
+            _ => Default::default(),
+        };
         let view = Callback::from(closure!(clone state, |_| state.dispatch(Action::View)));
         html! {
         <section id={section_id(id)} class="box">
             <div class="level">
                 <input class="input" type="text" placeholder="Section title" value={title} onchange={upd_title}/>
+                <div class="select">
+                    <select onchange={upd_lang}>
+                    { for MarkupLang::iter().map(|option| html! {
+                        <option value={option.to_string()} selected={option == lang}>{option.to_string()}</option>
+                    }) }
+                    </select>
+                </div>
+                <div class="buttons">{ for conversions }</div>
             </div>
-            <CodeArea value={content} class="textarea section-content" placeholder="Section content"/>
+            {report}
+            <CodeArea value={content.text} {lang} {completions} onchange={upd_content} class="textarea section-content" placeholder="Section content"/>
             <div class="level mt-3">
                 <button class="button is-primary is-rounded" onclick={view}>{"Update"}</button>
+                <span class="tag">{format!("{} words, {} characters", stats.words, stats.chars)}</span>
             </div>
         </section>
         }
     } else {
-        let content = content.to_dom();
+        let (titles, sections) = data::titles(id.doc);
+        let backlinks = render_backlinks(&wiki::backlinks(Target::Sec(id), &sections, &titles));
+        let mut notes = Notes::new(&format!("{}-", section_id(id))).with_titles(titles, id.doc);
+        let content = content.to_dom_with(&mut notes, &Policy::default());
         let edit = Callback::from(closure!(clone state, |_| state.dispatch(Action::Edit)));
         let keypress = Callback::from(move |e| state.dispatch(Action::ViewKeyPress(e)));
         html! {
-        <section class="box" ondblclick={edit} tabindex=0 onkeypress={keypress}>
+        <section id={section_id(id)} class="box" ondblclick={edit} tabindex=0 onkeypress={keypress}>
             <div class="level">
                 <span class="level-item title">{title}</span>
             </div>
-            {content}
+            <div class="content">{content}{notes.to_dom()}</div>
+            {backlinks}
         </section>
         }
     }
 }
+
+/// The sections linking to a section.
+fn render_backlinks(heads: &[&data::SecHead]) -> Html {
+    if heads.is_empty() {
+        return html!();
+    }
+    html! {
+    <div class="backlinks">
+        <p class="menu-label">{"Linked from"}</p>
+        <ul>
+        { for heads.iter().map(|head| html! {
+            <li><a href={format!("#{}", section_id(head.id))}>{head.title.clone()}</a></li>
+        }) }
+        </ul>
+    </div>
+    }
+}
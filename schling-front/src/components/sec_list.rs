@@ -1,8 +1,8 @@
 use closure::closure;
-use schling_common::id;
+use schling_common::{id, toc};
 use yew::prelude::*;
 
-use super::sec::Section;
+use super::{contents::Contents, doc::DocView, sec::Section};
 use crate::data::{self, Head, SecHead};
 
 #[derive(Clone, PartialEq, Properties)]
@@ -13,6 +13,8 @@ pub struct Props {
 #[derive(PartialEq)]
 struct State {
     document: data::Doc,
+    /// Whether the document is read as a whole, with endnotes.
+    read: bool,
 }
 
 impl Reducible for State {
@@ -31,15 +33,22 @@ impl Reducible for State {
                 ));
                 Self {
                     document: id.update(self.document.own_with_content(sections)),
+                    read: false,
                 }
                 .into()
             }
+            Action::Read(read) => Self {
+                document: self.document.clone(),
+                read,
+            }
+            .into(),
         }
     }
 }
 
 enum Action {
     Push,
+    Read(bool),
 }
 
 fn new_document(id: id::Doc) -> data::Doc {
@@ -51,20 +60,62 @@ pub fn sec_list(props: &Props) -> Html {
     let Props { id } = props.clone();
     let state = use_reducer_eq(|| State {
         document: id.load().unwrap_or_else(|| new_document(id)),
+        read: false,
     });
 
+    let read = state.read;
+    let toggle = Callback::from(closure!(clone state, |_| {
+        state.dispatch(Action::Read(!read));
+    }));
+    let toggle = html! {
+    <div class="level">
+        <button class="level-right button is-small is-rounded" onclick={toggle}>
+            {if read { "Edit sections" } else { "Read with endnotes" }}
+        </button>
+    </div>
+    };
+    let sections: Vec<data::Sec> = state
+        .document
+        .content
+        .iter()
+        .filter_map(|sec| sec.id.load())
+        .collect();
+    let sidebar = html! {
+    <aside class="column is-3 menu contents">
+        <p class="menu-label">{"Contents"}</p>
+        <Contents entries={toc::contents(&sections)} />
+    </aside>
+    };
+    if read {
+        return html! {
+        <>
+        {toggle}
+        <div class="columns" id={toc::document_anchor(id)}>
+            {sidebar}
+            <div class="column"><DocView {id} /></div>
+        </div>
+        </>
+        };
+    }
+
     let push = Callback::from(closure!(clone state, |_| {
         state.dispatch(Action::Push);
     }));
     html! {
     <>
-    <div>
-        {render_section(&state.document)}
-    </div>
-    <div class="level mt-3">
-        <button class="level-item button is-rounded is-link is-outlined" onclick={push}>
-            <i class="fa fa-plus"></i>
-        </button>
+    {toggle}
+    <div class="columns" id={toc::document_anchor(id)}>
+        {sidebar}
+        <div class="column">
+            <div>
+                {render_section(&state.document)}
+            </div>
+            <div class="level mt-3">
+                <button class="level-item button is-rounded is-link is-outlined" onclick={push}>
+                    <i class="fa fa-plus"></i>
+                </button>
+            </div>
+        </div>
     </div>
     </>
      }
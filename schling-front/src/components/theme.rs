@@ -0,0 +1,53 @@
+use schling_common::highlight::{stylesheet, Theme};
+use strum::IntoEnumIterator;
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+
+use crate::data::{CodeThemes, Head};
+
+fn theme_key(theme: Theme) -> String {
+    format!("{:?}", theme)
+}
+
+/// Selects the themes of highlighted code in light and in dark mode, and applies them.
+#[function_component(CodeTheme)]
+pub fn code_theme() -> Html {
+    let themes = use_state(|| CodeThemes.load().unwrap_or((Theme::LIGHT, Theme::DARK)));
+    let (light, dark) = *themes;
+
+    let select = |dark_mode: bool| {
+        let selected = if dark_mode { dark } else { light };
+        let themes = themes.clone();
+        let onchange = Callback::from(move |e: Event| {
+            if let Some(target) = e.target_dyn_into::<HtmlSelectElement>() {
+                let value = target.value();
+                if let Some(theme) = Theme::iter().find(|theme| theme_key(*theme) == value) {
+                    let (light, dark) = *themes;
+                    let update = if dark_mode {
+                        (light, theme)
+                    } else {
+                        (theme, dark)
+                    };
+                    themes.set(CodeThemes.update(update));
+                }
+            }
+        });
+        html! {
+        <div class="navbar-item select is-small" title={if dark_mode { "Dark code theme" } else { "Light code theme" }}>
+            <select {onchange}>
+            { for Theme::iter().filter(|theme| theme.is_dark() == dark_mode).map(|theme| html! {
+                <option value={theme_key(theme)} selected={theme == selected}>{theme.to_string()}</option>
+            }) }
+            </select>
+        </div>
+        }
+    };
+
+    html! {
+    <>
+        <style>{stylesheet(light, dark)}</style>
+        {select(false)}
+        {select(true)}
+    </>
+    }
+}
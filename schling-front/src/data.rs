@@ -1,9 +1,23 @@
 use gloo_console::info;
 use gloo_storage::{errors::StorageError, LocalStorage, Storage};
+use gloo_timers::callback::Timeout;
 
-use schling_common::{id, markup::Markup, ord_by};
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+
+use schling_common::{
+    backup::{Backup, Settings},
+    fountain,
+    highlight::Theme,
+    id,
+    markup::MarkupLang,
+    search::{Index, Lang},
+    wiki::Titles,
+};
 use serde::{Deserialize, Serialize};
 
+pub use schling_common::data::*;
+
 pub trait Head {
     type Body: Serialize + for<'de> Deserialize<'de>;
     fn key(&self) -> String;
@@ -26,67 +40,6 @@ macro_rules! tname {
     };
 }
 
-macro_rules! data_for_head {
-    ($head:ident, $body:ident, $content:ty) => {
-        #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
-        pub struct $head {
-            pub id: id::$body,
-            pub order: u32,
-            pub title: String,
-        }
-        ord_by!($head, order);
-
-        impl $head {
-            pub fn new(id: id::$body, order: u32, title: String) -> Self {
-                Self { id, order, title }
-            }
-
-            pub fn body(self, content: $content) -> $body {
-                $body {
-                    head: self,
-                    content,
-                }
-            }
-
-            pub fn own_with_title(&self, title: String) -> Self {
-                Self {
-                    id: self.id,
-                    order: self.order,
-                    title,
-                }
-            }
-        }
-
-        #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
-        pub struct $body {
-            pub head: $head,
-            pub content: $content,
-        }
-        ord_by!($body, head);
-
-        impl $body {
-            pub fn new(head: $head, content: $content) -> Self {
-                Self { head, content }
-            }
-
-            pub fn own_with_title(&self, title: String) -> Self {
-                Self {
-                    head: self.head.own_with_title(title),
-                    content: self.content.clone(),
-                }
-            }
-
-            pub fn own_with_content(&self, content: $content) -> Self {
-                Self {
-                    head: self.head.clone(),
-                    content,
-                }
-            }
-        }
-    };
-}
-
-data_for_head!(SecHead, Sec, Markup);
 impl Head for id::Sec {
     type Body = Sec;
 
@@ -96,7 +49,6 @@ impl Head for id::Sec {
     }
 }
 
-data_for_head!(DocHead, Doc, Vec<SecHead>);
 impl Head for id::Doc {
     type Body = Doc;
 
@@ -106,7 +58,6 @@ impl Head for id::Doc {
     }
 }
 
-data_for_head!(ProjHead, Proj, Vec<DocHead>);
 impl Head for id::Proj {
     type Body = Proj;
 
@@ -116,6 +67,186 @@ impl Head for id::Proj {
     }
 }
 
+/// Key of the full-text search index of a project.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndexOf(pub id::Proj);
+
+impl Head for IndexOf {
+    type Body = Index;
+
+    fn key(&self) -> String {
+        let key: String = self.0.into();
+        format!("{}-{}", tname!(Index), key)
+    }
+}
+
+/// Key of the color themes of highlighted code, in light and in dark mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CodeThemes;
+
+impl Head for CodeThemes {
+    type Body = (Theme, Theme);
+
+    fn key(&self) -> String {
+        tname!(Theme).to_string()
+    }
+}
+
+/// Milliseconds after the last edit before the search index is written.
+const INDEX_WRITE_DELAY: u32 = 1_000;
+
+thread_local! {
+    /// The search index with edits not written yet.
+    static EDITED_INDEX: RefCell<Option<(IndexOf, Index)>> = const { RefCell::new(None) };
+    /// The pending write of the edited index, cancelled when replaced.
+    static INDEX_WRITE: RefCell<Option<Timeout>> = const { RefCell::new(None) };
+}
+
+/// Writes the edited search index.
+fn write_index() {
+    if let Some((key, index)) = EDITED_INDEX.with(|edited| edited.borrow_mut().take()) {
+        key.update(index);
+    }
+}
+
+/// The search index of the project, with the edits not written yet.
+pub fn index(proj: id::Proj) -> Index {
+    let key = IndexOf(proj);
+    let edited = EDITED_INDEX.with(|edited| match &*edited.borrow() {
+        Some((edited, index)) if *edited == key => Some(index.clone()),
+        _ => None,
+    });
+    edited.unwrap_or_else(|| key.load().unwrap_or_default())
+}
+
+/// Updates the search index of the project containing the section.
+///
+/// The index is written once no section was edited for a while, rather than on every edit.
+pub fn reindex(section: &Sec) {
+    let key = IndexOf(section.head.id.doc.proj);
+    EDITED_INDEX.with(|edited| {
+        let mut edited = edited.borrow_mut();
+        let mut index = match edited.take() {
+            Some((edited, index)) if edited == key => index,
+            other => {
+                if let Some((edited, index)) = other {
+                    edited.update(index);
+                }
+                key.load().unwrap_or_default()
+            }
+        };
+        index.insert(section.head.id, &section.head.title, &section.content.text);
+        *edited = Some((key, index));
+    });
+    let write = Timeout::new(INDEX_WRITE_DELAY, write_index);
+    INDEX_WRITE.with(|pending| *pending.borrow_mut() = Some(write));
+}
+
+/// Rebuilds the search index of the project, or of the document when the project is not
+/// stored, stemming the words in the language.
+pub fn set_index_lang(doc: id::Doc, lang: Lang) -> Index {
+    write_index();
+    let mut index = Index::new(lang);
+    for sec in project_sections(&project_docs(doc)) {
+        index.insert(sec.head.id, &sec.head.title, &sec.content.text);
+    }
+    IndexOf(doc.proj).update(index)
+}
+
+/// The documents of the project, or the document itself when the project is not stored.
+fn project_docs(doc: id::Doc) -> Vec<Doc> {
+    let docs = match doc.proj.load() {
+        Some(proj) => proj.content.into_iter().map(|head| head.id).collect(),
+        None => vec![doc],
+    };
+    docs.into_iter().filter_map(|doc| doc.load()).collect()
+}
+
+/// The sections of the documents of the project.
+fn project_sections(docs: &[Doc]) -> Vec<Sec> {
+    docs.iter()
+        .flat_map(|doc| &doc.content)
+        .filter_map(|head| head.id.load())
+        .collect()
+}
+
+/// The character names used in the Fountain sections of the project, or of the document when
+/// the project is not stored.
+pub fn characters(doc: id::Doc) -> BTreeSet<String> {
+    project_sections(&project_docs(doc))
+        .into_iter()
+        .filter(|sec| sec.content.lang == MarkupLang::Fountain)
+        .flat_map(|sec| fountain::characters(&sec.content.text))
+        .collect()
+}
+
+/// The titles resolving the wiki links of the project, or of the document when the project is
+/// not stored, with the sections of the project.
+pub fn titles(doc: id::Doc) -> (Titles, Vec<Sec>) {
+    let docs = project_docs(doc);
+    let sections = project_sections(&docs);
+    let mut titles = Titles::default();
+    for doc in &docs {
+        titles.insert_doc(&doc.head);
+    }
+    for sec in &sections {
+        titles.insert_sec(&sec.head);
+    }
+    (titles, sections)
+}
+
+/// The settings of the editor kept in backups, by their key.
+fn settings() -> Settings {
+    let mut settings = Settings::new();
+    if let Some(themes) = CodeThemes.load().and_then(|t| serde_json::to_value(t).ok()) {
+        settings.insert(CodeThemes.key(), themes);
+    }
+    settings
+}
+
+/// The backup of the project with the settings of the editor, or of the document when the
+/// project is not stored.
+pub fn backup(doc: id::Doc) -> Backup {
+    let docs = project_docs(doc);
+    let sections = project_sections(&docs);
+    let proj = doc.proj.load().unwrap_or_else(|| {
+        let heads = docs.iter().map(|doc| doc.head.clone()).collect();
+        ProjHead::new(doc.proj, 0, String::new()).body(heads)
+    });
+    Backup {
+        proj,
+        docs,
+        sections,
+        // the revisions are kept in the history of the server only.
+        revisions: vec![],
+        settings: settings(),
+    }
+}
+
+/// Stores the project of the backup with the settings of the editor, rebuilding the search
+/// index of the project.
+pub fn restore(backup: Backup) {
+    write_index();
+    let key = IndexOf(backup.proj.head.id);
+    let mut index = Index::new(key.load().unwrap_or_default().lang);
+    for sec in backup.sections {
+        index.insert(sec.head.id, &sec.head.title, &sec.content.text);
+        let id = sec.head.id;
+        id.update(sec);
+    }
+    key.update(index);
+    for doc in backup.docs {
+        let id = doc.head.id;
+        id.update(doc);
+    }
+    let themes = backup.settings.get(&CodeThemes.key());
+    if let Some(themes) = themes.and_then(|t| serde_json::from_value(t.clone()).ok()) {
+        CodeThemes.update(themes);
+    }
+    let id = backup.proj.head.id;
+    id.update(backup.proj);
+}
+
 fn get<T>(key: &str) -> Result<T, StorageError>
 where
     T: Serialize + for<'de> Deserialize<'de>,
@@ -2,14 +2,16 @@ use yew::prelude::*;
 
 use schling_common::id;
 
-use crate::components::sec_list::SecList;
+use crate::components::{backup::ProjBackup, search::Search, sec_list::SecList};
 
 #[function_component(Home)]
 pub fn home() -> Html {
     let id = id::Doc::new(0, id::Proj::new(0));
     html! {
     <div class="container">
+        <Search {id} />
         <SecList {id} />
+        <ProjBackup {id} />
     </div>
     }
 }
@@ -0,0 +1,4 @@
+DROP TABLE sections;
+DROP TABLE documents;
+DROP TABLE projects;
+DROP FUNCTION search_config;
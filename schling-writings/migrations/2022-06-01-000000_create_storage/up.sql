@@ -0,0 +1,34 @@
+-- Maps the language of a section to the text search configuration used to stem its words.
+CREATE FUNCTION search_config(lang TEXT) RETURNS REGCONFIG
+LANGUAGE SQL IMMUTABLE AS $$
+    SELECT CASE lang
+        WHEN 'german' THEN 'german'::REGCONFIG
+        ELSE 'english'::REGCONFIG
+    END
+$$;
+
+CREATE TABLE projects (
+    id TEXT PRIMARY KEY,
+    body JSONB NOT NULL
+);
+
+CREATE TABLE documents (
+    id TEXT PRIMARY KEY,
+    proj TEXT NOT NULL,
+    body JSONB NOT NULL
+);
+
+CREATE TABLE sections (
+    id TEXT PRIMARY KEY,
+    proj TEXT NOT NULL,
+    lang TEXT NOT NULL DEFAULT 'english',
+    body JSONB NOT NULL,
+    search TSVECTOR GENERATED ALWAYS AS (
+        setweight(to_tsvector(search_config(lang), coalesce(body->'head'->>'title', '')), 'A') ||
+        setweight(to_tsvector(search_config(lang), coalesce(body->'content'->>'text', '')), 'B')
+    ) STORED
+);
+
+CREATE INDEX documents_proj ON documents (proj);
+CREATE INDEX sections_proj ON sections (proj);
+CREATE INDEX sections_search ON sections USING GIN (search);
@@ -0,0 +1,58 @@
+#[cfg(test)]
+use diesel::Connection;
+use diesel::{
+    pg::PgConnection,
+    r2d2::{ConnectionManager, PooledConnection},
+};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+
+use crate::error::Result;
+
+pub type Pool = diesel::r2d2::Pool<ConnectionManager<PgConnection>>;
+pub type Conn = PooledConnection<ConnectionManager<PgConnection>>;
+
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+
+/// Connects to the database and applies all pending migrations.
+pub fn connect(url: &str) -> Pool {
+    let pool = Pool::builder()
+        .build(ConnectionManager::new(url))
+        .unwrap_or_else(|e| panic!("unable to connect to the database.\nError: {}", e));
+    let mut conn = pool.get().expect("connection from a fresh pool");
+    conn.run_pending_migrations(MIGRATIONS)
+        .unwrap_or_else(|e| panic!("unable to migrate the database.\nError: {}", e));
+    pool
+}
+
+/// Runs the blocking database `action` on a connection of the pool.
+pub async fn run<T, F>(pool: &Pool, action: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut Conn) -> Result<T> + Send + 'static,
+{
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        action(&mut conn)
+    })
+    .await?
+}
+
+/// A connection to the migrated database in `TEST_DATABASE_URL`, in a transaction that is
+/// never committed.
+#[cfg(test)]
+pub fn test_conn() -> PgConnection {
+    static MIGRATED: std::sync::Mutex<bool> = std::sync::Mutex::new(false);
+    let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set");
+    let mut conn = PgConnection::establish(&url)
+        .unwrap_or_else(|e| panic!("unable to connect to the test database.\nError: {}", e));
+    let mut migrated = MIGRATED.lock().unwrap_or_else(|e| e.into_inner());
+    if !*migrated {
+        conn.run_pending_migrations(MIGRATIONS)
+            .unwrap_or_else(|e| panic!("unable to migrate the test database.\nError: {}", e));
+        *migrated = true;
+    }
+    conn.begin_test_transaction()
+        .expect("transaction on the test database");
+    conn
+}
@@ -0,0 +1,122 @@
+use std::fmt::{Display, Formatter};
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use schling_common::{backup, docx, epub, folder, pdf, scrivener, site};
+
+#[derive(Debug)]
+pub enum Error {
+    NotFound,
+    /// A malformed parameter of the request.
+    Invalid(String),
+    /// A feature the server is not configured for.
+    Unavailable(&'static str),
+    Pool(diesel::r2d2::PoolError),
+    Query(diesel::result::Error),
+    Json(serde_json::Error),
+    Task(tokio::task::JoinError),
+    Epub(epub::Error),
+    Pdf(pdf::Error),
+    Docx(docx::Error),
+    Site(site::Error),
+    Scrivener(scrivener::Error),
+    Folder(folder::Error),
+    Backup(backup::Error),
+    Git(git2::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NotFound => write!(f, "not found"),
+            Error::Invalid(e) => write!(f, "invalid request: {}", e),
+            Error::Unavailable(e) => write!(f, "unavailable: {}", e),
+            Error::Pool(e) => write!(f, "database connection failed: {}", e),
+            Error::Query(e) => write!(f, "database query failed: {}", e),
+            Error::Json(e) => write!(f, "malformed body: {}", e),
+            Error::Task(e) => write!(f, "task failed: {}", e),
+            Error::Epub(e) => write!(f, "{}", e),
+            Error::Pdf(e) => write!(f, "{}", e),
+            Error::Docx(e) => write!(f, "{}", e),
+            Error::Site(e) => write!(f, "{}", e),
+            Error::Scrivener(e) => write!(f, "{}", e),
+            Error::Folder(e) => write!(f, "{}", e),
+            Error::Backup(e) => write!(f, "{}", e),
+            Error::Git(e) => write!(f, "git: {}", e.message()),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+macro_rules! from_error {
+    ($variant:ident, $error:ty) => {
+        impl From<$error> for Error {
+            fn from(e: $error) -> Self {
+                Error::$variant(e)
+            }
+        }
+    };
+}
+
+from_error!(Pool, diesel::r2d2::PoolError);
+from_error!(Json, serde_json::Error);
+from_error!(Task, tokio::task::JoinError);
+from_error!(Epub, epub::Error);
+from_error!(Pdf, pdf::Error);
+from_error!(Docx, docx::Error);
+from_error!(Site, site::Error);
+from_error!(Scrivener, scrivener::Error);
+from_error!(Folder, folder::Error);
+from_error!(Backup, backup::Error);
+from_error!(Git, git2::Error);
+
+impl From<diesel::result::Error> for Error {
+    fn from(e: diesel::result::Error) -> Self {
+        match e {
+            diesel::result::Error::NotFound => Error::NotFound,
+            e => Error::Query(e),
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Error::NotFound => StatusCode::NOT_FOUND,
+            Error::Invalid(_)
+            | Error::Epub(epub::Error::Empty)
+            | Error::Pdf(pdf::Error::Empty)
+            | Error::Site(site::Error::Empty) => StatusCode::BAD_REQUEST,
+            // an uploaded document that cannot be read.
+            Error::Docx(docx::Error::Zip(_) | docx::Error::Xml(_) | docx::Error::Missing(_)) => {
+                StatusCode::BAD_REQUEST
+            }
+            Error::Scrivener(
+                scrivener::Error::Zip(_) | scrivener::Error::Xml(_) | scrivener::Error::Missing,
+            ) => StatusCode::BAD_REQUEST,
+            Error::Backup(
+                backup::Error::Zip(_)
+                | backup::Error::Json(..)
+                | backup::Error::Version(_)
+                | backup::Error::Missing(_),
+            ) => StatusCode::BAD_REQUEST,
+            Error::Json(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::Git(ref e) => match e.code() {
+                git2::ErrorCode::NotFound => StatusCode::NOT_FOUND,
+                git2::ErrorCode::Exists | git2::ErrorCode::UnbornBranch => StatusCode::CONFLICT,
+                git2::ErrorCode::InvalidSpec => StatusCode::BAD_REQUEST,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            // a file edited in the synced folder with broken front matter.
+            Error::Folder(folder::Error::Yaml(..)) => StatusCode::CONFLICT,
+            Error::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
@@ -0,0 +1,392 @@
+use std::path::Path;
+
+use git2::{
+    BlameOptions, BranchType, ErrorCode, ObjectType, Oid, Repository, RepositoryInitOptions,
+    Signature, Tree, TreeWalkMode, TreeWalkResult,
+};
+use schling_common::{folder::Files, id};
+use serde::Serialize;
+
+/// The branch of new repositories.
+pub const MAIN: &str = "main";
+
+/// The author of the commits of a save.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Author {
+    pub name: String,
+    pub email: String,
+}
+
+impl Default for Author {
+    fn default() -> Self {
+        Self {
+            name: "Schling".to_string(),
+            email: "schling@localhost".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Commit {
+    pub id: String,
+    pub author: String,
+    pub email: String,
+    /// The seconds since the Unix epoch.
+    pub time: i64,
+    pub message: String,
+}
+
+impl From<&git2::Commit<'_>> for Commit {
+    fn from(commit: &git2::Commit) -> Self {
+        let author = commit.author();
+        Self {
+            id: commit.id().to_string(),
+            author: author.name().unwrap_or_default().to_string(),
+            email: author.email().unwrap_or_default().to_string(),
+            time: author.when().seconds(),
+            message: commit.message().unwrap_or_default().trim_end().to_string(),
+        }
+    }
+}
+
+/// A line of a file with the commit that last changed it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Line {
+    pub text: String,
+    pub commit: Commit,
+}
+
+/// The bare repository of a project, with its files as laid out by [`schling_common::folder`].
+///
+/// The checked out branch is the one `HEAD` points to, and saves are commits on it.
+pub struct Repo {
+    repo: Repository,
+}
+
+impl Repo {
+    /// Opens the repository of the project in the folder, creating it when missing.
+    pub fn open(root: &Path, proj: id::Proj) -> Result<Self, git2::Error> {
+        let path = root.join(format!("{}.git", String::from(proj)));
+        let repo = match Repository::open_bare(&path) {
+            Ok(repo) => repo,
+            Err(err) if err.code() == ErrorCode::NotFound => Repository::init_opts(
+                &path,
+                RepositoryInitOptions::new().bare(true).initial_head(MAIN),
+            )?,
+            Err(err) => return Err(err),
+        };
+        Ok(Self { repo })
+    }
+
+    /// The name of the checked out branch.
+    pub fn branch(&self) -> Result<String, git2::Error> {
+        let head = self.repo.find_reference("HEAD")?;
+        let target = head.symbolic_target().unwrap_or_default();
+        Ok(target.trim_start_matches("refs/heads/").to_string())
+    }
+
+    pub fn branches(&self) -> Result<Vec<String>, git2::Error> {
+        let mut names = vec![];
+        for branch in self.repo.branches(Some(BranchType::Local))? {
+            if let Some(name) = branch?.0.name()? {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Creates a branch at the last commit of the checked out one.
+    pub fn create_branch(&self, name: &str) -> Result<(), git2::Error> {
+        let head = self.repo.head()?.peel_to_commit()?;
+        self.repo.branch(name, &head, false)?;
+        Ok(())
+    }
+
+    /// Checks out the branch, which the next saves are committed on.
+    pub fn checkout(&self, name: &str) -> Result<(), git2::Error> {
+        let branch = self.repo.find_branch(name, BranchType::Local)?;
+        let reference = branch.get().name().unwrap_or_default().to_string();
+        self.repo.set_head(&reference)
+    }
+
+    /// The files of the last commit of the checked out branch, none before the first commit.
+    pub fn files(&self) -> Result<Files, git2::Error> {
+        match self.repo.head() {
+            Ok(head) => self.tree_files(&head.peel_to_tree()?),
+            Err(err) if err.code() == ErrorCode::UnbornBranch => Ok(Files::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The files of the last commit of the branch, without checking it out.
+    pub fn branch_files(&self, name: &str) -> Result<Files, git2::Error> {
+        let branch = self.repo.find_branch(name, BranchType::Local)?;
+        self.tree_files(&branch.get().peel_to_tree()?)
+    }
+
+    fn tree_files(&self, tree: &Tree) -> Result<Files, git2::Error> {
+        let mut files = Files::new();
+        let mut error = None;
+        tree.walk(TreeWalkMode::PreOrder, |folder, entry| {
+            if entry.kind() != Some(ObjectType::Blob) {
+                return TreeWalkResult::Ok;
+            }
+            let path = format!("{}{}", folder, entry.name().unwrap_or_default());
+            match self.repo.find_blob(entry.id()) {
+                Ok(blob) => {
+                    if let Ok(content) = std::str::from_utf8(blob.content()) {
+                        files.insert(path, content.to_string());
+                    }
+                    TreeWalkResult::Ok
+                }
+                Err(err) => {
+                    error = Some(err);
+                    TreeWalkResult::Abort
+                }
+            }
+        })?;
+        match error {
+            Some(err) => Err(err),
+            None => Ok(files),
+        }
+    }
+
+    /// Commits the files on the checked out branch, unless they are the ones of its last
+    /// commit.
+    pub fn commit(
+        &self,
+        files: &Files,
+        author: &Author,
+        message: &str,
+    ) -> Result<Option<Oid>, git2::Error> {
+        let tree = self.write_tree(files)?;
+        let parent = match self.repo.head() {
+            Ok(head) => Some(head.peel_to_commit()?),
+            Err(err) if err.code() == ErrorCode::UnbornBranch => None,
+            Err(err) => return Err(err),
+        };
+        if parent
+            .as_ref()
+            .is_some_and(|parent| parent.tree_id() == tree.id())
+        {
+            return Ok(None);
+        }
+        let signature = Signature::now(&author.name, &author.email)?;
+        let parents: Vec<_> = parent.iter().collect();
+        let id = self.repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )?;
+        Ok(Some(id))
+    }
+
+    /// Writes the files as a tree, with a subtree per folder.
+    fn write_tree(&self, files: &Files) -> Result<Tree<'_>, git2::Error> {
+        let mut root = self.repo.treebuilder(None)?;
+        let mut folders: Vec<(&str, Vec<(&str, &String)>)> = vec![];
+        for (path, content) in files {
+            match path.split_once('/') {
+                Some((folder, name)) => match folders.last_mut() {
+                    Some((last, files)) if *last == folder => files.push((name, content)),
+                    _ => folders.push((folder, vec![(name, content)])),
+                },
+                None => {
+                    root.insert(path, self.repo.blob(content.as_bytes())?, 0o100644)?;
+                }
+            }
+        }
+        for (folder, entries) in folders {
+            let files = entries
+                .into_iter()
+                .map(|(name, content)| (name.to_string(), content.clone()))
+                .collect();
+            let tree = self.write_tree(&files)?;
+            root.insert(folder, tree.id(), 0o040000)?;
+        }
+        self.repo.find_tree(root.write()?)
+    }
+
+    /// The commits of the checked out branch, the latest first, only the ones changing the
+    /// file when there is a path.
+    pub fn history(&self, path: Option<&str>, limit: usize) -> Result<Vec<Commit>, git2::Error> {
+        match self.repo.head() {
+            Err(err) if err.code() == ErrorCode::UnbornBranch => return Ok(vec![]),
+            result => result?,
+        };
+        let mut walk = self.repo.revwalk()?;
+        walk.push_head()?;
+        let mut commits = vec![];
+        for id in walk {
+            if commits.len() == limit {
+                break;
+            }
+            let commit = self.repo.find_commit(id?)?;
+            let changed = match path {
+                None => true,
+                Some(path) => {
+                    let entry = |tree: Tree| tree.get_path(Path::new(path)).map(|e| e.id()).ok();
+                    let current = entry(commit.tree()?);
+                    match commit.parent_count() {
+                        0 => current.is_some(),
+                        _ => commit
+                            .parents()
+                            .map(|parent| parent.tree().ok().and_then(entry))
+                            .all(|previous| previous != current),
+                    }
+                }
+            };
+            if changed {
+                commits.push(Commit::from(&commit));
+            }
+        }
+        Ok(commits)
+    }
+
+    /// The contents of the file at the commits of the checked out branch changing it, the
+    /// latest first.
+    pub fn versions(&self, path: &str, limit: usize) -> Result<Vec<(Commit, String)>, git2::Error> {
+        let mut versions = vec![];
+        for commit in self.history(Some(path), limit)? {
+            let tree = self.repo.find_commit(Oid::from_str(&commit.id)?)?.tree()?;
+            let blob = match tree.get_path(Path::new(path)) {
+                Ok(entry) => entry.to_object(&self.repo)?.peel_to_blob()?,
+                // the commit removed the file.
+                Err(err) if err.code() == ErrorCode::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+            let content = String::from_utf8_lossy(blob.content()).into_owned();
+            versions.push((commit, content));
+        }
+        Ok(versions)
+    }
+
+    /// The lines of the file on the checked out branch, with the commits that last changed
+    /// them.
+    pub fn blame(&self, path: &str) -> Result<Vec<Line>, git2::Error> {
+        let head = self.repo.head()?.peel_to_commit()?;
+        let blob = head
+            .tree()?
+            .get_path(Path::new(path))?
+            .to_object(&self.repo)?
+            .peel_to_blob()?;
+        let mut options = BlameOptions::new();
+        options.newest_commit(head.id());
+        let blame = self.repo.blame_file(Path::new(path), Some(&mut options))?;
+        let text = String::from_utf8_lossy(blob.content());
+        let mut lines = vec![];
+        for (i, text) in text.lines().enumerate() {
+            let Some(hunk) = blame.get_line(i + 1) else {
+                continue;
+            };
+            let commit = self.repo.find_commit(hunk.final_commit_id())?;
+            lines.push(Line {
+                text: text.to_string(),
+                commit: Commit::from(&commit),
+            });
+        }
+        Ok(lines)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn files(entries: &[(&str, &str)]) -> Files {
+        entries
+            .iter()
+            .map(|(path, content)| (path.to_string(), content.to_string()))
+            .collect()
+    }
+
+    fn author(name: &str) -> Author {
+        Author {
+            name: name.to_string(),
+            email: format!("{}@example.com", name.to_lowercase()),
+        }
+    }
+
+    #[test]
+    fn commits_saves() {
+        let root = tempfile::tempdir().unwrap();
+        let repo = Repo::open(root.path(), id::Proj::new(1)).unwrap();
+        assert_eq!(repo.branch().unwrap(), MAIN);
+        assert!(repo.files().unwrap().is_empty());
+        assert!(repo.history(None, 10).unwrap().is_empty());
+
+        let first = files(&[("_index.md", "---\n---\n"), ("01-one/01-a.md", "A\n")]);
+        assert!(repo
+            .commit(&first, &author("Ada"), "First")
+            .unwrap()
+            .is_some());
+        assert!(repo
+            .commit(&first, &author("Ada"), "Again")
+            .unwrap()
+            .is_none());
+        let second = files(&[("_index.md", "---\n---\n"), ("01-one/01-a.md", "B\n")]);
+        repo.commit(&second, &author("Bob"), "Second").unwrap();
+
+        let repo = Repo::open(root.path(), id::Proj::new(1)).unwrap();
+        assert_eq!(repo.files().unwrap(), second);
+        let history = repo.history(None, 10).unwrap();
+        let messages: Vec<&str> = history.iter().map(|c| c.message.as_str()).collect();
+        assert_eq!(messages, ["Second", "First"]);
+        assert_eq!(history[0].author, "Bob");
+        assert_eq!(history[1].email, "ada@example.com");
+        assert_eq!(repo.history(Some("_index.md"), 10).unwrap().len(), 1);
+        let versions = repo.versions("01-one/01-a.md", 10).unwrap();
+        let contents: Vec<&str> = versions.iter().map(|(_, text)| text.as_str()).collect();
+        assert_eq!(contents, ["B\n", "A\n"]);
+        assert_eq!(versions[0].0, history[0]);
+    }
+
+    #[test]
+    fn blames_lines() {
+        let root = tempfile::tempdir().unwrap();
+        let repo = Repo::open(root.path(), id::Proj::new(2)).unwrap();
+        let path = "01-one/01-a.md";
+        repo.commit(&files(&[(path, "one\ntwo\n")]), &author("Ada"), "Write")
+            .unwrap();
+        repo.commit(&files(&[(path, "one\n2\n")]), &author("Bob"), "Edit")
+            .unwrap();
+        let lines: Vec<(String, String)> = repo
+            .blame(path)
+            .unwrap()
+            .into_iter()
+            .map(|line| (line.text, line.commit.author))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                ("one".to_string(), "Ada".to_string()),
+                ("2".to_string(), "Bob".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_drafts_on_branches() {
+        let root = tempfile::tempdir().unwrap();
+        let repo = Repo::open(root.path(), id::Proj::new(3)).unwrap();
+        let main = files(&[("a.md", "main\n")]);
+        repo.commit(&main, &author("Ada"), "Main").unwrap();
+        repo.create_branch("alternate").unwrap();
+        repo.checkout("alternate").unwrap();
+        let alternate = files(&[("a.md", "alternate\n")]);
+        repo.commit(&alternate, &author("Ada"), "Alternate")
+            .unwrap();
+
+        assert_eq!(repo.branches().unwrap(), ["alternate", MAIN]);
+        assert_eq!(repo.branch_files(MAIN).unwrap(), main);
+        assert_eq!(repo.files().unwrap(), alternate);
+        repo.checkout(MAIN).unwrap();
+        assert_eq!(repo.branch().unwrap(), MAIN);
+        assert_eq!(repo.files().unwrap(), main);
+        assert!(repo.checkout("missing").is_err());
+    }
+}
@@ -1,3 +1,22 @@
-fn main() {
-    println!("Hello, world!");
+mod db;
+mod error;
+mod git;
+mod routes;
+mod schema;
+mod search;
+mod store;
+
+const ADDRESS: &str = "0.0.0.0:8001";
+
+#[tokio::main]
+async fn main() {
+    let url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let pool = db::connect(&url);
+
+    let listener = tokio::net::TcpListener::bind(ADDRESS)
+        .await
+        .unwrap_or_else(|e| panic!("unable to bind to {}.\nError: {}", ADDRESS, e));
+    axum::serve(listener, routes::router(pool))
+        .await
+        .expect("server error");
 }
@@ -0,0 +1,1010 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap},
+    response::{Html, IntoResponse},
+    routing::{get, post, put},
+    Json, Router,
+};
+use diesel::{Connection, PgConnection};
+use schling_common::{
+    ast::Notes,
+    backup::{Backup, Revision},
+    data, docx,
+    epub::{self, Book, Metadata},
+    folder, id,
+    latex::{Class, Latex},
+    manuscript::{Manuscript, Typeface},
+    pdf::{self, PageSize},
+    sanitize::Policy,
+    scrivener,
+    search::{Hit, Lang},
+    site::{Site, Theme},
+    toc::{self, Entry},
+    wiki::{self, Target},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{self, Pool};
+use crate::error::{Error, Result};
+use crate::git::{self, Author, Repo};
+use crate::{search, store};
+
+pub fn router(pool: Pool) -> Router {
+    Router::new()
+        .route("/", get(health))
+        .route("/proj/:id", get(get_proj).put(put_proj))
+        .route("/proj/:id/search", get(search_proj))
+        .route("/proj/:id/epub", get(export_epub).post(export_covered_epub))
+        .route("/proj/:id/pdf", get(export_proj_pdf))
+        .route("/proj/:id/docx", post(import_docx))
+        .route("/proj/:id/scrivener", post(import_scrivener))
+        .route("/proj/:id/history", get(proj_history))
+        .route("/proj/:id/branches", get(list_branches).post(create_branch))
+        .route("/proj/:id/branch", put(checkout_branch))
+        .route(
+            "/proj/:id/backup",
+            get(download_backup).post(restore_backup),
+        )
+        .route("/proj/:id/sync", post(sync_folder))
+        .route("/proj/:id/site", get(export_site).post(export_themed_site))
+        .route("/doc/:id", get(get_doc).put(put_doc))
+        .route("/doc/:id/toc", get(doc_toc))
+        .route("/doc/:id/pdf", get(export_doc_pdf))
+        .route("/doc/:id/docx", get(export_docx))
+        .route("/doc/:id/latex", get(export_latex))
+        .route("/doc/:id/manuscript/docx", get(export_manuscript_docx))
+        .route("/doc/:id/manuscript/pdf", get(export_manuscript_pdf))
+        .route("/sec/:id", get(get_sec).put(put_sec))
+        .route("/sec/:id/html", get(render_sec))
+        .route("/sec/:id/backlinks", get(sec_backlinks))
+        .route("/sec/:id/history", get(sec_history))
+        .route("/sec/:id/blame", get(sec_blame))
+        .with_state(pool)
+}
+
+async fn health() -> &'static str {
+    "ok"
+}
+
+async fn get_proj(State(pool): State<Pool>, Path(id): Path<id::Proj>) -> Result<Json<data::Proj>> {
+    db::run(&pool, move |conn| store::load_proj(conn, id))
+        .await
+        .map(Json)
+}
+
+async fn put_proj(
+    State(pool): State<Pool>,
+    headers: HeaderMap,
+    Json(proj): Json<data::Proj>,
+) -> Result<()> {
+    db::run(&pool, move |conn| {
+        conn.transaction(|conn| {
+            store::save_proj(conn, &proj)?;
+            let message = format!("Save the project “{}”", proj.head.title);
+            commit(conn, proj.head.id, &author(&headers), &message)
+        })
+    })
+    .await
+}
+
+async fn get_doc(State(pool): State<Pool>, Path(id): Path<id::Doc>) -> Result<Json<data::Doc>> {
+    db::run(&pool, move |conn| store::load_doc(conn, id))
+        .await
+        .map(Json)
+}
+
+async fn put_doc(
+    State(pool): State<Pool>,
+    headers: HeaderMap,
+    Json(doc): Json<data::Doc>,
+) -> Result<()> {
+    db::run(&pool, move |conn| {
+        conn.transaction(|conn| {
+            store::save_doc(conn, &doc)?;
+            let message = format!("Save the document “{}”", doc.head.title);
+            commit_chapter(conn, doc.head.id, &author(&headers), &message)
+        })
+    })
+    .await
+}
+
+/// The table of contents of the document, with the anchors of its rendered sections.
+async fn doc_toc(State(pool): State<Pool>, Path(id): Path<id::Doc>) -> Result<Json<Vec<Entry>>> {
+    db::run(&pool, move |conn| {
+        let doc = store::load_doc(conn, id)?;
+        let sections = doc
+            .content
+            .iter()
+            .map(|head| store::load_sec(conn, head.id))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(toc::contents(&sections))
+    })
+    .await
+    .map(Json)
+}
+
+async fn get_sec(State(pool): State<Pool>, Path(id): Path<id::Sec>) -> Result<Json<data::Sec>> {
+    db::run(&pool, move |conn| store::load_sec(conn, id))
+        .await
+        .map(Json)
+}
+
+/// Renders the content of the section to sanitized HTML, linking its wiki links to the
+/// sections of the project.
+async fn render_sec(State(pool): State<Pool>, Path(id): Path<id::Sec>) -> Result<Html<String>> {
+    db::run(&pool, move |conn| {
+        let sec = store::load_sec(conn, id)?;
+        let (titles, _) = store::load_titles(conn, id.doc.proj)?;
+        let notes =
+            Notes::new(&format!("{}-", toc::section_anchor(id))).with_titles(titles, id.doc);
+        Ok(Html(
+            sec.content.render_html_with(notes, &Policy::default()),
+        ))
+    })
+    .await
+}
+
+/// The heads of the sections linking to the section.
+async fn sec_backlinks(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Sec>,
+) -> Result<Json<Vec<data::SecHead>>> {
+    db::run(&pool, move |conn| {
+        let (titles, sections) = store::load_titles(conn, id.doc.proj)?;
+        Ok(wiki::backlinks(Target::Sec(id), &sections, &titles)
+            .into_iter()
+            .cloned()
+            .collect())
+    })
+    .await
+    .map(Json)
+}
+
+#[derive(Deserialize)]
+struct SecParams {
+    #[serde(default)]
+    lang: Lang,
+}
+
+async fn put_sec(
+    State(pool): State<Pool>,
+    Query(params): Query<SecParams>,
+    headers: HeaderMap,
+    Json(sec): Json<data::Sec>,
+) -> Result<()> {
+    db::run(&pool, move |conn| {
+        conn.transaction(|conn| {
+            store::save_sec(conn, &sec, params.lang)?;
+            let message = format!("Save the section “{}”", sec.head.title);
+            commit_chapter(conn, sec.head.id.doc, &author(&headers), &message)
+        })
+    })
+    .await
+}
+
+/// The folder of the git repositories of the projects. When set, every save of a project is
+/// also a commit in its repository.
+const REPOS_DIR: &str = "REPOS_DIR";
+
+/// The repository of the project, none when the projects are not stored in git.
+fn repo(id: id::Proj) -> Result<Option<Repo>> {
+    match std::env::var_os(REPOS_DIR) {
+        Some(root) => Ok(Some(Repo::open(std::path::Path::new(&root), id)?)),
+        None => Ok(None),
+    }
+}
+
+fn required_repo(id: id::Proj) -> Result<Repo> {
+    repo(id)?.ok_or(Error::Unavailable("the projects are not stored in git"))
+}
+
+/// The author of a save, from the `X-Author-Name` and `X-Author-Email` headers.
+///
+/// The headers are trusted as they are, so the server is to be run behind a proxy that
+/// authenticates the writers and sets them, removing the ones sent by the clients.
+fn author(headers: &HeaderMap) -> Author {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+    let default = Author::default();
+    Author {
+        name: header("x-author-name").unwrap_or(default.name),
+        email: header("x-author-email").unwrap_or(default.email),
+    }
+}
+
+/// Commits the saved project to its repository, when the projects are stored in git.
+///
+/// Saves commit in the transaction of their changes, which a failed commit rolls back.
+fn commit(conn: &mut PgConnection, id: id::Proj, author: &Author, message: &str) -> Result<()> {
+    if let Some(repo) = repo(id)? {
+        let proj = store::load_proj(conn, id)?;
+        let chapters = store::load_chapters(conn, &proj, None)?;
+        repo.commit(&folder::export(&proj.head, &chapters), author, message)?;
+    }
+    Ok(())
+}
+
+/// Commits the saved document with its sections to the repository of its project, over the
+/// files of the last commit.
+fn commit_chapter(
+    conn: &mut PgConnection,
+    id: id::Doc,
+    author: &Author,
+    message: &str,
+) -> Result<()> {
+    let Some(repo) = repo(id.proj)? else {
+        return Ok(());
+    };
+    let mut files = repo.files()?;
+    if files.is_empty() {
+        return commit(conn, id.proj, author, message);
+    }
+    let proj = store::load_proj(conn, id.proj)?;
+    // documents outside of the project are not exported.
+    if let Some(position) = proj.content.iter().position(|head| head.id == id) {
+        folder::export_chapter(&mut files, position, &store::load_chapter(conn, id)?);
+        repo.commit(&files, author, message)?;
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    q: String,
+    /// The most hits to return, up to [`MAX_HITS`]; a negative limit is rejected.
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+fn default_limit() -> u32 {
+    20
+}
+
+const MAX_HITS: u32 = 100;
+
+async fn search_proj(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Proj>,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<Vec<Hit>>> {
+    db::run(&pool, move |conn| {
+        search::search(conn, id, &params.q, params.limit.min(MAX_HITS).into())
+    })
+    .await
+    .map(Json)
+}
+
+/// The authors and the language of an exported publication.
+#[derive(Deserialize)]
+struct CreditParams {
+    /// The comma separated names of the authors.
+    #[serde(default)]
+    authors: String,
+    #[serde(default = "default_language")]
+    lang: String,
+}
+
+impl CreditParams {
+    fn authors(&self) -> Vec<String> {
+        self.authors
+            .split(',')
+            .map(str::trim)
+            .filter(|author| !author.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct EpubParams {
+    /// The comma separated ids of the exported documents, all documents when missing.
+    docs: Option<String>,
+    #[serde(flatten)]
+    credits: CreditParams,
+    publisher: Option<String>,
+    description: Option<String>,
+}
+
+fn default_language() -> String {
+    "en".to_string()
+}
+
+/// Exports the documents of the project as an EPUB 3 book, with one chapter per document and
+/// a generated cover.
+async fn export_epub(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Proj>,
+    Query(params): Query<EpubParams>,
+) -> Result<impl IntoResponse> {
+    epub_response(pool, id, params, None).await
+}
+
+/// The media types of the cover images of books.
+const COVER_TYPES: &[&str] = &[
+    "image/jpeg",
+    "image/png",
+    "image/gif",
+    "image/webp",
+    "image/svg+xml",
+];
+
+/// Exports the documents of the project as an EPUB 3 book, with the cover image of the body
+/// in the media type of its `Content-Type`.
+async fn export_covered_epub(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Proj>,
+    Query(params): Query<EpubParams>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse> {
+    let media_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase())
+        .filter(|value| COVER_TYPES.contains(&value.as_str()))
+        .ok_or_else(|| {
+            Error::Invalid(format!(
+                "the cover must be one of {}",
+                COVER_TYPES.join(", ")
+            ))
+        })?;
+    if body.is_empty() {
+        return Err(Error::Invalid("the cover is empty".to_string()));
+    }
+    let cover = epub::Image {
+        media_type,
+        data: body.to_vec(),
+    };
+    epub_response(pool, id, params, Some(cover)).await
+}
+
+async fn epub_response(
+    pool: Pool,
+    id: id::Proj,
+    params: EpubParams,
+    cover: Option<epub::Image>,
+) -> Result<impl IntoResponse> {
+    let selected = selected_docs(params.docs.as_deref())?;
+    let book = db::run(&pool, move |conn| {
+        let proj = store::load_proj(conn, id)?;
+        let mut metadata = Metadata::new(&proj.head.title, &epub::identifier(id));
+        metadata.authors = params.credits.authors();
+        metadata.language = params.credits.lang;
+        metadata.publisher = params.publisher;
+        metadata.description = params.description;
+        let mut book = Book::new(metadata);
+        book.cover = cover;
+        for (doc, sections) in store::load_chapters(conn, &proj, selected.as_deref())? {
+            book = book.with_chapter(doc, sections);
+        }
+        Ok(book)
+    })
+    .await?;
+    let bytes = book.to_bytes()?;
+    Ok(attachment(
+        "application/epub+zip",
+        &book.metadata.title,
+        "epub",
+        bytes,
+    ))
+}
+
+#[derive(Deserialize)]
+struct SiteParams {
+    /// The comma separated ids of the published documents, all documents when missing.
+    docs: Option<String>,
+    /// The absolute URL the site is published at, for the links of its feed.
+    #[serde(default)]
+    base_url: String,
+    #[serde(flatten)]
+    credits: CreditParams,
+    description: Option<String>,
+}
+
+/// Exports the documents of the project as a static website in a zip archive.
+async fn export_site(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Proj>,
+    Query(params): Query<SiteParams>,
+) -> Result<impl IntoResponse> {
+    site_response(pool, id, params, Theme::default()).await
+}
+
+/// Exports the documents of the project as a static website, with the template and style
+/// sheet of the theme.
+async fn export_themed_site(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Proj>,
+    Query(params): Query<SiteParams>,
+    Json(theme): Json<Theme>,
+) -> Result<impl IntoResponse> {
+    site_response(pool, id, params, theme).await
+}
+
+async fn site_response(
+    pool: Pool,
+    id: id::Proj,
+    params: SiteParams,
+    theme: Theme,
+) -> Result<impl IntoResponse> {
+    let selected = selected_docs(params.docs.as_deref())?;
+    let site = db::run(&pool, move |conn| {
+        let proj = store::load_proj(conn, id)?;
+        let mut site = Site::new(&proj.head.title, &params.base_url);
+        site.authors = params.credits.authors();
+        site.language = params.credits.lang;
+        site.description = params.description;
+        site.theme = theme;
+        for (doc, sections) in store::load_chapters(conn, &proj, selected.as_deref())? {
+            site = site.with_chapter(doc, sections);
+        }
+        Ok(site)
+    })
+    .await?;
+    let bytes = site.to_bytes()?;
+    Ok(attachment("application/zip", &site.title, "zip", bytes))
+}
+
+/// The comma separated ids of the selected documents, all documents when missing.
+fn selected_docs(docs: Option<&str>) -> Result<Option<Vec<id::Doc>>> {
+    docs.map(|docs| {
+        docs.split(',')
+            .map(|doc| doc.trim().parse::<id::Doc>().map_err(Error::Invalid))
+            .collect()
+    })
+    .transpose()
+}
+
+#[derive(Deserialize)]
+struct PdfParams {
+    /// The comma separated ids of the exported documents of a project, all documents when
+    /// missing.
+    docs: Option<String>,
+    #[serde(flatten)]
+    credits: CreditParams,
+    /// The page size: `a5`, `6x9` or `letter`.
+    size: Option<String>,
+}
+
+impl PdfParams {
+    fn book(&self, title: &str) -> Result<pdf::Book> {
+        let size = match &self.size {
+            Some(size) => size.parse::<PageSize>().map_err(Error::Invalid)?,
+            None => PageSize::default(),
+        };
+        let mut book = pdf::Book::new(title, size);
+        book.authors = self.credits.authors();
+        book.language = self.credits.lang.clone();
+        Ok(book)
+    }
+}
+
+/// Exports the documents of the project as a PDF book, with one chapter per document.
+async fn export_proj_pdf(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Proj>,
+    Query(params): Query<PdfParams>,
+) -> Result<impl IntoResponse> {
+    let selected = selected_docs(params.docs.as_deref())?;
+    let book = db::run(&pool, move |conn| {
+        let proj = store::load_proj(conn, id)?;
+        let mut book = params.book(&proj.head.title)?;
+        for (doc, sections) in store::load_chapters(conn, &proj, selected.as_deref())? {
+            book = book.with_chapter(doc, sections);
+        }
+        Ok(book)
+    })
+    .await?;
+    pdf_response(&book)
+}
+
+/// Exports the document as a PDF book of a single chapter.
+async fn export_doc_pdf(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Doc>,
+    Query(params): Query<PdfParams>,
+) -> Result<impl IntoResponse> {
+    let book = db::run(&pool, move |conn| {
+        let (doc, sections) = store::load_chapter(conn, id)?;
+        Ok(params.book(&doc.head.title)?.with_chapter(doc, sections))
+    })
+    .await?;
+    pdf_response(&book)
+}
+
+fn pdf_response(book: &pdf::Book) -> Result<impl IntoResponse> {
+    let bytes = book.to_bytes()?;
+    Ok(attachment(PDF, &book.title, "pdf", bytes))
+}
+
+/// Exports the document as a Word document, with a page break between its sections.
+async fn export_docx(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Doc>,
+) -> Result<impl IntoResponse> {
+    let (doc, sections) = db::run(&pool, move |conn| store::load_chapter(conn, id)).await?;
+    let bytes = docx::export(&doc, &sections)?;
+    Ok(attachment(DOCX, &doc.head.title, "docx", bytes))
+}
+
+/// Imports the Word document of the body as the last document of the project, split into
+/// sections on its first level headings and page breaks.
+async fn import_docx(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Proj>,
+    Query(params): Query<SecParams>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<data::Doc>> {
+    db::run(&pool, move |conn| {
+        conn.transaction(|conn| {
+            let mut proj = store::load_proj(conn, id)?;
+            let value = proj
+                .content
+                .iter()
+                .map(|doc| doc.id.value + 1)
+                .max()
+                .unwrap_or(0);
+            let (mut doc, sections) = docx::import(&body, id::Doc::new(value, id))?;
+            doc.head.order = proj.content.len() as u32;
+            for sec in &sections {
+                store::save_sec(conn, sec, params.lang)?;
+            }
+            store::save_doc(conn, &doc)?;
+            proj.content.push(doc.head.clone());
+            store::save_proj(conn, &proj)?;
+            let message = format!("Import the document “{}”", doc.head.title);
+            commit(conn, id, &author(&headers), &message)?;
+            Ok(doc)
+        })
+    })
+    .await
+    .map(Json)
+}
+
+#[derive(Serialize)]
+struct ScrivenerImport {
+    docs: Vec<data::DocHead>,
+    /// What could not be converted, such as images and research files.
+    report: Vec<String>,
+}
+
+/// Imports a zip archive of a Scrivener project, adding its folders as documents to the
+/// project.
+async fn import_scrivener(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Proj>,
+    Query(params): Query<SecParams>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<ScrivenerImport>> {
+    db::run(&pool, move |conn| {
+        conn.transaction(|conn| {
+            let mut proj = store::load_proj(conn, id)?;
+            let value = proj
+                .content
+                .iter()
+                .map(|doc| doc.id.value + 1)
+                .max()
+                .unwrap_or(0);
+            let import = scrivener::import_zip(&body, id::Doc::new(value, id))?;
+            let mut docs = vec![];
+            for (mut doc, sections) in import.chapters {
+                doc.head.order = proj.content.len() as u32;
+                for sec in &sections {
+                    store::save_sec(conn, sec, params.lang)?;
+                }
+                store::save_doc(conn, &doc)?;
+                proj.content.push(doc.head.clone());
+                docs.push(doc.head);
+            }
+            store::save_proj(conn, &proj)?;
+            commit(conn, id, &author(&headers), "Import a Scrivener project")?;
+            Ok(ScrivenerImport {
+                docs,
+                report: import.report,
+            })
+        })
+    })
+    .await
+    .map(Json)
+}
+
+#[derive(Deserialize)]
+struct HistoryParams {
+    /// Only the commits changing the file at the path in the repository.
+    path: Option<String>,
+    #[serde(default = "default_history")]
+    limit: usize,
+}
+
+fn default_history() -> usize {
+    100
+}
+
+/// The commits of the checked out branch of the project, the latest first.
+async fn proj_history(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Proj>,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<Vec<git::Commit>>> {
+    db::run(&pool, move |_| {
+        let repo = required_repo(id)?;
+        Ok(repo.history(params.path.as_deref(), params.limit)?)
+    })
+    .await
+    .map(Json)
+}
+
+/// The path of the file of the section in the repository of its project.
+fn sec_path(conn: &mut PgConnection, id: id::Sec) -> Result<String> {
+    let proj = store::load_proj(conn, id.doc.proj)?;
+    let chapters = store::load_chapters(conn, &proj, None)?;
+    folder::section_path(&chapters, id).ok_or(Error::NotFound)
+}
+
+/// The commits changing the file of the section, the latest first.
+async fn sec_history(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Sec>,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<Vec<git::Commit>>> {
+    db::run(&pool, move |conn| {
+        let repo = required_repo(id.doc.proj)?;
+        let path = sec_path(conn, id)?;
+        Ok(repo.history(Some(&path), params.limit)?)
+    })
+    .await
+    .map(Json)
+}
+
+/// The lines of the file of the section with the commits that last changed them.
+async fn sec_blame(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Sec>,
+) -> Result<Json<Vec<git::Line>>> {
+    db::run(&pool, move |conn| {
+        let repo = required_repo(id.doc.proj)?;
+        let path = sec_path(conn, id)?;
+        Ok(repo.blame(&path)?)
+    })
+    .await
+    .map(Json)
+}
+
+#[derive(Serialize)]
+struct Branches {
+    /// The checked out branch, which saves are committed on.
+    current: String,
+    branches: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct BranchParams {
+    name: String,
+}
+
+async fn list_branches(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Proj>,
+) -> Result<Json<Branches>> {
+    db::run(&pool, move |_| {
+        let repo = required_repo(id)?;
+        Ok(Branches {
+            current: repo.branch()?,
+            branches: repo.branches()?,
+        })
+    })
+    .await
+    .map(Json)
+}
+
+/// Creates a branch at the last commit of the checked out one, for an alternate draft.
+async fn create_branch(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Proj>,
+    Json(params): Json<BranchParams>,
+) -> Result<()> {
+    db::run(&pool, move |_| {
+        Ok(required_repo(id)?.create_branch(&params.name)?)
+    })
+    .await
+}
+
+/// Checks out the branch, replacing the project with its last commit.
+async fn checkout_branch(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Proj>,
+    Query(sec_params): Query<SecParams>,
+    Json(params): Json<BranchParams>,
+) -> Result<Json<data::Proj>> {
+    db::run(&pool, move |conn| {
+        let repo = required_repo(id)?;
+        let (proj, chapters) = folder::import(&repo.branch_files(&params.name)?, id, false)?;
+        // the branch is checked out once the project is replaced by its files.
+        conn.transaction::<_, Error, _>(|conn| {
+            store::replace_proj(
+                conn,
+                &proj,
+                chapters.iter().map(|(doc, _)| doc),
+                chapters.iter().flat_map(|(_, sections)| sections),
+                sec_params.lang,
+            )?;
+            repo.checkout(&params.name)?;
+            Ok(())
+        })?;
+        Ok(proj)
+    })
+    .await
+    .map(Json)
+}
+
+/// Downloads the project with all its documents and sections as a backup archive.
+async fn download_backup(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Proj>,
+) -> Result<impl IntoResponse> {
+    let backup = db::run(&pool, move |conn| {
+        let proj = store::load_proj(conn, id)?;
+        let chapters = store::load_chapters(conn, &proj, None)?;
+        let revisions = match repo(id)? {
+            Some(repo) => revisions(&repo, &chapters)?,
+            None => vec![],
+        };
+        Ok(Backup::new(proj, chapters).with_revisions(revisions))
+    })
+    .await?;
+    let bytes = backup.to_bytes()?;
+    Ok(attachment(
+        "application/zip",
+        &backup.proj.head.title,
+        "backup.zip",
+        bytes,
+    ))
+}
+
+/// The most revisions of a section in a backup.
+const REVISIONS: usize = 100;
+
+/// The revisions of the sections, from the history of their files in the repository.
+fn revisions(repo: &Repo, chapters: &[folder::Chapter]) -> Result<Vec<Revision>> {
+    let mut revisions = vec![];
+    for sec in chapters.iter().flat_map(|(_, sections)| sections) {
+        let Some(path) = folder::section_path(chapters, sec.head.id) else {
+            continue;
+        };
+        for (commit, content) in repo.versions(&path, REVISIONS)? {
+            revisions.push(Revision {
+                sec: sec.head.id,
+                commit: commit.id,
+                author: commit.author,
+                email: commit.email,
+                time: commit.time,
+                message: commit.message,
+                content,
+            });
+        }
+    }
+    Ok(revisions)
+}
+
+/// Restores the project from a backup archive of it, replacing its documents and sections.
+///
+/// The revisions of the backup are not restored, as the repository of the project keeps its
+/// history and records the restore as a commit.
+async fn restore_backup(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Proj>,
+    Query(params): Query<SecParams>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<data::Proj>> {
+    let backup = Backup::read(&body)?;
+    let foreign = backup.proj.head.id != id
+        || backup.docs.iter().any(|doc| doc.head.id.proj != id)
+        || backup.sections.iter().any(|sec| sec.head.id.doc.proj != id);
+    if foreign {
+        return Err(Error::Invalid(
+            "the backup is not one of this project".to_string(),
+        ));
+    }
+    db::run(&pool, move |conn| {
+        conn.transaction(|conn| {
+            store::replace_proj(
+                conn,
+                &backup.proj,
+                &backup.docs,
+                &backup.sections,
+                params.lang,
+            )?;
+            let message = format!("Restore the project “{}”", backup.proj.head.title);
+            commit(conn, id, &author(&headers), &message)
+        })?;
+        Ok(backup.proj)
+    })
+    .await
+    .map(Json)
+}
+
+/// The folder the projects are synced with, a subfolder per project.
+const SYNC_DIR: &str = "SYNC_DIR";
+
+#[derive(Deserialize)]
+struct SyncParams {
+    /// Splits the Markdown files at their first level headings.
+    #[serde(default)]
+    split: bool,
+    #[serde(default)]
+    lang: Lang,
+}
+
+/// Syncs the project both ways with its folder of Markdown files, returning the changed
+/// files.
+async fn sync_folder(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Proj>,
+    Query(params): Query<SyncParams>,
+    headers: HeaderMap,
+) -> Result<Json<BTreeMap<String, folder::Change>>> {
+    let root = std::env::var_os(SYNC_DIR)
+        .ok_or(Error::Unavailable("the folder to sync with is not set"))?;
+    let root = std::path::PathBuf::from(root).join(String::from(id));
+    db::run(&pool, move |conn| {
+        let proj = store::load_proj(conn, id)?;
+        let chapters = store::load_chapters(conn, &proj, None)?;
+        let sync = folder::sync(&root, &proj.head, &chapters, params.split)?;
+        conn.transaction(|conn| {
+            store::replace_proj(
+                conn,
+                &sync.proj,
+                sync.chapters.iter().map(|(doc, _)| doc),
+                sync.chapters.iter().flat_map(|(_, sections)| sections),
+                params.lang,
+            )?;
+            let message = format!("Sync the project “{}”", sync.proj.head.title);
+            commit(conn, id, &author(&headers), &message)
+        })?;
+        Ok(sync.changes)
+    })
+    .await
+    .map(Json)
+}
+
+#[derive(Deserialize)]
+struct LatexParams {
+    /// The document class: `article`, `report` or `book`.
+    class: Option<String>,
+    #[serde(flatten)]
+    credits: CreditParams,
+}
+
+/// Exports the document as a LaTeX source file, with a section per titled section.
+async fn export_latex(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Doc>,
+    Query(params): Query<LatexParams>,
+) -> Result<impl IntoResponse> {
+    let class = match &params.class {
+        Some(class) => class.parse::<Class>().map_err(Error::Invalid)?,
+        None => Class::default(),
+    };
+    let (doc, sections) = db::run(&pool, move |conn| store::load_chapter(conn, id)).await?;
+    let title = doc.head.title.clone();
+    let mut latex = Latex::new(doc, sections, class);
+    latex.authors = params.credits.authors();
+    Ok(attachment(
+        "application/x-tex; charset=utf-8",
+        &title,
+        "tex",
+        latex.to_tex(),
+    ))
+}
+
+#[derive(Deserialize)]
+struct ManuscriptParams {
+    /// The legal name of the author.
+    #[serde(default)]
+    author: String,
+    /// The name the work is published under, when it differs from the legal name.
+    byline: Option<String>,
+    /// The newline separated lines of the contact information, such as the address.
+    #[serde(default)]
+    contact: String,
+    #[serde(default = "default_language")]
+    lang: String,
+    /// The typeface: `courier` or `times`.
+    font: Option<String>,
+}
+
+impl ManuscriptParams {
+    fn manuscript(self, doc: data::Doc, sections: Vec<data::Sec>) -> Result<Manuscript> {
+        let mut manuscript = Manuscript::new(doc, sections, self.author.trim());
+        manuscript.byline = self.byline;
+        manuscript.contact = self
+            .contact
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+        manuscript.language = self.lang;
+        if let Some(font) = &self.font {
+            manuscript.typeface = font.parse::<Typeface>().map_err(Error::Invalid)?;
+        }
+        Ok(manuscript)
+    }
+}
+
+async fn load_manuscript(pool: &Pool, id: id::Doc, params: ManuscriptParams) -> Result<Manuscript> {
+    db::run(pool, move |conn| {
+        let (doc, sections) = store::load_chapter(conn, id)?;
+        params.manuscript(doc, sections)
+    })
+    .await
+}
+
+/// Exports the document as a Word document in the Standard Manuscript Format.
+async fn export_manuscript_docx(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Doc>,
+    Query(params): Query<ManuscriptParams>,
+) -> Result<impl IntoResponse> {
+    let manuscript = load_manuscript(&pool, id, params).await?;
+    let bytes = manuscript.to_docx()?;
+    Ok(attachment(DOCX, &manuscript.title, "docx", bytes))
+}
+
+/// Exports the document as a PDF in the Standard Manuscript Format.
+async fn export_manuscript_pdf(
+    State(pool): State<Pool>,
+    Path(id): Path<id::Doc>,
+    Query(params): Query<ManuscriptParams>,
+) -> Result<impl IntoResponse> {
+    let manuscript = load_manuscript(&pool, id, params).await?;
+    let bytes = manuscript.to_pdf()?;
+    Ok(attachment(PDF, &manuscript.title, "pdf", bytes))
+}
+
+const PDF: &str = "application/pdf";
+const DOCX: &str = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+
+/// The body as a download of the type, named after the title with the extension.
+fn attachment(mime: &str, title: &str, ext: &str, body: impl IntoResponse) -> impl IntoResponse {
+    let file = format!("attachment; filename=\"{}.{}\"", file_name(title), ext);
+    (
+        [
+            (header::CONTENT_TYPE, mime.to_string()),
+            (header::CONTENT_DISPOSITION, file),
+        ],
+        body,
+    )
+}
+
+/// The title as a file name, without the characters reserved in paths and headers.
+fn file_name(title: &str) -> String {
+    let name: String = title
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.is_empty() {
+        "export".to_string()
+    } else {
+        name
+    }
+}
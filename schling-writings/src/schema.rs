@@ -0,0 +1,25 @@
+// The generated `search` column of `sections` is only accessed through raw queries.
+
+diesel::table! {
+    projects (id) {
+        id -> Text,
+        body -> Jsonb,
+    }
+}
+
+diesel::table! {
+    documents (id) {
+        id -> Text,
+        proj -> Text,
+        body -> Jsonb,
+    }
+}
+
+diesel::table! {
+    sections (id) {
+        id -> Text,
+        proj -> Text,
+        lang -> Text,
+        body -> Jsonb,
+    }
+}
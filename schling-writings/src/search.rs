@@ -0,0 +1,157 @@
+use diesel::{
+    prelude::*,
+    sql_types::{BigInt, Float4, Text},
+};
+use schling_common::{
+    id,
+    search::{Hit, Snippet},
+};
+
+use crate::error::Result;
+
+const START_SEL: &str = "<mark>";
+const STOP_SEL: &str = "</mark>";
+
+#[derive(QueryableByName)]
+struct Row {
+    #[diesel(sql_type = Text)]
+    id: String,
+    #[diesel(sql_type = Float4)]
+    score: f32,
+    #[diesel(sql_type = Text)]
+    headline: String,
+}
+
+/// Ranks the sections of the project matching the web search style `query`, stemmed in the
+/// language of each section.
+pub fn search(
+    conn: &mut PgConnection,
+    proj: id::Proj,
+    query: &str,
+    limit: i64,
+) -> Result<Vec<Hit>> {
+    let rows: Vec<Row> = diesel::sql_query(format!(
+        "SELECT id, \
+            ts_rank_cd(search, websearch_to_tsquery(search_config(lang), $1)) AS score, \
+            ts_headline(search_config(lang), body->'content'->>'text', \
+                websearch_to_tsquery(search_config(lang), $1), \
+                'StartSel={}, StopSel={}, MinWords=8, MaxWords=24') AS headline \
+        FROM sections \
+        WHERE proj = $2 AND search @@ websearch_to_tsquery(search_config(lang), $1) \
+        ORDER BY score DESC, id \
+        LIMIT $3",
+        START_SEL, STOP_SEL
+    ))
+    .bind::<Text, _>(query)
+    .bind::<Text, _>(proj.to_string())
+    .bind::<BigInt, _>(limit)
+    .load(conn)?;
+
+    Ok(rows.into_iter().filter_map(into_hit).collect())
+}
+
+fn into_hit(row: Row) -> Option<Hit> {
+    Some(Hit {
+        id: row.id.parse().ok()?,
+        score: row.score,
+        snippet: Snippet::from_marked(&row.headline, START_SEL, STOP_SEL),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use schling_common::{data, markup::Markup, search::Lang};
+
+    use super::*;
+    use crate::db::test_conn;
+    use crate::store;
+
+    fn save(conn: &mut PgConnection, id: id::Sec, title: &str, text: &str, lang: Lang) {
+        let sec = data::SecHead::new(id, id.value, title.to_string()).body(Markup::md_str(text));
+        store::save_sec(conn, &sec, lang).unwrap();
+    }
+
+    #[test]
+    fn reads_hits_from_rows() {
+        let row = Row {
+            id: "000000010000000200000003".to_string(),
+            score: 0.5,
+            headline: "the <mark>fox</mark> ran".to_string(),
+        };
+        let hit = into_hit(row).unwrap();
+        assert_eq!(hit.id, id::Sec::new(3, id::Doc::new(2, id::Proj::new(1))));
+        assert_eq!(
+            hit.snippet,
+            Snippet::from_marked("the <mark>fox</mark> ran", START_SEL, STOP_SEL)
+        );
+        let row = Row {
+            id: "not an id".to_string(),
+            score: 0.5,
+            headline: String::new(),
+        };
+        assert!(into_hit(row).is_none());
+    }
+
+    #[test]
+    #[ignore = "needs a PostgreSQL database in TEST_DATABASE_URL"]
+    fn ranks_titles_first() {
+        let conn = &mut test_conn();
+        let doc = id::Doc::new(1, id::Proj::new(0x5201));
+        let (body, title) = (id::Sec::new(1, doc), id::Sec::new(2, doc));
+        save(
+            conn,
+            body,
+            "Morning",
+            "The fox ran through the woods.",
+            Lang::English,
+        );
+        save(conn, title, "The fox", "It slept all day.", Lang::English);
+        let other = id::Sec::new(1, id::Doc::new(1, id::Proj::new(0x5202)));
+        save(conn, other, "Fox", "Another fox.", Lang::English);
+
+        let hits = search(conn, doc.proj, "fox", 20).unwrap();
+        let ids: Vec<id::Sec> = hits.iter().map(|hit| hit.id).collect();
+        assert_eq!(ids, [title, body]);
+        assert!(hits[0].score > hits[1].score);
+        assert_eq!(search(conn, doc.proj, "fox", 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    #[ignore = "needs a PostgreSQL database in TEST_DATABASE_URL"]
+    fn builds_web_search_queries() {
+        let conn = &mut test_conn();
+        let doc = id::Doc::new(1, id::Proj::new(0x5203));
+        let (english, german) = (id::Sec::new(1, doc), id::Sec::new(2, doc));
+        save(
+            conn,
+            english,
+            "",
+            "The red fox jumped over the dogs.",
+            Lang::English,
+        );
+        save(
+            conn,
+            german,
+            "",
+            "Die Häuser stehen am Fluss.",
+            Lang::German,
+        );
+        let ids = |conn: &mut PgConnection, query: &str| -> Vec<id::Sec> {
+            search(conn, doc.proj, query, 20)
+                .unwrap()
+                .into_iter()
+                .map(|hit| hit.id)
+                .collect()
+        };
+        // stemmed in the language of each section.
+        assert_eq!(ids(conn, "jumping dog"), [english]);
+        assert_eq!(ids(conn, "Haus"), [german]);
+        // phrases, alternatives and exclusions.
+        assert_eq!(ids(conn, "\"red fox\""), [english]);
+        assert!(ids(conn, "\"fox red\"").is_empty());
+        assert_eq!(ids(conn, "fox or fluss").len(), 2);
+        assert!(ids(conn, "fox -dog").is_empty());
+        let hit = &search(conn, doc.proj, "fox", 20).unwrap()[0];
+        assert!(hit.snippet.to_string().contains("fox"));
+    }
+}
@@ -0,0 +1,249 @@
+use diesel::prelude::*;
+use schling_common::{data, id, search::Lang, wiki::Titles};
+use serde_json::Value;
+
+use crate::error::Result;
+use crate::schema::{documents, projects, sections};
+
+pub fn load_proj(conn: &mut PgConnection, id: id::Proj) -> Result<data::Proj> {
+    let body: Value = projects::table
+        .find(id.to_string())
+        .select(projects::body)
+        .first(conn)?;
+    Ok(serde_json::from_value(body)?)
+}
+
+pub fn save_proj(conn: &mut PgConnection, proj: &data::Proj) -> Result<()> {
+    let body = serde_json::to_value(proj)?;
+    diesel::insert_into(projects::table)
+        .values((
+            projects::id.eq(proj.head.id.to_string()),
+            projects::body.eq(&body),
+        ))
+        .on_conflict(projects::id)
+        .do_update()
+        .set(projects::body.eq(&body))
+        .execute(conn)?;
+    Ok(())
+}
+
+pub fn load_doc(conn: &mut PgConnection, id: id::Doc) -> Result<data::Doc> {
+    let body: Value = documents::table
+        .find(id.to_string())
+        .select(documents::body)
+        .first(conn)?;
+    Ok(serde_json::from_value(body)?)
+}
+
+pub fn save_doc(conn: &mut PgConnection, doc: &data::Doc) -> Result<()> {
+    let body = serde_json::to_value(doc)?;
+    diesel::insert_into(documents::table)
+        .values((
+            documents::id.eq(doc.head.id.to_string()),
+            documents::proj.eq(doc.head.id.proj.to_string()),
+            documents::body.eq(&body),
+        ))
+        .on_conflict(documents::id)
+        .do_update()
+        .set(documents::body.eq(&body))
+        .execute(conn)?;
+    Ok(())
+}
+
+pub fn load_sec(conn: &mut PgConnection, id: id::Sec) -> Result<data::Sec> {
+    let body: Value = sections::table
+        .find(id.to_string())
+        .select(sections::body)
+        .first(conn)?;
+    Ok(serde_json::from_value(body)?)
+}
+
+/// The sections of all documents of the project, in the order of their ids.
+pub fn load_proj_sections(conn: &mut PgConnection, proj: id::Proj) -> Result<Vec<data::Sec>> {
+    let bodies: Vec<Value> = sections::table
+        .filter(sections::proj.eq(proj.to_string()))
+        .order(sections::id)
+        .select(sections::body)
+        .load(conn)?;
+    Ok(bodies
+        .into_iter()
+        .map(serde_json::from_value)
+        .collect::<serde_json::Result<_>>()?)
+}
+
+/// The titles of the documents and sections of the project, resolving wiki links.
+pub fn load_titles(conn: &mut PgConnection, proj: id::Proj) -> Result<(Titles, Vec<data::Sec>)> {
+    let docs: Vec<Value> = documents::table
+        .filter(documents::proj.eq(proj.to_string()))
+        .select(documents::body)
+        .load(conn)?;
+    let mut titles = Titles::default();
+    for doc in docs {
+        let doc: data::Doc = serde_json::from_value(doc)?;
+        titles.insert_doc(&doc.head);
+    }
+    let sections = load_proj_sections(conn, proj)?;
+    for sec in &sections {
+        titles.insert_sec(&sec.head);
+    }
+    Ok((titles, sections))
+}
+
+/// Stores the section, the search index is updated by the database.
+pub fn save_sec(conn: &mut PgConnection, sec: &data::Sec, lang: Lang) -> Result<()> {
+    let body = serde_json::to_value(sec)?;
+    let lang = lang.to_string();
+    diesel::insert_into(sections::table)
+        .values((
+            sections::id.eq(sec.head.id.to_string()),
+            sections::proj.eq(sec.head.id.doc.proj.to_string()),
+            sections::lang.eq(&lang),
+            sections::body.eq(&body),
+        ))
+        .on_conflict(sections::id)
+        .do_update()
+        .set((sections::lang.eq(&lang), sections::body.eq(&body)))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// The selected documents of the project, in order, with their sections.
+pub fn load_chapters(
+    conn: &mut PgConnection,
+    proj: &data::Proj,
+    selected: Option<&[id::Doc]>,
+) -> Result<Vec<(data::Doc, Vec<data::Sec>)>> {
+    proj.content
+        .iter()
+        .filter(|head| selected.is_none_or(|docs| docs.contains(&head.id)))
+        .map(|head| load_chapter(conn, head.id))
+        .collect()
+}
+
+/// The document with its sections.
+pub fn load_chapter(conn: &mut PgConnection, id: id::Doc) -> Result<(data::Doc, Vec<data::Sec>)> {
+    let doc = load_doc(conn, id)?;
+    let sections = doc
+        .content
+        .iter()
+        .map(|head| load_sec(conn, head.id))
+        .collect::<Result<Vec<_>>>()?;
+    Ok((doc, sections))
+}
+
+/// Stores the project with the documents and sections, deleting the other documents and
+/// sections of the project. Callers run it in a transaction to replace the project at once.
+pub fn replace_proj<'a>(
+    conn: &mut PgConnection,
+    proj: &data::Proj,
+    docs: impl IntoIterator<Item = &'a data::Doc>,
+    sections: impl IntoIterator<Item = &'a data::Sec>,
+    lang: Lang,
+) -> Result<()> {
+    let mut sec_ids = vec![];
+    for sec in sections {
+        save_sec(conn, sec, lang)?;
+        sec_ids.push(sec.head.id.to_string());
+    }
+    let mut doc_ids = vec![];
+    for doc in docs {
+        save_doc(conn, doc)?;
+        doc_ids.push(doc.head.id.to_string());
+    }
+    let id = proj.head.id.to_string();
+    diesel::delete(
+        sections::table
+            .filter(sections::proj.eq(&id))
+            .filter(sections::id.ne_all(&sec_ids)),
+    )
+    .execute(conn)?;
+    diesel::delete(
+        documents::table
+            .filter(documents::proj.eq(&id))
+            .filter(documents::id.ne_all(&doc_ids)),
+    )
+    .execute(conn)?;
+    save_proj(conn, proj)
+}
+
+#[cfg(test)]
+mod test {
+    use schling_common::markup::Markup;
+
+    use super::*;
+    use crate::db::test_conn;
+
+    fn sec(doc: id::Doc, value: u32, title: &str, text: &str) -> data::Sec {
+        data::SecHead::new(id::Sec::new(value, doc), value, title.to_string())
+            .body(Markup::md_str(text))
+    }
+
+    #[test]
+    #[ignore = "needs a PostgreSQL database in TEST_DATABASE_URL"]
+    fn round_trips_projects() {
+        let conn = &mut test_conn();
+        let proj = id::Proj::new(0x5101);
+        let (one, two) = (id::Doc::new(1, proj), id::Doc::new(2, proj));
+        let sections = [sec(one, 0, "Start", "Once."), sec(two, 0, "End", "Done.")];
+        for sec in &sections {
+            save_sec(conn, sec, Lang::English).unwrap();
+        }
+        let docs =
+            [(one, "One", &sections[0]), (two, "Two", &sections[1])].map(|(id, title, sec)| {
+                data::DocHead::new(id, id.value, title.to_string()).body(vec![sec.head.clone()])
+            });
+        for doc in &docs {
+            save_doc(conn, doc).unwrap();
+        }
+        let heads = docs.iter().map(|doc| doc.head.clone()).collect();
+        let proj = data::ProjHead::new(proj, 0, "Works".to_string()).body(heads);
+        save_proj(conn, &proj).unwrap();
+
+        assert_eq!(load_proj(conn, proj.head.id).unwrap(), proj);
+        assert_eq!(load_doc(conn, one).unwrap(), docs[0]);
+        assert_eq!(load_sec(conn, sections[1].head.id).unwrap(), sections[1]);
+        let chapters = load_chapters(conn, &proj, Some(&[two])).unwrap();
+        assert_eq!(chapters, [(docs[1].clone(), vec![sections[1].clone()])]);
+        assert!(matches!(
+            load_doc(conn, id::Doc::new(3, proj.head.id)),
+            Err(crate::error::Error::NotFound)
+        ));
+    }
+
+    #[test]
+    #[ignore = "needs a PostgreSQL database in TEST_DATABASE_URL"]
+    fn overwrites_saved_sections() {
+        let conn = &mut test_conn();
+        let doc = id::Doc::new(1, id::Proj::new(0x5102));
+        save_sec(conn, &sec(doc, 0, "Draft", "First."), Lang::English).unwrap();
+        let edited = sec(doc, 0, "Final", "Second.");
+        save_sec(conn, &edited, Lang::German).unwrap();
+        assert_eq!(load_sec(conn, edited.head.id).unwrap(), edited);
+        assert_eq!(load_proj_sections(conn, doc.proj).unwrap(), [edited]);
+    }
+
+    #[test]
+    #[ignore = "needs a PostgreSQL database in TEST_DATABASE_URL"]
+    fn replaces_projects() {
+        let conn = &mut test_conn();
+        let proj = id::Proj::new(0x5103);
+        let (one, two) = (id::Doc::new(1, proj), id::Doc::new(2, proj));
+        let sections = [sec(one, 0, "Start", "Once."), sec(two, 0, "End", "Done.")];
+        let docs = [(one, &sections[0]), (two, &sections[1])].map(|(id, sec)| {
+            data::DocHead::new(id, id.value, "Chapter".to_string()).body(vec![sec.head.clone()])
+        });
+        let heads = docs.iter().map(|doc| doc.head.clone()).collect();
+        let full = data::ProjHead::new(proj, 0, "Works".to_string()).body(heads);
+        replace_proj(conn, &full, &docs, &sections, Lang::English).unwrap();
+
+        let short =
+            data::ProjHead::new(proj, 0, "Works".to_string()).body(vec![docs[0].head.clone()]);
+        replace_proj(conn, &short, &docs[..1], &sections[..1], Lang::English).unwrap();
+        assert_eq!(load_proj(conn, proj).unwrap(), short);
+        assert_eq!(load_proj_sections(conn, proj).unwrap(), sections[..1]);
+        assert!(matches!(
+            load_doc(conn, two),
+            Err(crate::error::Error::NotFound)
+        ));
+    }
+}